@@ -0,0 +1,51 @@
+drop view balance_history;
+
+alter table transfers
+    drop constraint trade_has_both_sides,
+    drop constraint trades_need_id,
+    drop column thing_id
+;
+
+create view balance_history as
+  select
+    "rowid",
+    "from_user" as user,
+    "from_balance" as balance,
+    "quantity",
+    -1 as sign,
+    "happened_at",
+    "ty",
+    "comment",
+    "to_user" as other_party,
+    "message_id",
+    "to_motion",
+    "to_votes",
+    "transfer_ty",
+    "auction_id"
+  from transfers 
+  where
+    "from_user" is not null
+  union all
+  select
+    "rowid",
+    "to_user" as user,
+    "to_balance" as balance,
+    "quantity",
+    1 as sign,
+    "happened_at",
+    "ty",
+    "comment",
+    "from_user" as other_party,
+    "message_id",
+    NULL::bigint as "to_motion",
+    NULL::bigint as "to_votes",
+    "transfer_ty",
+    "auction_id"
+  from transfers
+  where
+    "to_user" is not null
+;
+
+-- Postgres has no way to remove a value from an enum; 'trade' stays in transfer_type.
+
+drop table trade_offers;
@@ -0,0 +1,78 @@
+create table trade_offers (
+    rowid bigint primary key references thing_ids(rowid),
+    created_at timestamptz not null,
+    offerer bigint not null,
+    counterparty bigint not null,
+    offer_ty text not null references item_types("name"),
+    offer_amt bigint not null,
+    want_ty text not null references item_types("name"),
+    want_amt bigint not null,
+    expires_at timestamptz not null,
+    message_id bigint, --the discord message the counterparty can react to, if the offer was made from discord
+    accepted_at timestamptz,
+    cancelled_at timestamptz,
+    constraint trade_positive_amounts check (offer_amt > 0 and want_amt > 0),
+    constraint trade_two_parties check (offerer <> counterparty),
+    constraint trade_resolved_once check (accepted_at is null or cancelled_at is null)
+);
+
+create index trade_offers_message_id on trade_offers(message_id) where message_id is not null;
+create index trade_offers_pending on trade_offers(expires_at) where accepted_at is null and cancelled_at is null;
+
+-- Postgres 12+ is needed to add enum values inside of a (migration) transaction
+alter type transfer_type add value 'trade';
+
+--The columns of balance_history can't be changed while this view exists, so we kill and re-create it.
+drop view balance_history;
+
+-- Generic reference to whatever thing (trade, etc) caused this transfer. Newer transfer types use this instead of
+-- growing another column every time.
+alter table transfers
+    add column thing_id bigint references thing_ids(rowid),
+    add constraint trades_need_id check (transfer_ty::text <> 'trade' or thing_id is not null),
+    add constraint trade_has_both_sides check (transfer_ty::text <> 'trade' or (from_user is not null and to_user is not null))
+;
+
+create index on transfers (thing_id) where thing_id is not null;
+
+create view balance_history as
+  select
+    "rowid",
+    "from_user" as user,
+    "from_balance" as balance,
+    "quantity",
+    -1 as sign,
+    "happened_at",
+    "ty",
+    "comment",
+    "to_user" as other_party,
+    "message_id",
+    "to_motion",
+    "to_votes",
+    "transfer_ty",
+    "auction_id",
+    "thing_id"
+  from transfers 
+  where
+    "from_user" is not null
+  union all
+  select
+    "rowid",
+    "to_user" as user,
+    "to_balance" as balance,
+    "quantity",
+    1 as sign,
+    "happened_at",
+    "ty",
+    "comment",
+    "from_user" as other_party,
+    "message_id",
+    NULL::bigint as "to_motion",
+    NULL::bigint as "to_votes",
+    "transfer_ty",
+    "auction_id",
+    "thing_id"
+  from transfers
+  where
+    "to_user" is not null
+;
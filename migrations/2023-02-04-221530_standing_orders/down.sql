@@ -0,0 +1,7 @@
+alter table transfers drop constraint standing_orders_need_id;
+
+-- Values can't be removed from an enum; 'standing_order' stays in transfer_type.
+
+drop table notifications;
+drop table standing_order_runs;
+drop table standing_orders;
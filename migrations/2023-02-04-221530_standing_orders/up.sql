@@ -0,0 +1,43 @@
+create table standing_orders (
+    rowid bigint primary key references thing_ids(rowid),
+    created_at timestamptz not null,
+    payer bigint not null,
+    payee bigint not null,
+    ty text not null references item_types("name"),
+    amount bigint not null,
+    every_days integer not null,
+    -- start of the earliest period that has not been executed yet
+    next_run_at timestamptz not null,
+    cancelled_at timestamptz,
+    constraint standing_order_positive_amount check (amount > 0),
+    constraint standing_order_positive_period check (every_days > 0),
+    constraint standing_order_two_parties check (payer <> payee)
+);
+
+create index standing_orders_due on standing_orders(next_run_at) where cancelled_at is null;
+
+-- One row per period that has been executed (successfully or not), so that a period is never paid twice.
+create table standing_order_runs (
+    standing_order bigint not null references standing_orders(rowid),
+    period_start timestamptz not null,
+    ran_at timestamptz not null,
+    paid boolean not null, --false if the payer couldn't cover it
+    primary key (standing_order, period_start)
+);
+
+-- Direct messages waiting to be sent by the worker
+create table notifications (
+    rowid bigserial primary key,
+    created_at timestamptz not null,
+    recipient bigint not null,
+    body text not null,
+    sent_at timestamptz
+);
+
+create index notifications_unsent on notifications(created_at) where sent_at is null;
+
+alter type transfer_type add value 'standing_order';
+
+alter table transfers
+    add constraint standing_orders_need_id check (transfer_ty::text <> 'standing_order' or thing_id is not null)
+;
@@ -0,0 +1,59 @@
+alter table motions
+    drop constraint action_executed_once,
+    drop column action_error,
+    drop column action_executed_at,
+    drop column action
+;
+
+drop view balance_history;
+
+alter table transfers
+    drop constraint treasury_spend_direction,
+    drop constraint treasury_mint_direction,
+    drop constraint treasury_auction_direction,
+    drop constraint treasury_auction_need_id
+;
+
+create view balance_history as
+  select
+    "rowid",
+    "from_user" as user,
+    "from_balance" as balance,
+    "quantity",
+    -1 as sign,
+    "happened_at",
+    "ty",
+    "comment",
+    "to_user" as other_party,
+    "message_id",
+    "to_motion",
+    "to_votes",
+    "transfer_ty",
+    "auction_id",
+    "thing_id"
+  from transfers 
+  where
+    "from_user" is not null
+  union all
+  select
+    "rowid",
+    "to_user" as user,
+    "to_balance" as balance,
+    "quantity",
+    1 as sign,
+    "happened_at",
+    "ty",
+    "comment",
+    "from_user" as other_party,
+    "message_id",
+    NULL::bigint as "to_motion",
+    NULL::bigint as "to_votes",
+    "transfer_ty",
+    "auction_id",
+    "thing_id"
+  from transfers
+  where
+    "to_user" is not null
+;
+
+-- Postgres has no way to remove a value from an enum; the treasury transfer types stay in transfer_type.
@@ -0,0 +1,76 @@
+-- The treasury is the CONsortium's own account, user id 5 (0 and 1 are pits of fire, 2 through 4 are debug users).
+-- It receives motion fees and auction proceeds, funds auto-auction lots, and can only be spent by passed motions.
+
+alter type transfer_type add value 'auction_proceeds'; --the winning bid, paid out of escrow to the auctioneer (or the treasury)
+alter type transfer_type add value 'auction_return'; --nobody won, the escrowed offer goes back to whoever put it up
+alter type transfer_type add value 'treasury_mint'; --fungibles created in the treasury to fund an auto-auction lot
+alter type transfer_type add value 'treasury_spend'; --paid out of the treasury by a passed motion
+
+--The columns of balance_history can't be changed while this view exists, so we kill and re-create it.
+drop view balance_history;
+
+alter table transfers
+    add constraint treasury_auction_need_id check (
+        transfer_ty::text not in ('auction_proceeds', 'auction_return') or auction_id is not null
+    ),
+    add constraint treasury_auction_direction check (
+        transfer_ty::text not in ('auction_proceeds', 'auction_return') or (from_user is null and to_user is not null)
+    ),
+    add constraint treasury_mint_direction check (
+        transfer_ty::text <> 'treasury_mint' or (from_user is null and to_user = 5)
+    ),
+    add constraint treasury_spend_direction check (
+        transfer_ty::text <> 'treasury_spend' or (from_user = 5 and to_user is not null and thing_id is not null)
+    )
+;
+
+-- Motion fees are now paid to the treasury, so the receiving side needs to know which motion too
+create view balance_history as
+  select
+    "rowid",
+    "from_user" as user,
+    "from_balance" as balance,
+    "quantity",
+    -1 as sign,
+    "happened_at",
+    "ty",
+    "comment",
+    "to_user" as other_party,
+    "message_id",
+    "to_motion",
+    "to_votes",
+    "transfer_ty",
+    "auction_id",
+    "thing_id"
+  from transfers 
+  where
+    "from_user" is not null
+  union all
+  select
+    "rowid",
+    "to_user" as user,
+    "to_balance" as balance,
+    "quantity",
+    1 as sign,
+    "happened_at",
+    "ty",
+    "comment",
+    "from_user" as other_party,
+    "message_id",
+    "to_motion",
+    "to_votes",
+    "transfer_ty",
+    "auction_id",
+    "thing_id"
+  from transfers
+  where
+    "to_user" is not null
+;
+
+-- Executable motions: a json-encoded MotionAction (see src/motion_actions.rs) that is carried out if the motion passes
+alter table motions
+    add column action text,
+    add column action_executed_at timestamptz,
+    add column action_error text, --why the action couldn't be carried out, if it couldn't
+    add constraint action_executed_once check (action is not null or action_executed_at is null)
+;
@@ -0,0 +1,3 @@
+alter table item_types drop column retired_at;
+
+drop table admins;
@@ -0,0 +1,9 @@
+-- The bot keeps this in step with discord: the owner of the guild the motions channel is in, and its members with
+-- that guild's admin role. It's here for the website, which can't see roles.
+create table admins (
+    "user" bigint primary key,
+    added_at timestamptz not null default now()
+);
+
+-- A retired item type can no longer be given, bid or offered, but its history stays readable.
+alter table item_types add column retired_at timestamptz;
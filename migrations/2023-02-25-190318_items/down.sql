@@ -0,0 +1,40 @@
+drop view auction_and_winner;
+
+drop table item_transfers;
+
+delete from auctions where offer_item is not null;
+
+alter table auctions
+    drop constraint auction_offer_item_alone,
+    drop constraint auction_offer_one_kind,
+    drop column offer_item,
+    alter column offer_ty set not null,
+    alter column offer_amt set not null
+;
+
+create view auction_and_winner as
+  select
+    a.rowid as auction_id,
+    a.created_at,
+    a.auctioneer,
+    a.offer_ty,
+    a.offer_amt,
+    a.bid_ty,
+    a.bid_min,
+    a.finished,
+    a.max_bid_user,
+    a.max_bid_amt,
+    COALESCE(t.happened_at, a.created_at) as last_change,
+    t.rowid as transfer_id,
+    t.from_user as winner_id,
+    t.quantity as winner_bid,
+    t.happened_at as bid_at,
+    COALESCE(a.last_timer_bump, t.happened_at, a.created_at) as last_timer_bump
+  from
+    auctions a
+  left join lateral
+    (select * from transfers where auction_id = a.rowid and transfer_ty = 'auction_reserve' order by happened_at desc limit 1) t
+  on true
+;
+
+drop table items;
@@ -0,0 +1,79 @@
+-- Non-fungible items: each one is unique, has its own damm-checked id (shared with motions, auctions etc.) and
+-- exactly one owner. While an item is up for auction it's held in escrow and has no owner.
+create table items (
+    rowid bigint primary key references thing_ids(rowid),
+    created_at timestamptz not null,
+    kind text not null, --eg title, badge, trophy
+    "name" text not null,
+    metadata jsonb not null default '{}',
+    "owner" bigint,
+    constraint item_kind_valid check (length(kind) between 1 and 32),
+    constraint item_name_valid check (length("name") between 1 and 64),
+    constraint item_metadata_object check (jsonb_typeof(metadata) = 'object')
+);
+
+create index items_owner on items("owner") where "owner" is not null;
+
+-- Every change of ownership, including creation. Uses the same transfer types as fungibles.
+create table item_transfers (
+    rowid bigserial primary key,
+    item bigint not null references items(rowid),
+    happened_at timestamptz not null,
+    from_user bigint,
+    to_user bigint,
+    transfer_ty transfer_type not null,
+    auction_id bigint references auctions(rowid),
+    message_id bigint,
+    constraint item_transfer_has_a_side check (from_user is not null or to_user is not null),
+    constraint item_transfer_ty_valid check (
+        transfer_ty in ('admin_fabricate', 'give', 'admin_give', 'auction_create', 'auction_payout', 'auction_return')
+    ),
+    constraint item_auction_need_id check (
+        transfer_ty not in ('auction_create', 'auction_payout', 'auction_return') or auction_id is not null
+    )
+);
+
+create index item_transfers_item on item_transfers(item, happened_at);
+
+-- An auction's lot is either a quantity of some item type, or a single item.
+drop view auction_and_winner;
+
+alter table auctions
+    alter column offer_ty drop not null,
+    alter column offer_amt drop not null,
+    add column offer_item bigint references items(rowid),
+    add constraint auction_offer_one_kind check (
+        (offer_item is null) = (offer_ty is not null and offer_amt is not null)
+    ),
+    add constraint auction_offer_item_alone check (
+        offer_item is null or (offer_ty is null and offer_amt is null)
+    )
+;
+
+create view auction_and_winner as
+  select
+    a.rowid as auction_id,
+    a.created_at,
+    a.auctioneer,
+    a.offer_ty,
+    a.offer_amt,
+    a.bid_ty,
+    a.bid_min,
+    a.finished,
+    a.max_bid_user,
+    a.max_bid_amt,
+    COALESCE(t.happened_at, a.created_at) as last_change,
+    t.rowid as transfer_id,
+    t.from_user as winner_id,
+    t.quantity as winner_bid,
+    t.happened_at as bid_at,
+    a.last_timer_bump,
+    a.offer_item,
+    i.name as offer_item_name
+  from
+    auctions a
+  left join items i on i.rowid = a.offer_item
+  left join lateral
+    (select * from transfers where auction_id = a.rowid and transfer_ty = 'auction_reserve' order by happened_at desc limit 1) t
+  on true
+;
@@ -0,0 +1,9 @@
+alter table transfers
+    drop constraint loan_has_both_sides,
+    drop constraint loans_need_id
+;
+
+-- Values can't be removed from an enum; 'loan_principal' and 'loan_repayment' stay in transfer_type.
+
+drop table loan_periods;
+drop table loans;
@@ -0,0 +1,55 @@
+create table loans (
+    rowid bigint primary key references thing_ids(rowid),
+    created_at timestamptz not null,
+    lender bigint not null,
+    borrower bigint not null,
+    ty text not null references item_types("name"),
+    principal bigint not null,
+    -- interest charged on the outstanding amount each period, in hundredths of a percent
+    interest_bps integer not null,
+    every_days integer not null,
+    installments integer not null,
+    expires_at timestamptz not null,
+    message_id bigint, --the discord message the borrower can react to, if the offer was made from discord
+    accepted_at timestamptz,
+    cancelled_at timestamptz,
+    -- start of the earliest period that has not been run yet, set once the loan is accepted
+    next_payment_at timestamptz,
+    periods_run integer not null default 0,
+    -- principal plus accrued interest not yet repaid
+    outstanding bigint not null default 0,
+    repaid_at timestamptz,
+    -- set when collection is given up on, some periods after the last installment if it's still owed
+    defaulted_at timestamptz,
+    constraint loan_positive_principal check (principal > 0),
+    constraint loan_interest_range check (interest_bps between 0 and 10000),
+    constraint loan_positive_period check (every_days > 0),
+    constraint loan_installments_range check (installments between 1 and 520),
+    constraint loan_two_parties check (lender <> borrower),
+    constraint loan_resolved_once check (accepted_at is null or cancelled_at is null),
+    constraint loan_schedule_when_accepted check ((accepted_at is null) = (next_payment_at is null)),
+    constraint loan_outstanding_nonnegative check (outstanding >= 0),
+    constraint loan_repaid_or_defaulted check (repaid_at is null or defaulted_at is null)
+);
+
+create index loans_message_id on loans(message_id) where message_id is not null;
+create index loans_due on loans(next_payment_at) where repaid_at is null and defaulted_at is null and next_payment_at is not null;
+
+-- One row per period that has been run, so that interest is never charged twice for the same period.
+create table loan_periods (
+    loan bigint not null references loans(rowid),
+    period_start timestamptz not null,
+    ran_at timestamptz not null,
+    interest bigint not null,
+    due bigint not null,
+    paid bigint not null, --less than due if the borrower couldn't cover all of it
+    primary key (loan, period_start)
+);
+
+alter type transfer_type add value 'loan_principal';
+alter type transfer_type add value 'loan_repayment';
+
+alter table transfers
+    add constraint loans_need_id check (transfer_ty::text not in ('loan_principal', 'loan_repayment') or thing_id is not null),
+    add constraint loan_has_both_sides check (transfer_ty::text not in ('loan_principal', 'loan_repayment') or (from_user is not null and to_user is not null))
+;
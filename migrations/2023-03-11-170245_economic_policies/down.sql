@@ -0,0 +1,5 @@
+drop table economic_policies;
+
+alter table transfers drop constraint economic_policy_burns;
+
+-- Values can't be removed from an enum; 'gen_upkeep', 'wealth_tax' and 'pc_decay' stay in transfer_type.
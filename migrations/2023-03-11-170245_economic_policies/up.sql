@@ -0,0 +1,28 @@
+alter type transfer_type add value 'gen_upkeep'; --pc burned each generation period for every gen held
+alter type transfer_type add value 'wealth_tax'; --pc burned each generation period from the part of a balance above a threshold
+alter type transfer_type add value 'pc_decay'; --a percentage of every pc balance burned each generation period
+
+alter table transfers
+    add constraint economic_policy_burns check (
+        transfer_ty::text not in ('gen_upkeep', 'wealth_tax', 'pc_decay') or (from_user is not null and to_user is null)
+    )
+;
+
+-- Monetary policies applied by the worker right after generating pc, in the same transaction. Changed by passing a
+-- motion (see MotionAction::SetPolicy).
+create table economic_policies (
+    "name" text primary key,
+    enabled boolean not null default false,
+    -- gen_upkeep: pc per gen held; wealth_tax and pc_decay: hundredths of a percent of the balance
+    rate bigint not null default 0,
+    -- wealth_tax only: pc balances up to this are untaxed
+    threshold bigint not null default 0,
+    changed_at timestamptz,
+    changed_by_motion bigint references motions(rowid),
+    constraint policy_name_known check ("name" in ('gen_upkeep', 'wealth_tax', 'pc_decay')),
+    constraint policy_rate_nonnegative check (rate >= 0),
+    constraint policy_percentage_range check ("name" = 'gen_upkeep' or rate <= 10000),
+    constraint policy_threshold_nonnegative check (threshold >= 0)
+);
+
+insert into economic_policies ("name") values ('gen_upkeep'), ('wealth_tax'), ('pc_decay');
@@ -0,0 +1,10 @@
+alter table transfers
+    drop constraint market_payout_direction,
+    drop constraint market_reserve_direction,
+    drop constraint market_need_id
+;
+
+-- Values can't be removed from an enum; 'market_reserve', 'market_fill' and 'market_refund' stay in transfer_type.
+
+drop table market_fills;
+drop table market_orders;
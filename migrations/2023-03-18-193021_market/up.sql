@@ -0,0 +1,50 @@
+-- A limit order book between any two item types. An order buys or sells `base_ty`, priced in `quote_ty` per unit.
+create table market_orders (
+    rowid bigint primary key references thing_ids(rowid),
+    created_at timestamptz not null,
+    "owner" bigint not null,
+    is_buy boolean not null,
+    base_ty text not null references item_types("name"),
+    quote_ty text not null references item_types("name"),
+    price bigint not null,
+    quantity bigint not null,
+    -- how much of `quantity` hasn't been filled yet; its escrow is still held
+    remaining bigint not null,
+    cancelled_at timestamptz,
+    constraint market_order_two_types check (base_ty <> quote_ty),
+    constraint market_order_positive check (price > 0 and quantity > 0),
+    constraint market_order_remaining_range check (remaining between 0 and quantity)
+);
+
+create index market_orders_open on market_orders(base_ty, quote_ty, is_buy, price) where remaining > 0 and cancelled_at is null;
+create index market_orders_owner on market_orders("owner");
+
+create table market_fills (
+    rowid bigserial primary key,
+    happened_at timestamptz not null,
+    buy_order bigint not null references market_orders(rowid),
+    sell_order bigint not null references market_orders(rowid),
+    base_ty text not null references item_types("name"),
+    quote_ty text not null references item_types("name"),
+    quantity bigint not null,
+    price bigint not null,
+    constraint market_fill_positive check (price > 0 and quantity > 0)
+);
+
+create index market_fills_pair on market_fills(base_ty, quote_ty, happened_at);
+
+alter type transfer_type add value 'market_reserve'; --placing an order, what it could spend is held in escrow
+alter type transfer_type add value 'market_fill'; --paid out of a matched order's escrow to the other side
+alter type transfer_type add value 'market_refund'; --escrow returned, on cancelling or when filled at a better price
+
+alter table transfers
+    add constraint market_need_id check (
+        transfer_ty::text not in ('market_reserve', 'market_fill', 'market_refund') or thing_id is not null
+    ),
+    add constraint market_reserve_direction check (
+        transfer_ty::text <> 'market_reserve' or (from_user is not null and to_user is null)
+    ),
+    add constraint market_payout_direction check (
+        transfer_ty::text not in ('market_fill', 'market_refund') or (from_user is null and to_user is not null)
+    )
+;
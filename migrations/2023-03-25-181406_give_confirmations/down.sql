@@ -0,0 +1,2 @@
+drop table pending_gives;
+alter table item_types drop column confirm_give_above;
@@ -0,0 +1,23 @@
+-- Gives from discord of more than this many have to be confirmed with a reaction first. Null means no limit.
+alter table item_types add column confirm_give_above bigint;
+update item_types set confirm_give_above = 10 where "name" = 'gen';
+update item_types set confirm_give_above = 10000 where "name" = 'pc';
+
+-- A give from discord waiting for the giver to confirm it. The transfer only happens once they do.
+create table pending_gives (
+    rowid bigserial primary key,
+    created_at timestamptz not null,
+    expires_at timestamptz not null,
+    giver bigint not null,
+    recipient bigint not null,
+    ty text not null references item_types("name"),
+    quantity bigint not null,
+    -- the $give command, recorded on the transfer like any other give
+    command_message_id bigint not null,
+    -- the bot's message asking for confirmation, which the reactions go on
+    message_id bigint unique,
+    confirmed_at timestamptz,
+    cancelled_at timestamptz,
+    constraint pending_give_positive check (quantity > 0),
+    constraint pending_give_confirmed_or_cancelled check (confirmed_at is null or cancelled_at is null)
+);
@@ -0,0 +1,8 @@
+drop table force_gives;
+
+alter table transfers
+    drop constraint force_give_undo_has_both_sides,
+    drop constraint burn_direction
+;
+
+-- Values can't be removed from an enum; 'burn' and 'force_give_undo' stay in transfer_type.
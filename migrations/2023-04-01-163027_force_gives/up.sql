@@ -0,0 +1,30 @@
+alter type transfer_type add value 'burn'; --fungibles destroyed on purpose by their owner
+alter type transfer_type add value 'force_give_undo'; --an admin reversed a force give within its undo window
+
+alter table transfers
+    add constraint burn_direction check (
+        transfer_ty::text <> 'burn' or (from_user is not null and to_user is null)
+    ),
+    add constraint force_give_undo_has_both_sides check (
+        transfer_ty::text <> 'force_give_undo' or (from_user is not null and to_user is not null)
+    )
+;
+
+-- Every $force_give, now admin-only. A give to an id the bot had never seen in any guild can be undone for a while,
+-- since nobody may be able to spend it.
+create table force_gives (
+    rowid bigserial primary key,
+    happened_at timestamptz not null,
+    giver bigint not null,
+    recipient bigint not null,
+    ty text not null references item_types("name"),
+    quantity bigint not null,
+    message_id bigint not null,
+    recipient_seen boolean not null,
+    -- null when the recipient was seen, and there's no undoing it
+    undo_until timestamptz,
+    undone_at timestamptz,
+    undone_by bigint,
+    constraint force_give_undone_by check ((undone_at is null) = (undone_by is null)),
+    constraint force_give_undo_window check (undone_at is null or undo_until is not null)
+);
@@ -0,0 +1,2 @@
+drop table auction_events;
+drop table auction_watchers;
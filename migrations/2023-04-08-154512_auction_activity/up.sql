@@ -0,0 +1,21 @@
+-- Users who asked to be told about new bids on an auction
+create table auction_watchers (
+    auction_id bigint not null references auctions(rowid),
+    "user" bigint not null,
+    created_at timestamptz not null,
+    primary key (auction_id, "user")
+);
+
+-- Things that happen to an auction that don't show up as a transfer
+create table auction_events (
+    rowid bigserial primary key,
+    auction_id bigint not null references auctions(rowid),
+    happened_at timestamptz not null,
+    -- 'outbid': user no longer has the high bid of amount
+    -- 'max_bid_exhausted': someone bid past user's max bid of amount
+    kind text not null,
+    "user" bigint not null,
+    amount bigint not null,
+    constraint auction_event_kind check (kind in ('outbid', 'max_bid_exhausted'))
+);
+create index auction_events_auction on auction_events (auction_id, happened_at);
@@ -0,0 +1,41 @@
+drop view auction_and_winner;
+
+drop table sealed_bids;
+
+alter table auctions drop column sealed;
+
+alter table transfers
+    drop constraint sealed_bid_refund_direction,
+    drop constraint sealed_bid_escrow_direction,
+    drop constraint sealed_bids_need_id
+;
+
+-- Values can't be removed from an enum; 'sealed_bid_escrow' and 'sealed_bid_refund' stay in transfer_type.
+
+create view auction_and_winner as
+  select
+    a.rowid as auction_id,
+    a.created_at,
+    a.auctioneer,
+    a.offer_ty,
+    a.offer_amt,
+    a.bid_ty,
+    a.bid_min,
+    a.finished,
+    a.max_bid_user,
+    a.max_bid_amt,
+    COALESCE(t.happened_at, a.created_at) as last_change,
+    t.rowid as transfer_id,
+    t.from_user as winner_id,
+    t.quantity as winner_bid,
+    t.happened_at as bid_at,
+    a.last_timer_bump,
+    a.offer_item,
+    i.name as offer_item_name
+  from
+    auctions a
+  left join items i on i.rowid = a.offer_item
+  left join lateral
+    (select * from transfers where auction_id = a.rowid and transfer_ty = 'auction_reserve' order by happened_at desc limit 1) t
+  on true
+;
@@ -0,0 +1,58 @@
+alter type transfer_type add value 'sealed_bid_escrow'; --placing a sealed bid, held until the auction ends
+alter type transfer_type add value 'sealed_bid_refund'; --a sealed bid given back when it's replaced or the auction ends
+
+alter table transfers
+    add constraint sealed_bids_need_id check (
+        transfer_ty::text not in ('sealed_bid_escrow', 'sealed_bid_refund') or auction_id is not null
+    ),
+    add constraint sealed_bid_escrow_direction check (
+        transfer_ty::text <> 'sealed_bid_escrow' or (from_user is not null and to_user is null)
+    ),
+    add constraint sealed_bid_refund_direction check (
+        transfer_ty::text <> 'sealed_bid_refund' or (from_user is null and to_user is not null)
+    )
+;
+
+-- Bids on a sealed auction are hidden until it ends. The highest bidder wins and pays the second-highest bid (or the
+-- minimum, if nobody else bid). The timer is never bumped, so it ends a fixed time after it starts.
+alter table auctions add column sealed boolean not null default false;
+
+-- One bid per bidder, held with a 'sealed_bid_escrow' transfer. Bidding again replaces it.
+create table sealed_bids (
+    auction_id bigint not null references auctions(rowid),
+    bidder bigint not null,
+    amount bigint not null,
+    placed_at timestamptz not null,
+    primary key (auction_id, bidder),
+    constraint sealed_bid_positive check (amount > 0)
+);
+
+drop view auction_and_winner;
+create view auction_and_winner as
+  select
+    a.rowid as auction_id,
+    a.created_at,
+    a.auctioneer,
+    a.offer_ty,
+    a.offer_amt,
+    a.bid_ty,
+    a.bid_min,
+    a.finished,
+    a.max_bid_user,
+    a.max_bid_amt,
+    COALESCE(t.happened_at, a.created_at) as last_change,
+    t.rowid as transfer_id,
+    t.from_user as winner_id,
+    t.quantity as winner_bid,
+    t.happened_at as bid_at,
+    a.last_timer_bump,
+    a.offer_item,
+    i.name as offer_item_name,
+    a.sealed
+  from
+    auctions a
+  left join items i on i.rowid = a.offer_item
+  left join lateral
+    (select * from transfers where auction_id = a.rowid and transfer_ty = 'auction_reserve' order by happened_at desc limit 1) t
+  on true
+;
@@ -0,0 +1,52 @@
+-- A dutch auction's price starts at dutch_start and drops by dutch_drop every dutch_every_minutes, down to bid_min. The
+-- first bidder buys it outright at dutch_price, which the worker keeps current, and the auction ends right then.
+alter table auctions
+    add column dutch_start bigint,
+    add column dutch_drop bigint,
+    add column dutch_every_minutes int,
+    add column dutch_price bigint,
+    add constraint dutch_all_or_nothing check (
+        (dutch_start is null) = (dutch_drop is null) and
+        (dutch_start is null) = (dutch_every_minutes is null) and
+        (dutch_start is null) = (dutch_price is null)
+    ),
+    add constraint dutch_schedule check (
+        dutch_start is null or (dutch_start > bid_min and dutch_drop > 0 and dutch_every_minutes > 0)
+    ),
+    add constraint dutch_price_in_range check (dutch_price is null or dutch_price between bid_min and dutch_start),
+    add constraint dutch_not_sealed check (dutch_start is null or not sealed)
+;
+
+drop view auction_and_winner;
+create view auction_and_winner as
+  select
+    a.rowid as auction_id,
+    a.created_at,
+    a.auctioneer,
+    a.offer_ty,
+    a.offer_amt,
+    a.bid_ty,
+    a.bid_min,
+    a.finished,
+    a.max_bid_user,
+    a.max_bid_amt,
+    COALESCE(t.happened_at, a.created_at) as last_change,
+    t.rowid as transfer_id,
+    t.from_user as winner_id,
+    t.quantity as winner_bid,
+    t.happened_at as bid_at,
+    a.last_timer_bump,
+    a.offer_item,
+    i.name as offer_item_name,
+    a.sealed,
+    a.dutch_start,
+    a.dutch_drop,
+    a.dutch_every_minutes,
+    a.dutch_price
+  from
+    auctions a
+  left join items i on i.rowid = a.offer_item
+  left join lateral
+    (select * from transfers where auction_id = a.rowid and transfer_ty = 'auction_reserve' order by happened_at desc limit 1) t
+  on true
+;
@@ -0,0 +1,54 @@
+drop view auction_and_winner;
+
+drop table unit_bids;
+
+alter table auctions
+    drop constraint clearing_price_multi_unit,
+    drop constraint multi_unit_alone,
+    drop constraint multi_unit_fungible,
+    drop column clearing_price,
+    drop column multi_unit
+;
+
+alter table transfers
+    drop constraint auction_unit_payout_direction,
+    drop constraint unit_bid_refund_direction,
+    drop constraint unit_bid_escrow_direction,
+    drop constraint unit_bids_need_id
+;
+
+-- Values can't be removed from an enum; 'unit_bid_escrow', 'unit_bid_refund' and 'auction_unit_payout' stay in
+-- transfer_type.
+
+create view auction_and_winner as
+  select
+    a.rowid as auction_id,
+    a.created_at,
+    a.auctioneer,
+    a.offer_ty,
+    a.offer_amt,
+    a.bid_ty,
+    a.bid_min,
+    a.finished,
+    a.max_bid_user,
+    a.max_bid_amt,
+    COALESCE(t.happened_at, a.created_at) as last_change,
+    t.rowid as transfer_id,
+    t.from_user as winner_id,
+    t.quantity as winner_bid,
+    t.happened_at as bid_at,
+    a.last_timer_bump,
+    a.offer_item,
+    i.name as offer_item_name,
+    a.sealed,
+    a.dutch_start,
+    a.dutch_drop,
+    a.dutch_every_minutes,
+    a.dutch_price
+  from
+    auctions a
+  left join items i on i.rowid = a.offer_item
+  left join lateral
+    (select * from transfers where auction_id = a.rowid and transfer_ty = 'auction_reserve' order by happened_at desc limit 1) t
+  on true
+;
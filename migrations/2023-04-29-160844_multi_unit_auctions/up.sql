@@ -0,0 +1,76 @@
+alter type transfer_type add value 'unit_bid_escrow'; --placing a bid on a multi-unit auction, quantity times price is held
+alter type transfer_type add value 'unit_bid_refund'; --a multi-unit bid given back, in full or whatever wasn't spent at the clearing price
+alter type transfer_type add value 'auction_unit_payout'; --some of a multi-unit auction's units, to one of its winners; unlike 'auction_payout' there can be many per auction
+
+alter table transfers
+    add constraint unit_bids_need_id check (
+        transfer_ty::text not in ('unit_bid_escrow', 'unit_bid_refund', 'auction_unit_payout') or auction_id is not null
+    ),
+    add constraint unit_bid_escrow_direction check (
+        transfer_ty::text <> 'unit_bid_escrow' or (from_user is not null and to_user is null)
+    ),
+    add constraint unit_bid_refund_direction check (
+        transfer_ty::text <> 'unit_bid_refund' or (from_user is null and to_user is not null)
+    ),
+    add constraint auction_unit_payout_direction check (
+        transfer_ty::text <> 'auction_unit_payout' or (from_user is null and to_user is not null)
+    )
+;
+
+-- A multi-unit auction sells offer_amt units of a fungible. Each bidder asks for a quantity at a price each, the
+-- highest bids are filled (the last one maybe only partly), and every winner pays the same clearing price: the lowest
+-- price that got filled. bid_min is the minimum price each. Like a sealed auction, the timer is never bumped.
+alter table auctions
+    add column multi_unit boolean not null default false,
+    add column clearing_price bigint,
+    add constraint multi_unit_fungible check (not multi_unit or offer_amt is not null),
+    add constraint multi_unit_alone check (not multi_unit or (not sealed and dutch_start is null)),
+    add constraint clearing_price_multi_unit check (clearing_price is null or multi_unit)
+;
+
+-- One bid per bidder, held with a 'unit_bid_escrow' transfer of quantity * unit_price. Bidding again replaces it.
+create table unit_bids (
+    auction_id bigint not null references auctions(rowid),
+    bidder bigint not null,
+    quantity bigint not null,
+    unit_price bigint not null,
+    placed_at timestamptz not null,
+    primary key (auction_id, bidder),
+    constraint unit_bid_positive check (quantity > 0 and unit_price > 0)
+);
+
+drop view auction_and_winner;
+create view auction_and_winner as
+  select
+    a.rowid as auction_id,
+    a.created_at,
+    a.auctioneer,
+    a.offer_ty,
+    a.offer_amt,
+    a.bid_ty,
+    a.bid_min,
+    a.finished,
+    a.max_bid_user,
+    a.max_bid_amt,
+    COALESCE(t.happened_at, a.created_at) as last_change,
+    t.rowid as transfer_id,
+    t.from_user as winner_id,
+    t.quantity as winner_bid,
+    t.happened_at as bid_at,
+    a.last_timer_bump,
+    a.offer_item,
+    i.name as offer_item_name,
+    a.sealed,
+    a.dutch_start,
+    a.dutch_drop,
+    a.dutch_every_minutes,
+    a.dutch_price,
+    a.multi_unit,
+    a.clearing_price
+  from
+    auctions a
+  left join items i on i.rowid = a.offer_item
+  left join lateral
+    (select * from transfers where auction_id = a.rowid and transfer_ty = 'auction_reserve' order by happened_at desc limit 1) t
+  on true
+;
@@ -0,0 +1,84 @@
+-- How long an auction runs: from when it starts, or from the last bid for an open auction. Null means the usual
+-- 96 hours.
+alter table auctions
+    add column duration_minutes int,
+    add constraint duration_positive check (duration_minutes is null or duration_minutes > 0)
+;
+
+-- Auctions the treasury puts up on a schedule. `schedule` is a cron expression, "<minute> <hour> <day of month>
+-- <month> <day of week>", in `timezone`. `last_run` is when the last one started, or when it was last changed; the
+-- next starts at the first time after that matching the schedule.
+create table auto_auctions (
+    rowid bigserial primary key,
+    name text not null unique,
+    schedule text not null,
+    timezone text not null,
+    offer_ty text not null references item_types("name"),
+    offer_amt bigint not null,
+    bid_ty text not null references item_types("name"),
+    bid_min bigint not null,
+    duration_minutes int not null,
+    multi_unit boolean not null,
+    enabled boolean not null default true,
+    last_run timestamptz not null,
+    changed_at timestamptz not null,
+    changed_by_motion bigint references motions(rowid),
+    constraint auto_auction_positive check (offer_amt > 0 and bid_min > 0 and duration_minutes > 0)
+);
+
+-- The weekly auction that used to be hardcoded: 10 gens, by the unit, at 7am Pacific on the day of the week it last
+-- ran, if it ever did.
+insert into auto_auctions (name, schedule, timezone, offer_ty, offer_amt, bid_ty, bid_min, duration_minutes, multi_unit, enabled, last_run, changed_at)
+select
+    'weekly',
+    '0 7 * * ' || coalesce(extract(dow from last_auto_auction at time zone 'US/Pacific')::int, 0),
+    'US/Pacific',
+    'gen',
+    10,
+    'pc',
+    1,
+    96 * 60,
+    true,
+    last_auto_auction is not null,
+    coalesce(last_auto_auction, now()),
+    now()
+from single;
+
+alter table single drop column last_auto_auction;
+
+drop view auction_and_winner;
+create view auction_and_winner as
+  select
+    a.rowid as auction_id,
+    a.created_at,
+    a.auctioneer,
+    a.offer_ty,
+    a.offer_amt,
+    a.bid_ty,
+    a.bid_min,
+    a.finished,
+    a.max_bid_user,
+    a.max_bid_amt,
+    COALESCE(t.happened_at, a.created_at) as last_change,
+    t.rowid as transfer_id,
+    t.from_user as winner_id,
+    t.quantity as winner_bid,
+    t.happened_at as bid_at,
+    a.last_timer_bump,
+    a.offer_item,
+    i.name as offer_item_name,
+    a.sealed,
+    a.dutch_start,
+    a.dutch_drop,
+    a.dutch_every_minutes,
+    a.dutch_price,
+    a.multi_unit,
+    a.clearing_price,
+    a.duration_minutes
+  from
+    auctions a
+  left join items i on i.rowid = a.offer_item
+  left join lateral
+    (select * from transfers where auction_id = a.rowid and transfer_ty = 'auction_reserve' order by happened_at desc limit 1) t
+  on true
+;
@@ -0,0 +1,48 @@
+drop view auction_and_winner;
+
+alter table auctions
+    drop constraint bought_at_buy_now,
+    drop constraint buy_now_above_reserve,
+    drop constraint reserve_above_min,
+    drop constraint reserve_buy_now_open,
+    drop column cancelled_at,
+    drop column bought_at,
+    drop column buy_now_price,
+    drop column reserve_price
+;
+
+create view auction_and_winner as
+  select
+    a.rowid as auction_id,
+    a.created_at,
+    a.auctioneer,
+    a.offer_ty,
+    a.offer_amt,
+    a.bid_ty,
+    a.bid_min,
+    a.finished,
+    a.max_bid_user,
+    a.max_bid_amt,
+    COALESCE(t.happened_at, a.created_at) as last_change,
+    t.rowid as transfer_id,
+    t.from_user as winner_id,
+    t.quantity as winner_bid,
+    t.happened_at as bid_at,
+    a.last_timer_bump,
+    a.offer_item,
+    i.name as offer_item_name,
+    a.sealed,
+    a.dutch_start,
+    a.dutch_drop,
+    a.dutch_every_minutes,
+    a.dutch_price,
+    a.multi_unit,
+    a.clearing_price,
+    a.duration_minutes
+  from
+    auctions a
+  left join items i on i.rowid = a.offer_item
+  left join lateral
+    (select * from transfers where auction_id = a.rowid and transfer_ty = 'auction_reserve' order by happened_at desc limit 1) t
+  on true
+;
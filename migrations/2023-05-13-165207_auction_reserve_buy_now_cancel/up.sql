@@ -0,0 +1,57 @@
+-- An open auction can have a hidden reserve price, below which the lot isn't sold, and a buy-it-now price that anyone
+-- can pay to end it right away, until the bidding reaches it. Either one ends the auction early: `bought_at` when
+-- someone buys it now, `cancelled_at` when the auctioneer cancels it before anyone bids. The worker then pays out or
+-- returns the lot as usual.
+alter table auctions
+    add column reserve_price bigint,
+    add column buy_now_price bigint,
+    add column bought_at timestamptz,
+    add column cancelled_at timestamptz,
+    add constraint reserve_buy_now_open check (
+        (reserve_price is null and buy_now_price is null) or (not sealed and not multi_unit and dutch_start is null)
+    ),
+    add constraint reserve_above_min check (reserve_price is null or reserve_price >= bid_min),
+    add constraint buy_now_above_reserve check (buy_now_price is null or buy_now_price >= greatest(bid_min, reserve_price)),
+    add constraint bought_at_buy_now check (bought_at is null or buy_now_price is not null)
+;
+
+drop view auction_and_winner;
+create view auction_and_winner as
+  select
+    a.rowid as auction_id,
+    a.created_at,
+    a.auctioneer,
+    a.offer_ty,
+    a.offer_amt,
+    a.bid_ty,
+    a.bid_min,
+    a.finished,
+    a.max_bid_user,
+    a.max_bid_amt,
+    COALESCE(t.happened_at, a.created_at) as last_change,
+    t.rowid as transfer_id,
+    t.from_user as winner_id,
+    t.quantity as winner_bid,
+    t.happened_at as bid_at,
+    a.last_timer_bump,
+    a.offer_item,
+    i.name as offer_item_name,
+    a.sealed,
+    a.dutch_start,
+    a.dutch_drop,
+    a.dutch_every_minutes,
+    a.dutch_price,
+    a.multi_unit,
+    a.clearing_price,
+    a.duration_minutes,
+    a.reserve_price,
+    a.buy_now_price,
+    a.bought_at,
+    a.cancelled_at
+  from
+    auctions a
+  left join items i on i.rowid = a.offer_item
+  left join lateral
+    (select * from transfers where auction_id = a.rowid and transfer_ty = 'auction_reserve' order by happened_at desc limit 1) t
+  on true
+;
@@ -0,0 +1,57 @@
+-- An open auction can end at a fixed time, `duration_minutes` (or the default) after it starts, instead of that long
+-- after the last new high bidder. A new high bid in the final `snipe_window_minutes` then pushes the end out to
+-- `snipe_extend_minutes` after that bid, by bumping `last_timer_bump`. Both zero is a hard close.
+alter table auctions
+    add column snipe_window_minutes int,
+    add column snipe_extend_minutes int,
+    add constraint snipe_both check ((snipe_window_minutes is null) = (snipe_extend_minutes is null)),
+    add constraint snipe_sensible check (
+        (snipe_window_minutes = 0 and snipe_extend_minutes = 0) or (snipe_window_minutes > 0 and snipe_extend_minutes > 0)
+    ),
+    add constraint snipe_open check (
+        snipe_window_minutes is null or (not sealed and not multi_unit and dutch_start is null)
+    )
+;
+
+drop view auction_and_winner;
+create view auction_and_winner as
+  select
+    a.rowid as auction_id,
+    a.created_at,
+    a.auctioneer,
+    a.offer_ty,
+    a.offer_amt,
+    a.bid_ty,
+    a.bid_min,
+    a.finished,
+    a.max_bid_user,
+    a.max_bid_amt,
+    COALESCE(t.happened_at, a.created_at) as last_change,
+    t.rowid as transfer_id,
+    t.from_user as winner_id,
+    t.quantity as winner_bid,
+    t.happened_at as bid_at,
+    a.last_timer_bump,
+    a.offer_item,
+    i.name as offer_item_name,
+    a.sealed,
+    a.dutch_start,
+    a.dutch_drop,
+    a.dutch_every_minutes,
+    a.dutch_price,
+    a.multi_unit,
+    a.clearing_price,
+    a.duration_minutes,
+    a.reserve_price,
+    a.buy_now_price,
+    a.bought_at,
+    a.cancelled_at,
+    a.snipe_window_minutes,
+    a.snipe_extend_minutes
+  from
+    auctions a
+  left join items i on i.rowid = a.offer_item
+  left join lateral
+    (select * from transfers where auction_id = a.rowid and transfer_ty = 'auction_reserve' order by happened_at desc limit 1) t
+  on true
+;
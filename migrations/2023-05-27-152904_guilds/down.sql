@@ -0,0 +1,55 @@
+alter table pending_gives drop column economy;
+
+alter table motions
+    drop column channel_id,
+    drop column guild_id,
+    drop column economy
+;
+
+drop view balance_history;
+
+alter table transfers drop column economy;
+
+create view balance_history as
+  select
+    "rowid",
+    "from_user" as user,
+    "from_balance" as balance,
+    "quantity",
+    -1 as sign,
+    "happened_at",
+    "ty",
+    "comment",
+    "to_user" as other_party,
+    "message_id",
+    "to_motion",
+    "to_votes",
+    "transfer_ty",
+    "auction_id",
+    "thing_id"
+  from transfers 
+  where
+    "from_user" is not null
+  union all
+  select
+    "rowid",
+    "to_user" as user,
+    "to_balance" as balance,
+    "quantity",
+    1 as sign,
+    "happened_at",
+    "ty",
+    "comment",
+    "from_user" as other_party,
+    "message_id",
+    "to_motion",
+    "to_votes",
+    "transfer_ty",
+    "auction_id",
+    "thing_id"
+  from transfers
+  where
+    "to_user" is not null
+;
+
+drop table guilds;
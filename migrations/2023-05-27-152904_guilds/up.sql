@@ -0,0 +1,76 @@
+-- Settings for each discord guild the bot is in. A guild without a row uses the defaults: motions go to the
+-- bureaucracy channel, the prefix is $, and it shares the economy.
+create table guilds (
+    guild_id bigint primary key,
+    -- where motions called in this guild are posted
+    motions_channel bigint,
+    -- where the results of its motions are announced, its motions channel if null
+    announcement_channel bigint,
+    command_prefix text not null default '$',
+    -- members with this role can change these settings and mint in an isolated economy
+    admin_role bigint,
+    -- whether the guild has its own ledger and motions, separate from everyone else's
+    isolated boolean not null default false,
+    constraint command_prefix_sensible check (length(command_prefix) between 1 and 5 and command_prefix !~ '\s')
+);
+
+-- An economy is either the shared one, 0, or an isolated guild's own, numbered by its guild id. Balances, motions,
+-- and pending gives only ever involve one economy. Everything else (auctions, trades, loans, the market, and so on)
+-- is only in the shared economy.
+drop view balance_history;
+
+alter table transfers add column economy bigint not null default 0;
+
+create view balance_history as
+  select
+    "rowid",
+    "from_user" as user,
+    "from_balance" as balance,
+    "quantity",
+    -1 as sign,
+    "happened_at",
+    "ty",
+    "comment",
+    "to_user" as other_party,
+    "message_id",
+    "to_motion",
+    "to_votes",
+    "transfer_ty",
+    "auction_id",
+    "thing_id",
+    "economy"
+  from transfers 
+  where
+    "from_user" is not null
+  union all
+  select
+    "rowid",
+    "to_user" as user,
+    "to_balance" as balance,
+    "quantity",
+    1 as sign,
+    "happened_at",
+    "ty",
+    "comment",
+    "from_user" as other_party,
+    "message_id",
+    "to_motion",
+    "to_votes",
+    "transfer_ty",
+    "auction_id",
+    "thing_id",
+    "economy"
+  from transfers
+  where
+    "to_user" is not null
+;
+
+alter table motions
+    add column economy bigint not null default 0,
+    -- where the motion was called, null for a DM
+    add column guild_id bigint,
+    -- the channel bot_message_id is in, null for the bureaucracy channel
+    add column channel_id bigint
+;
+
+alter table pending_gives add column economy bigint not null default 0;
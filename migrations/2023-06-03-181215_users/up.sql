@@ -0,0 +1,34 @@
+-- Everyone the bot or the website has seen, so names can be shown instead of ids. Kept current from discord logins,
+-- guild member events, and the bot's cache.
+create table users (
+    discord_id bigint primary key,
+    -- the discord username, or a fixed name for the special accounts below
+    name text not null,
+    updated_at timestamptz not null default now(),
+    constraint name_not_empty check (length(name) > 0)
+);
+
+-- The special accounts and everyone who used to be listed in names.rs, until they're next seen
+insert into users (discord_id, name) values
+    (0, 'TheOtherPitOfFire'),
+    (1, 'PitOfFire'),
+    (2, 'Dos'),
+    (3, 'Three'),
+    (4, 'Four'),
+    (5, 'The CONsortium'),
+    (113455311058108416, 'bowlercaptain'),
+    (125003180219170816, 'Colin'),
+    (155438323354042368, 'Ben'),
+    (165858230327574528, 'Shelvacu'),
+    (175691653770641409, 'DDR'),
+    (173650493145350145, 'Sparks'),
+    (182663630280589312, 'Azure'),
+    (189163550890000384, 'hemaglox'),
+    (189620154122895360, 'Leeli'),
+    (240939050360504320, 'InvisiBrony'),
+    (265905786469744640, 'AtomicTaco'),
+    (271540455584301057, 'Anthony'),
+    (308798067023544320, 'Razgriz'),
+    (359950109229973504, 'ManganeseFrog'),
+    (368635452925935616, 'TopHatimus'),
+    (373610438560317441, 'Matt');
@@ -0,0 +1,32 @@
+use diesel::prelude::*;
+use crate::schema::admins::dsl as adsl;
+use crate::models::UserId;
+
+/// Admins are the owner of the guild the motions channel is in and its members with that guild's admin role. The bot
+/// checks discord itself; this is its copy for the website, which can't see roles.
+pub fn is_admin(
+    conn: &diesel::PgConnection,
+    user: UserId,
+) -> QueryResult<bool> {
+    diesel::select(diesel::dsl::exists(
+        adsl::admins.filter(adsl::user.eq(user))
+    )).get_result(conn)
+}
+
+/// Replaces the recorded admins with `users`
+pub fn sync(
+    conn: &diesel::PgConnection,
+    users: &[UserId],
+) -> QueryResult<()> {
+    conn.transaction(|| {
+        diesel::delete(adsl::admins.filter(adsl::user.ne_all(users.to_vec()))).execute(conn)?;
+        if !users.is_empty() {
+            let rows:Vec<_> = users.iter().map(|user| adsl::user.eq(*user)).collect();
+            diesel::insert_into(adsl::admins)
+                .values(&rows)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,1549 @@
+use chrono::{DateTime,Utc};
+use diesel::prelude::*;
+use crate::schema::auctions::dsl as adsl;
+use crate::schema::auction_events::dsl as aedsl;
+use crate::schema::auction_watchers::dsl as awdsl;
+use crate::schema::sealed_bids::dsl as sbdsl;
+use crate::schema::unit_bids::dsl as ubdsl;
+use crate::schema::thing_ids::dsl as tid;
+use crate::view_schema::auction_and_winner::dsl as anw;
+use crate::models::{UserId,AuctionWinner,AuctionEvent,SealedBid,UnitBid,AUCTION_EVENT_OUTBID,AUCTION_EVENT_MAX_BID_EXHAUSTED};
+use crate::transfers::{CurrencyId,TransactionBuilder,TransferHandler,TransferError};
+use crate::items::ItemError;
+use crate::notifications::notify;
+use crate::names::name_of;
+
+pub const CREATE_USAGE:&str = "Usage: `$auction <amount> <type> for <minimum bid> <type> [sealed]` or `$auction item#<id> for <minimum bid> <type> [sealed]`. \
+For a dutch auction, end with `dutch from <starting price> dropping <amount> every <n> minutes|hours` instead of `sealed`. \
+To sell fungibles by the unit, end with `each` instead, and the minimum bid is per unit. \
+An auction with open bidding can also have a hidden `reserve <price>`, a `buy now <price>`, \
+and a fixed end with `ends in <n> minutes|hours|days [extending <m> minutes [within <k> minutes]]`.";
+pub const BID_USAGE:&str = "Usage: `$bid <auction id> <amount> [max]`, or `$bid <auction id> <quantity> at <price each>` for an auction sold by the unit";
+
+/// How a dutch auction's price falls: from `start` by `drop` every `every_minutes`, down to the minimum bid
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct DutchSchedule {
+    pub start: i64,
+    pub drop: i64,
+    pub every_minutes: i32,
+}
+
+/// Ends `after_minutes` after it starts, rather than that long after the last new high bidder. A new high bid in the
+/// final `window_minutes` pushes the end out to `extend_minutes` after that bid; both zero is a hard close.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct FixedEnd {
+    pub after_minutes: i32,
+    pub window_minutes: i32,
+    pub extend_minutes: i32,
+}
+
+/// Extras only an auction with open bidding can have
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Default)]
+pub struct OpenTerms {
+    /// Hidden from bidders; if the bidding doesn't reach it, nothing is sold
+    pub reserve_price: Option<i64>,
+    /// Anyone can pay this to win right away, until the bidding reaches it
+    pub buy_now_price: Option<i64>,
+    pub fixed_end: Option<FixedEnd>,
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum AuctionKind {
+    /// Ascending bids, with max bids, and the timer bumped by every new high bidder
+    Open(OpenTerms),
+    /// See `place_sealed_bid`
+    Sealed,
+    Dutch(DutchSchedule),
+    /// See `clear_units`
+    MultiUnit,
+}
+
+impl AuctionKind {
+    /// Adds a reserve and buy-it-now price, which only an open auction can have
+    pub fn with_terms(self, terms: OpenTerms) -> Result<AuctionKind, AuctionError> {
+        match self {
+            AuctionKind::Open(_) => Ok(AuctionKind::Open(terms)),
+            _ if terms == OpenTerms::default() => Ok(self),
+            _ => Err(AuctionError::NotOpen),
+        }
+    }
+}
+
+/// What a user is putting up for auction
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum NewLot {
+    Fungible{amount: i64, ty: CurrencyId},
+    Item{item_id: i64},
+}
+
+#[derive(Debug)]
+pub enum AuctionError {
+    /// Bidding on some of a fungible with more of the same
+    SameType,
+    NotPositive,
+    /// The auctioneer doesn't have the lot they're offering
+    NotEnough,
+    Overflow,
+    Item(ItemError),
+    NotFound,
+    Ended,
+    /// Not more than the current bid
+    BidTooLow,
+    BelowMinimum,
+    /// The bidder can't cover their bid in the given type
+    CantAfford(CurrencyId),
+    /// Max bids and watching make no sense when bids are secret
+    Sealed,
+    /// Dutch auctions are bought outright rather than bid on
+    Dutch,
+    /// Neither a dutch auction nor one with a buy-it-now price that the bidding hasn't reached
+    NoBuyNow,
+    BadDutchSchedule,
+    /// The reserve must be at least the minimum bid, and the buy-it-now price at least both
+    BadReserve,
+    BadFixedEnd,
+    /// Reserves, buy-it-now prices and fixed ends are only for auctions with open bidding
+    NotOpen,
+    /// Only the auctioneer can cancel their auction
+    NotAuctioneer,
+    /// Auctioneers can't bid on or buy their own lot
+    OwnAuction,
+    /// Someone has already bid, so it can't be cancelled
+    HasBids,
+    /// Multi-unit auctions are bid on with a quantity and a price each
+    MultiUnit,
+    NotMultiUnit,
+    MultiUnitItem,
+    TooManyUnits,
+    Db(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for AuctionError {
+    fn from(e: diesel::result::Error) -> Self {
+        AuctionError::Db(e)
+    }
+}
+
+impl From<TransferError> for AuctionError {
+    fn from(e: TransferError) -> Self {
+        match e {
+            TransferError::NotEnough => AuctionError::NotEnough,
+            TransferError::Overflow => AuctionError::Overflow,
+        }
+    }
+}
+
+impl From<ItemError> for AuctionError {
+    fn from(e: ItemError) -> Self {
+        match e {
+            ItemError::Db(e) => AuctionError::Db(e),
+            e => AuctionError::Item(e),
+        }
+    }
+}
+
+impl std::fmt::Display for AuctionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuctionError::SameType => write!(f, "Bids must be in a different type than what's being auctioned."),
+            AuctionError::NotPositive => write!(f, "The lot and minimum bid must both be more than nothing."),
+            AuctionError::NotEnough => write!(f, "You don't have enough to put that up for auction."),
+            AuctionError::Overflow => write!(f, "Auction failed: Overflow."),
+            AuctionError::Item(e) => write!(f, "{}", e),
+            AuctionError::NotFound => write!(f, "Auction not found."),
+            AuctionError::Ended => write!(f, "That auction has ended."),
+            AuctionError::BidTooLow => write!(f, "Bid failed: You must bid more than that."),
+            AuctionError::BelowMinimum => write!(f, "Your bid is below the minimum bid amount."),
+            AuctionError::CantAfford(ty) => write!(f, "Bid failed: You do not have enough {}.", ty),
+            AuctionError::Sealed => write!(f, "Bids on a sealed auction are secret until it ends, so there are no max bids or bid notifications."),
+            AuctionError::Dutch => write!(f, "That's a dutch auction; buy it at the current price with `$buy_now`."),
+            AuctionError::NoBuyNow => write!(f, "That auction can't be bought outright."),
+            AuctionError::BadDutchSchedule => write!(f, "A dutch auction must start above its minimum bid, and drop by some amount every so many minutes."),
+            AuctionError::BadReserve => write!(f, "The reserve price can't be below the minimum bid, and the buy-it-now price can't be below either."),
+            AuctionError::BadFixedEnd => write!(f, "An auction must last some time, and a late bid must extend it by some time if bids in the last few minutes extend it at all."),
+            AuctionError::NotOpen => write!(f, "Only auctions with open bidding can have a reserve price, a buy-it-now price, or a fixed end."),
+            AuctionError::NotAuctioneer => write!(f, "Only the auctioneer can cancel an auction."),
+            AuctionError::OwnAuction => write!(f, "You can't bid on or buy your own auction."),
+            AuctionError::HasBids => write!(f, "Someone has already bid, so the auction can't be cancelled."),
+            AuctionError::MultiUnit => write!(f, "That auction sells by the unit; bid with `$bid <auction id> <quantity> at <price each>`."),
+            AuctionError::NotMultiUnit => write!(f, "That auction doesn't sell by the unit; bid with `$bid <auction id> <amount>`."),
+            AuctionError::MultiUnitItem => write!(f, "Only fungibles can be sold by the unit."),
+            AuctionError::TooManyUnits => write!(f, "You can't bid for more units than are up for auction."),
+            AuctionError::Db(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AuctionError {}
+
+/// Starts an auction of the auctioneer's own fungibles or item, taking the lot into escrow until it finishes. Returns
+/// the auction's id.
+pub fn create(
+    conn: &diesel::PgConnection,
+    auctioneer: UserId,
+    lot: NewLot,
+    bid_ty: CurrencyId,
+    bid_min: i64,
+    kind: AuctionKind,
+    now: DateTime<Utc>,
+) -> Result<i64, AuctionError> {
+    if bid_min <= 0 {
+        return Err(AuctionError::NotPositive);
+    }
+    let dutch = match kind {
+        AuctionKind::Dutch(schedule) => {
+            if schedule.start <= bid_min || schedule.drop <= 0 || schedule.every_minutes <= 0 {
+                return Err(AuctionError::BadDutchSchedule);
+            }
+            Some(schedule)
+        },
+        _ => None,
+    };
+    let terms = match kind {
+        AuctionKind::Open(terms) => {
+            let reserve_ok = terms.reserve_price.map(|r| r >= bid_min).unwrap_or(true);
+            let buy_now_ok = terms.buy_now_price.map(|b| b >= bid_min.max(terms.reserve_price.unwrap_or(0))).unwrap_or(true);
+            if !reserve_ok || !buy_now_ok {
+                return Err(AuctionError::BadReserve);
+            }
+            if let Some(end) = terms.fixed_end {
+                let extends = end.window_minutes > 0 && end.extend_minutes > 0;
+                let hard_close = end.window_minutes == 0 && end.extend_minutes == 0;
+                if end.after_minutes <= 0 || !(extends || hard_close) {
+                    return Err(AuctionError::BadFixedEnd);
+                }
+            }
+            terms
+        },
+        _ => OpenTerms::default(),
+    };
+    let sealed = kind == AuctionKind::Sealed;
+    let multi_unit = kind == AuctionKind::MultiUnit;
+    if multi_unit && matches!(lot, NewLot::Item{..}) {
+        return Err(AuctionError::MultiUnitItem);
+    }
+    if let NewLot::Fungible{amount, ref ty} = lot {
+        if amount <= 0 {
+            return Err(AuctionError::NotPositive);
+        }
+        if *ty == bid_ty {
+            return Err(AuctionError::SameType);
+        }
+    }
+    conn.transaction(|| {
+        let auction_id:i64 = diesel::insert_into(tid::thing_ids).default_values().returning(tid::rowid).get_result(conn)?;
+        match lot {
+            NewLot::Fungible{amount, ty} => {
+                diesel::insert_into(adsl::auctions).values((
+                    adsl::rowid.eq(auction_id),
+                    adsl::created_at.eq(now),
+                    adsl::auctioneer.eq(auctioneer.into_i64()),
+                    adsl::offer_ty.eq(&ty),
+                    adsl::offer_amt.eq(amount),
+                    adsl::bid_ty.eq(&bid_ty),
+                    adsl::bid_min.eq(bid_min),
+                    adsl::last_timer_bump.eq(now),
+                    adsl::sealed.eq(sealed),
+                    adsl::dutch_start.eq(dutch.map(|d| d.start)),
+                    adsl::dutch_drop.eq(dutch.map(|d| d.drop)),
+                    adsl::dutch_every_minutes.eq(dutch.map(|d| d.every_minutes)),
+                    adsl::dutch_price.eq(dutch.map(|d| d.start)),
+                    adsl::multi_unit.eq(multi_unit),
+                    adsl::reserve_price.eq(terms.reserve_price),
+                    adsl::buy_now_price.eq(terms.buy_now_price),
+                    adsl::duration_minutes.eq(terms.fixed_end.map(|e| e.after_minutes)),
+                    adsl::snipe_window_minutes.eq(terms.fixed_end.map(|e| e.window_minutes)),
+                    adsl::snipe_extend_minutes.eq(terms.fixed_end.map(|e| e.extend_minutes)),
+                )).execute(conn)?;
+                let t = TransactionBuilder::new(
+                    amount,
+                    ty,
+                    now,
+                ).auction_create(auctioneer, auction_id);
+                TransferHandler::handle_single(conn, t)??;
+            },
+            NewLot::Item{item_id} => {
+                diesel::insert_into(adsl::auctions).values((
+                    adsl::rowid.eq(auction_id),
+                    adsl::created_at.eq(now),
+                    adsl::auctioneer.eq(auctioneer.into_i64()),
+                    adsl::offer_item.eq(item_id),
+                    adsl::bid_ty.eq(&bid_ty),
+                    adsl::bid_min.eq(bid_min),
+                    adsl::last_timer_bump.eq(now),
+                    adsl::sealed.eq(sealed),
+                    adsl::dutch_start.eq(dutch.map(|d| d.start)),
+                    adsl::dutch_drop.eq(dutch.map(|d| d.drop)),
+                    adsl::dutch_every_minutes.eq(dutch.map(|d| d.every_minutes)),
+                    adsl::dutch_price.eq(dutch.map(|d| d.start)),
+                    adsl::multi_unit.eq(multi_unit),
+                    adsl::reserve_price.eq(terms.reserve_price),
+                    adsl::buy_now_price.eq(terms.buy_now_price),
+                    adsl::duration_minutes.eq(terms.fixed_end.map(|e| e.after_minutes)),
+                    adsl::snipe_window_minutes.eq(terms.fixed_end.map(|e| e.window_minutes)),
+                    adsl::snipe_extend_minutes.eq(terms.fixed_end.map(|e| e.extend_minutes)),
+                )).execute(conn)?;
+                crate::items::escrow_for_auction(conn, item_id, auctioneer, auction_id, now)?;
+            },
+        }
+        Ok(auction_id)
+    })
+}
+
+#[derive(Debug,Clone)]
+pub enum BidStanding {
+    Open{winner: UserId, winning_bid: i64, max_bid: Option<(UserId, i64)>},
+    /// Nobody knows where they stand until a sealed auction ends
+    Sealed{your_bid: i64},
+    /// How a multi-unit auction would clear if it ended now
+    Units{quantity: i64, would_get: i64, clearing_price: Option<i64>},
+}
+
+/// Where an auction stands after a bid.
+#[derive(Debug,Clone)]
+pub struct BidOutcome {
+    /// Describes what happened, addressed to the bidder
+    pub message: String,
+    pub bid_ty: CurrencyId,
+    pub standing: BidStanding,
+}
+
+impl BidOutcome {
+    /// Whether `user` is winning and what their max bid is, addressed to them.
+    pub fn position_for(&self, user: UserId) -> String {
+        let (winner, winning_bid, max_bid) = match self.standing {
+            BidStanding::Open{winner, winning_bid, max_bid} => (winner, winning_bid, max_bid),
+            BidStanding::Sealed{your_bid} => return format!(
+                "Your sealed bid is {}{}. All bids are revealed when the auction ends.",
+                your_bid,
+                self.bid_ty,
+            ),
+            BidStanding::Units{quantity, would_get, clearing_price} => return format!(
+                "If the auction ended now you'd get {} of the {} you bid for{}.",
+                would_get,
+                quantity,
+                clearing_price.map(|p| format!(", and everyone who got any would pay {}{} each", p, self.bid_ty)).unwrap_or_default(),
+            ),
+        };
+        let position = if winner == user {
+            format!("You are the high bidder at {}{}.", winning_bid, self.bid_ty)
+        } else {
+            format!(
+                "You are not winning; {} is the high bidder at {}{}.",
+                name_of(winner),
+                winning_bid,
+                self.bid_ty,
+            )
+        };
+        match max_bid {
+            Some((max_user, amount)) if max_user == user => format!("{} Your max bid is {}{}.", position, amount, self.bid_ty),
+            _ => format!("{} You have no max bid.", position),
+        }
+    }
+}
+
+/// Bidding on or buying your own lot would let an auctioneer push up the price, or get round the reserve and the rules
+/// for cancelling, so it's refused
+pub fn check_not_auctioneer(auctioneer: Option<i64>, user: UserId) -> Result<(), AuctionError> {
+    if auctioneer == Some(user.into_i64()) {
+        Err(AuctionError::OwnAuction)
+    } else {
+        Ok(())
+    }
+}
+
+/// What a max bid of `max` bids for its owner when it needs to be at least `floor`. With a reserve it goes straight to the
+/// reserve, or as close as `max` allows, so that a max bid above the reserve can't win below it.
+pub fn proxy_bid(floor: i64, max: i64, reserve_price: Option<i64>) -> i64 {
+    match reserve_price {
+        Some(reserve) if reserve > floor => reserve.min(max),
+        _ => floor,
+    }
+}
+
+/// Places a bid of `amount`, or sets a max bid of `amount` if `is_max_bid`, which bids automatically on the bidder's
+/// behalf up to that amount. Used by both the web and discord.
+pub fn bid(
+    conn: &diesel::PgConnection,
+    auction_id: i64,
+    challenger_id: UserId,
+    amount: i64,
+    is_max_bid: bool,
+    now: DateTime<Utc>,
+) -> Result<BidOutcome, AuctionError> {
+    if amount <= 0 {
+        return Err(AuctionError::NotPositive);
+    }
+    conn.transaction(|| {
+        // This needs to be a separate statement from the AuctionWinner because AuctionWinner joins multiple tables
+        // and thus cannot be locked with .for_update().
+        let maybe_auction_id:Option<i64> = adsl::auctions
+            .select(adsl::rowid)
+            .filter(adsl::rowid.eq(auction_id))
+            .for_update()
+            .get_result(conn)
+            .optional()?;
+
+        if maybe_auction_id.is_none() {
+            return Err(AuctionError::NotFound);
+        }
+
+        let auction:AuctionWinner = anw::auction_and_winner
+            .select(AuctionWinner::cols())
+            .filter(anw::auction_id.eq(auction_id))
+            .get_result(conn)?;
+
+        if auction.is_over(now) {
+            return Err(AuctionError::Ended);
+        }
+
+        if auction.sealed {
+            return place_sealed_bid(conn, &auction, challenger_id, amount, is_max_bid, now);
+        }
+
+        check_not_auctioneer(auction.auctioneer, challenger_id)?;
+
+        if auction.is_dutch() {
+            return Err(AuctionError::Dutch);
+        }
+
+        if auction.multi_unit {
+            return Err(AuctionError::MultiUnit);
+        }
+
+        if amount < auction.current_min_bid() {
+            return Err(AuctionError::BidTooLow);
+        }
+
+        let mut to_lock = vec![challenger_id];
+        if let Some(prev_bidder) = auction.winner_id {
+            if prev_bidder != challenger_id {
+                to_lock.push(prev_bidder);
+            }
+        }
+
+        let mut handle = TransferHandler::new(
+            conn,
+            to_lock,
+            vec![auction.bid_ty.clone()],
+        )?;
+
+        let mut challenger_available_balance = handle.balance(challenger_id, auction.bid_ty.clone());
+        if let Some((user, amt)) = auction.winner() {
+            if user == challenger_id {
+                challenger_available_balance = challenger_available_balance.checked_add(amt).unwrap();
+            }
+        }
+
+        // This test must be *before* the bid is tested against the current max bid; Otherwise the bid could be increased if someone else has a higher max bid, regardless of if you have the dough to support it.
+        if challenger_available_balance < amount {
+            return Err(AuctionError::CantAfford(auction.bid_ty.clone()));
+        }
+
+        let maybe_old_bid = auction.winner();
+        let old_max_bid = auction.max_bid().map(|m| (m.user, m.amount));
+
+        let new_bid;
+        let new_max_bid;
+        let mut message;
+
+        if let Some(max_bid_bad) = auction.max_bid() {
+            let max_bid_user = max_bid_bad.user;
+
+            let attempted_max_bid = max_bid_bad.amount;
+            // The highest value that could be the actual max bid
+            let max_max_bid = handle.balance(max_bid_bad.user, max_bid_bad.currency) + auction.winner().unwrap().1;
+            let max_bid_amount = if attempted_max_bid > max_max_bid { max_max_bid } else { attempted_max_bid };
+
+            if challenger_id == max_bid_user {
+                if is_max_bid {
+                    new_bid = (max_bid_user, proxy_bid(auction.winner().unwrap().1, amount, auction.reserve_price));
+                    new_max_bid = Some((max_bid_user, amount));
+                    message = format!(
+                        "You have set your max bid to {amount}{ty}.",
+                        ty = auction.bid_ty,
+                        amount = amount,
+                    );
+                } else if amount >= auction.winner().unwrap().1 {
+                    new_bid = (max_bid_user, amount);
+                    new_max_bid = Some((max_bid_user, attempted_max_bid));
+                    message = format!(
+                        "You have increased your actual bid to {amount}{ty}",
+                        ty = auction.bid_ty,
+                        amount = amount,
+                    );
+                } else {
+                    unreachable!();
+                }
+            } else if amount <= max_bid_amount {
+                new_bid = (max_bid_user, proxy_bid(amount, max_bid_amount, auction.reserve_price));
+                new_max_bid = Some((max_bid_user, attempted_max_bid));
+                message = format!(
+                    "Your bid was not greater than {champion}'s existing max bid; The bid is now at {amount}{ty}.",
+                    ty = auction.bid_ty,
+                    champion = name_of(new_bid.0),
+                    amount = new_bid.1,
+                );
+            } else if is_max_bid {
+                new_bid = (challenger_id, proxy_bid(max_bid_amount.checked_add(1).unwrap(), amount, auction.reserve_price));
+                new_max_bid = Some((challenger_id, amount));
+                message = format!(
+                    "You have set a max bid of {max}{ty}. The bid is now at {amount}{ty}",
+                    ty = auction.bid_ty,
+                    max = amount,
+                    amount = new_bid.1,
+                );
+            } else {
+                new_bid = (challenger_id, amount);
+                new_max_bid = None;
+                message = format!(
+                    "You have successfully bid {amount}{ty}",
+                    ty = auction.bid_ty,
+                    amount = new_bid.1
+                );
+            }
+        } else if let Some(old_bid) = auction.winner() {
+            if amount <= old_bid.1 {
+                new_bid = old_bid;
+                new_max_bid = None;
+                message = format!(
+                    "Your bid is below the current bid of {amount}{ty}.",
+                    ty = auction.bid_ty,
+                    amount = old_bid.1,
+                );
+            } else if is_max_bid {
+                let floor = if challenger_id == old_bid.0 { old_bid.1 } else { old_bid.1.checked_add(1).unwrap() };
+                new_bid = (challenger_id, proxy_bid(floor, amount, auction.reserve_price));
+                new_max_bid = Some((challenger_id, amount));
+                message = format!(
+                    "You have set a max bid of {max}{ty}. The bid is now {amount}{ty}",
+                    ty = auction.bid_ty,
+                    max = amount,
+                    amount = new_bid.1,
+                );
+            } else {
+                new_bid = (challenger_id, amount);
+                new_max_bid = None;
+                message = format!(
+                    "You have successfully bid {amount}{ty}",
+                    ty = auction.bid_ty,
+                    amount = new_bid.1,
+                );
+            }
+        } else if amount >= auction.bid_min {
+            if is_max_bid {
+                new_bid = (challenger_id, proxy_bid(auction.bid_min, amount, auction.reserve_price));
+                new_max_bid = Some((challenger_id, amount));
+                message = format!(
+                    "You have placed the first bid of {amount}{ty}, and set your max bid to {max}{ty}",
+                    ty = auction.bid_ty,
+                    amount = new_bid.1,
+                    max = amount,
+                );
+            } else {
+                new_bid = (challenger_id, amount);
+                new_max_bid = None;
+                message = format!(
+                    "You have placed the first bid of {amount}{ty}",
+                    ty = auction.bid_ty,
+                    amount = amount,
+                );
+            }
+        } else {
+            return Err(AuctionError::BelowMinimum);
+        }
+
+        if maybe_old_bid != Some(new_bid) {
+            if let Some(old_bid) = maybe_old_bid {
+                // refund old bidder
+                let t = TransactionBuilder::new(old_bid.1, auction.bid_ty.clone(), now)
+                    .auction_refund(old_bid.0, &auction);
+                match handle.transfer(t) {
+                    Err(TransferError::NotEnough) => unreachable!("It is nonsensical for an addition of funds to lack sufficient balance."),
+                    Err(TransferError::Overflow) => panic!("The consortium has reached a state such that more than 2^63 fungibles exist, and this code was not designed to handle that"),
+                    Ok(v) => v?,
+                }
+            }
+            // charge new bidder
+            let t = TransactionBuilder::new(new_bid.1, auction.bid_ty.clone(), now)
+                .auction_reserve(new_bid.0, &auction);
+            match handle.transfer(t) {
+                Err(TransferError::NotEnough) => unreachable!("We have a lock on transactions for this user, and already checked they have enough fungibles."),
+                Err(TransferError::Overflow) => panic!("The consortium has reached a state such that more than 2^63 fungibles exist, and this code was not designed to handle that"),
+                Ok(v) => v?,
+            }
+            announce_bid(conn, &auction, challenger_id, maybe_old_bid, old_max_bid, new_bid, now)?;
+        }
+
+        // The timer is bumped iff the winning bidder changed, and for an auction with a fixed end, only near the end
+        let new_timer_bumped = if maybe_old_bid.map(|b| b.0) != Some(new_bid.0) && auction.bid_extends(now) {
+            now
+        } else { auction.last_timer_bump };
+
+        // The reserve itself stays secret, but not whether the bidding has reached it
+        if auction.reserve_price.map(|reserve| new_bid.1 < reserve).unwrap_or(false) {
+            message.push_str(" The reserve price hasn't been met yet.");
+        }
+
+        diesel::update(adsl::auctions.filter(adsl::rowid.eq(auction_id))).set((
+            adsl::last_timer_bump.eq(new_timer_bumped),
+            adsl::max_bid_user.eq(new_max_bid.map(|a| a.0)),
+            adsl::max_bid_amt.eq(new_max_bid.map(|a| a.1)),
+        )).execute(conn)?;
+
+        Ok(BidOutcome{
+            message,
+            bid_ty: auction.bid_ty.clone(),
+            standing: BidStanding::Open{
+                winner: new_bid.0,
+                winning_bid: new_bid.1,
+                max_bid: new_max_bid,
+            },
+        })
+    })
+}
+
+/// Holds a sealed bid, replacing the bidder's earlier one if they had one. `auction` must be locked.
+fn place_sealed_bid(
+    conn: &diesel::PgConnection,
+    auction: &AuctionWinner,
+    bidder: UserId,
+    amount: i64,
+    is_max_bid: bool,
+    now: DateTime<Utc>,
+) -> Result<BidOutcome, AuctionError> {
+    check_not_auctioneer(auction.auctioneer, bidder)?;
+    if is_max_bid {
+        return Err(AuctionError::Sealed);
+    }
+    if amount < auction.bid_min {
+        return Err(AuctionError::BelowMinimum);
+    }
+    let previous:Option<i64> = sbdsl::sealed_bids
+        .select(sbdsl::amount)
+        .filter(sbdsl::auction_id.eq(auction.auction_id))
+        .filter(sbdsl::bidder.eq(bidder))
+        .get_result(conn)
+        .optional()?;
+    let mut handle = TransferHandler::new(
+        conn,
+        vec![bidder],
+        vec![auction.bid_ty.clone()],
+    )?;
+    let available = handle.balance(bidder, auction.bid_ty.clone()).checked_add(previous.unwrap_or(0)).unwrap();
+    if available < amount {
+        return Err(AuctionError::CantAfford(auction.bid_ty.clone()));
+    }
+    if let Some(previous) = previous {
+        let t = TransactionBuilder::new(previous, auction.bid_ty.clone(), now)
+            .sealed_bid_refund(bidder, auction);
+        handle.transfer(t)??;
+    }
+    let t = TransactionBuilder::new(amount, auction.bid_ty.clone(), now)
+        .sealed_bid_escrow(bidder, auction);
+    handle.transfer(t)??;
+    diesel::insert_into(sbdsl::sealed_bids).values((
+        sbdsl::auction_id.eq(auction.auction_id),
+        sbdsl::bidder.eq(bidder),
+        sbdsl::amount.eq(amount),
+        sbdsl::placed_at.eq(now),
+    ))
+        .on_conflict((sbdsl::auction_id, sbdsl::bidder))
+        .do_update()
+        .set((
+            sbdsl::amount.eq(amount),
+            sbdsl::placed_at.eq(now),
+        ))
+        .execute(conn)?;
+    let message = if let Some(previous) = previous {
+        format!("You have replaced your sealed bid of {previous}{ty} with {amount}{ty}.", previous = previous, amount = amount, ty = auction.bid_ty)
+    } else {
+        format!("You have placed a sealed bid of {amount}{ty}.", amount = amount, ty = auction.bid_ty)
+    };
+    Ok(BidOutcome{
+        message,
+        bid_ty: auction.bid_ty.clone(),
+        standing: BidStanding::Sealed{your_bid: amount},
+    })
+}
+
+/// Highest bid first; of equal bids, the one placed first wins.
+pub fn rank_sealed_bids(bids: &mut [SealedBid]) {
+    bids.sort_by(|a, b| b.amount.cmp(&a.amount).then(a.placed_at.cmp(&b.placed_at)));
+}
+
+/// The winner of ranked sealed bids and what they pay: the second-highest bid, or the minimum if nobody else bid.
+pub fn sealed_price(ranked: &[SealedBid], bid_min: i64) -> Option<(UserId, i64)> {
+    let winner = ranked.first()?;
+    let price = ranked.get(1).map(|b| b.amount).unwrap_or(bid_min);
+    Some((winner.bidder, price))
+}
+
+/// Ranked highest first
+pub fn sealed_bids(
+    conn: &diesel::PgConnection,
+    auction_id: i64,
+) -> QueryResult<Vec<SealedBid>> {
+    let mut bids:Vec<SealedBid> = sbdsl::sealed_bids
+        .select(SealedBid::cols())
+        .filter(sbdsl::auction_id.eq(auction_id))
+        .get_results(conn)?;
+    rank_sealed_bids(&mut bids);
+    Ok(bids)
+}
+
+pub fn sealed_bid_of(
+    conn: &diesel::PgConnection,
+    auction_id: i64,
+    bidder: UserId,
+) -> QueryResult<Option<i64>> {
+    sbdsl::sealed_bids
+        .select(sbdsl::amount)
+        .filter(sbdsl::auction_id.eq(auction_id))
+        .filter(sbdsl::bidder.eq(bidder))
+        .get_result(conn)
+        .optional()
+}
+
+/// Gives back every sealed bid, then charges the winner their price as an ordinary winning bid so the auction can
+/// finish like any other. `handle` must have every bidder locked.
+pub fn settle_sealed(
+    handle: &mut TransferHandler,
+    auction: &AuctionWinner,
+    ranked: &[SealedBid],
+    now: DateTime<Utc>,
+) -> QueryResult<()> {
+    for bid in ranked {
+        let t = TransactionBuilder::new(bid.amount, auction.bid_ty.clone(), now)
+            .sealed_bid_refund(bid.bidder, auction);
+        handle.transfer(t).unwrap()?;
+    }
+    if let Some((winner, price)) = sealed_price(ranked, auction.bid_min) {
+        let t = TransactionBuilder::new(price, auction.bid_ty.clone(), now)
+            .auction_reserve(winner, auction);
+        handle.transfer(t).unwrap()?;
+    }
+    Ok(())
+}
+
+/// Logs and DMs the bidder who lost the high bid, if anyone did, and tells watchers about the new bid.
+fn announce_bid(
+    conn: &diesel::PgConnection,
+    auction: &AuctionWinner,
+    bidder: UserId,
+    old_bid: Option<(UserId, i64)>,
+    old_max_bid: Option<(UserId, i64)>,
+    new_bid: (UserId, i64),
+    now: DateTime<Utc>,
+) -> QueryResult<()> {
+    let outbid = old_bid.filter(|(user, _)| *user != new_bid.0);
+    if let Some((loser, lost_bid)) = outbid {
+        let exhausted_max = old_max_bid.filter(|(user, _)| *user == loser).map(|(_, amount)| amount);
+        let (kind, amount) = match exhausted_max {
+            Some(max) => (AUCTION_EVENT_MAX_BID_EXHAUSTED, max),
+            None => (AUCTION_EVENT_OUTBID, lost_bid),
+        };
+        diesel::insert_into(aedsl::auction_events).values((
+            aedsl::auction_id.eq(auction.auction_id),
+            aedsl::happened_at.eq(now),
+            aedsl::kind.eq(kind),
+            aedsl::user.eq(loser),
+            aedsl::amount.eq(amount),
+        )).execute(conn)?;
+        let what = if let Some(max) = exhausted_max {
+            format!("Your max bid of {} {} on auction#{} has been exhausted", max, auction.bid_ty, auction.damm())
+        } else {
+            format!("You've been outbid on auction#{}", auction.damm())
+        };
+        notify(conn, loser, format!(
+            "{what} ({lot}): {winner} now has the high bid of {amount} {ty}, and your {lost} {ty} has been returned. \
+            Bid again with `$bid {id} <amount>` or at {site}/auctions/{id}",
+            what = what,
+            lot = auction.lot_description(),
+            winner = name_of(new_bid.0),
+            amount = new_bid.1,
+            lost = lost_bid,
+            ty = auction.bid_ty,
+            id = auction.damm(),
+            site = crate::SITE_URL,
+        ), now)?;
+    }
+
+    let watchers:Vec<UserId> = awdsl::auction_watchers
+        .select(awdsl::user)
+        .filter(awdsl::auction_id.eq(auction.auction_id))
+        .get_results(conn)?;
+    for watcher in watchers {
+        if watcher == bidder || watcher == new_bid.0 || Some(watcher) == outbid.map(|(user, _)| user) {
+            continue;
+        }
+        notify(conn, watcher, format!(
+            "New bid on auction#{id} ({lot}): {amount} {ty} by {winner}. {site}/auctions/{id}",
+            id = auction.damm(),
+            lot = auction.lot_description(),
+            amount = new_bid.1,
+            ty = auction.bid_ty,
+            winner = name_of(new_bid.0),
+            site = crate::SITE_URL,
+        ), now)?;
+    }
+    Ok(())
+}
+
+/// Starts telling `user` about new bids on an auction. Watching an auction twice is fine.
+pub fn watch(
+    conn: &diesel::PgConnection,
+    auction_id: i64,
+    user: UserId,
+    now: DateTime<Utc>,
+) -> Result<(), AuctionError> {
+    let (finished, sealed, dutch, multi_unit):(bool, bool, bool, bool) = adsl::auctions
+        .select((adsl::finished, adsl::sealed, adsl::dutch_price.is_not_null(), adsl::multi_unit))
+        .filter(adsl::rowid.eq(auction_id))
+        .get_result(conn)
+        .optional()?
+        .ok_or(AuctionError::NotFound)?;
+    if finished {
+        return Err(AuctionError::Ended);
+    }
+    if sealed {
+        return Err(AuctionError::Sealed);
+    }
+    if dutch {
+        return Err(AuctionError::Dutch);
+    }
+    if multi_unit {
+        return Err(AuctionError::MultiUnit);
+    }
+    diesel::insert_into(awdsl::auction_watchers).values((
+        awdsl::auction_id.eq(auction_id),
+        awdsl::user.eq(user),
+        awdsl::created_at.eq(now),
+    )).on_conflict_do_nothing().execute(conn)?;
+    Ok(())
+}
+
+/// Returns whether they were watching it
+pub fn unwatch(
+    conn: &diesel::PgConnection,
+    auction_id: i64,
+    user: UserId,
+) -> QueryResult<bool> {
+    diesel::delete(
+        awdsl::auction_watchers
+            .filter(awdsl::auction_id.eq(auction_id))
+            .filter(awdsl::user.eq(user))
+    ).execute(conn).map(|n| n > 0)
+}
+
+pub fn is_watching(
+    conn: &diesel::PgConnection,
+    auction_id: i64,
+    user: UserId,
+) -> QueryResult<bool> {
+    diesel::select(diesel::dsl::exists(
+        awdsl::auction_watchers
+            .filter(awdsl::auction_id.eq(auction_id))
+            .filter(awdsl::user.eq(user))
+    )).get_result(conn)
+}
+
+pub fn events(
+    conn: &diesel::PgConnection,
+    auction_id: i64,
+) -> QueryResult<Vec<AuctionEvent>> {
+    aedsl::auction_events
+        .select(AuctionEvent::cols())
+        .filter(aedsl::auction_id.eq(auction_id))
+        .order((aedsl::happened_at.asc(), aedsl::rowid.asc()))
+        .get_results(conn)
+}
+
+/// Highest price first; of equal prices, the bid placed first is filled first.
+pub fn rank_unit_bids(bids: &mut [UnitBid]) {
+    bids.sort_by(|a, b| b.unit_price.cmp(&a.unit_price).then(a.placed_at.cmp(&b.placed_at)));
+}
+
+/// Fills ranked bids from the top until `supply` runs out; the last one filled may only get part of what it asked for.
+/// Returns how many units each bid gets, in the same order, and the clearing price everyone pays: the lowest price
+/// that got any units.
+pub fn clear_units(supply: i64, ranked: &[UnitBid]) -> (Vec<i64>, Option<i64>) {
+    let mut remaining = supply;
+    let mut clearing_price = None;
+    let fills = ranked.iter().map(|bid| {
+        let fill = bid.quantity.min(remaining);
+        remaining -= fill;
+        if fill > 0 {
+            clearing_price = Some(bid.unit_price);
+        }
+        fill
+    }).collect();
+    (fills, clearing_price)
+}
+
+/// Everything finishing a multi-unit auction moves
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct UnitSettlement {
+    /// For each ranked bid, the units it gets and how much of what it held is refunded
+    pub fills: Vec<(i64, i64)>,
+    pub clearing_price: Option<i64>,
+    /// Paid to the auctioneer: the clearing price for every unit sold
+    pub proceeds: i64,
+    /// Returned to the auctioneer
+    pub unsold: i64,
+}
+
+pub fn plan_unit_settlement(supply: i64, ranked: &[UnitBid]) -> UnitSettlement {
+    let (fills, clearing_price) = clear_units(supply, ranked);
+    let price = clearing_price.unwrap_or(0);
+    let fills:Vec<(i64, i64)> = ranked.iter().zip(fills).map(|(bid, fill)| {
+        (fill, bid.quantity * bid.unit_price - fill * price)
+    }).collect();
+    let sold:i64 = fills.iter().map(|(fill, _)| fill).sum();
+    UnitSettlement{
+        fills,
+        clearing_price,
+        proceeds: sold * price,
+        unsold: supply - sold,
+    }
+}
+
+/// Ranked highest first
+pub fn unit_bids(
+    conn: &diesel::PgConnection,
+    auction_id: i64,
+) -> QueryResult<Vec<UnitBid>> {
+    let mut bids:Vec<UnitBid> = ubdsl::unit_bids
+        .select(UnitBid::cols())
+        .filter(ubdsl::auction_id.eq(auction_id))
+        .get_results(conn)?;
+    rank_unit_bids(&mut bids);
+    Ok(bids)
+}
+
+/// Bids for `quantity` units at `unit_price` each, holding the whole amount and replacing the bidder's earlier bid if
+/// they had one.
+pub fn place_unit_bid(
+    conn: &diesel::PgConnection,
+    auction_id: i64,
+    bidder: UserId,
+    quantity: i64,
+    unit_price: i64,
+    now: DateTime<Utc>,
+) -> Result<BidOutcome, AuctionError> {
+    if quantity <= 0 || unit_price <= 0 {
+        return Err(AuctionError::NotPositive);
+    }
+    conn.transaction(|| {
+        adsl::auctions
+            .select(adsl::rowid)
+            .filter(adsl::rowid.eq(auction_id))
+            .for_update()
+            .get_result::<i64>(conn)
+            .optional()?
+            .ok_or(AuctionError::NotFound)?;
+        let auction:AuctionWinner = anw::auction_and_winner
+            .select(AuctionWinner::cols())
+            .filter(anw::auction_id.eq(auction_id))
+            .get_result(conn)?;
+        if !auction.multi_unit {
+            return Err(AuctionError::NotMultiUnit);
+        }
+        check_not_auctioneer(auction.auctioneer, bidder)?;
+        if auction.is_over(now) {
+            return Err(AuctionError::Ended);
+        }
+        let supply = auction.offer_amt.unwrap();
+        if quantity > supply {
+            return Err(AuctionError::TooManyUnits);
+        }
+        if unit_price < auction.bid_min {
+            return Err(AuctionError::BelowMinimum);
+        }
+        let total = quantity.checked_mul(unit_price).ok_or(AuctionError::Overflow)?;
+
+        let previous:Option<(i64, i64)> = ubdsl::unit_bids
+            .select((ubdsl::quantity, ubdsl::unit_price))
+            .filter(ubdsl::auction_id.eq(auction_id))
+            .filter(ubdsl::bidder.eq(bidder))
+            .get_result(conn)
+            .optional()?;
+        let previous_total = previous.map(|(q, p)| q * p).unwrap_or(0);
+        let mut handle = TransferHandler::new(
+            conn,
+            vec![bidder],
+            vec![auction.bid_ty.clone()],
+        )?;
+        if handle.balance(bidder, auction.bid_ty.clone()).checked_add(previous_total).unwrap() < total {
+            return Err(AuctionError::CantAfford(auction.bid_ty.clone()));
+        }
+        if previous_total > 0 {
+            let t = TransactionBuilder::new(previous_total, auction.bid_ty.clone(), now)
+                .unit_bid_refund(bidder, &auction);
+            handle.transfer(t)??;
+        }
+        let t = TransactionBuilder::new(total, auction.bid_ty.clone(), now)
+            .unit_bid_escrow(bidder, &auction);
+        handle.transfer(t)??;
+        diesel::insert_into(ubdsl::unit_bids).values((
+            ubdsl::auction_id.eq(auction_id),
+            ubdsl::bidder.eq(bidder),
+            ubdsl::quantity.eq(quantity),
+            ubdsl::unit_price.eq(unit_price),
+            ubdsl::placed_at.eq(now),
+        ))
+            .on_conflict((ubdsl::auction_id, ubdsl::bidder))
+            .do_update()
+            .set((
+                ubdsl::quantity.eq(quantity),
+                ubdsl::unit_price.eq(unit_price),
+                ubdsl::placed_at.eq(now),
+            ))
+            .execute(conn)?;
+
+        let ranked = unit_bids(conn, auction_id)?;
+        let (fills, clearing_price) = clear_units(supply, &ranked);
+        let would_get = ranked.iter().zip(fills).find(|(b, _)| b.bidder == bidder).map(|(_, fill)| fill).unwrap_or(0);
+        let message = if previous.is_some() {
+            format!("You have replaced your bid with one for {} at {}{} each.", quantity, unit_price, auction.bid_ty)
+        } else {
+            format!("You have bid for {} at {}{} each, holding {}{}.", quantity, unit_price, auction.bid_ty, total, auction.bid_ty)
+        };
+        Ok(BidOutcome{
+            message,
+            bid_ty: auction.bid_ty.clone(),
+            standing: BidStanding::Units{quantity, would_get, clearing_price},
+        })
+    })
+}
+
+/// Finishes a multi-unit auction: pays out the filled units, charges everyone the clearing price and refunds the rest of
+/// their bids, pays the proceeds to the auctioneer and gives them back any unsold units. Returns each bid with how many
+/// units it got, highest first, and the clearing price.
+pub fn settle_multi_unit(
+    conn: &diesel::PgConnection,
+    auction: &AuctionWinner,
+    now: DateTime<Utc>,
+) -> QueryResult<(Vec<(UnitBid, i64)>, Option<i64>)> {
+    let offer_ty = auction.offer_ty.clone().unwrap();
+    let supply = auction.offer_amt.unwrap();
+    // Multi-unit auctions are all newer than the treasury, so they always have an auctioneer
+    let auctioneer = UserId::try_from(auction.auctioneer.unwrap()).unwrap();
+    let ranked = unit_bids(conn, auction.auction_id)?;
+    let settlement = plan_unit_settlement(supply, &ranked);
+
+    let mut users:Vec<UserId> = ranked.iter().map(|b| b.bidder).collect();
+    users.push(auctioneer);
+    let mut handle = TransferHandler::new(
+        conn,
+        users,
+        vec![auction.bid_ty.clone(), offer_ty.clone()],
+    )?;
+    // Each winner gets their own payout, unlike the single `auction_payout` of other auctions
+    for (bid, &(fill, refund)) in ranked.iter().zip(&settlement.fills) {
+        if fill > 0 {
+            let t = TransactionBuilder::new(fill, offer_ty.clone(), now)
+                .auction_unit_payout(bid.bidder, auction);
+            handle.transfer(t).unwrap()?;
+        }
+        if refund > 0 {
+            let t = TransactionBuilder::new(refund, auction.bid_ty.clone(), now)
+                .unit_bid_refund(bid.bidder, auction);
+            handle.transfer(t).unwrap()?;
+        }
+    }
+    if settlement.proceeds > 0 {
+        let t = TransactionBuilder::new(settlement.proceeds, auction.bid_ty.clone(), now)
+            .auction_proceeds(auctioneer, auction);
+        handle.transfer(t).unwrap()?;
+    }
+    if settlement.unsold > 0 {
+        let t = TransactionBuilder::new(settlement.unsold, offer_ty, now)
+            .auction_return(auctioneer, auction);
+        handle.transfer(t).unwrap()?;
+    }
+    diesel::update(adsl::auctions.filter(adsl::rowid.eq(auction.auction_id)))
+        .set((
+            adsl::finished.eq(true),
+            adsl::clearing_price.eq(settlement.clearing_price),
+        ))
+        .execute(conn)?;
+    let fills = settlement.fills.iter().map(|(fill, _)| *fill);
+    Ok((ranked.into_iter().zip(fills).collect(), settlement.clearing_price))
+}
+
+/// Parses the words after `dutch`: `from <start> dropping <amount> every <n> minutes|hours`
+pub fn parse_dutch(words: &[String]) -> Result<DutchSchedule, &'static str> {
+    const USAGE:&str = "Dutch auctions look like `dutch from <starting price> dropping <amount> every <n> minutes|hours`";
+    let words:Vec<&str> = words.iter().map(|w| w.as_str()).collect();
+    match words.as_slice() {
+        [from, start, dropping, drop, every, n, unit]
+            if from.eq_ignore_ascii_case("from")
+            && dropping.eq_ignore_ascii_case("dropping")
+            && every.eq_ignore_ascii_case("every") => {
+            let start:i64 = start.parse().map_err(|_| USAGE)?;
+            let drop:i64 = drop.parse().map_err(|_| USAGE)?;
+            let n:i32 = n.parse().map_err(|_| USAGE)?;
+            let per = match unit.to_ascii_lowercase().as_str() {
+                "m" | "min" | "mins" | "minute" | "minutes" => 1,
+                "h" | "hr" | "hrs" | "hour" | "hours" => 60,
+                _ => return Err(USAGE),
+            };
+            let every_minutes = n.checked_mul(per).ok_or(USAGE)?;
+            Ok(DutchSchedule{start, drop, every_minutes})
+        },
+        _ => Err(USAGE),
+    }
+}
+
+/// eg "4 days", "90 minutes" or "1 hour"
+pub fn describe_minutes(minutes: i64) -> String {
+    let (amount, unit) = if minutes != 0 && minutes % (60 * 24) == 0 {
+        (minutes / (60 * 24), "day")
+    } else if minutes != 0 && minutes % 60 == 0 {
+        (minutes / 60, "hour")
+    } else {
+        (minutes, "minute")
+    };
+    format!("{} {}{}", amount, unit, if amount == 1 { "" } else { "s" })
+}
+
+/// What a dutch auction that started at `started_at` costs at `now`
+pub fn dutch_price_at(
+    schedule: DutchSchedule,
+    floor: i64,
+    started_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> i64 {
+    let steps = (now - started_at).num_minutes().max(0) / i64::from(schedule.every_minutes);
+    steps.checked_mul(schedule.drop)
+        .and_then(|dropped| schedule.start.checked_sub(dropped))
+        .unwrap_or(floor)
+        .max(floor)
+}
+
+/// Brings the price of every unsold dutch auction up to date. Run by the worker every tick.
+pub fn reprice_dutch(
+    conn: &diesel::PgConnection,
+    now: DateTime<Utc>,
+) -> QueryResult<()> {
+    let unsold:Vec<AuctionWinner> = anw::auction_and_winner
+        .select(AuctionWinner::cols())
+        .filter(anw::finished.eq(false))
+        .filter(anw::dutch_price.is_not_null())
+        .filter(anw::winner_id.is_null())
+        .get_results(conn)?;
+    for auction in unsold {
+        let schedule = DutchSchedule{
+            start: auction.dutch_start.unwrap(),
+            drop: auction.dutch_drop.unwrap(),
+            every_minutes: auction.dutch_every_minutes.unwrap(),
+        };
+        let price = dutch_price_at(schedule, auction.bid_min, auction.created_at, now);
+        if Some(price) != auction.dutch_price {
+            diesel::update(adsl::auctions.filter(adsl::rowid.eq(auction.auction_id)))
+                .set(adsl::dutch_price.eq(price))
+                .execute(conn)?;
+        }
+    }
+    Ok(())
+}
+
+/// Buys an auction outright: a dutch auction at its current price, or an open one at its buy-it-now price, giving
+/// back the high bid if there is one. The price is held like a winning bid, and the worker finishes the auction on its
+/// next pass. Returns what it cost.
+pub fn buy_now(
+    conn: &diesel::PgConnection,
+    auction_id: i64,
+    buyer: UserId,
+    now: DateTime<Utc>,
+) -> Result<(AuctionWinner, i64), AuctionError> {
+    conn.transaction(|| {
+        adsl::auctions
+            .select(adsl::rowid)
+            .filter(adsl::rowid.eq(auction_id))
+            .for_update()
+            .get_result::<i64>(conn)
+            .optional()?
+            .ok_or(AuctionError::NotFound)?;
+        let auction:AuctionWinner = anw::auction_and_winner
+            .select(AuctionWinner::cols())
+            .filter(anw::auction_id.eq(auction_id))
+            .get_result(conn)?;
+        check_not_auctioneer(auction.auctioneer, buyer)?;
+        let price = if let Some(price) = auction.dutch_price {
+            if auction.winner_id.is_some() {
+                return Err(AuctionError::Ended);
+            }
+            price
+        } else {
+            auction.buy_now_available().ok_or(AuctionError::NoBuyNow)?
+        };
+        if auction.is_over(now) {
+            return Err(AuctionError::Ended);
+        }
+        let outbid = auction.winner().filter(|(user, _)| *user != buyer);
+        let mut to_lock = vec![buyer];
+        to_lock.extend(outbid.map(|(user, _)| user));
+        let mut handle = TransferHandler::new(
+            conn,
+            to_lock,
+            vec![auction.bid_ty.clone()],
+        )?;
+        let own_bid = auction.winner().filter(|(user, _)| *user == buyer).map(|(_, amount)| amount).unwrap_or(0);
+        if handle.balance(buyer, auction.bid_ty.clone()).checked_add(own_bid).unwrap() < price {
+            return Err(AuctionError::CantAfford(auction.bid_ty.clone()));
+        }
+        if let Some((bidder, amount)) = auction.winner() {
+            let t = TransactionBuilder::new(amount, auction.bid_ty.clone(), now)
+                .auction_refund(bidder, &auction);
+            handle.transfer(t)??;
+        }
+        let t = TransactionBuilder::new(price, auction.bid_ty.clone(), now)
+            .auction_reserve(buyer, &auction);
+        handle.transfer(t)??;
+        if !auction.is_dutch() {
+            diesel::update(adsl::auctions.filter(adsl::rowid.eq(auction_id))).set((
+                adsl::bought_at.eq(now),
+                adsl::max_bid_user.eq(None::<UserId>),
+                adsl::max_bid_amt.eq(None::<i64>),
+            )).execute(conn)?;
+            announce_bought(conn, &auction, buyer, outbid, price, now)?;
+        }
+        Ok((auction, price))
+    })
+}
+
+/// Logs and DMs the high bidder who lost out to someone buying it now, and tells watchers it's been bought.
+fn announce_bought(
+    conn: &diesel::PgConnection,
+    auction: &AuctionWinner,
+    buyer: UserId,
+    outbid: Option<(UserId, i64)>,
+    price: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<()> {
+    if let Some((loser, lost_bid)) = outbid {
+        diesel::insert_into(aedsl::auction_events).values((
+            aedsl::auction_id.eq(auction.auction_id),
+            aedsl::happened_at.eq(now),
+            aedsl::kind.eq(AUCTION_EVENT_OUTBID),
+            aedsl::user.eq(loser),
+            aedsl::amount.eq(lost_bid),
+        )).execute(conn)?;
+        notify(conn, loser, format!(
+            "{buyer} bought auction#{id} ({lot}) outright for {price} {ty}, and your bid of {lost} {ty} has been returned.",
+            buyer = name_of(buyer),
+            id = auction.damm(),
+            lot = auction.lot_description(),
+            price = price,
+            lost = lost_bid,
+            ty = auction.bid_ty,
+        ), now)?;
+    }
+    let watchers:Vec<UserId> = awdsl::auction_watchers
+        .select(awdsl::user)
+        .filter(awdsl::auction_id.eq(auction.auction_id))
+        .get_results(conn)?;
+    for watcher in watchers {
+        if watcher == buyer || Some(watcher) == outbid.map(|(user, _)| user) {
+            continue;
+        }
+        notify(conn, watcher, format!(
+            "{buyer} bought auction#{id} ({lot}) outright for {price} {ty}.",
+            buyer = name_of(buyer),
+            id = auction.damm(),
+            lot = auction.lot_description(),
+            price = price,
+            ty = auction.bid_ty,
+        ), now)?;
+    }
+    Ok(())
+}
+
+/// Ends an auction nobody has bid on yet. The worker gives the lot back to the auctioneer on its next pass.
+pub fn cancel(
+    conn: &diesel::PgConnection,
+    auction_id: i64,
+    user: UserId,
+    now: DateTime<Utc>,
+) -> Result<AuctionWinner, AuctionError> {
+    conn.transaction(|| {
+        adsl::auctions
+            .select(adsl::rowid)
+            .filter(adsl::rowid.eq(auction_id))
+            .for_update()
+            .get_result::<i64>(conn)
+            .optional()?
+            .ok_or(AuctionError::NotFound)?;
+        let auction:AuctionWinner = anw::auction_and_winner
+            .select(AuctionWinner::cols())
+            .filter(anw::auction_id.eq(auction_id))
+            .get_result(conn)?;
+        if auction.auctioneer != Some(user.into_i64()) {
+            return Err(AuctionError::NotAuctioneer);
+        }
+        if auction.is_over(now) {
+            return Err(AuctionError::Ended);
+        }
+        let has_hidden_bids = if auction.sealed {
+            !sealed_bids(conn, auction_id)?.is_empty()
+        } else if auction.multi_unit {
+            !unit_bids(conn, auction_id)?.is_empty()
+        } else { false };
+        if auction.winner_id.is_some() || has_hidden_bids {
+            return Err(AuctionError::HasBids);
+        }
+        diesel::update(adsl::auctions.filter(adsl::rowid.eq(auction_id)))
+            .set(adsl::cancelled_at.eq(now))
+            .execute(conn)?;
+        let watchers:Vec<UserId> = awdsl::auction_watchers
+            .select(awdsl::user)
+            .filter(awdsl::auction_id.eq(auction_id))
+            .get_results(conn)?;
+        for watcher in watchers {
+            notify(conn, watcher, format!(
+                "Auction#{} ({}) was cancelled by {}.",
+                auction.damm(),
+                auction.lot_description(),
+                auction.auctioneer_name(),
+            ), now)?;
+        }
+        Ok(auction)
+    })
+}
+
+/// Parses and removes `reserve <price>`, `buy now <price>` and
+/// `ends in <n> <unit> [extending <m> <unit> [within <k> <unit>]]` from anywhere in `words`
+pub fn parse_open_terms(words: &mut Vec<String>) -> Result<OpenTerms, &'static str> {
+    const USAGE:&str = "A reserve looks like `reserve <price>`, a buy-it-now price like `buy now <price>`, and a fixed end \
+    like `ends in <n> minutes|hours|days`, optionally followed by `extending <m> minutes` to push the end out to m minutes \
+    after any new high bid in the last m minutes, or `extending <m> minutes within <k> minutes` for bids in the last k";
+    let mut terms = OpenTerms::default();
+    if let Some(idx) = words.iter().position(|w| w.eq_ignore_ascii_case("reserve")) {
+        let price = words.get(idx+1).and_then(|w| w.parse().ok()).ok_or(USAGE)?;
+        terms.reserve_price = Some(price);
+        words.drain(idx..idx+2);
+    }
+    if let Some(idx) = words.windows(2).position(|w| w[0].eq_ignore_ascii_case("buy") && w[1].eq_ignore_ascii_case("now")) {
+        let price = words.get(idx+2).and_then(|w| w.parse().ok()).ok_or(USAGE)?;
+        terms.buy_now_price = Some(price);
+        words.drain(idx..idx+3);
+    }
+    if let Some(idx) = words.windows(2).position(|w| w[0].eq_ignore_ascii_case("ends") && w[1].eq_ignore_ascii_case("in")) {
+        let duration_at = |at: usize| -> Result<i32, &'static str> {
+            match (words.get(at), words.get(at+1)) {
+                (Some(amount), Some(unit)) => crate::auto_auctions::parse_duration(amount, unit).ok_or(USAGE),
+                _ => Err(USAGE),
+            }
+        };
+        let word_is = |at: usize, word: &str| words.get(at).map(|w| w.eq_ignore_ascii_case(word)).unwrap_or(false);
+        let after_minutes = duration_at(idx+2)?;
+        let mut end = idx+4;
+        let (window_minutes, extend_minutes) = if word_is(end, "extending") {
+            let extend = duration_at(end+1)?;
+            end += 3;
+            if word_is(end, "within") {
+                let window = duration_at(end+1)?;
+                end += 3;
+                (window, extend)
+            } else {
+                (extend, extend)
+            }
+        } else {
+            (0, 0)
+        };
+        terms.fixed_end = Some(FixedEnd{after_minutes, window_minutes, extend_minutes});
+        words.drain(idx..end);
+    }
+    Ok(terms)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_not_auctioneer,AuctionError,proxy_bid,rank_sealed_bids,sealed_price,parse_dutch,dutch_price_at,DutchSchedule,rank_unit_bids,clear_units,plan_unit_settlement,UnitSettlement,parse_open_terms,OpenTerms,FixedEnd,describe_minutes};
+    use crate::models::{SealedBid,UnitBid,UserId};
+    use crate::test_util::words;
+    use chrono::{TimeZone,Utc};
+
+    fn user(n: u64) -> UserId {
+        UserId::try_from(n).unwrap()
+    }
+
+    fn bid(bidder: u64, amount: i64, second: u32) -> SealedBid {
+        SealedBid{
+            auction_id: 1,
+            bidder: user(bidder),
+            amount,
+            placed_at: Utc.with_ymd_and_hms(2023, 4, 15, 12, 0, second).unwrap(),
+        }
+    }
+
+    #[test]
+    fn vickrey() {
+        assert_eq!(sealed_price(&[], 10), None);
+
+        let mut bids = vec![bid(1, 50, 0), bid(2, 80, 1), bid(3, 60, 2)];
+        rank_sealed_bids(&mut bids);
+        assert_eq!(bids.iter().map(|b| b.amount).collect::<Vec<_>>(), vec![80, 60, 50]);
+        assert_eq!(sealed_price(&bids, 10), Some((user(2), 60)));
+
+        let mut lone = vec![bid(1, 50, 0)];
+        rank_sealed_bids(&mut lone);
+        assert_eq!(sealed_price(&lone, 10), Some((user(1), 10)));
+
+        // A tie goes to whoever bid first, at the tied price
+        let mut tied = vec![bid(1, 70, 5), bid(2, 70, 3), bid(3, 20, 0)];
+        rank_sealed_bids(&mut tied);
+        assert_eq!(sealed_price(&tied, 10), Some((user(2), 70)));
+    }
+
+    #[test]
+    fn dutch() {
+        assert_eq!(
+            parse_dutch(&words("from 500 dropping 10 every 30 minutes")),
+            Ok(DutchSchedule{start: 500, drop: 10, every_minutes: 30}),
+        );
+        assert_eq!(
+            parse_dutch(&words("FROM 500 DROPPING 25 EVERY 2 h")),
+            Ok(DutchSchedule{start: 500, drop: 25, every_minutes: 120}),
+        );
+        assert!(parse_dutch(&words("from 500 dropping 10 every 30")).is_err());
+        assert!(parse_dutch(&words("from 500 dropping 10 every 30 days")).is_err());
+        assert!(parse_dutch(&words("from lots dropping 10 every 30 minutes")).is_err());
+
+        let schedule = DutchSchedule{start: 500, drop: 10, every_minutes: 30};
+        let start = Utc.with_ymd_and_hms(2023, 4, 22, 12, 0, 0).unwrap();
+        assert_eq!(dutch_price_at(schedule, 100, start, start), 500);
+        assert_eq!(dutch_price_at(schedule, 100, start, start + chrono::Duration::minutes(29)), 500);
+        assert_eq!(dutch_price_at(schedule, 100, start, start + chrono::Duration::minutes(30)), 490);
+        assert_eq!(dutch_price_at(schedule, 100, start, start + chrono::Duration::hours(10)), 300);
+        assert_eq!(dutch_price_at(schedule, 100, start, start + chrono::Duration::days(30)), 100);
+        let steep = DutchSchedule{start: i64::MAX, drop: i64::MAX, every_minutes: 1};
+        assert_eq!(dutch_price_at(steep, 100, start, start + chrono::Duration::days(30)), 100);
+    }
+
+    fn unit_bid(bidder: u64, quantity: i64, unit_price: i64, second: u32) -> UnitBid {
+        UnitBid{
+            auction_id: 1,
+            bidder: user(bidder),
+            quantity,
+            unit_price,
+            placed_at: Utc.with_ymd_and_hms(2023, 4, 29, 12, 0, second).unwrap(),
+        }
+    }
+
+    #[test]
+    fn uniform_clearing() {
+        assert_eq!(clear_units(10, &[]), (vec![], None));
+
+        // The last bid filled only gets part of what it asked for, and sets the price
+        let mut bids = vec![unit_bid(1, 4, 5, 0), unit_bid(2, 3, 9, 1), unit_bid(3, 5, 7, 2), unit_bid(4, 2, 3, 3)];
+        rank_unit_bids(&mut bids);
+        assert_eq!(bids.iter().map(|b| b.unit_price).collect::<Vec<_>>(), vec![9, 7, 5, 3]);
+        assert_eq!(clear_units(10, &bids), (vec![3, 5, 2, 0], Some(5)));
+
+        // Not enough demand: everyone is filled, at the lowest price bid
+        assert_eq!(clear_units(100, &bids), (vec![3, 5, 4, 2], Some(3)));
+
+        // Equal prices are filled in the order they were placed
+        let mut tied = vec![unit_bid(1, 6, 4, 9), unit_bid(2, 6, 4, 1)];
+        rank_unit_bids(&mut tied);
+        assert_eq!(tied[0].bidder, user(2));
+        assert_eq!(clear_units(10, &tied), (vec![6, 4], Some(4)));
+    }
+
+    #[test]
+    fn unit_settlement() {
+        // Two winners, each paid their units separately at the clearing price; the loser gets everything back
+        let mut bids = vec![unit_bid(1, 4, 5, 0), unit_bid(2, 3, 9, 1), unit_bid(3, 2, 3, 2)];
+        rank_unit_bids(&mut bids);
+        assert_eq!(plan_unit_settlement(6, &bids), UnitSettlement{
+            fills: vec![(3, 12), (3, 5), (0, 6)],
+            clearing_price: Some(5),
+            proceeds: 30,
+            unsold: 0,
+        });
+
+        // Not enough demand: the rest goes back to the auctioneer
+        assert_eq!(plan_unit_settlement(20, &bids), UnitSettlement{
+            fills: vec![(3, 18), (4, 8), (2, 0)],
+            clearing_price: Some(3),
+            proceeds: 27,
+            unsold: 11,
+        });
+
+        assert_eq!(plan_unit_settlement(5, &[]), UnitSettlement{fills: vec![], clearing_price: None, proceeds: 0, unsold: 5});
+    }
+
+    #[test]
+    fn open_terms() {
+        let mut plain = words("10 gen for 100 pc");
+        assert_eq!(parse_open_terms(&mut plain), Ok(OpenTerms::default()));
+        assert_eq!(plain, words("10 gen for 100 pc"));
+
+        let mut both = words("10 gen for 100 pc RESERVE 150 buy now 400");
+        assert_eq!(parse_open_terms(&mut both), Ok(OpenTerms{reserve_price: Some(150), buy_now_price: Some(400)}));
+        assert_eq!(both, words("10 gen for 100 pc"));
+
+        let mut buy_now_first = words("item#1234 for 50 pc buy now 90 reserve 60");
+        assert_eq!(parse_open_terms(&mut buy_now_first), Ok(OpenTerms{reserve_price: Some(60), buy_now_price: Some(90)}));
+        assert_eq!(buy_now_first, words("item#1234 for 50 pc"));
+
+        assert!(parse_open_terms(&mut words("10 gen for 100 pc reserve")).is_err());
+        assert!(parse_open_terms(&mut words("10 gen for 100 pc buy now lots")).is_err());
+
+        let mut hard_close = words("10 gen for 100 pc ends in 2 days");
+        assert_eq!(parse_open_terms(&mut hard_close), Ok(OpenTerms{
+            fixed_end: Some(FixedEnd{after_minutes: 2880, window_minutes: 0, extend_minutes: 0}),
+            ..OpenTerms::default()
+        }));
+        assert_eq!(hard_close, words("10 gen for 100 pc"));
+
+        let mut soft_close = words("10 gen for 100 pc ends in 48 hours extending 10 minutes reserve 150");
+        assert_eq!(parse_open_terms(&mut soft_close), Ok(OpenTerms{
+            reserve_price: Some(150),
+            fixed_end: Some(FixedEnd{after_minutes: 2880, window_minutes: 10, extend_minutes: 10}),
+            ..OpenTerms::default()
+        }));
+        assert_eq!(soft_close, words("10 gen for 100 pc"));
+
+        let mut windowed = words("10 gen for 100 pc ends in 1 day extending 5 minutes within 15 minutes");
+        assert_eq!(parse_open_terms(&mut windowed), Ok(OpenTerms{
+            fixed_end: Some(FixedEnd{after_minutes: 1440, window_minutes: 15, extend_minutes: 5}),
+            ..OpenTerms::default()
+        }));
+        assert_eq!(windowed, words("10 gen for 100 pc"));
+
+        assert!(parse_open_terms(&mut words("10 gen for 100 pc ends in soon")).is_err());
+        assert!(parse_open_terms(&mut words("10 gen for 100 pc ends in 2 days extending 5")).is_err());
+    }
+
+    #[test]
+    fn auctioneers_cant_bid() {
+        assert!(matches!(check_not_auctioneer(Some(10), user(10)), Err(AuctionError::OwnAuction)));
+        assert!(check_not_auctioneer(Some(10), user(11)).is_ok());
+        // Auctions from before the treasury have no auctioneer
+        assert!(check_not_auctioneer(None, user(10)).is_ok());
+    }
+
+    #[test]
+    fn proxy_bids_meet_the_reserve() {
+        assert_eq!(proxy_bid(10, 500, None), 10);
+        // A lone max bid above the reserve opens at the reserve, not the minimum
+        assert_eq!(proxy_bid(10, 500, Some(150)), 150);
+        // One below the reserve bids all it can
+        assert_eq!(proxy_bid(10, 120, Some(150)), 120);
+        // Once the bidding is past the reserve, it makes no difference
+        assert_eq!(proxy_bid(200, 500, Some(150)), 200);
+        assert_eq!(proxy_bid(150, 500, Some(150)), 150);
+    }
+
+    #[test]
+    fn minutes() {
+        assert_eq!(describe_minutes(5760), "4 days");
+        assert_eq!(describe_minutes(60), "1 hour");
+        assert_eq!(describe_minutes(90), "90 minutes");
+        assert_eq!(describe_minutes(1), "1 minute");
+    }
+}
@@ -0,0 +1,368 @@
+use chrono::{DateTime,Utc,Datelike,TimeZone};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use crate::schema::auto_auctions::dsl as aadsl;
+use crate::models::AutoAuction;
+use crate::transfers::CurrencyId;
+
+pub const DEF_USAGE:&str = "An auto-auction is defined like `<minute> <hour> <day of month> <month> <day of week> <timezone> <amount> <type> for <minimum bid> <type> [each] lasting <n> minutes|hours|days`, eg `0 7 * * 6 US/Pacific 10 gen for 1 pc each lasting 96 hours`";
+
+#[derive(Debug)]
+pub enum AutoAuctionError {
+    InvalidName,
+    BadSchedule,
+    BadTimezone,
+    NotPositive,
+    NotFound,
+    Db(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for AutoAuctionError {
+    fn from(e: diesel::result::Error) -> Self {
+        AutoAuctionError::Db(e)
+    }
+}
+
+impl std::fmt::Display for AutoAuctionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AutoAuctionError::InvalidName => write!(f, "Auto-auction names must be 1 to 32 lowercase letters, digits, or dashes."),
+            AutoAuctionError::BadSchedule => write!(f, "The schedule must be a cron expression that matches some time, like `0 7 * * 6` for 7am every Saturday."),
+            AutoAuctionError::BadTimezone => write!(f, "Unknown timezone; use a name like `US/Pacific` or `UTC`."),
+            AutoAuctionError::NotPositive => write!(f, "The lot, minimum bid, and duration must all be more than nothing."),
+            AutoAuctionError::NotFound => write!(f, "No auto-auction with that name."),
+            AutoAuctionError::Db(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AutoAuctionError {}
+
+/// A cron expression, "<minute> <hour> <day of month> <month> <day of week>". Each field is `*`, a number, a range
+/// `a-b`, any of those followed by a step `/n`, or a comma-separated list of them. Days of the week count from 0 for
+/// Sunday, and 7 is Sunday too. As in cron, if both day fields are restricted, a day matching either one matches.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+/// A bit for each value the field matches
+fn parse_field(field: &str, min: u32, max: u32) -> Option<u64> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<usize>().ok().filter(|s| *s > 0)?),
+            None => (part, 1),
+        };
+        let (start, end):(u32, u32) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (a.parse().ok()?, b.parse().ok()?)
+        } else {
+            let n = range.parse().ok()?;
+            // `5/15` means every 15 starting at 5
+            (n, if step > 1 { max } else { n })
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        for v in (start..=end).step_by(step) {
+            bits |= 1 << v;
+        }
+    }
+    Some(bits)
+}
+
+impl Schedule {
+    pub fn parse(s: &str) -> Option<Self> {
+        let fields:Vec<&str> = s.split_whitespace().collect();
+        let (minute, hour, dom, month, dow) = match fields.as_slice() {
+            [minute, hour, dom, month, dow] => (*minute, *hour, *dom, *month, *dow),
+            _ => return None,
+        };
+        let days_of_week = parse_field(dow, 0, 7)?;
+        Some(Schedule{
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days_of_month: parse_field(dom, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            days_of_week: (days_of_week | days_of_week >> 7) & 0x7f,
+            any_day_of_month: dom == "*",
+            any_day_of_week: dow == "*",
+        })
+    }
+
+    fn matches_day(&self, date: chrono::NaiveDate) -> bool {
+        let dom = self.days_of_month & (1 << date.day()) != 0;
+        let dow = self.days_of_week & (1 << date.weekday().num_days_from_sunday()) != 0;
+        let day = match (self.any_day_of_month, self.any_day_of_week) {
+            (_, true) => dom,
+            (true, false) => dow,
+            (false, false) => dom || dow,
+        };
+        day && self.months & (1 << date.month()) != 0
+    }
+
+    /// The first time after `after` that matches, on the clock in `tz`. A local time skipped by daylight saving never
+    /// matches, and one that happens twice matches the first time. None if nothing matches in the next five years, eg
+    /// for the 30th of February.
+    pub fn next_after(&self, tz: Tz, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.with_timezone(&tz).date_naive();
+        for days in 0..(366 * 5) {
+            let date = start + chrono::Duration::days(days);
+            if !self.matches_day(date) {
+                continue;
+            }
+            for hour in (0..24).filter(|h| self.hours & (1 << h) != 0) {
+                for minute in (0..60).filter(|m| self.minutes & (1 << m) != 0) {
+                    let local = date.and_hms_opt(hour, minute, 0).unwrap();
+                    if let Some(at) = tz.from_local_datetime(&local).earliest() {
+                        let at = at.with_timezone(&Utc);
+                        if at > after {
+                            return Some(at);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Everything about an auto-auction that can be changed. Stored in a motion's action when one is changed by motion.
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub struct AutoAuctionDef {
+    /// See `Schedule`
+    pub schedule: String,
+    pub timezone: String,
+    pub offer_ty: CurrencyId,
+    pub offer_amt: i64,
+    pub bid_ty: CurrencyId,
+    /// Per unit if `multi_unit`
+    pub bid_min: i64,
+    pub duration_minutes: i32,
+    pub multi_unit: bool,
+}
+
+impl AutoAuctionDef {
+    /// eg "10 gen for at least 1 pc each, at `0 7 * * 6` US/Pacific, lasting 5760 minutes"
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {} for at least {} {}{}, at `{}` {}, lasting {} minutes",
+            self.offer_amt,
+            self.offer_ty,
+            self.bid_min,
+            self.bid_ty,
+            if self.multi_unit { " each" } else { "" },
+            self.schedule,
+            self.timezone,
+            self.duration_minutes,
+        )
+    }
+
+    pub fn validate(&self) -> Result<(Schedule, Tz), AutoAuctionError> {
+        if self.offer_amt <= 0 || self.bid_min <= 0 || self.duration_minutes <= 0 {
+            return Err(AutoAuctionError::NotPositive);
+        }
+        let tz:Tz = self.timezone.parse().map_err(|_| AutoAuctionError::BadTimezone)?;
+        let schedule = Schedule::parse(&self.schedule).ok_or(AutoAuctionError::BadSchedule)?;
+        if schedule.next_after(tz, Utc::now()).is_none() {
+            return Err(AutoAuctionError::BadSchedule);
+        }
+        Ok((schedule, tz))
+    }
+}
+
+/// Parses eg `96 hours`
+pub fn parse_duration(amount: &str, unit: &str) -> Option<i32> {
+    let amount:i32 = amount.parse().ok().filter(|a| *a > 0)?;
+    let minutes_per = match unit.to_ascii_lowercase().trim_end_matches('s') {
+        "minute" => 1,
+        "hour" => 60,
+        "day" => 60 * 24,
+        _ => return None,
+    };
+    amount.checked_mul(minutes_per)
+}
+
+pub fn valid_name(name: &str) -> bool {
+    (1..=32).contains(&name.len())
+    && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+pub fn get_all(
+    conn: &diesel::PgConnection,
+) -> QueryResult<Vec<AutoAuction>> {
+    aadsl::auto_auctions
+        .select(AutoAuction::cols())
+        .order(aadsl::name)
+        .get_results(conn)
+}
+
+/// Creates or replaces the named auto-auction, enabled. The next one starts at the first time matching its schedule
+/// from now, even if an earlier one was missed.
+pub fn set(
+    conn: &diesel::PgConnection,
+    name: &str,
+    def: &AutoAuctionDef,
+    changed_by_motion: Option<i64>,
+    now: DateTime<Utc>,
+) -> Result<(), AutoAuctionError> {
+    if !valid_name(name) {
+        return Err(AutoAuctionError::InvalidName);
+    }
+    def.validate()?;
+    diesel::insert_into(aadsl::auto_auctions)
+        .values((
+            aadsl::name.eq(name),
+            aadsl::schedule.eq(&def.schedule),
+            aadsl::timezone.eq(&def.timezone),
+            aadsl::offer_ty.eq(&def.offer_ty),
+            aadsl::offer_amt.eq(def.offer_amt),
+            aadsl::bid_ty.eq(&def.bid_ty),
+            aadsl::bid_min.eq(def.bid_min),
+            aadsl::duration_minutes.eq(def.duration_minutes),
+            aadsl::multi_unit.eq(def.multi_unit),
+            aadsl::last_run.eq(now),
+            aadsl::changed_at.eq(now),
+            aadsl::changed_by_motion.eq(changed_by_motion),
+        ))
+        .on_conflict(aadsl::name)
+        .do_update()
+        .set((
+            aadsl::schedule.eq(&def.schedule),
+            aadsl::timezone.eq(&def.timezone),
+            aadsl::offer_ty.eq(&def.offer_ty),
+            aadsl::offer_amt.eq(def.offer_amt),
+            aadsl::bid_ty.eq(&def.bid_ty),
+            aadsl::bid_min.eq(def.bid_min),
+            aadsl::duration_minutes.eq(def.duration_minutes),
+            aadsl::multi_unit.eq(def.multi_unit),
+            aadsl::enabled.eq(true),
+            aadsl::last_run.eq(now),
+            aadsl::changed_at.eq(now),
+            aadsl::changed_by_motion.eq(changed_by_motion),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Pausing keeps the definition around. Like `set`, enabling one doesn't make up for runs it missed while disabled.
+pub fn set_enabled(
+    conn: &diesel::PgConnection,
+    name: &str,
+    enabled: bool,
+    changed_by_motion: Option<i64>,
+    now: DateTime<Utc>,
+) -> Result<(), AutoAuctionError> {
+    let count = diesel::update(aadsl::auto_auctions.filter(aadsl::name.eq(name)))
+        .set((
+            aadsl::enabled.eq(enabled),
+            aadsl::last_run.eq(now),
+            aadsl::changed_at.eq(now),
+            aadsl::changed_by_motion.eq(changed_by_motion),
+        ))
+        .execute(conn)?;
+    if count == 0 { Err(AutoAuctionError::NotFound) } else { Ok(()) }
+}
+
+pub fn remove(
+    conn: &diesel::PgConnection,
+    name: &str,
+) -> Result<(), AutoAuctionError> {
+    let count = diesel::delete(aadsl::auto_auctions.filter(aadsl::name.eq(name))).execute(conn)?;
+    if count == 0 { Err(AutoAuctionError::NotFound) } else { Ok(()) }
+}
+
+/// The enabled auto-auctions that are due to start, locked until the transaction ends. Marks each as run at `now`.
+pub fn take_due(
+    conn: &diesel::PgConnection,
+    now: DateTime<Utc>,
+) -> QueryResult<Vec<AutoAuction>> {
+    let due:Vec<AutoAuction> = aadsl::auto_auctions
+        .select(AutoAuction::cols())
+        .filter(aadsl::enabled.eq(true))
+        .for_update()
+        .get_results::<AutoAuction>(conn)?
+        .into_iter()
+        .filter(|a| a.next_run().map(|next| next <= now).unwrap_or(false))
+        .collect();
+    for auto_auction in &due {
+        diesel::update(aadsl::auto_auctions.filter(aadsl::rowid.eq(auto_auction.rowid)))
+            .set(aadsl::last_run.eq(now))
+            .execute(conn)?;
+    }
+    Ok(due)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Utc,TimeZone};
+    use super::{Schedule,parse_duration};
+
+    #[test]
+    fn cron_fields() {
+        assert!(Schedule::parse("0 7 * * 6").is_some());
+        assert!(Schedule::parse("*/15 9-17 1,15 * 1-5").is_some());
+        assert!(Schedule::parse("0 7 * *").is_none());
+        assert!(Schedule::parse("60 7 * * *").is_none());
+        assert!(Schedule::parse("0 7 0 * *").is_none());
+        assert!(Schedule::parse("0 7 * * 8").is_none());
+        assert!(Schedule::parse("*/0 * * * *").is_none());
+        assert!(Schedule::parse("5-1 * * * *").is_none());
+        assert_eq!(Schedule::parse("0 0 * * 7"), Schedule::parse("0 0 * * 0"));
+    }
+
+    #[test]
+    fn next_run() {
+        let pacific = chrono_tz::US::Pacific;
+        let utc = chrono_tz::UTC;
+        // Saturday 2023-05-06, 7am Pacific is 14:00 UTC during daylight saving
+        let weekly = Schedule::parse("0 7 * * 6").unwrap();
+        let sat = Utc.with_ymd_and_hms(2023, 5, 6, 14, 0, 0).unwrap();
+        assert_eq!(weekly.next_after(pacific, Utc.with_ymd_and_hms(2023, 5, 1, 0, 0, 0).unwrap()), Some(sat));
+        // Strictly after
+        assert_eq!(weekly.next_after(pacific, sat), Some(Utc.with_ymd_and_hms(2023, 5, 13, 14, 0, 0).unwrap()));
+
+        let quarter_hours = Schedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(
+            quarter_hours.next_after(utc, Utc.with_ymd_and_hms(2023, 5, 6, 23, 50, 0).unwrap()),
+            Some(Utc.with_ymd_and_hms(2023, 5, 7, 0, 0, 0).unwrap()),
+        );
+
+        // Both days restricted: either one matches. 2023-05-01 is a Monday.
+        let first_or_friday = Schedule::parse("0 0 1 * 5").unwrap();
+        assert_eq!(
+            first_or_friday.next_after(utc, Utc.with_ymd_and_hms(2023, 4, 29, 0, 0, 0).unwrap()),
+            Some(Utc.with_ymd_and_hms(2023, 5, 1, 0, 0, 0).unwrap()),
+        );
+        assert_eq!(
+            first_or_friday.next_after(utc, Utc.with_ymd_and_hms(2023, 5, 1, 0, 0, 0).unwrap()),
+            Some(Utc.with_ymd_and_hms(2023, 5, 5, 0, 0, 0).unwrap()),
+        );
+
+        // 2:30am doesn't happen on 2023-03-12 in the US, so it's the next day
+        let half_two = Schedule::parse("30 2 * * *").unwrap();
+        assert_eq!(
+            half_two.next_after(pacific, Utc.with_ymd_and_hms(2023, 3, 12, 8, 0, 0).unwrap()),
+            Some(Utc.with_ymd_and_hms(2023, 3, 13, 9, 30, 0).unwrap()),
+        );
+
+        assert_eq!(Schedule::parse("0 0 30 2 *").unwrap().next_after(utc, Utc::now()), None);
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("96", "hours"), Some(5760));
+        assert_eq!(parse_duration("1", "day"), Some(1440));
+        assert_eq!(parse_duration("30", "Minutes"), Some(30));
+        assert_eq!(parse_duration("0", "hours"), None);
+        assert_eq!(parse_duration("3", "weeks"), None);
+    }
+}
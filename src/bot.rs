@@ -24,9 +24,12 @@ use serenity::framework::standard::{
     macros::{
         command,
         group,
+        check,
         hook
     },
     Args,
+    CommandOptions,
+    Reason,
 };
 use regex::Regex;
 
@@ -43,6 +46,8 @@ use crate::motion_label::motion_label;
 use crate::models::{self, ItemType};
 use crate::transfers::{TransferHandler, TransactionBuilder, TransferError, CurrencyId};
 
+mod interactions;
+
 pub type DbPool = diesel::r2d2::Pool<diesel::r2d2::ConnectionManager<diesel::PgConnection>>;
 
 struct DbPoolKey;
@@ -51,42 +56,35 @@ impl serenity::prelude::TypeMapKey for DbPoolKey {
 }
 
 #[group]
-#[commands(ping, give, force_give, balances, motion, supermotion, submotion, vote, hack_message_update, help, version_info)]
+#[commands(ping, give, burn, trade, auction, bid, buy_now, cancel_auction, watch, unwatch, lend, loans, standing_order, standing_orders, cancel_standing_order, balances, motion, supermotion, submotion, treasury_spend, policy, policies, auto_auction, auto_auctions, buy, sell, market, orders, cancel_order, vote, hack_message_update, help, version_info)]
 struct General;
 
 #[group]
 #[commands(fabricate, debug_make_auction)]
 struct Debug;
 
+#[group]
+#[checks(GuildAdmin)]
+#[commands(guild_config, mint)]
+struct GuildAdmin;
+
+#[group]
+#[checks(Admin)]
+#[commands(force_give, undo_force_give, force_gives, item_type_create, item_type_position, item_type_retire, item_type_unretire, item_type_confirm_above, item_type_alias_add, item_type_alias_remove, item_create, auto_auction_set, auto_auction_remove, auto_auction_pause, auto_auction_resume)]
+struct Admin;
+
 use std::env;
 
 struct Handler;
 
-#[derive(Debug,Clone,Copy,PartialEq,Eq)]
-enum SpecialEmojiAction {
-    Direction(bool),
-    Amount(u64),
-}
-
 lazy_static! {
     static ref USER_PING_RE:Regex = Regex::new(r"^\s*<@!?(\d+)>\s*$").unwrap();
-    static ref SPECIAL_EMOJI:std::collections::HashMap<u64,SpecialEmojiAction> = hashmap!{
-        770749957723783169 => SpecialEmojiAction::Amount(1),
-        770750097793089596 => SpecialEmojiAction::Amount(2),
-        770750182874021888 => SpecialEmojiAction::Amount(5),
-        770750211281780776 => SpecialEmojiAction::Amount(10),
-        770750297621921802 => SpecialEmojiAction::Amount(20),
-        770750316530499604 => SpecialEmojiAction::Amount(50),
-        770750332946874388 => SpecialEmojiAction::Amount(100),
-        770750231776198698 => SpecialEmojiAction::Amount(10),
-        770749937960747029 => SpecialEmojiAction::Amount(1),
-        770750552291410000 => SpecialEmojiAction::Direction(true),
-        770750576257531914 => SpecialEmojiAction::Direction(false),
-    };
 }
 
 
 pub const VOTE_BASE_COST:u16 = 40;
+const TRADE_ACCEPT_EMOJI:char = '\u{2705}';
+const TRADE_DECLINE_EMOJI:char = '\u{274C}';
 #[cfg(not(feature = "debug"))]
 pub const MOTIONS_CHANNEL:u64 = 609093491150028800; //bureaucracy channel
 #[cfg(feature = "debug")]
@@ -111,90 +109,240 @@ trait FromCommandArgs : Sized {
 impl FromCommandArgs for SerenityUserId {
     async fn from_command_args(ctx: &Context, msg: &Message, arg: &str) -> Result<Self, &'static str> {
         trace!("from_command_args");
-        if arg == "." || arg == "self" {
-            return Ok(msg.author.id);
-        }
-        if let Ok(raw_id) = arg.parse():Result<u64,_> {
-            return Ok(SerenityUserId::from(raw_id));
-        }
+        find_user(ctx, msg.author.id, arg).await
+    }
+}
 
-        if let Some(ma) = USER_PING_RE.captures(arg) {
-            if let Ok(raw_id) = ma.get(1).unwrap().as_str().parse():Result<u64,_> {
-                return Ok(SerenityUserId::from(raw_id));
-            }
+/// Finds the user `arg` refers to, by id, mention, tag, nickname or username. `.` and `self` mean `author`.
+async fn find_user(ctx: &Context, author: SerenityUserId, arg: &str) -> Result<SerenityUserId, &'static str> {
+    trace!("find_user");
+    if arg == "." || arg == "self" {
+        return Ok(author);
+    }
+    if let Ok(raw_id) = arg.parse():Result<u64,_> {
+        return Ok(SerenityUserId::from(raw_id));
+    }
+
+    if let Some(ma) = USER_PING_RE.captures(arg) {
+        if let Ok(raw_id) = ma.get(1).unwrap().as_str().parse():Result<u64,_> {
+            return Ok(SerenityUserId::from(raw_id));
         }
+    }
 
-        if arg.contains('#') {
-            let pieces = arg.rsplitn(2,'#').collect():Vec<&str>;
-            if let Ok(discriminator) = pieces[0].parse():Result<u16, _> {
-                if discriminator <= 9999 {
-                    let name = pieces[1];
-                    let users = ctx.cache.users().await;
-                    let maybe_user = users
-                        .values()
-                        .find(|user| {
-                            user.discriminator == discriminator && user.name.to_ascii_uppercase() == name.to_ascii_uppercase()
-                        });
-                    if let Some(user) = maybe_user {
-                        return Ok(user.id);
-                    }
+    if arg.contains('#') {
+        let pieces = arg.rsplitn(2,'#').collect():Vec<&str>;
+        if let Ok(discriminator) = pieces[0].parse():Result<u16, _> {
+            if discriminator <= 9999 {
+                let name = pieces[1];
+                let users = ctx.cache.users().await;
+                let maybe_user = users
+                    .values()
+                    .find(|user| {
+                        user.discriminator == discriminator && user.name.to_ascii_uppercase() == name.to_ascii_uppercase()
+                    });
+                if let Some(user) = maybe_user {
+                    return Ok(user.id);
                 }
             }
         }
+    }
 
-        for guild_id in ctx.cache.guilds().await {
-            if let Some(members) = ctx.cache.guild_field(guild_id, |g| g.members.clone()).await {
-                for (userid, member) in members {
-                    if let Some(nick) = member.nick.as_ref() {
-                        if nick.to_ascii_uppercase() == arg.to_ascii_uppercase() {
-                            return Ok(userid);
-                        }
-                    }
-                    if member.user.name.to_ascii_uppercase() == arg.to_ascii_uppercase() {
+    for guild_id in ctx.cache.guilds().await {
+        if let Some(members) = ctx.cache.guild_field(guild_id, |g| g.members.clone()).await {
+            for (userid, member) in members {
+                if let Some(nick) = member.nick.as_ref() {
+                    if nick.to_ascii_uppercase() == arg.to_ascii_uppercase() {
                         return Ok(userid);
                     }
                 }
+                if member.user.name.to_ascii_uppercase() == arg.to_ascii_uppercase() {
+                    return Ok(userid);
+                }
             }
         }
-        Err("Could not find any User.")
     }
+    Err("Could not find any User.")
 }
 
 #[serenity::async_trait]
 impl EventHandler for Handler {
+    async fn ready(&self, ctx: Context, _ready: serenity::model::gateway::Ready) {
+        trace!("ready");
+        interactions::register_commands(&ctx).await;
+        // Ready happens again after reconnecting, but one of these is enough
+        static REFRESH_NAMES:std::sync::Once = std::sync::Once::new();
+        let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+        REFRESH_NAMES.call_once(move || { tokio::spawn(refresh_names(pool)); });
+    }
+
+    async fn guild_create(&self, ctx: Context, guild: serenity::model::guild::Guild, _is_new: bool) {
+        trace!("guild_create");
+        let seen = guild.members.values().map(|m| (m.user.id.into(), m.user.name.clone())).collect();
+        record_names(&ctx, seen).await;
+        if home_guild(&ctx).await == Some(guild.id) {
+            sync_admins(&ctx).await;
+        }
+    }
+
+    async fn guild_member_addition(&self, ctx: Context, _guild_id: serenity::model::id::GuildId, member: serenity::model::guild::Member) {
+        trace!("guild_member_addition");
+        record_names(&ctx, vec![(member.user.id.into(), member.user.name)]).await;
+    }
+
+    async fn guild_member_update(&self, ctx: Context, _old: Option<serenity::model::guild::Member>, member: serenity::model::guild::Member) {
+        trace!("guild_member_update");
+        let in_home = home_guild(&ctx).await == Some(member.guild_id);
+        record_names(&ctx, vec![(member.user.id.into(), member.user.name)]).await;
+        if in_home {
+            sync_admins(&ctx).await;
+        }
+    }
+
+    async fn guild_member_removal(&self, ctx: Context, guild_id: serenity::model::id::GuildId, _user: serenity::model::prelude::User, _member: Option<serenity::model::guild::Member>) {
+        trace!("guild_member_removal");
+        if home_guild(&ctx).await == Some(guild_id) {
+            sync_admins(&ctx).await;
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: serenity::model::interactions::Interaction) {
+        interactions::interaction_create(&ctx, interaction).await;
+    }
+
     async fn reaction_add(&self, ctx: Context, r: serenity::model::channel::Reaction) {
         trace!("reaction_add");
-        let mut vote_count = 0;
-        let mut vote_direction = None;
         let maybe_user_id = r.user_id;
         if let Some(user_id) = maybe_user_id {
             if user_id == ctx.cache.current_user_id().await {
                 return;
             }
             let message_id = r.message_id;
-            if let serenity::model::channel::ReactionType::Custom{animated: _, id, name: _} = r.emoji {
-                if let Some(action) = SPECIAL_EMOJI.get(&id.0) {
-                    match action {
-                        SpecialEmojiAction::Direction(dir) => vote_direction = Some(*dir),
-                        SpecialEmojiAction::Amount(a) => vote_count = *a,
-                    }
+            if let serenity::model::channel::ReactionType::Unicode(ref emoji) = r.emoji {
+                let accept = emoji.starts_with(TRADE_ACCEPT_EMOJI);
+                if accept || emoji.starts_with(TRADE_DECLINE_EMOJI) {
                     let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
-                    let resp = vote_common_async(
-                        pool,
-                        vote_direction,
-                        vote_count as i64,
-                        user_id.into(),
-                        None,
-                        Some(message_id.0 as i64),
-                        None,
-                    ).await;
-                    user_id.create_dm_channel(&ctx).await.unwrap().say(&ctx, resp).await.unwrap();
+                    let res = match trade_reaction_async(Arc::clone(&pool), message_id.0 as i64, user_id.into(), accept).await {
+                        Some(res) => Some(res.map_err(|e| e.to_string())),
+                        None => match loan_reaction_async(Arc::clone(&pool), message_id.0 as i64, user_id.into(), accept).await {
+                            Some(res) => Some(res.map_err(|e| e.to_string())),
+                            None => give_reaction_async(pool, message_id.0 as i64, user_id.into(), accept).await
+                                .map(|res| res.map_err(|e| e.to_string())),
+                        },
+                    };
+                    match res {
+                        Some(Ok(announcement)) => {
+                            r.channel_id.say(&ctx, announcement).await.unwrap();
+                        },
+                        Some(Err(e)) => {
+                            user_id.create_dm_channel(&ctx).await.unwrap().say(&ctx, e).await.unwrap();
+                        },
+                        None => (), // Not a reaction on a trade, loan offer, or give awaiting confirmation
+                    }
                 }
             }
         }
     }
 }
 
+/// Records the usernames of people the bot has seen, for name_of
+async fn record_names(ctx: &Context, seen: Vec<(models::UserId, String)>) {
+    let now = Utc::now();
+    if let Err(e) = with_conn(ctx, move |conn| crate::names::record(conn, &seen, now)).await {
+        warn!("Failed to record names: {:?}", e);
+    }
+}
+
+/// Keeps picking up names recorded by the website, for embeds and replies that use name_of
+async fn refresh_names(pool: Arc<DbPool>) {
+    loop {
+        let pool = Arc::clone(&pool);
+        let res = task::spawn_blocking(move || crate::names::refresh_if_stale(&*pool.get().unwrap())).await.unwrap();
+        if let Err(e) = res {
+            warn!("Failed to refresh names: {:?}", e);
+        }
+        tokio::time::sleep(crate::names::REFRESH_EVERY).await;
+    }
+}
+
+async fn trade_reaction_async(
+    pool: Arc<DbPool>,
+    message_id: i64,
+    user_id: models::UserId,
+    accept: bool,
+) -> Option<Result<String, crate::trades::TradeError>> {
+    trace!("trade_reaction_async");
+    let now = Utc::now();
+    task::spawn_blocking(move || {
+        let conn = pool.get().unwrap();
+        let trade_id = crate::trades::find_by_message(&*conn, message_id).unwrap()?;
+        Some(if accept {
+            crate::trades::accept(&*conn, trade_id, user_id, now).map(|trade| format!(
+                "Trade#{} accepted: {}.",
+                trade.damm(),
+                crate::trades::describe(&trade),
+            ))
+        } else {
+            crate::trades::cancel(&*conn, trade_id, user_id, now).map(|trade| format!(
+                "Trade#{} was {}.",
+                trade.damm(),
+                if user_id == trade.offerer { "withdrawn" } else { "declined" },
+            ))
+        })
+    }).await.unwrap()
+}
+
+async fn loan_reaction_async(
+    pool: Arc<DbPool>,
+    message_id: i64,
+    user_id: models::UserId,
+    accept: bool,
+) -> Option<Result<String, crate::loans::LoanError>> {
+    trace!("loan_reaction_async");
+    let now = Utc::now();
+    task::spawn_blocking(move || {
+        let conn = pool.get().unwrap();
+        let loan_id = crate::loans::find_by_message(&*conn, message_id).unwrap()?;
+        Some(if accept {
+            crate::loans::accept(&*conn, loan_id, user_id, now).map(|loan| format!(
+                "Loan#{} accepted: {}. The first payment is due <t:{}:R>.",
+                loan.damm(),
+                crate::loans::describe(&loan),
+                loan.next_payment_at.unwrap().timestamp(),
+            ))
+        } else {
+            crate::loans::cancel(&*conn, loan_id, user_id, now).map(|loan| format!(
+                "Loan#{} was {}.",
+                loan.damm(),
+                if user_id == loan.lender { "withdrawn" } else { "declined" },
+            ))
+        })
+    }).await.unwrap()
+}
+
+async fn give_reaction_async(
+    pool: Arc<DbPool>,
+    message_id: i64,
+    user_id: models::UserId,
+    accept: bool,
+) -> Option<Result<String, crate::give_confirmations::GiveError>> {
+    trace!("give_reaction_async");
+    let now = Utc::now();
+    task::spawn_blocking(move || {
+        let conn = pool.get().unwrap();
+        let pending_id = crate::give_confirmations::find_by_message(&*conn, message_id).unwrap()?;
+        Some(if accept {
+            crate::give_confirmations::confirm(&*conn, pending_id, user_id, now).map(|pending| format!(
+                "Successfully transferred {} {} to {}.",
+                pending.quantity,
+                pending.ty,
+                pending.recipient.into_serenity().mention(),
+            ))
+        } else {
+            crate::give_confirmations::cancel(&*conn, pending_id, user_id, now).map(|_| "Give cancelled.".to_string())
+        })
+    }).await.unwrap()
+}
+
 fn nth_vote_cost(n:i64) -> Result<i64,()> {
     trace!("nth_vote_cost");
     let res:f64 = (VOTE_BASE_COST as f64) * (1.05f64).powf((n-1) as f64);
@@ -206,14 +354,128 @@ fn nth_vote_cost(n:i64) -> Result<i64,()> {
 }
 
 #[hook]
-async fn on_dispatch_error_hook(_context: &Context, msg: &Message, error: DispatchError){
+async fn on_dispatch_error_hook(context: &Context, msg: &Message, error: DispatchError){
     println!(
         "{:?}\nerr'd with {:?}",
         msg, error
     );
+    if let DispatchError::CheckFailed(_, Reason::User(reason)) = error {
+        let _ = msg.reply(context, reason).await;
+    }
+}
+
+/// The guild the motions channel is in, whose owner and members with its admin role are the bot's admins
+async fn home_guild(ctx: &Context) -> Option<serenity::model::id::GuildId> {
+    ctx.cache.guild_channel(MOTIONS_CHANNEL).await.map(|channel| channel.guild_id)
+}
+
+fn has_role(member: &serenity::model::guild::Member, role: Option<i64>) -> bool {
+    role.map(|role| member.roles.iter().any(|r| r.0 as i64 == role)).unwrap_or(false)
+}
+
+async fn is_admin(ctx: &Context, user: SerenityUserId) -> CommandResult<bool> {
+    let home = match home_guild(ctx).await {
+        Some(home) => home,
+        None => return Ok(false),
+    };
+    if ctx.cache.guild_field(home, |g| g.owner_id).await == Some(user) {
+        return Ok(true);
+    }
+    let home_raw = home.0 as i64;
+    let config = config_of(ctx, home_raw).await?;
+    // Someone who isn't in the home guild can't have its role
+    Ok(home.member(ctx, user).await.map(|member| has_role(&member, config.admin_role)).unwrap_or(false))
+}
+
+/// Records who the admins are for the website, from the home guild's members as cached
+async fn sync_admins(ctx: &Context) {
+    let home = match home_guild(ctx).await {
+        Some(home) => home,
+        None => return,
+    };
+    let home_raw = home.0 as i64;
+    let role = match config_of(ctx, home_raw).await {
+        Ok(config) => config.admin_role,
+        Err(e) => {
+            warn!("Failed to sync admins: {:?}", e);
+            return;
+        },
+    };
+    let admins:Option<Vec<models::UserId>> = ctx.cache.guild_field(home, |g| g.members.values()
+        .filter(|m| m.user.id == g.owner_id || has_role(m, role))
+        .map(|m| m.user.id.into())
+        .collect()
+    ).await;
+    if let Some(admins) = admins {
+        if let Err(e) = with_conn(ctx, move |conn| crate::admins::sync(conn, &admins)).await {
+            warn!("Failed to sync admins: {:?}", e);
+        }
+    }
+}
+
+#[check]
+#[name = "Admin"]
+async fn admin_check(ctx: &Context, msg: &Message, _: &mut Args, _: &CommandOptions) -> Result<(), Reason> {
+    match is_admin(ctx, msg.author.id).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(Reason::User("Only admins can do that.".to_string())),
+        Err(e) => Err(Reason::Log(format!("{:?}", e))),
+    }
 }
 
 
+/// Everything except balances, gives and motions is only in the shared economy, so commands for the rest don't work
+/// in an isolated guild
+#[check]
+#[name = "SharedEconomy"]
+async fn shared_economy_check(ctx: &Context, msg: &Message, _: &mut Args, _: &CommandOptions) -> Result<(), Reason> {
+    match economy_of(ctx, msg.guild_id).await {
+        Ok(crate::guilds::SHARED_ECONOMY) => Ok(()),
+        Ok(_) => Err(Reason::User("That isn't available in a server with its own economy.".to_string())),
+        Err(e) => Err(Reason::Log(format!("{:?}", e))),
+    }
+}
+
+/// Admins, the guild's owner, and members with its admin role can change its settings
+#[check]
+#[name = "GuildAdmin"]
+async fn guild_admin_check(ctx: &Context, msg: &Message, _: &mut Args, _: &CommandOptions) -> Result<(), Reason> {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Err(Reason::User("That only works in a server.".to_string())),
+    };
+    let guild_id_raw = guild_id.0 as i64;
+    let res = config_of(ctx, guild_id_raw).await;
+    let config = match res {
+        Ok(v) => v,
+        Err(e) => return Err(Reason::Log(format!("{:?}", e))),
+    };
+    let is_admin = match is_admin(ctx, msg.author.id).await {
+        Ok(v) => v,
+        Err(e) => return Err(Reason::Log(format!("{:?}", e))),
+    };
+    let is_owner = ctx.cache.guild_field(guild_id, |g| g.owner_id).await == Some(msg.author.id);
+    let has_role = match (config.admin_role, &msg.member) {
+        (Some(role), Some(member)) => member.roles.iter().any(|r| r.0 as i64 == role),
+        _ => false,
+    };
+    if is_admin || is_owner || has_role {
+        Ok(())
+    } else {
+        Err(Reason::User("Only this server's admins can do that.".to_string()))
+    }
+}
+
+#[hook]
+async fn guild_prefix(ctx: &Context, msg: &Message) -> Option<String> {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id.0 as i64,
+        None => return Some(crate::guilds::DEFAULT_PREFIX.to_string()),
+    };
+    let config = config_of(ctx, guild_id).await;
+    Some(config.map(|c| c.command_prefix).unwrap_or_else(|_| crate::guilds::DEFAULT_PREFIX.to_string()))
+}
+
 #[hook]
 async fn after_hook(ctx: &Context, msg: &Message, _cmd_name: &str, error: Result<(), CommandError>) {
     trace!("after_hook");
@@ -248,17 +510,14 @@ pub async fn bot_main() {
         }
     }
     
-    #[cfg(feature = "debug")]
-    let prefix = "&";
-    #[cfg(not(feature = "debug"))]
-    let prefix = "$";
     let mut framework = StandardFramework::new()
     .configure(|c| {
-        c.prefix(prefix).allow_dm(true).on_mention(Some(MY_ID))
+        // An empty prefix means none; each guild's prefix comes from guild_prefix
+        c.prefix("").dynamic_prefix(guild_prefix).allow_dm(true).on_mention(Some(MY_ID))
     })
     .on_dispatch_error(on_dispatch_error_hook)
     .after(after_hook);
-    framework = framework.group(&GENERAL_GROUP);
+    framework = framework.group(&GENERAL_GROUP).group(&ADMIN_GROUP).group(&GUILDADMIN_GROUP);
     #[cfg(feature = "debug")]
     { framework = framework.group(&GENERAL_GROUP).group(&DEBUG_GROUP); }
     trace!("framework configured");
@@ -268,6 +527,8 @@ pub async fn bot_main() {
     let mut client = {
         use serenity::client::bridge::gateway::GatewayIntents;
         Client::builder(&env::var("DISCORD_TOKEN").expect("token"))
+            // The bot's application shares its user's id
+            .application_id(MY_ID_INT)
             .event_handler(Handler)
             .framework(framework)
             .intents(
@@ -332,6 +593,8 @@ pub async fn update_motion_message(
     let pass = is_win(yes_votes, no_votes, &power);
     let cap_label = motion_label(&power);
     msg.edit(cnh, |m| {
+        // Motions called before voting moved to buttons get them on their next update
+        m.components(interactions::motion_buttons);
         m.embed(|e| {
             e.field(cap_label, motion_text, false);
             if pass {
@@ -359,7 +622,17 @@ pub async fn update_motion_message(
 async fn hack_message_update(ctx: &Context, _msg: &Message, mut args: Args) -> CommandResult {
     trace!("hack_message_update");
     let motion_message_id:u64 = args.single()?;
-    let mut motion_message = ctx.http.get_message(MOTIONS_CHANNEL, motion_message_id).await?;
+    let channel_id = with_conn(ctx, move |conn| {
+        use diesel::prelude::*;
+        use schema::motions::dsl as mdsl;
+        mdsl::motions
+            .select(mdsl::channel_id)
+            .filter(mdsl::bot_message_id.eq(motion_message_id as i64))
+            .get_result::<Option<i64>>(conn)
+            .optional()
+    }).await?.flatten();
+    let channel_id = channel_id.map(|id| id as u64).unwrap_or(MOTIONS_CHANNEL);
+    let mut motion_message = ctx.http.get_message(channel_id, motion_message_id).await?;
     update_motion_message(ctx, Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap()), &mut motion_message).await
 }
 
@@ -372,9 +645,11 @@ async fn ping(ctx: &Context, msg: &Message) -> CommandResult {
 }
 
 // Use like &debug_make_auction 10 gen 1 pc
-// to create an auction offering 10 gens at a minimum bid of 1 pc
+// to create an auction offering 10 gens at a minimum bid of 1 pc,
+// or like &debug_make_auction item#1234 1 pc to auction off an item on behalf of its owner
 #[command]
-#[num_args(4)]
+#[min_args(3)]
+#[max_args(4)]
 async fn debug_make_auction(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     use diesel::prelude::*;
     use schema::auctions::dsl as adsl;
@@ -382,7 +657,41 @@ async fn debug_make_auction(ctx: &Context, msg: &Message, mut args: Args) -> Com
     let now = Utc::now();
     let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
 
-    let offer_amt:i64 = args.single()?;
+    let first:String = args.single()?;
+    if let Some(item_id) = crate::items::parse_ref(&first) {
+        let min_bid_amt:i64 = args.single()?;
+        if min_bid_amt < 1 { return Err("fuck".into()); }
+        let bid_ty = find_item_type(&*pool, args.single()?).await?;
+        let bid_ty_id = bid_ty.id.clone();
+        let (auction_id, item) = with_conn(ctx, move |conn| -> Result<(i64, models::Item), crate::items::ItemError> {
+            conn.transaction(|| {
+                let item = crate::items::get(conn, item_id)?.ok_or(crate::items::ItemError::NotFound)?;
+                let owner = item.owner.ok_or(crate::items::ItemError::NotOwner)?;
+                let auction_id:i64 = diesel::insert_into(tid::thing_ids).default_values().returning(tid::rowid).get_result(conn)?;
+                diesel::insert_into(adsl::auctions).values((
+                    adsl::rowid.eq(auction_id),
+                    adsl::created_at.eq(now),
+                    adsl::auctioneer.eq(owner.into_i64()),
+                    adsl::offer_item.eq(item_id),
+                    adsl::bid_ty.eq(bid_ty_id),
+                    adsl::bid_min.eq(min_bid_amt),
+                    adsl::last_timer_bump.eq(now),
+                )).execute(conn)?;
+                crate::items::escrow_for_auction(conn, item_id, owner, auction_id, now)?;
+                Ok((auction_id, item))
+            })
+        }).await?;
+        msg.reply(&ctx, format!(
+            "Create auction#{} auctioning {} for a minimum of {} {}",
+            damm::add_to_str(auction_id.to_string()),
+            crate::items::describe(&item),
+            min_bid_amt,
+            bid_ty.long_name_ambiguous,
+        )).await?;
+        return Ok(());
+    }
+
+    let offer_amt:i64 = first.parse()?;
     if offer_amt < 1 { return Err("fuck".into()); }
     let offer_ty = find_item_type(&*pool, args.single()?).await?;
 
@@ -454,6 +763,323 @@ async fn fabricate(ctx: &Context, msg: &Message, mut args: Args) -> CommandResul
     Ok(())
 }
 
+/// Runs `f` with a database connection on a thread where blocking is allowed.
+async fn with_conn<T: Send + 'static>(
+    ctx: &Context,
+    f: impl FnOnce(&diesel::PgConnection) -> T + Send + 'static,
+) -> T {
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+    task::spawn_blocking(move || {
+        let conn = pool.get().unwrap();
+        f(&*conn)
+    }).await.unwrap()
+}
+
+/// A guild's settings, only going to the database the first time
+async fn config_of(ctx: &Context, guild_id: i64) -> diesel::QueryResult<models::GuildConfig> {
+    match crate::guilds::cached(guild_id) {
+        Some(config) => Ok(config),
+        None => with_conn(ctx, move |conn| crate::guilds::get(conn, guild_id)).await,
+    }
+}
+
+/// The economy that commands sent in `guild_id` act on. DMs are in the shared economy.
+async fn economy_of(ctx: &Context, guild_id: Option<serenity::model::id::GuildId>) -> diesel::QueryResult<i64> {
+    match guild_id {
+        Some(guild_id) => config_of(ctx, guild_id.0 as i64).await.map(|config| config.economy()),
+        None => Ok(crate::guilds::SHARED_ECONOMY),
+    }
+}
+
+// Use like $guild_config to show this server's settings, or $guild_config prefix ! to change one
+#[command]
+#[max_args(2)]
+async fn guild_config(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    trace!("guild_config");
+    let guild_id = msg.guild_id.unwrap().0 as i64;
+    let config = match args.len() {
+        0 => with_conn(ctx, move |conn| crate::guilds::get(conn, guild_id)).await?,
+        2 => {
+            let name:String = args.single()?;
+            let value:String = args.single()?;
+            let setting = crate::guilds::parse_setting(&name, &value)?;
+            let config = with_conn(ctx, move |conn| crate::guilds::set(conn, guild_id, setting)).await?;
+            sync_admins(ctx).await;
+            config
+        },
+        _ => return Err(crate::guilds::SETTINGS_USAGE.into()),
+    };
+    msg.channel_id.send_message(&ctx, |cm| {
+        cm.embed(|e| e.title("Server settings").description(crate::guilds::describe(&config)))
+    }).await?;
+    Ok(())
+}
+
+// Use like $mint @user 100 pc
+// to create fungibles out of nothing in a server with its own economy, to get it started
+#[command]
+#[min_args(2)]
+#[max_args(3)]
+async fn mint(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    trace!("mint");
+    let now = Utc::now();
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+    let economy = economy_of(ctx, msg.guild_id).await?;
+    if economy == crate::guilds::SHARED_ECONOMY {
+        return Err("Minting only works in a server with its own economy.".into());
+    }
+    let user_str:String = args.single()?;
+    let user = SerenityUserId::from_command_args(ctx, msg, &user_str).await?;
+    let rest:Vec<String> = args.iter::<String>().map(|a| a.unwrap()).collect();
+    let (amount, ty) = parse_amount_and_type(&*pool, rest).await?;
+
+    let mut fail:Option<&'static str> = None;
+    let ty_copy = ty.clone();
+    pool.transaction(|txn| {
+        let t = TransactionBuilder::new(
+            amount,
+            ty_copy.id,
+            now,
+        ).fabricate(user.into(), false).message_id(msg.id);
+        match TransferHandler::handle_single_in(txn, economy, t) {
+            Err(_) => {
+                fail = Some("Overflow.");
+                return Ok(());
+            },
+            Ok(v) => v?,
+        }
+        Ok(())
+    }).await?;
+    if let Some(fail_msg) = fail {
+        msg.reply(&ctx, fail_msg).await?;
+    } else {
+        msg.reply(&ctx, format!("Minted {} {} for {}.", amount, &ty.long_name_ambiguous, user.mention())).await?;
+    }
+    Ok(())
+}
+
+// Use like $item_type_create gold "Gold Stars" "gold star(s)"
+// Long names with spaces must be quoted. An optional fourth argument sets the position in listings.
+#[command]
+#[min_args(3)]
+#[max_args(4)]
+async fn item_type_create(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    trace!("item_type_create");
+    let name:String = args.single_quoted()?;
+    let long_name_plural:String = args.single_quoted()?;
+    let long_name_ambiguous:String = args.single_quoted()?;
+    let position:Option<i32> = if args.remaining() > 0 { Some(args.single()?) } else { None };
+    let reply_name = name.clone();
+    with_conn(ctx, move |conn| crate::item_types::create(
+        conn,
+        &name,
+        &long_name_plural,
+        &long_name_ambiguous,
+        position,
+    )).await?;
+    msg.reply(&ctx, format!("Created item type `{}`.", reply_name)).await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(2)]
+async fn item_type_position(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    trace!("item_type_position");
+    let name:String = args.single()?;
+    let position:i32 = args.single()?;
+    with_conn(ctx, move |conn| crate::item_types::set_position(conn, &name, position)).await?;
+    msg.reply(&ctx, "Position updated.").await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+async fn item_type_retire(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    trace!("item_type_retire");
+    let name:String = args.single()?;
+    let now = Utc::now();
+    with_conn(ctx, move |conn| crate::item_types::set_retired(conn, &name, Some(now))).await?;
+    msg.reply(&ctx, "Retired. It can no longer be transferred, but its history remains.").await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+async fn item_type_unretire(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    trace!("item_type_unretire");
+    let name:String = args.single()?;
+    with_conn(ctx, move |conn| crate::item_types::set_retired(conn, &name, None)).await?;
+    msg.reply(&ctx, "Unretired.").await?;
+    Ok(())
+}
+
+// Use like $item_type_confirm_above pc 10000
+// to require confirmation for gives of more than 10000 pc, or `off` to never require it for that amount
+#[command]
+#[num_args(2)]
+async fn item_type_confirm_above(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    trace!("item_type_confirm_above");
+    let name:String = args.single()?;
+    let threshold_str:String = args.single()?;
+    let threshold:Option<i64> = if threshold_str.eq_ignore_ascii_case("off") {
+        None
+    } else {
+        match threshold_str.parse() {
+            Ok(v) if v >= 0 => Some(v),
+            _ => return Err("Threshold must be a non-negative number, or `off`.".into()),
+        }
+    };
+    with_conn(ctx, move |conn| crate::item_types::set_confirm_give_above(conn, &name, threshold)).await?;
+    msg.reply(&ctx, "Confirmation threshold updated.").await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(2)]
+async fn item_type_alias_add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    trace!("item_type_alias_add");
+    let name:String = args.single()?;
+    let alias:String = args.single()?;
+    with_conn(ctx, move |conn| crate::item_types::add_alias(conn, &name, &alias)).await?;
+    msg.reply(&ctx, "Alias added.").await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+async fn item_type_alias_remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    trace!("item_type_alias_remove");
+    let alias:String = args.single()?;
+    with_conn(ctx, move |conn| crate::item_types::remove_alias(conn, &alias)).await?;
+    msg.reply(&ctx, "Alias removed.").await?;
+    Ok(())
+}
+
+// Use like $item_create shelvacu title "Best Dressed" year=2022 awarded_by=colin
+// Quote names with spaces. Anything after the name is key=value metadata.
+#[command]
+#[min_args(3)]
+async fn item_create(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    trace!("item_create");
+    let now = Utc::now();
+    let user_str:String = args.single()?;
+    let owner:models::UserId = SerenityUserId::from_command_args(ctx, msg, &user_str).await?.into();
+    let kind:String = args.single()?;
+    let name:String = args.single_quoted()?;
+    if kind.is_empty() || kind.len() > 32 {
+        return Err("Kind must be 1 to 32 characters.".into());
+    }
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err("Name must be 1 to 64 characters.".into());
+    }
+    let mut metadata = serde_json::Map::new();
+    for arg in args.iter::<String>().quoted().map(|a| a.unwrap()) {
+        match arg.split_once('=') {
+            Some((key, value)) if !key.is_empty() => {
+                metadata.insert(key.to_string(), serde_json::Value::String(value.to_string()));
+            },
+            _ => return Err(format!("Metadata must look like key=value, not {}", arg).into()),
+        }
+    }
+    let item_id = with_conn(ctx, move |conn| crate::items::create(
+        conn,
+        owner,
+        &kind,
+        &name,
+        serde_json::Value::Object(metadata),
+        now,
+    )).await?;
+    msg.reply(&ctx, format!(
+        "Created item#{}, owned by {}. {}/items/{}",
+        damm::add_to_str(item_id.to_string()),
+        crate::names::name_of(owner),
+        crate::SITE_URL,
+        damm::add_to_str(item_id.to_string()),
+    )).await?;
+    Ok(())
+}
+
+/// Parses an auto-auction definition from the start of `words` (see `auto_auctions::DEF_USAGE`), returning it and how
+/// many words it took.
+async fn parse_auto_auction_def(pool: &DbPool, words: &[String]) -> CommandResult<(crate::auto_auctions::AutoAuctionDef, usize)> {
+    let usage = || -> Box<dyn std::error::Error + Send + Sync> { crate::auto_auctions::DEF_USAGE.into() };
+    if words.len() < 6 {
+        return Err(usage());
+    }
+    let for_idx = words.iter().position(|w| w.eq_ignore_ascii_case("for")).ok_or_else(usage)?;
+    let lasting_idx = words.iter().position(|w| w.eq_ignore_ascii_case("lasting")).ok_or_else(usage)?;
+    if for_idx < 7 || lasting_idx < for_idx + 2 || words.len() < lasting_idx + 3 {
+        return Err(usage());
+    }
+    let multi_unit = words[lasting_idx - 1].eq_ignore_ascii_case("each");
+    let bid_end = if multi_unit { lasting_idx - 1 } else { lasting_idx };
+    let (offer_amt, offer_ty) = parse_amount_and_type(pool, words[6..for_idx].to_vec()).await?;
+    let (bid_min, bid_ty) = parse_amount_and_type(pool, words[for_idx+1..bid_end].to_vec()).await?;
+    let duration_minutes = crate::auto_auctions::parse_duration(&words[lasting_idx+1], &words[lasting_idx+2]).ok_or_else(usage)?;
+    Ok((crate::auto_auctions::AutoAuctionDef{
+        schedule: words[..5].join(" "),
+        timezone: words[5].clone(),
+        offer_ty: offer_ty.id,
+        offer_amt,
+        bid_ty: bid_ty.id,
+        bid_min,
+        duration_minutes,
+        multi_unit,
+    }, lasting_idx + 3))
+}
+
+// Use like $auto_auction_set weekly 0 7 * * 6 US/Pacific 10 gen for 1 pc each lasting 96 hours
+// to create or replace the auto-auction named weekly
+#[command]
+#[min_args(12)]
+async fn auto_auction_set(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    trace!("auto_auction_set");
+    let now = Utc::now();
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+    let name:String = args.single()?;
+    let words:Vec<String> = args.iter::<String>().map(|a| a.unwrap()).collect();
+    let (def, used) = parse_auto_auction_def(&*pool, &words).await?;
+    if used != words.len() {
+        return Err(crate::auto_auctions::DEF_USAGE.into());
+    }
+    let description = def.describe();
+    with_conn(ctx, move |conn| crate::auto_auctions::set(conn, &name, &def, None, now)).await?;
+    msg.reply(&ctx, format!("Auto-auction set: {}.", description)).await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+async fn auto_auction_remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    trace!("auto_auction_remove");
+    let name:String = args.single()?;
+    with_conn(ctx, move |conn| crate::auto_auctions::remove(conn, &name)).await?;
+    msg.reply(&ctx, "Auto-auction removed.").await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+async fn auto_auction_pause(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    trace!("auto_auction_pause");
+    let now = Utc::now();
+    let name:String = args.single()?;
+    with_conn(ctx, move |conn| crate::auto_auctions::set_enabled(conn, &name, false, None, now)).await?;
+    msg.reply(&ctx, "Auto-auction paused.").await?;
+    Ok(())
+}
+
+#[command]
+#[num_args(1)]
+async fn auto_auction_resume(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    trace!("auto_auction_resume");
+    let now = Utc::now();
+    let name:String = args.single()?;
+    with_conn(ctx, move |conn| crate::auto_auctions::set_enabled(conn, &name, true, None, now)).await?;
+    msg.reply(&ctx, "Auto-auction resumed.").await?;
+    Ok(())
+}
+
 #[command]
 #[aliases("?","h")]
 async fn help(ctx: &Context, msg: &Message) -> CommandResult {
@@ -480,23 +1106,42 @@ async fn version_info(ctx: &Context, msg: &Message) -> CommandResult {
 #[aliases("b","bal","balance","i","inv","inventory")]
 async fn balances(ctx: &Context, msg: &Message) -> CommandResult {
     trace!("balances");
+    let economy = economy_of(ctx, msg.guild_id).await?;
+    let fields = balance_fields(ctx, msg.author.id.into(), economy).await?;
+    msg.channel_id.send_message(&ctx, |cm| {
+        cm.embed(|e| {
+            e.title("Your balances:");
+            for (name, value) in fields {
+                e.field(name, value, false);
+            }
+            e
+        });
+        cm
+    }).await?;
+    Ok(())
+}
+
+/// The embed fields listing what `author` has in `economy`: a balance of each item type, then their items and active
+/// loans, which are only in the shared economy
+async fn balance_fields(ctx: &Context, author: models::UserId, economy: i64) -> CommandResult<Vec<(String, String)>> {
     use diesel::prelude::*;
     use view_schema::balance_history::dsl as bh;
     use schema::item_types::dsl as it;
     
 
     let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
-    let item_types:Vec<ItemType> = it::item_types
-        .select(ItemType::cols())
+    let item_types:Vec<(ItemType, bool)> = it::item_types
+        .select((ItemType::cols(), it::retired_at.is_not_null()))
         .order(it::position)
         .get_results_async(&*pool).await?;
     
-    let mut balances = Vec::new();
-    for it in item_types {
+    let mut fields = Vec::new();
+    for (it, retired) in item_types {
         let bal = bh::balance_history
         .select(bh::balance)
-        .filter(bh::user.eq(msg.author.id.0 as i64))
+        .filter(bh::user.eq(author))
         .filter(bh::ty.eq(it.db_name()))
+        .filter(bh::economy.eq(economy))
         .order((bh::happened_at.desc(), bh::rowid.desc(), bh::sign.desc()))
         .limit(1)
         .get_result_async(&*pool)
@@ -504,19 +1149,38 @@ async fn balances(ctx: &Context, msg: &Message) -> CommandResult {
         .optional()
         .map(|opt| opt.unwrap_or(0i64))?:i64;
 
-        balances.push((it, bal));
+        if retired && bal == 0 {
+            continue;
+        }
+        fields.push((it.long_name_plural, bal.to_string()));
     };
-    msg.channel_id.send_message(&ctx, |cm| {
-        cm.embed(|e| {
-            e.title("Your balances:");
-            for (item_type, amount) in &balances {
-                e.field(&item_type.long_name_plural, amount, false);
-            }
-            e
-        });
-        cm
-    }).await?;
-    Ok(())
+    if economy != crate::guilds::SHARED_ECONOMY {
+        return Ok(fields);
+    }
+    let items = with_conn(ctx, move |conn| crate::items::owned_by(conn, author)).await?;
+    let now = Utc::now();
+    let loans = with_conn(ctx, move |conn| crate::loans::outstanding_for(conn, author, now)).await?;
+    let loans:Vec<_> = loans.into_iter().filter(|l| l.status(now) == models::LoanStatus::Active).collect();
+    if !items.is_empty() {
+        // Embed fields are limited to 1024 characters
+        let mut listed = items.iter().take(10).map(crate::items::describe).collect::<Vec<_>>();
+        if items.len() > 10 {
+            listed.push(format!("...and {} more", items.len() - 10));
+        }
+        fields.push(("Items".to_string(), listed.join("\n")));
+    }
+    if !loans.is_empty() {
+        let mut listed = loans.iter().take(10).map(|loan| if loan.borrower == author {
+            format!("You owe {} {} {} (loan#{})", crate::names::name_of(loan.lender), loan.outstanding, loan.ty, loan.damm())
+        } else {
+            format!("{} owes you {} {} (loan#{})", crate::names::name_of(loan.borrower), loan.outstanding, loan.ty, loan.damm())
+        }).collect::<Vec<_>>();
+        if loans.len() > 10 {
+            listed.push(format!("...and {} more", loans.len() - 10));
+        }
+        fields.push(("Loans".to_string(), listed.join("\n")));
+    }
+    Ok(fields)
 }
 
 
@@ -527,18 +1191,163 @@ async fn give(ctx:&Context, msg:&Message, args:Args) -> CommandResult {
     give_common(ctx, msg, args, true).await
 }
 
+// Admin only. Gives to ids the bot has never seen can be undone with $undo_force_give for a while.
 #[command]
+#[checks(SharedEconomy)]
 #[min_args(2)]
 #[max_args(3)]
 async fn force_give(ctx:&Context, msg:&Message, args:Args) -> CommandResult {
     give_common(ctx, msg, args, false).await
 }
 
+async fn force_give_fungible(
+    ctx:&Context,
+    msg:&Message,
+    user:SerenityUserId,
+    user_seen:bool,
+    amount:i64,
+    ty:&ItemType,
+) -> CommandResult {
+    let now = Utc::now();
+    let giver:models::UserId = msg.author.id.into();
+    let ty_id = ty.id.clone();
+    let message_id = msg.id.0 as i64;
+    let record = with_conn(ctx, move |conn| crate::force_gives::perform(
+        conn,
+        giver,
+        user.into(),
+        ty_id,
+        amount,
+        message_id,
+        user_seen,
+        now,
+        *crate::FORCE_GIVE_UNDO_WINDOW,
+    )).await?;
+    let undo_note = match record.undo_until {
+        Some(until) => format!(
+            " I've never seen that user, so this can be undone with `$undo_force_give {}` until <t:{}:f>.",
+            record.rowid,
+            until.timestamp(),
+        ),
+        None => String::new(),
+    };
+    msg.reply(&ctx, format!(
+        "Force gave {} {} to {}.{}",
+        amount,
+        &ty.long_name_ambiguous,
+        user.mention(),
+        undo_note,
+    )).await?;
+    Ok(())
+}
+
+#[command]
+#[checks(SharedEconomy)]
+#[num_args(1)]
+async fn undo_force_give(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("undo_force_give");
+    let now = Utc::now();
+    let id:i64 = args.single::<String>()?.trim_start_matches('#').parse().map_err(|_| "Invalid force give id.")?;
+    let admin:models::UserId = msg.author.id.into();
+    let record = with_conn(ctx, move |conn| crate::force_gives::undo(conn, id, admin, now)).await?;
+    msg.reply(&ctx, format!(
+        "Undid force give #{}: {} {} returned from user id {} to {}.",
+        record.rowid,
+        record.quantity,
+        record.ty,
+        record.recipient,
+        record.giver.into_serenity().mention(),
+    )).await?;
+    Ok(())
+}
+
+#[command]
+async fn force_gives(ctx:&Context, msg:&Message) -> CommandResult {
+    trace!("force_gives");
+    let now = Utc::now();
+    let records = with_conn(ctx, |conn| crate::force_gives::recent(conn, 10)).await?;
+    if records.is_empty() {
+        msg.reply(&ctx, "No force gives yet.").await?;
+        return Ok(());
+    }
+    msg.channel_id.send_message(&ctx, |cm| {
+        cm.embed(|e| {
+            e.title("Recent force gives:");
+            for record in &records {
+                let status = if let Some(undone_at) = record.undone_at {
+                    format!("undone <t:{}:R>", undone_at.timestamp())
+                } else if record.can_undo(now) {
+                    format!("can be undone until <t:{}:f>", record.undo_until.unwrap().timestamp())
+                } else {
+                    "final".to_string()
+                };
+                e.field(
+                    format!("#{}", record.rowid),
+                    format!(
+                        "{} gave {} {} to user id {} <t:{}:R>; {}",
+                        record.giver.into_serenity().mention(),
+                        record.quantity,
+                        record.ty,
+                        record.recipient,
+                        record.happened_at.timestamp(),
+                        status,
+                    ),
+                    false,
+                );
+            }
+            e
+        });
+        cm
+    }).await?;
+    Ok(())
+}
+
+// Use like $burn 100 pc
+// to destroy 100 of your own pc, on purpose
+#[command]
+#[min_args(1)]
+#[max_args(2)]
+async fn burn(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("burn");
+    let now = Utc::now();
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+    let rest:Vec<String> = args.iter::<String>().map(|a| a.unwrap()).collect();
+    let (amount, ty) = parse_amount_and_type(&*pool, rest).await?;
+    let economy = economy_of(ctx, msg.guild_id).await?;
+
+    let mut fail:Option<&'static str> = None;
+    let ty_copy = ty.clone();
+    pool.transaction(|txn| {
+        let t = TransactionBuilder::new(
+            amount,
+            ty_copy.id,
+            now,
+        ).burn(
+            msg.author.id.into(),
+        ).message_id(msg.id);
+        match TransferHandler::handle_single_in(txn, economy, t) {
+            Err(TransferError::NotEnough) => {
+                fail = Some("Insufficient balance.");
+                return Ok(());
+            },
+            Err(TransferError::Overflow) => {
+                fail = Some("Overflow.");
+                return Ok(());
+            },
+            Ok(v) => v?,
+        }
+        Ok(())
+    }).await?;
+    if let Some(fail_msg) = fail {
+        msg.reply(&ctx, fail_msg).await?;
+    } else {
+        msg.reply(&ctx, format!("Burned {} {}. They're gone for good.", amount, &ty.long_name_ambiguous)).await?;
+    }
+    Ok(())
+}
+
 async fn give_common(ctx:&Context, msg:&Message, mut args:Args, check_user:bool) -> CommandResult {
     trace!("give_common");
-    use diesel::prelude::*;
-    use schema::item_types::dsl as it;
-    use schema::item_type_aliases::dsl as ita;
     let now = Utc::now();
     let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
 
@@ -551,20 +1360,165 @@ async fn give_common(ctx:&Context, msg:&Message, mut args:Args, check_user:bool)
             Err(e) => return Err(Box::new(e)),
         }
     } else { false };
-    if check_user && !ctx.cache.users().await.contains_key(&user) && !user_in_guild {
+    let user_seen = user_in_guild || ctx.cache.users().await.contains_key(&user);
+    if check_user && !user_seen {
         return Err("User not found".into());
     }
+    let economy = economy_of(ctx, msg.guild_id).await?;
+    let rest:Vec<String> = args.iter::<String>().map(|a| a.unwrap()).collect();
+    if let [item_ref] = rest.as_slice() {
+        if let Some(item_id) = crate::items::parse_ref(item_ref) {
+            if !check_user {
+                // Force gives are recorded and undone as fungibles; items move with $give
+                return Err("Items can't be force given. Use $give.".into());
+            }
+            if economy != crate::guilds::SHARED_ECONOMY {
+                return Err("Items can't be given in a server with its own economy.".into());
+            }
+            return give_item(ctx, msg, item_id, user).await;
+        }
+    }
+    let (amount, ty) = parse_amount_and_type(&*pool, rest).await?;
+    if !check_user {
+        return force_give_fungible(ctx, msg, user, user_seen, amount, &ty).await;
+    }
+
+    let giver:models::UserId = msg.author.id.into();
+    let ty_id = ty.id.clone();
+    let resolved_by_name = !user_ref_is_exact(&user_str);
+    let reasons = with_conn(ctx, move |conn| crate::give_confirmations::check(
+        conn,
+        economy,
+        giver,
+        user.into(),
+        &ty_id,
+        amount,
+        resolved_by_name,
+    )).await?;
+    if !reasons.is_empty() {
+        return ask_to_confirm_give(ctx, msg, economy, user, amount, &ty, &reasons).await;
+    }
+
+    let mut fail:Option<&'static str> = None;
+    let ty_copy = ty.clone();
+    pool.transaction(|txn| {
+        let t = TransactionBuilder::new(
+            amount,
+            ty_copy.id,
+            now,
+        ).give(
+            msg.author.id.into(),
+            user.into(),
+            false,
+        ).message_id(msg.id);
+        match TransferHandler::handle_single_in(txn, economy, t) {
+            Err(TransferError::NotEnough) => {
+                fail = Some("Insufficient balance.");
+                return Ok(());
+            },
+            Err(TransferError::Overflow) => {
+                fail = Some("Overflow.");
+                return Ok(());
+            },
+            Ok(v) => v?,
+        }
+        Ok(())
+    }).await?;
+    if let Some(fail_msg) = fail {
+        msg.reply(&ctx, fail_msg).await?;
+    }else{
+        msg.reply(&ctx, format!(
+            "Successfully transferred {} {} to {}.",
+            amount,
+            &ty.long_name_ambiguous,
+            user.mention()
+        )).await?;
+    }
+    
+    Ok(())
+}
+
+/// Whether a user argument names someone exactly, by id or mention, rather than by a name that could match the wrong
+/// person
+fn user_ref_is_exact(arg: &str) -> bool {
+    arg == "." || arg == "self" || arg.parse::<u64>().is_ok() || USER_PING_RE.is_match(arg)
+}
+
+/// Holds off on a give until the giver confirms it with a reaction
+async fn ask_to_confirm_give(
+    ctx:&Context,
+    msg:&Message,
+    economy:i64,
+    user:SerenityUserId,
+    amount:i64,
+    ty:&ItemType,
+    reasons:&[crate::give_confirmations::ConfirmReason],
+) -> CommandResult {
+    let now = Utc::now();
+    let expires_at = now + *crate::GIVE_CONFIRMATION_EXPIRATION;
+    let recipient = match user.to_user(&ctx).await {
+        Ok(u) => format!("{} (id {})", u.tag(), u.id),
+        Err(_) => format!("id {}", user),
+    };
+    let giver:models::UserId = msg.author.id.into();
+    let ty_id = ty.id.clone();
+    let command_message_id = msg.id.0 as i64;
+    let pending_id = with_conn(ctx, move |conn| crate::give_confirmations::create(
+        conn,
+        economy,
+        giver,
+        user.into(),
+        ty_id,
+        amount,
+        command_message_id,
+        now,
+        expires_at,
+    )).await?;
+    let reasons:Vec<String> = reasons.iter().map(|r| r.to_string()).collect();
+    let bot_msg = msg.reply(&ctx, format!(
+        "Give {amount} {ty} to {recipient}? Please confirm, because {reasons}. \
+        React with {accept} to send it or {decline} to cancel. This expires <t:{expires}:R>.",
+        ty = ty.long_name_ambiguous,
+        reasons = reasons.join(", and "),
+        accept = TRADE_ACCEPT_EMOJI,
+        decline = TRADE_DECLINE_EMOJI,
+        expires = expires_at.timestamp(),
+    )).await?;
+    let message_id = bot_msg.id.0 as i64;
+    with_conn(ctx, move |conn| crate::give_confirmations::set_message_id(conn, pending_id, message_id)).await?;
+    bot_msg.react(&ctx, TRADE_ACCEPT_EMOJI).await?;
+    bot_msg.react(&ctx, TRADE_DECLINE_EMOJI).await?;
+    Ok(())
+}
+
+async fn give_item(ctx:&Context, msg:&Message, item_id:i64, user:SerenityUserId) -> CommandResult {
+    let now = Utc::now();
+    let from:models::UserId = msg.author.id.into();
+    let message_id = msg.id.0 as i64;
+    let res = with_conn(ctx, move |conn| -> Result<models::Item, crate::items::ItemError> {
+        crate::items::give(conn, item_id, from, user.into(), Some(message_id), now)?;
+        Ok(crate::items::get(conn, item_id)?.unwrap())
+    }).await;
+    match res {
+        Ok(item) => {
+            msg.reply(&ctx, format!(
+                "Successfully gave {} to {}.",
+                crate::items::describe(&item),
+                user.mention(),
+            )).await?;
+        },
+        Err(crate::items::ItemError::Db(e)) => return Err(e.into()),
+        Err(e) => { msg.reply(&ctx, e.to_string()).await?; },
+    }
+    Ok(())
+}
+
+/// Parses an amount and item type out of args like `100 pc`, `pc 100`, or `100pc`
+async fn parse_amount_and_type(pool: &DbPool, args: Vec<String>) -> CommandResult<(i64, ItemType)> {
     let mut maybe_ty:Option<ItemType> = None;
     let mut amount:Option<i64> = None;
-    for arg_result in args.iter::<String>(){
-        let arg = arg_result.unwrap();
-        let alias:Option<ItemType> = ita::item_type_aliases
-            .inner_join(it::item_types)
-            .select(ItemType::cols())
-            .filter(ita::alias.eq(&arg))
-            .get_result_async(&*pool)
-            .await
-            .optional()?;
+    for arg in args {
+        let alias = lookup_item_type(pool, &arg).await?;
         if let Some(ty) = alias {
             maybe_ty = Some(ty);
         } else if let Some(idx) = arg.find(|c| !('0'..='9').contains(&c)) {
@@ -573,13 +1527,7 @@ async fn give_common(ctx:&Context, msg:&Message, mut args:Args, check_user:bool)
             }
             let (count_str, ty_str) = arg.split_at(idx);
             if !ty_str.is_empty() {
-                let alias:Option<ItemType> = ita::item_type_aliases
-                    .inner_join(it::item_types)
-                    .select(ItemType::cols())
-                    .filter(ita::alias.eq(&ty_str))
-                    .get_result_async(&*pool)
-                    .await
-                    .optional()?;
+                let alias = lookup_item_type(pool, ty_str).await?;
                 if let Some(ty) = alias {
                     maybe_ty = Some(ty);
                 } else {
@@ -601,64 +1549,536 @@ async fn give_common(ctx:&Context, msg:&Message, mut args:Args, check_user:bool)
         }
     }
 
-    if let (Some(amount), Some(ty)) = (amount, maybe_ty) {
-
-        let mut fail:Option<&'static str> = None;
-        let ty_copy = ty.clone();
-        pool.transaction(|txn| {
-            let t = TransactionBuilder::new(
-                amount,
-                ty_copy.id,
-                now,
-            ).give(
-                msg.author.id.into(),
-                user.into(),
-                false,
-            ).message_id(msg.id);
-            match TransferHandler::handle_single(txn, t) {
-                Err(TransferError::NotEnough) => {
-                    fail = Some("Insufficient balance.");
-                    return Ok(());
-                },
-                Err(TransferError::Overflow) => {
-                    fail = Some("Overflow.");
-                    return Ok(());
-                },
-                Ok(v) => v?,
-            }
-            Ok(())
-        }).await?;
-        if let Some(fail_msg) = fail {
-            msg.reply(&ctx, fail_msg).await?;
-        }else{
-            msg.reply(&ctx, format!(
-                "Successfully transferred {} {} to {}.",
-                amount,
-                &ty.long_name_ambiguous,
-                user.mention()
-            )).await?;
+    match (amount, maybe_ty) {
+        (Some(amount), Some(ty)) => Ok((amount, ty)),
+        (None, _) => Err("Amount not provided.".into()),
+        (_, None) => Err("Type not provided.".into()),
+    }
+}
+
+// Use like $trade shelvacu 10 gen for 400 pc
+// to offer shelvacu 10 gens in exchange for 400 pc
+#[command]
+#[checks(SharedEconomy)]
+#[min_args(4)]
+async fn trade(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("trade");
+    let now = Utc::now();
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+
+    let user_str:String = args.single()?;
+    let counterparty = SerenityUserId::from_command_args(ctx, msg, &user_str).await?;
+    if counterparty == msg.author.id {
+        return Err("You can't trade with yourself.".into());
+    }
+    let rest:Vec<String> = args.iter::<String>().map(|a| a.unwrap()).collect();
+    let for_idx = match rest.iter().position(|a| a.eq_ignore_ascii_case("for")) {
+        Some(idx) => idx,
+        None => return Err("Usage: $trade <user> <amount> <type> for <amount> <type>".into()),
+    };
+    let (offer_amt, offer_ty) = parse_amount_and_type(&*pool, rest[..for_idx].to_vec()).await?;
+    let (want_amt, want_ty) = parse_amount_and_type(&*pool, rest[for_idx+1..].to_vec()).await?;
+    if offer_amt <= 0 || want_amt <= 0 {
+        return Err("Both sides of a trade must be more than nothing.".into());
+    }
+    let expires_at = now + *crate::TRADE_EXPIRATION;
+
+    let offer_id = offer_ty.id.clone();
+    let want_id = want_ty.id.clone();
+    let trade_id:i64 = pool.transaction(|conn| crate::trades::create_offer(
+        conn,
+        msg.author.id.into(),
+        counterparty.into(),
+        (offer_amt, offer_id),
+        (want_amt, want_id),
+        now,
+        expires_at,
+    )).await?;
+    let trade_damm_id = damm::add_to_str(trade_id.to_string());
+
+    let bot_msg = msg.channel_id.send_message(&ctx, |m| {
+        m.content(format!(
+            "{offerer} offers {offer_amt} {offer_ty} to {counterparty} in exchange for {want_amt} {want_ty}.\n\
+            {counterparty}, react with {accept} to accept or {decline} to decline, or visit {site}/trades/{id}. \
+            This trade#{id} offer expires <t:{expires}:R>.",
+            offerer = msg.author.mention(),
+            counterparty = counterparty.mention(),
+            offer_ty = offer_ty.long_name_ambiguous,
+            want_ty = want_ty.long_name_ambiguous,
+            accept = TRADE_ACCEPT_EMOJI,
+            decline = TRADE_DECLINE_EMOJI,
+            site = crate::SITE_URL,
+            id = trade_damm_id,
+            expires = expires_at.timestamp(),
+        ))
+    }).await?;
+    pool.transaction(|conn| crate::trades::set_message_id(conn, trade_id, bot_msg.id.0 as i64)).await?;
+    bot_msg.react(&ctx, TRADE_ACCEPT_EMOJI).await?;
+    bot_msg.react(&ctx, TRADE_DECLINE_EMOJI).await?;
+
+    Ok(())
+}
+
+// Use like $auction 10 gen for 100 pc
+// or $auction item#1234 for 50 pc
+// to auction off your own fungibles or item, with a minimum bid,
+// or $auction 10 gen for 5 pc each to sell them by the unit,
+// or $auction 10 gen for 100 pc reserve 200 buy now 500 with a hidden reserve and a buy-it-now price
+#[command]
+#[checks(SharedEconomy)]
+#[min_args(4)]
+async fn auction(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("auction");
+    let now = Utc::now();
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+    let mut words:Vec<String> = args.iter::<String>().map(|a| a.unwrap()).collect();
+    let terms = crate::auctions::parse_open_terms(&mut words)?;
+    let kind = if let Some(dutch_idx) = words.iter().position(|a| a.eq_ignore_ascii_case("dutch")) {
+        let schedule = crate::auctions::parse_dutch(&words[dutch_idx+1..])?;
+        words.truncate(dutch_idx);
+        crate::auctions::AuctionKind::Dutch(schedule)
+    } else if words.last().map(|w| w.eq_ignore_ascii_case("sealed")).unwrap_or(false) {
+        words.pop();
+        crate::auctions::AuctionKind::Sealed
+    } else if words.last().map(|w| w.eq_ignore_ascii_case("each")).unwrap_or(false) {
+        words.pop();
+        crate::auctions::AuctionKind::MultiUnit
+    } else {
+        crate::auctions::AuctionKind::Open(Default::default())
+    };
+    let kind = kind.with_terms(terms)?;
+    let for_idx = match words.iter().position(|a| a.eq_ignore_ascii_case("for")) {
+        Some(idx) => idx,
+        None => return Err(crate::auctions::CREATE_USAGE.into()),
+    };
+    let lot = match &words[..for_idx] {
+        [item_ref] if crate::items::parse_ref(item_ref).is_some() => crate::auctions::NewLot::Item{
+            item_id: crate::items::parse_ref(item_ref).unwrap(),
+        },
+        offer => {
+            let (amount, ty) = parse_amount_and_type(&*pool, offer.to_vec()).await?;
+            crate::auctions::NewLot::Fungible{amount, ty: ty.id}
+        },
+    };
+    let (bid_min, bid_ty) = parse_amount_and_type(&*pool, words[for_idx+1..].to_vec()).await?;
+    let auctioneer:models::UserId = msg.author.id.into();
+    let bid_ty_id = bid_ty.id.clone();
+    let auction = with_conn(ctx, move |conn| -> Result<models::AuctionWinner, crate::auctions::AuctionError> {
+        use diesel::prelude::*;
+        use view_schema::auction_and_winner::dsl as anw;
+        let auction_id = crate::auctions::create(conn, auctioneer, lot, bid_ty_id, bid_min, kind, now)?;
+        Ok(anw::auction_and_winner
+            .select(models::AuctionWinner::cols())
+            .filter(anw::auction_id.eq(auction_id))
+            .get_result(conn)?)
+    }).await?;
+    let ends = match kind {
+        crate::auctions::AuctionKind::Sealed => format!(
+            "It's sealed: bids are secret until it ends <t:{}:R>, and the winner pays the second-highest bid. \
+            Bid with `$bid {} <amount>`",
+            auction.end_at().timestamp(),
+            auction.damm(),
+        ),
+        crate::auctions::AuctionKind::Dutch(schedule) => format!(
+            "It's a dutch auction: the price starts at {} and drops by {} every {} minutes until someone buys it, \
+            or until it ends <t:{}:R>. Buy it with `$buy_now {}`",
+            schedule.start,
+            schedule.drop,
+            schedule.every_minutes,
+            auction.end_at().timestamp(),
+            auction.damm(),
+        ),
+        crate::auctions::AuctionKind::Open(terms) => format!(
+            "{}{} As it stands, that's <t:{}:R>. Bid with `$bid {} <amount>`{}",
+            if terms.reserve_price.is_some() { "It has a reserve price, which you alone can see on its page. " } else { "" },
+            auction.timing_rule(),
+            auction.end_at().timestamp(),
+            auction.damm(),
+            terms.buy_now_price.map(|p| format!(", buy it now for {} {} with `$buy_now {}`", p, bid_ty.long_name_ambiguous, auction.damm())).unwrap_or_default(),
+        ),
+        crate::auctions::AuctionKind::MultiUnit => format!(
+            "It's sold by the unit: the highest bids get their units when it ends <t:{}:R>, and everyone who gets any \
+            pays the lowest winning price. Bid with `$bid {} <quantity> at <price each>`",
+            auction.end_at().timestamp(),
+            auction.damm(),
+        ),
+    };
+    msg.reply(&ctx, format!(
+        "Started auction#{id} of {lot} for bids of at least {bid_min} {bid_ty}. {ends} or at {site}/auctions/{id}",
+        id = auction.damm(),
+        lot = auction.lot_description(),
+        bid_ty = bid_ty.long_name_ambiguous,
+        ends = ends,
+        site = crate::SITE_URL,
+    )).await?;
+    Ok(())
+}
+
+// Use like $bid 1234 500 to bid 500 on auction#1234, or $bid 1234 500 max to bid automatically up to 500,
+// or $bid 1234 3 at 20 to bid for 3 units at 20 each on an auction sold by the unit
+#[command]
+#[checks(SharedEconomy)]
+#[min_args(2)]
+#[max_args(4)]
+async fn bid(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("bid");
+    let now = Utc::now();
+    let id_str:String = args.single()?;
+    let auction_id = parse_auction_id(&id_str)?;
+    let amount:i64 = match args.single() {
+        Ok(amount) => amount,
+        Err(_) => return Err(crate::auctions::BID_USAGE.into()),
+    };
+    let bidder:models::UserId = msg.author.id.into();
+    let outcome = match args.single::<String>() {
+        Ok(word) if word.eq_ignore_ascii_case("at") => {
+            let unit_price:i64 = match args.single() {
+                Ok(price) => price,
+                Err(_) => return Err(crate::auctions::BID_USAGE.into()),
+            };
+            with_conn(ctx, move |conn| crate::auctions::place_unit_bid(conn, auction_id, bidder, amount, unit_price, now)).await?
+        },
+        Ok(word) if word.eq_ignore_ascii_case("max") && args.is_empty() => {
+            with_conn(ctx, move |conn| crate::auctions::bid(conn, auction_id, bidder, amount, true, now)).await?
+        },
+        Ok(_) => return Err(crate::auctions::BID_USAGE.into()),
+        Err(_) => with_conn(ctx, move |conn| crate::auctions::bid(conn, auction_id, bidder, amount, false, now)).await?,
+    };
+    msg.reply(&ctx, format!(
+        "{} {}",
+        outcome.message,
+        outcome.position_for(bidder),
+    )).await?;
+    Ok(())
+}
+
+#[command]
+#[checks(SharedEconomy)]
+#[num_args(1)]
+async fn buy_now(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("buy_now");
+    let now = Utc::now();
+    let id_str:String = args.single()?;
+    let auction_id = parse_auction_id(&id_str)?;
+    let buyer:models::UserId = msg.author.id.into();
+    let (auction, price) = with_conn(ctx, move |conn| crate::auctions::buy_now(conn, auction_id, buyer, now)).await?;
+    msg.reply(&ctx, format!(
+        "You bought {lot} from auction#{id} for {price} {ty}. It'll be yours as soon as the auction finishes, in a few seconds.",
+        lot = auction.lot_description(),
+        id = auction.damm(),
+        price = price,
+        ty = auction.bid_ty,
+    )).await?;
+    Ok(())
+}
+
+// Use like $cancel_auction 1234 to end your auction#1234 before anyone bids and get the lot back
+#[command]
+#[checks(SharedEconomy)]
+#[num_args(1)]
+async fn cancel_auction(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("cancel_auction");
+    let now = Utc::now();
+    let id_str:String = args.single()?;
+    let auction_id = parse_auction_id(&id_str)?;
+    let user:models::UserId = msg.author.id.into();
+    let auction = with_conn(ctx, move |conn| crate::auctions::cancel(conn, auction_id, user, now)).await?;
+    msg.reply(&ctx, format!(
+        "Cancelled auction#{}. {} will be back with you in a few seconds.",
+        auction.damm(),
+        auction.lot_description(),
+    )).await?;
+    Ok(())
+}
+
+/// Accepts `1234`, `#1234` or `auction#1234`
+fn parse_auction_id(id_str:&str) -> Result<i64, &'static str> {
+    if let Some(digit_arr) = damm::validate(id_str.trim_start_matches("auction").trim_start_matches('#')) {
+        let mut auction_id:i64 = 0;
+        for d in &digit_arr {
+            auction_id *= 10;
+            auction_id += *d as i64;
         }
-    } else if amount.is_none() {
-        return Err("Amount not provided.".into());
+        Ok(auction_id)
     } else {
-        return Err("Type not provided.".into());
+        Err("Invalid auction id, please try again.")
     }
-    
+}
+
+#[command]
+#[checks(SharedEconomy)]
+#[num_args(1)]
+async fn watch(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("watch");
+    let now = Utc::now();
+    let id_str:String = args.single()?;
+    let auction_id = parse_auction_id(&id_str)?;
+    let user:models::UserId = msg.author.id.into();
+    with_conn(ctx, move |conn| crate::auctions::watch(conn, auction_id, user, now)).await?;
+    msg.reply(&ctx, "You'll get a DM for each new bid on that auction until it ends. Use `$unwatch` to stop.").await?;
     Ok(())
 }
 
-#[allow(dead_code)] //Some strange bug means rust thinks this func isn't used, even when it definitely is.
-async fn find_item_type(pool: &DbPool, ty_str:String) -> CommandResult<ItemType> {
+#[command]
+#[num_args(1)]
+async fn unwatch(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("unwatch");
+    let id_str:String = args.single()?;
+    let auction_id = parse_auction_id(&id_str)?;
+    let user:models::UserId = msg.author.id.into();
+    let was_watching = with_conn(ctx, move |conn| crate::auctions::unwatch(conn, auction_id, user)).await?;
+    if was_watching {
+        msg.reply(&ctx, "You'll no longer get DMs about new bids on that auction.").await?;
+    } else {
+        msg.reply(&ctx, "You weren't watching that auction.").await?;
+    }
+    Ok(())
+}
+
+// Use like $lend shelvacu 1000 pc at 2% every week for 4 payments
+// to offer shelvacu 1000 pc, repaid in 4 weekly payments with 2% interest charged each week
+#[command]
+#[checks(SharedEconomy)]
+#[aliases("loan")]
+#[min_args(9)]
+async fn lend(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("lend");
+    let now = Utc::now();
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+
+    let user_str:String = args.single()?;
+    let borrower = SerenityUserId::from_command_args(ctx, msg, &user_str).await?;
+    if borrower == msg.author.id {
+        return Err("You can't lend to yourself.".into());
+    }
+    let rest:Vec<String> = args.iter::<String>().map(|a| a.unwrap()).collect();
+    let at_idx = match rest.iter().position(|a| a.eq_ignore_ascii_case("at")) {
+        Some(idx) => idx,
+        None => return Err(crate::loans::TERMS_USAGE.into()),
+    };
+    let (principal, ty) = parse_amount_and_type(&*pool, rest[..at_idx].to_vec()).await?;
+    if principal == 0 {
+        return Err("The loan must be for more than nothing.".into());
+    }
+    let (interest_bps, every_days, installments) = crate::loans::parse_terms(&rest[at_idx..], now)?;
+    let expires_at = now + *crate::TRADE_EXPIRATION;
+
+    let ty_id = ty.id.clone();
+    let loan_id:i64 = pool.transaction(|conn| crate::loans::create_offer(
+        conn,
+        msg.author.id.into(),
+        borrower.into(),
+        principal,
+        ty_id,
+        interest_bps,
+        every_days,
+        installments,
+        now,
+        expires_at,
+    )).await?;
+    let loan_damm_id = damm::add_to_str(loan_id.to_string());
+
+    let bot_msg = msg.channel_id.send_message(&ctx, |m| {
+        m.content(format!(
+            "{lender} offers to lend {principal} {ty} to {borrower} at {rate} interest every {every_days} day{s}, \
+            repaid in {installments} payment{ps}.\n\
+            {borrower}, react with {accept} to accept or {decline} to decline, or visit {site}/loans/{id}. \
+            This loan#{id} offer expires <t:{expires}:R>.",
+            lender = msg.author.mention(),
+            borrower = borrower.mention(),
+            ty = ty.long_name_ambiguous,
+            rate = crate::loans::format_rate(interest_bps),
+            s = if every_days == 1 { "" } else { "s" },
+            ps = if installments == 1 { "" } else { "s" },
+            accept = TRADE_ACCEPT_EMOJI,
+            decline = TRADE_DECLINE_EMOJI,
+            site = crate::SITE_URL,
+            id = loan_damm_id,
+            expires = expires_at.timestamp(),
+        ))
+    }).await?;
+    pool.transaction(|conn| crate::loans::set_message_id(conn, loan_id, bot_msg.id.0 as i64)).await?;
+    bot_msg.react(&ctx, TRADE_ACCEPT_EMOJI).await?;
+    bot_msg.react(&ctx, TRADE_DECLINE_EMOJI).await?;
+
+    Ok(())
+}
+
+#[command]
+async fn loans(ctx:&Context, msg:&Message) -> CommandResult {
+    trace!("loans");
+    let now = Utc::now();
+    let user:models::UserId = msg.author.id.into();
+    let loans = with_conn(ctx, move |conn| crate::loans::outstanding_for(conn, user, now)).await?;
+
+    if loans.is_empty() {
+        msg.reply(&ctx, "You have no outstanding loans.").await?;
+        return Ok(());
+    }
+    msg.channel_id.send_message(&ctx, |cm| {
+        cm.embed(|e| {
+            e.title("Your loans:");
+            for loan in &loans {
+                let status = match loan.next_payment_at {
+                    Some(next) => format!(
+                        "{} {} outstanding, next payment <t:{}:R>",
+                        loan.outstanding,
+                        loan.ty,
+                        next.timestamp(),
+                    ),
+                    None => format!("offer expires <t:{}:R>", loan.expires_at.timestamp()),
+                };
+                e.field(
+                    format!("#{}", loan.damm()),
+                    format!("{}; {}", crate::loans::describe(loan), status),
+                    false,
+                );
+            }
+            e
+        });
+        cm
+    }).await?;
+    Ok(())
+}
+
+// Use like $standing_order shelvacu 100 pc every monday
+// to pay shelvacu 100 pc every week, starting next monday
+#[command]
+#[checks(SharedEconomy)]
+#[aliases("stipend")]
+#[min_args(4)]
+async fn standing_order(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("standing_order");
+    let now = Utc::now();
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+
+    let user_str:String = args.single()?;
+    let payee = SerenityUserId::from_command_args(ctx, msg, &user_str).await?;
+    if payee == msg.author.id {
+        return Err("You can't pay yourself.".into());
+    }
+    let rest:Vec<String> = args.iter::<String>().map(|a| a.unwrap()).collect();
+    let every_idx = match rest.iter().position(|a| a.eq_ignore_ascii_case("every")) {
+        Some(idx) => idx,
+        None => return Err("Usage: $standing_order <user> <amount> <type> every <schedule>".into()),
+    };
+    let (amount, ty) = parse_amount_and_type(&*pool, rest[..every_idx].to_vec()).await?;
+    if amount <= 0 {
+        return Err("Amount must be more than nothing.".into());
+    }
+    let (every_days, first_run_at) = crate::standing_orders::parse_schedule(&rest[every_idx+1..], now)?;
+
+    let ty_id = ty.id.clone();
+    let order_id:i64 = pool.transaction(|conn| crate::standing_orders::create(
+        conn,
+        msg.author.id.into(),
+        payee.into(),
+        amount,
+        ty_id,
+        every_days,
+        first_run_at,
+        now,
+    )).await?;
+
+    msg.reply(&ctx, format!(
+        "Standing order #{} created: {} {} to {} every {} day{}, first payment <t:{}:R>. Cancel it with `$cancel_standing_order {0}`.",
+        damm::add_to_str(order_id.to_string()),
+        amount,
+        ty.long_name_ambiguous,
+        payee.mention(),
+        every_days,
+        if every_days == 1 { "" } else { "s" },
+        first_run_at.timestamp(),
+    )).await?;
+
+    Ok(())
+}
+
+#[command]
+#[aliases("stipends")]
+async fn standing_orders(ctx:&Context, msg:&Message) -> CommandResult {
+    trace!("standing_orders");
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+    let user:models::UserId = msg.author.id.into();
+    let orders = task::spawn_blocking(move || {
+        let conn = pool.get().unwrap();
+        crate::standing_orders::active_for(&*conn, user)
+    }).await.unwrap()?;
+
+    if orders.is_empty() {
+        msg.reply(&ctx, "You have no standing orders.").await?;
+        return Ok(());
+    }
+    msg.channel_id.send_message(&ctx, |cm| {
+        cm.embed(|e| {
+            e.title("Your standing orders:");
+            for order in &orders {
+                e.field(
+                    format!("#{}", order.damm()),
+                    format!(
+                        "{}, next payment <t:{}:R>",
+                        crate::standing_orders::describe(order),
+                        order.next_run_at.timestamp(),
+                    ),
+                    false,
+                );
+            }
+            e
+        });
+        cm
+    }).await?;
+    Ok(())
+}
+
+#[command]
+#[aliases("cancel_stipend")]
+#[num_args(1)]
+async fn cancel_standing_order(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("cancel_standing_order");
+    let now = Utc::now();
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+    let id_str:String = args.single()?;
+    let order_id:i64 = if let Some(digit_arr) = damm::validate(id_str.trim_start_matches('#')) {
+        let mut order_id:i64 = 0;
+        for d in &digit_arr {
+            order_id *= 10;
+            order_id += *d as i64;
+        }
+        order_id
+    } else {
+        return Err("Invalid standing order id, please try again.".into());
+    };
+    let user:models::UserId = msg.author.id.into();
+    let cancelled = pool.transaction(move |conn| crate::standing_orders::cancel(conn, order_id, user, now)).await?;
+    match cancelled {
+        Some(order) => msg.reply(&ctx, format!("Cancelled standing order #{}: {}.", order.damm(), crate::standing_orders::describe(&order))).await?,
+        None => msg.reply(&ctx, "No active standing order with that id involves you.").await?,
+    };
+    Ok(())
+}
+
+/// Finds the item type with the given alias. Retired item types are an error, as they can't be used anymore.
+async fn lookup_item_type(pool: &DbPool, alias:&str) -> CommandResult<Option<ItemType>> {
     use diesel::prelude::*;
     use schema::item_types::dsl as it;
     use schema::item_type_aliases::dsl as ita;
-    let maybe_res = ita::item_type_aliases
+    let maybe_res:Option<(ItemType, Option<chrono::DateTime<Utc>>)> = ita::item_type_aliases
         .inner_join(it::item_types)
-        .select(ItemType::cols())
-        .filter(ita::alias.eq(&ty_str))
-        .get_result_async(&*pool)
+        .select((ItemType::cols(), it::retired_at))
+        .filter(ita::alias.eq(alias))
+        .get_result_async(pool)
         .await
         .optional()?;
+    match maybe_res {
+        Some((ty, Some(_))) => Err(format!("{} have been retired.", ty.long_name_plural).into()),
+        Some((ty, None)) => Ok(Some(ty)),
+        None => Ok(None),
+    }
+}
+
+#[allow(dead_code)] //Some strange bug means rust thinks this func isn't used, even when it definitely is.
+async fn find_item_type(pool: &DbPool, ty_str:String) -> CommandResult<ItemType> {
+    let maybe_res = lookup_item_type(pool, &ty_str).await?;
     match maybe_res {
         None => Err("Unrecognized type".into()),
         Some(v) => Ok(v),
@@ -667,26 +2087,290 @@ async fn find_item_type(pool: &DbPool, ty_str:String) -> CommandResult<ItemType>
 
 #[command]
 async fn motion(ctx:&Context, msg:&Message, args:Args) -> CommandResult {
-    motion_common(ctx, msg, args, BigDecimal::from(1)).await
+    motion_common(ctx, msg, args.rest(), BigDecimal::from(1), None).await
 }
 
 #[command]
 async fn supermotion(ctx:&Context, msg:&Message, args:Args) -> CommandResult {
-    motion_common(ctx, msg, args, BigDecimal::from(2)).await
+    motion_common(ctx, msg, args.rest(), BigDecimal::from(2), None).await
 }
 
 #[command]
 async fn submotion(ctx:&Context, msg:&Message, args:Args) -> CommandResult {
-    motion_common(ctx, msg, args, BigDecimal::from(0.5)).await
+    motion_common(ctx, msg, args.rest(), BigDecimal::from(0.5), None).await
+}
+
+// Use like $treasury_spend colin 100 pc Reimbursement for the minecraft server
+// to call a motion that pays colin 100 pc out of the treasury if it passes
+#[command]
+#[checks(SharedEconomy)]
+#[min_args(4)]
+async fn treasury_spend(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("treasury_spend");
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+
+    let user_str:String = args.single()?;
+    let to = SerenityUserId::from_command_args(ctx, msg, &user_str).await?;
+    let amount_and_type = vec![args.single::<String>()?, args.single::<String>()?];
+    let (amount, ty) = parse_amount_and_type(&*pool, amount_and_type).await?;
+    if amount == 0 {
+        return Err("Amount must be more than nothing.".into());
+    }
+    let action = crate::motion_actions::MotionAction::TreasurySpend{
+        to: to.into(),
+        amount,
+        ty: ty.id,
+    };
+    let motion_text = format!("{}: {}", action.describe(), args.rest());
+    motion_common(ctx, msg, &motion_text, BigDecimal::from(1), Some(action)).await
+}
+
+// Use like $policy tax 1% above 10000 Make the rich pay their share
+// to call a motion that turns on the wealth tax if it passes
+#[command]
+#[checks(SharedEconomy)]
+#[min_args(2)]
+async fn policy(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("policy");
+    let name:String = args.single()?;
+    let kind = match crate::economic_policy::PolicyKind::from_name(&name) {
+        Some(k) => k,
+        None => return Err("Policy must be one of `gen_upkeep`, `wealth_tax`, or `pc_decay`.".into()),
+    };
+    let setting_len = match kind {
+        crate::economic_policy::PolicyKind::WealthTax if !args.current().map(|a| a.eq_ignore_ascii_case("off")).unwrap_or(false) => 3,
+        _ => 1,
+    };
+    let mut setting_words = Vec::new();
+    for _ in 0..setting_len {
+        setting_words.push(args.single::<String>().map_err(|_| crate::economic_policy::SETTING_USAGE)?);
+    }
+    let setting = crate::economic_policy::PolicySetting::parse(kind, &setting_words)?;
+    let action = crate::motion_actions::MotionAction::SetPolicy{policy: kind, setting};
+    let motion_text = format!("{}: {}", action.describe(), args.rest());
+    motion_common(ctx, msg, &motion_text, BigDecimal::from(1), Some(action)).await
+}
+
+#[command]
+async fn policies(ctx:&Context, msg:&Message) -> CommandResult {
+    trace!("policies");
+    let policies = with_conn(ctx, crate::economic_policy::get_all).await?;
+    msg.channel_id.send_message(&ctx, |cm| {
+        cm.embed(|e| {
+            e.title("Economic policies:");
+            e.description("Applied every generation period, after pc is generated. Change one with `$policy`.");
+            for (kind, setting) in &policies {
+                e.field(kind.title(), setting.describe(*kind), false);
+            }
+            e
+        });
+        cm
+    }).await?;
+    Ok(())
+}
+
+// Use like $auto_auction weekly 0 7 * * 6 US/Pacific 20 gen for 1 pc each lasting 96 hours Twice the gens
+// or $auto_auction weekly off Nobody bids anyway
+// to call a motion that changes or removes an auto-auction if it passes
+#[command]
+#[checks(SharedEconomy)]
+#[min_args(2)]
+async fn auto_auction(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("auto_auction");
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+    let name:String = args.single()?;
+    if !crate::auto_auctions::valid_name(&name) {
+        return Err(crate::auto_auctions::AutoAuctionError::InvalidName.into());
+    }
+    let words:Vec<String> = args.rest().split_whitespace().map(String::from).collect();
+    let (def, used) = if words[0].eq_ignore_ascii_case("off") {
+        (None, 1)
+    } else {
+        let (def, used) = parse_auto_auction_def(&*pool, &words).await?;
+        def.validate()?;
+        (Some(def), used)
+    };
+    let action = crate::motion_actions::MotionAction::SetAutoAuction{name, def};
+    let motion_text = format!("{}: {}", action.describe(), words[used..].join(" "));
+    motion_common(ctx, msg, &motion_text, BigDecimal::from(1), Some(action)).await
+}
+
+#[command]
+async fn auto_auctions(ctx:&Context, msg:&Message) -> CommandResult {
+    trace!("auto_auctions");
+    let auto_auctions = with_conn(ctx, crate::auto_auctions::get_all).await?;
+    msg.channel_id.send_message(&ctx, |cm| {
+        cm.embed(|e| {
+            e.title("Auto-auctions:");
+            e.description("Put up by the treasury on a schedule. Change one with `$auto_auction`.");
+            for auto_auction in &auto_auctions {
+                let next = match (auto_auction.enabled, auto_auction.next_run()) {
+                    (false, _) => "Paused".to_string(),
+                    (true, Some(next)) => format!("Next <t:{}:R>", next.timestamp()),
+                    (true, None) => "Never".to_string(),
+                };
+                e.field(&auto_auction.name, format!("{}. {}", auto_auction.def().describe(), next), false);
+            }
+            e
+        });
+        cm
+    }).await?;
+    Ok(())
+}
+
+const MARKET_USAGE:&str = "Usage: `$buy <quantity> <type> at <price> <type>`, eg `$buy 3 gen at 500 pc`, or the same with `$sell`";
+
+async fn market_order_common(ctx:&Context, msg:&Message, mut args:Args, is_buy:bool) -> CommandResult {
+    let now = Utc::now();
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+    let words:Vec<String> = args.iter::<String>().map(|a| a.unwrap()).collect();
+    let at_idx = match words.iter().position(|a| a.eq_ignore_ascii_case("at")) {
+        Some(idx) => idx,
+        None => return Err(MARKET_USAGE.into()),
+    };
+    let (quantity, base) = parse_amount_and_type(&*pool, words[..at_idx].to_vec()).await?;
+    let (price, quote) = parse_amount_and_type(&*pool, words[at_idx+1..].to_vec()).await?;
+    let user:models::UserId = msg.author.id.into();
+    let (base_id, quote_id) = (base.id.clone(), quote.id.clone());
+    let (order, fills) = with_conn(ctx, move |conn| crate::market::place(conn, user, is_buy, quantity, base_id, price, quote_id, now)).await?;
+    let filled:i64 = fills.iter().map(|f| f.quantity).sum();
+    msg.reply(&ctx, format!(
+        "Placed order #{} to {}. {} filled immediately, {} left on the book. Cancel the rest with `$cancel_order {}`.",
+        order.damm(),
+        crate::market::describe(&order),
+        filled,
+        order.remaining,
+        order.damm(),
+    )).await?;
+    Ok(())
+}
+
+// Use like $buy 3 gen at 500 pc
+// to bid for 3 gens at up to 500 pc each
+#[command]
+#[checks(SharedEconomy)]
+#[min_args(3)]
+async fn buy(ctx:&Context, msg:&Message, args:Args) -> CommandResult {
+    trace!("buy");
+    market_order_common(ctx, msg, args, true).await
+}
+
+// Use like $sell 3 gen at 500 pc
+// to offer 3 gens for at least 500 pc each
+#[command]
+#[checks(SharedEconomy)]
+#[min_args(3)]
+async fn sell(ctx:&Context, msg:&Message, args:Args) -> CommandResult {
+    trace!("sell");
+    market_order_common(ctx, msg, args, false).await
+}
+
+// Use like $market gen pc
+// to see the order book for gens priced in pc, the default
+#[command]
+#[max_args(2)]
+async fn market(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("market");
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+    let (base, quote) = if args.is_empty() {
+        (find_item_type(&*pool, "gen".to_string()).await?, find_item_type(&*pool, "pc".to_string()).await?)
+    } else {
+        let base:String = args.single()?;
+        let quote:String = args.single().map_err(|_| "Give both item types, eg `$market gen pc`")?;
+        (find_item_type(&*pool, base).await?, find_item_type(&*pool, quote).await?)
+    };
+    let (base_id, quote_id) = (base.id.clone(), quote.id.clone());
+    let (bids, asks, fills) = with_conn(ctx, move |conn| -> diesel::QueryResult<_> {
+        Ok((
+            crate::market::depth(conn, &base_id, &quote_id, true)?,
+            crate::market::depth(conn, &base_id, &quote_id, false)?,
+            crate::market::recent_fills(conn, &base_id, &quote_id, 1)?,
+        ))
+    }).await?;
+    let show_levels = |levels:&[crate::market::DepthLevel]| if levels.is_empty() {
+        "none".to_string()
+    } else {
+        levels.iter().map(|l| format!("{} @ {}", l.quantity, l.price)).collect::<Vec<_>>().join("\n")
+    };
+    msg.channel_id.send_message(&ctx, |cm| {
+        cm.embed(|e| {
+            e.title(format!("Market: {} for {}", base.long_name_plural, quote.long_name_plural));
+            e.description(format!(
+                "Prices in {} per {}. Last trade: {}. More at {}/market",
+                quote.id,
+                base.id,
+                fills.first().map(|f| format!("{} @ {}", f.quantity, f.price)).unwrap_or_else(|| "none".to_string()),
+                crate::SITE_URL,
+            ));
+            e.field("Bids", show_levels(&bids), true);
+            e.field("Asks", show_levels(&asks), true);
+            e
+        });
+        cm
+    }).await?;
+    Ok(())
+}
+
+#[command]
+async fn orders(ctx:&Context, msg:&Message) -> CommandResult {
+    trace!("orders");
+    let user:models::UserId = msg.author.id.into();
+    let orders = with_conn(ctx, move |conn| crate::market::open_orders_for(conn, user)).await?;
+    if orders.is_empty() {
+        msg.reply(&ctx, "You have no open market orders.").await?;
+        return Ok(());
+    }
+    msg.channel_id.send_message(&ctx, |cm| {
+        cm.embed(|e| {
+            e.title("Your open market orders:");
+            for order in &orders {
+                e.field(
+                    format!("#{}", order.damm()),
+                    format!("{}; {} of {} left", crate::market::describe(order), order.remaining, order.quantity),
+                    false,
+                );
+            }
+            e
+        });
+        cm
+    }).await?;
+    Ok(())
 }
 
-async fn motion_common(ctx:&Context, msg:&Message, args:Args, power: BigDecimal) -> CommandResult {
+#[command]
+#[num_args(1)]
+async fn cancel_order(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
+    trace!("cancel_order");
+    let now = Utc::now();
+    let id_str:String = args.single()?;
+    let order_id:i64 = if let Some(digit_arr) = damm::validate(id_str.trim_start_matches('#')) {
+        let mut order_id:i64 = 0;
+        for d in &digit_arr {
+            order_id *= 10;
+            order_id += *d as i64;
+        }
+        order_id
+    } else {
+        return Err("Invalid order id, please try again.".into());
+    };
+    let user:models::UserId = msg.author.id.into();
+    let order = with_conn(ctx, move |conn| crate::market::cancel(conn, order_id, user, now)).await?;
+    msg.reply(&ctx, format!("Cancelled order #{}; the unfilled {} have been returned.", order.damm(), order.remaining)).await?;
+    Ok(())
+}
+
+async fn motion_common(
+    ctx:&Context,
+    msg:&Message,
+    motion_text:&str,
+    power: BigDecimal,
+    action: Option<crate::motion_actions::MotionAction>,
+) -> CommandResult {
     trace!("motion_common");
     use diesel::prelude::*;
     use schema::motions::dsl as mdsl;
     use schema::motion_votes::dsl as mvdsl;
     use view_schema::balance_history::dsl as bhdsl;
-    let motion_text = args.rest();
     //let mut motion_message_outer:Option<_> = None;
     let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
 
@@ -702,10 +2386,21 @@ async fn motion_common(ctx:&Context, msg:&Message, args:Args, power: BigDecimal)
         return Ok(())
     }
 
+    let guild_id = msg.guild_id.map(|id| id.0 as i64);
+    let config = match guild_id {
+        Some(guild_id) => Some(config_of(ctx, guild_id).await?),
+        None => None,
+    };
+    let economy = config.as_ref().map(|c| c.economy()).unwrap_or(crate::guilds::SHARED_ECONOMY);
+    let prefix = config.as_ref().map(|c| c.command_prefix.as_str()).unwrap_or(crate::guilds::DEFAULT_PREFIX);
+    // Motions are announced in the channel they were posted in, so it's only recorded when it isn't the default
+    let channel_id = config.as_ref().and_then(|c| c.motions_channel);
+
     let balance:i64 = bhdsl::balance_history
         .select(bhdsl::balance)
         .filter(bhdsl::ty.eq("pc"))
         .filter(bhdsl::user.eq(msg.author.id.0 as i64))
+        .filter(bhdsl::economy.eq(economy))
         .order((bhdsl::happened_at.desc(), bhdsl::rowid.desc(), bhdsl::sign.desc()))
         .limit(1)
         .get_result_async(&*pool).await?;
@@ -731,17 +2426,29 @@ async fn motion_common(ctx:&Context, msg:&Message, args:Args, power: BigDecimal)
     let motion_id:i64 = diesel::insert_into(schema::thing_ids::table).default_values().returning(schema::thing_ids::dsl::rowid).get_result_async(&*pool).await?;
 
     let cap_label = motion_label(&power);
-    let mut bot_msg = serenity::model::id::ChannelId(MOTIONS_CHANNEL).send_message(&ctx, |m| {
-        m.content(format!(
-            "A motion has been called by {0}\nSay `$vote {1}` or visit {2}/motions/{1} to vote!",
-            msg.author.mention(),
-            damm::add_to_str(motion_id.to_string()),
-            crate::SITE_URL,
-        )).embed(|e| {
+    let mut bot_msg = serenity::model::id::ChannelId(channel_id.map(|id| id as u64).unwrap_or(MOTIONS_CHANNEL)).send_message(&ctx, |m| {
+        if economy == crate::guilds::SHARED_ECONOMY {
+            m.content(format!(
+                "A motion has been called by {0}\nUse the buttons below, say `{3}vote {1}`, or visit {2}/motions/{1} to vote!",
+                msg.author.mention(),
+                damm::add_to_str(motion_id.to_string()),
+                crate::SITE_URL,
+                prefix,
+            ));
+        } else {
+            // The site only shows the shared economy's motions
+            m.content(format!(
+                "A motion has been called by {0}\nUse the buttons below or say `{2}vote {1}` to vote!",
+                msg.author.mention(),
+                damm::add_to_str(motion_id.to_string()),
+                prefix,
+            ));
+        }
+        m.embed(|e| {
             e.field(cap_label, motion_text, false)
             .field("Votes", "**for 1**/0 against", false)
             .field(crate::names::name_of(msg.author.id), "1 for", true)
-        })
+        }).components(interactions::motion_buttons)
     }).await?;
 
     let mut delete_message = false;
@@ -749,9 +2456,10 @@ async fn motion_common(ctx:&Context, msg:&Message, args:Args, power: BigDecimal)
     pool.transaction(|txn| {
         diesel::sql_query("LOCK TABLE motions IN EXCLUSIVE MODE;").execute(&*txn)?;
         let motion_count_utc_today:i64 = motion_count().get_result(&*txn)?;
-        let mut handle = TransferHandler::new(
+        let mut handle = TransferHandler::in_economy(
             txn,
-            vec![msg.author.id.into()],
+            economy,
+            vec![msg.author.id.into(), models::UserId::TREASURY],
             vec![CurrencyId::PC]
         )?;
         let balance = handle.balance(msg.author.id.into(), CurrencyId::PC);
@@ -771,6 +2479,10 @@ async fn motion_common(ctx:&Context, msg:&Message, args:Args, power: BigDecimal)
             mdsl::last_result_change.eq(now),
             mdsl::power.eq(power),
             mdsl::motioned_by.eq(msg.author.id.0 as i64),
+            mdsl::action.eq(action.as_ref().map(|a| a.to_db())),
+            mdsl::economy.eq(economy),
+            mdsl::guild_id.eq(guild_id),
+            mdsl::channel_id.eq(channel_id),
         )).returning(mdsl::rowid).get_result(&*txn)?;
 
         diesel::insert_into(mvdsl::motion_votes).values((
@@ -798,26 +2510,6 @@ async fn motion_common(ctx:&Context, msg:&Message, args:Args, power: BigDecimal)
         let _ = bot_msg.delete(&ctx).await;
     } else {
         update_motion_message(&ctx, Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap()), &mut bot_msg).await?;
-        let mut emojis:Vec<_> = (*SPECIAL_EMOJI).iter().collect();
-        emojis.sort_unstable_by_key(|(_,a)| match *a {
-            SpecialEmojiAction::Direction(false) => -2,
-            SpecialEmojiAction::Direction(true) => -1,
-            SpecialEmojiAction::Amount(a) => (*a) as i64
-        });
-        for (emoji_id, _) in emojis {
-            //dbg!(&emoji_id);
-            serenity::model::id::ChannelId::from(MOTIONS_CHANNEL)
-                .create_reaction(
-                    &ctx,
-                    &bot_msg,
-                    serenity::model::channel::ReactionType::Custom{
-                        animated: false,
-                        id: (*emoji_id).into(),
-                        name: Some("no".to_string())
-                    }
-                ).await?
-            ;
-        }
     }
 
     Ok(())
@@ -885,53 +2577,56 @@ const IGNORE_WORDS:&[&str] = &["in", "i", "I", "think", "say", "fuck", "hell"];
 async fn vote(ctx:&Context, msg:&Message, mut args:Args) -> CommandResult {
     trace!("vote");
     let checksummed_motion_id:String = args.single()?;
-    //dbg!(&checksummed_motion_id);
-    if let Some(digit_arr) = damm::validate(&checksummed_motion_id) {
+    let motion_id = parse_motion_id(&checksummed_motion_id)?;
+
+    let mut vote_count = 1;
+    let mut vote_direction:Option<bool> = None;
+    for args_result in args.iter::<String>() {
+        //dbg!(&args_result);
+        let arg = args_result?;
+        if YES_WORDS.contains(&&*arg) {
+            vote_direction = Some(true);
+        }else if NO_WORDS.contains(&&*arg) {
+            vote_direction = Some(false);
+        }else if ZERO_WORDS.contains(&&*arg) {
+            vote_count = 0;
+        }else if IGNORE_WORDS.contains(&&*arg) {
+            //ignore
+        }else {
+            match arg.parse():Result<u32, _> {
+                Err(e) => return Err(e.into()),
+                Ok(v) => vote_count = v as i64,
+            }
+        }
+    }
+
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+    let resp = vote_common_async(
+        pool,
+        vote_direction,
+        vote_count,
+        msg.author.id.into(),
+        Some(motion_id),
+        None,
+        Some(msg.id.0 as i64),
+    ).await;
+
+    msg.reply(ctx, resp).await.unwrap();
+    Ok(())
+}
+
+/// Accepts `1234`, `#1234` or `motion#1234`
+fn parse_motion_id(id_str:&str) -> Result<i64, &'static str> {
+    if let Some(digit_arr) = damm::validate(id_str.trim_start_matches("motion").trim_start_matches('#')) {
         let mut motion_id:i64 = 0;
         for d in &digit_arr {
             motion_id *= 10;
             motion_id += *d as i64;
         }
-        let motion_id = motion_id;
-        //dbg!(&motion_id);
-
-        let mut vote_count = 1;
-        let mut vote_direction:Option<bool> = None;
-        for args_result in args.iter::<String>() {
-            //dbg!(&args_result);
-            let arg = args_result?;
-            if YES_WORDS.contains(&&*arg) {
-                vote_direction = Some(true);
-            }else if NO_WORDS.contains(&&*arg) {
-                vote_direction = Some(false);
-            }else if ZERO_WORDS.contains(&&*arg) {
-                vote_count = 0;
-            }else if IGNORE_WORDS.contains(&&*arg) {
-                //ignore
-            }else {
-                match arg.parse():Result<u32, _> {
-                    Err(e) => return Err(e.into()),
-                    Ok(v) => vote_count = v as i64,
-                }
-            }
-        }
-
-        let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
-        let resp = vote_common_async(
-            pool,
-            vote_direction,
-            vote_count,
-            msg.author.id.into(),
-            Some(motion_id),
-            None,
-            Some(msg.id.0 as i64),
-        ).await;
-
-        msg.reply(ctx, resp).await.unwrap();
-    }else{
-        return Err("Invalid motion id, please try again.".into());
+        Ok(motion_id)
+    } else {
+        Err("Invalid motion id, please try again.")
     }
-    Ok(())
 }
 
 use std::borrow::Cow;
@@ -987,7 +2682,7 @@ pub fn vote_common(
         use crate::schema::motions::dsl as mdsl;
         use crate::schema::motion_votes::dsl as mvdsl;
 
-        let res:Option<(i64, bool, BigDecimal, i64)> = mdsl::motions
+        let res:Option<(i64, bool, BigDecimal, i64, i64)> = mdsl::motions
         .filter(mdsl::rowid.eq(motion_id.unwrap_or(-1)).or(mdsl::bot_message_id.eq(message_id.unwrap_or(-1))))
         .select((
             mdsl::rowid,
@@ -996,19 +2691,22 @@ pub fn vote_common(
             ),
             mdsl::power,
             mdsl::bot_message_id,
+            mdsl::economy,
         ))
         .for_update()
         .get_result(conn)
         .optional()?;
         //dbg!(&res);
 
-        let mut handle = TransferHandler::new(
+        // Votes are paid for in the economy the motion was called in
+        let mut handle = TransferHandler::in_economy(
             conn,
-            vec![user_id],
+            res.as_ref().map(|r| r.4).unwrap_or(crate::guilds::SHARED_ECONOMY),
+            vec![user_id, models::UserId::TREASURY],
             vec![CurrencyId::PC],
         )?;
 
-        if let Some((motion_id, not_announced, power, _motion_message_id)) = res {
+        if let Some((motion_id, not_announced, power, _motion_message_id, _economy)) = res {
             outer_motion_id = Some(motion_id);
             if not_announced {
                 //dbg!();
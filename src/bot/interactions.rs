@@ -0,0 +1,584 @@
+//! Slash commands, their autocompletion, and the voting buttons on motion messages
+
+use super::*;
+
+use serenity::builder::CreateComponents;
+use serenity::model::id::GuildId;
+use serenity::model::interactions::{
+    Interaction,
+    InteractionResponseType,
+    InteractionApplicationCommandCallbackDataFlags,
+    application_command::{
+        ApplicationCommand,
+        ApplicationCommandInteraction,
+        ApplicationCommandInteractionDataOption,
+        ApplicationCommandOptionType,
+    },
+    autocomplete::AutocompleteInteraction,
+    message_component::{
+        ButtonStyle,
+        InteractionMessage,
+        MessageComponentInteraction,
+    },
+};
+
+/// Discord shows at most this many autocomplete suggestions
+const MAX_CHOICES:usize = 25;
+
+/// What each amount button on a motion message casts. Buttons can be clicked again, so these only need to make large
+/// numbers of votes quick.
+const VOTE_AMOUNTS:&[i64] = &[1, 2, 5, 10, 20, 50, 100];
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum VoteButton {
+    /// Picks a side without casting any votes
+    Direction(bool),
+    Amount(i64),
+}
+
+impl VoteButton {
+    fn custom_id(self) -> String {
+        match self {
+            VoteButton::Direction(true) => "vote:for".to_string(),
+            VoteButton::Direction(false) => "vote:against".to_string(),
+            VoteButton::Amount(amount) => format!("vote:{}", amount),
+        }
+    }
+
+    fn parse(custom_id:&str) -> Option<Self> {
+        match custom_id.strip_prefix("vote:")? {
+            "for" => Some(VoteButton::Direction(true)),
+            "against" => Some(VoteButton::Direction(false)),
+            amount => amount.parse().ok()
+                .filter(|amount| VOTE_AMOUNTS.contains(amount))
+                .map(VoteButton::Amount),
+        }
+    }
+}
+
+/// The buttons on the reply to a `/give` that needs confirming. Confirm carries what was resolved from the command, so
+/// nothing is looked up again before it's sent.
+#[derive(Debug,Clone,PartialEq,Eq)]
+enum GiveButton {
+    Confirm{to: u64, amount: i64, ty: String},
+    Cancel,
+}
+
+impl GiveButton {
+    fn custom_id(&self) -> String {
+        match self {
+            GiveButton::Confirm{to, amount, ty} => format!("give:{}:{}:{}", to, amount, ty),
+            GiveButton::Cancel => "give:cancel".to_string(),
+        }
+    }
+
+    fn parse(custom_id:&str) -> Option<Self> {
+        let rest = custom_id.strip_prefix("give:")?;
+        if rest == "cancel" {
+            return Some(GiveButton::Cancel);
+        }
+        let mut parts = rest.splitn(3, ':');
+        let to = parts.next()?.parse().ok()?;
+        let amount = parts.next()?.parse().ok().filter(|amount:&i64| *amount >= 0)?;
+        let ty = parts.next()?.to_string();
+        Some(GiveButton::Confirm{to, amount, ty})
+    }
+}
+
+fn give_buttons(c: &mut CreateComponents, confirm: GiveButton) -> &mut CreateComponents {
+    c.create_action_row(|row| row
+        .create_button(|b| b
+            .custom_id(confirm.custom_id())
+            .label("Confirm")
+            .style(ButtonStyle::Success)
+        ).create_button(|b| b
+            .custom_id(GiveButton::Cancel.custom_id())
+            .label("Cancel")
+            .style(ButtonStyle::Secondary)
+        )
+    )
+}
+
+/// Adds the voting buttons to a motion message
+pub fn motion_buttons(c: &mut CreateComponents) -> &mut CreateComponents {
+    c.create_action_row(|row| {
+        row.create_button(|b| b
+            .custom_id(VoteButton::Direction(true).custom_id())
+            .label("For")
+            .style(ButtonStyle::Success)
+        ).create_button(|b| b
+            .custom_id(VoteButton::Direction(false).custom_id())
+            .label("Against")
+            .style(ButtonStyle::Danger)
+        )
+    });
+    // An action row holds at most five buttons
+    for amounts in VOTE_AMOUNTS.chunks(5) {
+        c.create_action_row(|row| {
+            for &amount in amounts {
+                row.create_button(|b| b
+                    .custom_id(VoteButton::Amount(amount).custom_id())
+                    .label(format!("+{}", amount))
+                    .style(ButtonStyle::Secondary)
+                );
+            }
+            row
+        });
+    }
+    c
+}
+
+/// Replaces the bot's global slash commands with the ones handled here. New commands can take up to an hour to show
+/// up in every server.
+pub async fn register_commands(ctx: &Context) {
+    use ApplicationCommandOptionType as Ty;
+    let res = ApplicationCommand::set_global_application_commands(&ctx.http, |commands| {
+        commands.create_application_command(|c| c
+            .name("balances")
+            .description("Shows your balances, items and loans")
+        ).create_application_command(|c| c
+            .name("give")
+            .description("Gives fungibles to someone")
+            .create_option(|o| o.name("user").description("Who to give to").kind(Ty::String).required(true).set_autocomplete(true))
+            .create_option(|o| o.name("amount").description("How many to give").kind(Ty::Integer).required(true))
+            .create_option(|o| o.name("type").description("What to give").kind(Ty::String).required(true).set_autocomplete(true))
+        ).create_application_command(|c| c
+            .name("vote")
+            .description("Casts votes on a motion, costing capital")
+            .create_option(|o| o.name("motion").description("The motion's id").kind(Ty::String).required(true))
+            .create_option(|o| o
+                .name("direction")
+                .description("Required on your first vote on the motion")
+                .kind(Ty::String)
+                .add_string_choice("for", "for")
+                .add_string_choice("against", "against")
+            )
+            .create_option(|o| o.name("amount").description("How many votes to cast, 1 by default").kind(Ty::Integer))
+        ).create_application_command(|c| c
+            .name("bid")
+            .description("Bids on an auction")
+            .create_option(|o| o.name("auction").description("The auction's id").kind(Ty::String).required(true))
+            .create_option(|o| o.name("amount").description("Your bid, or how many units with unit_price").kind(Ty::Integer).required(true))
+            .create_option(|o| o.name("max").description("Bid automatically up to the amount").kind(Ty::Boolean))
+            .create_option(|o| o.name("unit_price").description("The price each, on an auction sold by the unit").kind(Ty::Integer))
+        ).create_application_command(|c| c
+            .name("buy_now")
+            .description("Buys an auction's lot at its current buy-now price")
+            .create_option(|o| o.name("auction").description("The auction's id").kind(Ty::String).required(true))
+        )
+    }).await;
+    if let Err(e) = res {
+        warn!("Could not register slash commands: {:?}", e);
+    }
+}
+
+pub async fn interaction_create(ctx: &Context, interaction: Interaction) {
+    trace!("interaction_create");
+    let res = match interaction {
+        Interaction::ApplicationCommand(command) => command_interaction(ctx, &command).await,
+        Interaction::MessageComponent(component) => component_interaction(ctx, &component).await,
+        Interaction::Autocomplete(autocomplete) => autocomplete_interaction(ctx, &autocomplete).await,
+        _ => Ok(()),
+    };
+    if let Err(e) = res {
+        warn!("Error handling interaction: {:?}", e);
+    }
+}
+
+enum Reply {
+    Text(String),
+    /// A title and fields
+    Embed(String, Vec<(String, String)>),
+    /// A question, with buttons to answer it. Only shown to whoever ran the command, since only they can answer.
+    Confirm(String, GiveButton),
+}
+
+async fn command_interaction(ctx: &Context, command: &ApplicationCommandInteraction) -> CommandResult {
+    trace!("command_interaction");
+    let user = command.user.id;
+    let options = &command.data.options;
+    let economy = economy_of(ctx, command.guild_id).await?;
+    let shared = economy == crate::guilds::SHARED_ECONOMY;
+    let res = match command.data.name.as_str() {
+        "balances" => balance_fields(ctx, user.into(), economy).await
+            .map(|fields| Reply::Embed("Your balances:".to_string(), fields)),
+        "give" => give_reply(ctx, user, command.guild_id, economy, options).await,
+        "vote" => vote_reply(ctx, user, options).await,
+        "bid" | "buy_now" if !shared => Err("That isn't available in a server with its own economy.".into()),
+        "bid" => bid_reply(ctx, user, options).await,
+        "buy_now" => buy_now_reply(ctx, user, options).await,
+        _ => Err("Unknown command.".into()),
+    };
+    // Like the replies to failed `$` commands, except only whoever ran it can see them. Balances and votes are nobody
+    // else's business either; gives, bids and buys are announced like their `$` counterparts.
+    let (reply, failed) = match res {
+        Ok(reply) => (reply, false),
+        Err(e) => (Reply::Text(e.to_string()), true),
+    };
+    let private = failed
+        || matches!(command.data.name.as_str(), "balances" | "vote")
+        || matches!(reply, Reply::Confirm(..));
+    command.create_interaction_response(&ctx.http, |r| r
+        .kind(InteractionResponseType::ChannelMessageWithSource)
+        .interaction_response_data(|d| {
+            match reply {
+                Reply::Text(text) => { d.content(text); },
+                Reply::Embed(title, fields) => {
+                    d.create_embed(|e| {
+                        e.title(title);
+                        for (name, value) in fields {
+                            e.field(name, value, false);
+                        }
+                        e
+                    });
+                },
+                Reply::Confirm(text, confirm) => {
+                    d.content(text);
+                    d.components(|c| give_buttons(c, confirm));
+                },
+            }
+            if private {
+                d.flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL);
+            }
+            d
+        })
+    ).await?;
+    Ok(())
+}
+
+fn option_value<'a>(options: &'a [ApplicationCommandInteractionDataOption], name: &str) -> Option<&'a serde_json::Value> {
+    options.iter().find(|o| o.name == name).and_then(|o| o.value.as_ref())
+}
+
+fn string_option<'a>(options: &'a [ApplicationCommandInteractionDataOption], name: &str) -> Option<&'a str> {
+    option_value(options, name).and_then(|v| v.as_str())
+}
+
+fn int_option(options: &[ApplicationCommandInteractionDataOption], name: &str) -> Option<i64> {
+    option_value(options, name).and_then(|v| v.as_i64())
+}
+
+fn bool_option(options: &[ApplicationCommandInteractionDataOption], name: &str) -> Option<bool> {
+    option_value(options, name).and_then(|v| v.as_bool())
+}
+
+/// Discord enforces required options, so this only fails if the registered commands are out of date
+fn required<T>(value: Option<T>, name: &str) -> CommandResult<T> {
+    value.ok_or_else(|| format!("Missing option {}.", name).into())
+}
+
+async fn give_reply(
+    ctx: &Context,
+    giver: SerenityUserId,
+    guild_id: Option<GuildId>,
+    economy: i64,
+    options: &[ApplicationCommandInteractionDataOption],
+) -> CommandResult<Reply> {
+    trace!("give_reply");
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+    let user_str = required(string_option(options, "user"), "user")?;
+    let user = find_user(ctx, giver, user_str).await?;
+    let user_in_guild = match guild_id {
+        Some(guild_id) => guild_id.member(ctx, user).await.is_ok(),
+        None => false,
+    };
+    if !user_in_guild && !ctx.cache.users().await.contains_key(&user) {
+        return Err("User not found".into());
+    }
+    let amount = required(int_option(options, "amount"), "amount")?;
+    if amount < 0 {
+        return Err("No negatives >:(".into());
+    }
+    let ty = find_item_type(&*pool, required(string_option(options, "type"), "type")?.to_string()).await?;
+
+    let ty_id = ty.id.clone();
+    let resolved_by_name = !user_ref_is_exact(user_str);
+    let reasons = with_conn(ctx, move |conn| crate::give_confirmations::check(
+        conn,
+        economy,
+        giver.into(),
+        user.into(),
+        &ty_id,
+        amount,
+        resolved_by_name,
+    )).await?;
+    if reasons.is_empty() {
+        return send_give(ctx, giver, user, economy, amount, &ty).await;
+    }
+    let recipient = match user.to_user(&ctx).await {
+        Ok(u) => format!("{} (id {})", u.tag(), u.id),
+        Err(_) => format!("id {}", user),
+    };
+    let reasons:Vec<String> = reasons.iter().map(|r| r.to_string()).collect();
+    Ok(Reply::Confirm(
+        format!(
+            "Give {} {} to {}? Please confirm, because {}.",
+            amount,
+            ty.long_name_ambiguous,
+            recipient,
+            reasons.join(", and "),
+        ),
+        GiveButton::Confirm{to: user.0, amount, ty: ty.id.to_string()},
+    ))
+}
+
+/// Makes a give from `/give`, straight away or once it's been confirmed
+async fn send_give(
+    ctx: &Context,
+    giver: SerenityUserId,
+    user: SerenityUserId,
+    economy: i64,
+    amount: i64,
+    ty: &ItemType,
+) -> CommandResult<Reply> {
+    let now = Utc::now();
+    let ty_id = ty.id.clone();
+    let res = with_conn(ctx, move |conn| conn.transaction::<_, diesel::result::Error, _>(|| {
+        let t = TransactionBuilder::new(
+            amount,
+            ty_id,
+            now,
+        ).give(
+            giver.into(),
+            user.into(),
+            false,
+        );
+        match TransferHandler::handle_single_in(conn, economy, t) {
+            Err(e) => Ok(Err(e)),
+            Ok(v) => v.map(Ok),
+        }
+    })).await?;
+    match res {
+        Err(TransferError::NotEnough) => Err("Insufficient balance.".into()),
+        Err(TransferError::Overflow) => Err("Overflow.".into()),
+        Ok(()) => Ok(Reply::Text(format!(
+            "Successfully transferred {} {} to {}.",
+            amount,
+            ty.long_name_ambiguous,
+            user.mention(),
+        ))),
+    }
+}
+
+async fn vote_reply(
+    ctx: &Context,
+    user: SerenityUserId,
+    options: &[ApplicationCommandInteractionDataOption],
+) -> CommandResult<Reply> {
+    trace!("vote_reply");
+    let motion_id = parse_motion_id(required(string_option(options, "motion"), "motion")?)?;
+    let direction = match string_option(options, "direction") {
+        Some("for") => Some(true),
+        Some("against") => Some(false),
+        _ => None,
+    };
+    let amount = int_option(options, "amount").unwrap_or(1);
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+    let resp = vote_common_async(
+        pool,
+        direction,
+        amount,
+        user.into(),
+        Some(motion_id),
+        None,
+        None,
+    ).await;
+    Ok(Reply::Text(resp.into_owned()))
+}
+
+async fn bid_reply(
+    ctx: &Context,
+    user: SerenityUserId,
+    options: &[ApplicationCommandInteractionDataOption],
+) -> CommandResult<Reply> {
+    trace!("bid_reply");
+    let now = Utc::now();
+    let auction_id = parse_auction_id(required(string_option(options, "auction"), "auction")?)?;
+    let amount = required(int_option(options, "amount"), "amount")?;
+    let bidder:models::UserId = user.into();
+    let outcome = match int_option(options, "unit_price") {
+        Some(unit_price) => with_conn(ctx, move |conn| crate::auctions::place_unit_bid(conn, auction_id, bidder, amount, unit_price, now)).await?,
+        None => {
+            let max = bool_option(options, "max").unwrap_or(false);
+            with_conn(ctx, move |conn| crate::auctions::bid(conn, auction_id, bidder, amount, max, now)).await?
+        },
+    };
+    Ok(Reply::Text(format!(
+        "{} {}",
+        outcome.message,
+        outcome.position_for(bidder),
+    )))
+}
+
+async fn buy_now_reply(
+    ctx: &Context,
+    user: SerenityUserId,
+    options: &[ApplicationCommandInteractionDataOption],
+) -> CommandResult<Reply> {
+    trace!("buy_now_reply");
+    let now = Utc::now();
+    let auction_id = parse_auction_id(required(string_option(options, "auction"), "auction")?)?;
+    let buyer:models::UserId = user.into();
+    let (auction, price) = with_conn(ctx, move |conn| crate::auctions::buy_now(conn, auction_id, buyer, now)).await?;
+    Ok(Reply::Text(format!(
+        "{buyer} bought {lot} from auction#{id} for {price} {ty}. It'll be theirs as soon as the auction finishes, in a few seconds.",
+        buyer = user.mention(),
+        lot = auction.lot_description(),
+        id = auction.damm(),
+        price = price,
+        ty = auction.bid_ty,
+    )))
+}
+
+/// Answers the buttons on a `/give` waiting for confirmation, replacing the question with how it went
+async fn give_button(ctx: &Context, component: &MessageComponentInteraction, button: GiveButton) -> CommandResult {
+    trace!("give_button");
+    let reply = match button {
+        GiveButton::Cancel => "Cancelled.".to_string(),
+        GiveButton::Confirm{to, amount, ty} => {
+            let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+            let res = match find_item_type(&*pool, ty).await {
+                Ok(ty) => {
+                    let economy = economy_of(ctx, component.guild_id).await?;
+                    send_give(ctx, component.user.id, SerenityUserId::from(to), economy, amount, &ty).await
+                },
+                Err(e) => Err(e),
+            };
+            match res {
+                Ok(Reply::Text(text)) => text,
+                Ok(_) => unreachable!("send_give only replies with text"),
+                Err(e) => e.to_string(),
+            }
+        },
+    };
+    component.create_interaction_response(&ctx.http, |r| r
+        .kind(InteractionResponseType::UpdateMessage)
+        .interaction_response_data(|d| d
+            .content(reply)
+            .components(|c| c)
+        )
+    ).await?;
+    Ok(())
+}
+
+/// Votes with a button on a motion message, or answers a `/give` confirmation. The reply is only shown to whoever
+/// clicked.
+async fn component_interaction(ctx: &Context, component: &MessageComponentInteraction) -> CommandResult {
+    trace!("component_interaction");
+    if let Some(button) = GiveButton::parse(&component.data.custom_id) {
+        return give_button(ctx, component, button).await;
+    }
+    let button = match VoteButton::parse(&component.data.custom_id) {
+        Some(button) => button,
+        None => return Ok(()),
+    };
+    let message_id = match &component.message {
+        InteractionMessage::Regular(message) => message.id,
+        InteractionMessage::Ephemeral(_) => return Ok(()),
+    };
+    let (direction, amount) = match button {
+        VoteButton::Direction(direction) => (Some(direction), 0),
+        VoteButton::Amount(amount) => (None, amount),
+    };
+    let pool = Arc::clone(ctx.data.read().await.get::<DbPoolKey>().unwrap());
+    let resp = vote_common_async(
+        pool,
+        direction,
+        amount,
+        component.user.id.into(),
+        None,
+        Some(message_id.0 as i64),
+        None,
+    ).await;
+    component.create_interaction_response(&ctx.http, |r| r
+        .kind(InteractionResponseType::ChannelMessageWithSource)
+        .interaction_response_data(|d| d
+            .content(resp)
+            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+        )
+    ).await?;
+    Ok(())
+}
+
+async fn autocomplete_interaction(ctx: &Context, autocomplete: &AutocompleteInteraction) -> CommandResult {
+    trace!("autocomplete_interaction");
+    let focused = match autocomplete.data.options.iter().find(|o| o.focused) {
+        Some(option) => option,
+        None => return Ok(()),
+    };
+    let typed = focused.value.as_ref().and_then(|v| v.as_str()).unwrap_or("").to_string();
+    let choices = match focused.name.as_str() {
+        "user" => user_choices(ctx, &typed).await,
+        "type" => with_conn(ctx, move |conn| crate::item_types::aliases_starting_with(conn, &typed, MAX_CHOICES as i64)).await?
+            .into_iter()
+            .map(|(alias, plural)| (format!("{} ({})", alias, plural), alias))
+            .collect(),
+        _ => Vec::new(),
+    };
+    autocomplete.create_autocomplete_response(&ctx.http, |r| {
+        for (name, value) in choices {
+            r.add_string_choice(name, value);
+        }
+        r
+    }).await?;
+    Ok(())
+}
+
+/// Members the bot can see whose nickname or username contains `typed`, ignoring case. The values are user ids, so
+/// picking one is as exact as a mention.
+async fn user_choices(ctx: &Context, typed: &str) -> Vec<(String, String)> {
+    let typed = typed.to_lowercase();
+    let mut seen = std::collections::HashSet::new();
+    let mut choices = Vec::new();
+    for guild_id in ctx.cache.guilds().await {
+        if let Some(members) = ctx.cache.guild_field(guild_id, |g| g.members.clone()).await {
+            for (user_id, member) in members {
+                let matches = member.nick.iter()
+                    .chain(std::iter::once(&member.user.name))
+                    .any(|name| name.to_lowercase().contains(&typed));
+                if matches && seen.insert(user_id) {
+                    let name = match &member.nick {
+                        Some(nick) => format!("{} ({})", nick, member.user.tag()),
+                        None => member.user.tag(),
+                    };
+                    choices.push((name, user_id.to_string()));
+                    if choices.len() == MAX_CHOICES {
+                        return choices;
+                    }
+                }
+            }
+        }
+    }
+    choices
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vote_buttons() {
+        assert_eq!(VoteButton::parse("vote:for"), Some(VoteButton::Direction(true)));
+        assert_eq!(VoteButton::parse("vote:against"), Some(VoteButton::Direction(false)));
+        assert_eq!(VoteButton::parse("vote:20"), Some(VoteButton::Amount(20)));
+        assert_eq!(VoteButton::parse("vote:3"), None);
+        assert_eq!(VoteButton::parse("vote:-1"), None);
+        assert_eq!(VoteButton::parse("trade:for"), None);
+        for &amount in VOTE_AMOUNTS {
+            let button = VoteButton::Amount(amount);
+            assert_eq!(VoteButton::parse(&button.custom_id()), Some(button));
+        }
+    }
+
+    #[test]
+    fn give_button_ids() {
+        let confirm = GiveButton::Confirm{to: 165858230327574528, amount: 500, ty: "pc".to_string()};
+        assert_eq!(confirm.custom_id(), "give:165858230327574528:500:pc");
+        assert_eq!(GiveButton::parse(&confirm.custom_id()), Some(confirm));
+        assert_eq!(GiveButton::parse(&GiveButton::Cancel.custom_id()), Some(GiveButton::Cancel));
+        assert_eq!(GiveButton::parse("give:12:-5:pc"), None);
+        assert_eq!(GiveButton::parse("give:someone:5:pc"), None);
+        assert_eq!(GiveButton::parse("give:12:5"), None);
+        assert_eq!(GiveButton::parse("vote:for"), None);
+    }
+}
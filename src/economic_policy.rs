@@ -0,0 +1,235 @@
+use chrono::{DateTime,Utc};
+use diesel::prelude::*;
+use crate::schema::economic_policies::dsl as epdsl;
+use crate::models::{UserId,TransferType};
+use crate::transfers::{CurrencyId,TransactionBuilder,TransferHandler};
+use crate::loans::{parse_rate,format_rate};
+
+pub const SETTING_USAGE:&str = "Setting must be `off`, `<n>` pc per gen for gen_upkeep, `<rate>%` for pc_decay, or `<rate>% above <threshold>` for wealth_tax";
+
+/// A monetary policy run by the worker every generation period. Each one burns pc, recorded as its own transfer type.
+/// Only balances are charged, so pc held by open auctions, bids, market orders and loan offers is exempt, which the
+/// motion text says.
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash,Serialize,Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyKind {
+    /// A flat amount of pc per gen held
+    GenUpkeep,
+    /// A percentage of the pc held above a threshold
+    WealthTax,
+    /// A percentage of all pc held
+    PcDecay,
+}
+
+impl PolicyKind {
+    /// In the order they are applied
+    pub const ALL:[PolicyKind; 3] = [PolicyKind::GenUpkeep, PolicyKind::WealthTax, PolicyKind::PcDecay];
+
+    pub fn db_name(self) -> &'static str {
+        match self {
+            PolicyKind::GenUpkeep => "gen_upkeep",
+            PolicyKind::WealthTax => "wealth_tax",
+            PolicyKind::PcDecay => "pc_decay",
+        }
+    }
+
+    /// Accepts the database name or a short alias, eg "tax"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "gen_upkeep" | "upkeep" => Some(PolicyKind::GenUpkeep),
+            "wealth_tax" | "tax" => Some(PolicyKind::WealthTax),
+            "pc_decay" | "decay" => Some(PolicyKind::PcDecay),
+            _ => None,
+        }
+    }
+
+    pub fn transfer_type(self) -> TransferType {
+        match self {
+            PolicyKind::GenUpkeep => TransferType::GenUpkeep,
+            PolicyKind::WealthTax => TransferType::WealthTax,
+            PolicyKind::PcDecay => TransferType::PcDecay,
+        }
+    }
+
+    pub fn from_transfer_type(ty: TransferType) -> Option<Self> {
+        PolicyKind::ALL.iter().copied().find(|k| k.transfer_type() == ty)
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            PolicyKind::GenUpkeep => "Gen upkeep",
+            PolicyKind::WealthTax => "Wealth tax",
+            PolicyKind::PcDecay => "PC decay",
+        }
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Serialize,Deserialize)]
+pub struct PolicySetting {
+    pub enabled: bool,
+    /// pc per gen for gen upkeep, hundredths of a percent otherwise
+    pub rate: i64,
+    /// Only used by the wealth tax
+    pub threshold: i64,
+}
+
+impl PolicySetting {
+    pub const OFF:Self = PolicySetting{enabled: false, rate: 0, threshold: 0};
+
+    /// eg "2 pc per gen", "0.5% of spendable pc above 10000", "off"
+    pub fn describe(&self, kind: PolicyKind) -> String {
+        if !self.enabled {
+            return "off".to_string();
+        }
+        match kind {
+            PolicyKind::GenUpkeep => format!("{} pc per gen", self.rate),
+            PolicyKind::WealthTax => format!("{} of spendable pc above {}", format_rate(self.rate as i32), self.threshold),
+            PolicyKind::PcDecay => format!("{} of spendable pc", format_rate(self.rate as i32)),
+        }
+    }
+
+    /// Parses the words after the policy name, eg `off`, `2`, `0.5%`, or `1% above 10000`
+    pub fn parse(kind: PolicyKind, words: &[String]) -> Result<Self, &'static str> {
+        let lower:Vec<String> = words.iter().map(|w| w.to_ascii_lowercase()).collect();
+        let lower:Vec<&str> = lower.iter().map(|w| w.as_str()).collect();
+        let setting = match (kind, lower.as_slice()) {
+            (_, ["off"]) => return Ok(PolicySetting::OFF),
+            (PolicyKind::GenUpkeep, [amount]) => PolicySetting{
+                enabled: true,
+                rate: amount.parse().map_err(|_| SETTING_USAGE)?,
+                threshold: 0,
+            },
+            (PolicyKind::PcDecay, [rate]) => PolicySetting{
+                enabled: true,
+                rate: parse_rate(rate).ok_or(SETTING_USAGE)? as i64,
+                threshold: 0,
+            },
+            (PolicyKind::WealthTax, [rate, "above", threshold]) => PolicySetting{
+                enabled: true,
+                rate: parse_rate(rate).ok_or(SETTING_USAGE)? as i64,
+                threshold: threshold.parse().map_err(|_| SETTING_USAGE)?,
+            },
+            _ => return Err(SETTING_USAGE),
+        };
+        if setting.rate <= 0 || setting.threshold < 0 {
+            return Err("The rate must be more than nothing; use `off` to turn a policy off.");
+        }
+        Ok(setting)
+    }
+
+    /// How much pc the policy takes from someone holding `gens` and `pc` this period. Never more than they have.
+    pub fn charge(&self, kind: PolicyKind, gens: i64, pc: i64) -> i64 {
+        if !self.enabled || pc <= 0 {
+            return 0;
+        }
+        let charge = match kind {
+            PolicyKind::GenUpkeep => gens.max(0).saturating_mul(self.rate),
+            PolicyKind::WealthTax => ((pc - self.threshold).max(0) as i128 * self.rate as i128 / 10000) as i64,
+            PolicyKind::PcDecay => (pc as i128 * self.rate as i128 / 10000) as i64,
+        };
+        charge.min(pc)
+    }
+}
+
+pub fn get_all(
+    conn: &diesel::PgConnection,
+) -> QueryResult<Vec<(PolicyKind, PolicySetting)>> {
+    let rows:Vec<(String, bool, i64, i64)> = epdsl::economic_policies
+        .select((epdsl::name, epdsl::enabled, epdsl::rate, epdsl::threshold))
+        .get_results(conn)?;
+    let mut policies:Vec<(PolicyKind, PolicySetting)> = rows.into_iter().map(|(name, enabled, rate, threshold)| (
+        PolicyKind::from_name(&name).expect("unknown policy in database"),
+        PolicySetting{enabled, rate, threshold},
+    )).collect();
+    policies.sort_by_key(|(kind, _)| PolicyKind::ALL.iter().position(|k| k == kind));
+    Ok(policies)
+}
+
+pub fn set(
+    conn: &diesel::PgConnection,
+    kind: PolicyKind,
+    setting: PolicySetting,
+    motion_id: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<()> {
+    diesel::update(epdsl::economic_policies.filter(epdsl::name.eq(kind.db_name())))
+        .set((
+            epdsl::enabled.eq(setting.enabled),
+            epdsl::rate.eq(setting.rate),
+            epdsl::threshold.eq(setting.threshold),
+            epdsl::changed_at.eq(now),
+            epdsl::changed_by_motion.eq(motion_id),
+        ))
+        .execute(conn)
+        .map(|_| ())
+}
+
+/// Whether any enabled policy charges people who hold pc but no gens
+pub fn charges_pc_holders(policies: &[(PolicyKind, PolicySetting)]) -> bool {
+    policies.iter().any(|(kind, setting)| setting.enabled && *kind != PolicyKind::GenUpkeep)
+}
+
+/// Applies each enabled policy to `user` in turn, burning what it charges. `handle` must have locked the user's gen
+/// and pc balances. The treasury is exempt.
+pub fn apply(
+    handle: &mut TransferHandler,
+    policies: &[(PolicyKind, PolicySetting)],
+    user: UserId,
+    now: DateTime<Utc>,
+) -> QueryResult<()> {
+    if user == UserId::TREASURY {
+        return Ok(());
+    }
+    for (kind, setting) in policies {
+        let gens = handle.balance(user, CurrencyId::GEN);
+        let pc = handle.balance(user, CurrencyId::PC);
+        let charge = setting.charge(*kind, gens, pc);
+        if charge > 0 {
+            let t = TransactionBuilder::new(
+                charge,
+                CurrencyId::PC,
+                now,
+            ).economic_policy(user, *kind);
+            handle.transfer(t).unwrap()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PolicyKind,PolicySetting};
+    use crate::test_util::words;
+
+    #[test]
+    fn settings() {
+        use PolicyKind::*;
+        assert_eq!(PolicySetting::parse(GenUpkeep, &words("2")), Ok(PolicySetting{enabled: true, rate: 2, threshold: 0}));
+        assert_eq!(PolicySetting::parse(PcDecay, &words("0.5%")), Ok(PolicySetting{enabled: true, rate: 50, threshold: 0}));
+        assert_eq!(PolicySetting::parse(WealthTax, &words("1% ABOVE 10000")), Ok(PolicySetting{enabled: true, rate: 100, threshold: 10000}));
+        assert_eq!(PolicySetting::parse(WealthTax, &words("off")), Ok(PolicySetting::OFF));
+        assert!(PolicySetting::parse(WealthTax, &words("1%")).is_err());
+        assert!(PolicySetting::parse(PcDecay, &words("2")).is_err());
+        assert!(PolicySetting::parse(GenUpkeep, &words("0")).is_err());
+        assert!(PolicySetting::parse(GenUpkeep, &words("-1")).is_err());
+        assert!(PolicySetting::parse(PcDecay, &words("0%")).is_err());
+    }
+
+    #[test]
+    fn charges() {
+        use PolicyKind::*;
+        let upkeep = PolicySetting{enabled: true, rate: 3, threshold: 0};
+        assert_eq!(upkeep.charge(GenUpkeep, 10, 1000), 30);
+        // Can't take more than they have
+        assert_eq!(upkeep.charge(GenUpkeep, 10, 20), 20);
+        assert_eq!(upkeep.charge(GenUpkeep, i64::MAX, 1000), 1000);
+        let tax = PolicySetting{enabled: true, rate: 100, threshold: 10000};
+        assert_eq!(tax.charge(WealthTax, 0, 9000), 0);
+        assert_eq!(tax.charge(WealthTax, 0, 15000), 50);
+        let decay = PolicySetting{enabled: true, rate: 50, threshold: 0};
+        assert_eq!(decay.charge(PcDecay, 0, 1000), 5);
+        // Rounds down
+        assert_eq!(decay.charge(PcDecay, 0, 199), 0);
+        assert_eq!(PolicySetting::OFF.charge(PcDecay, 5, 1000), 0);
+    }
+}
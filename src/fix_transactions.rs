@@ -12,6 +12,7 @@ struct Transfer {
     to_user: Option<i64>,
     from_balance: Option<i64>,
     to_balance: Option<i64>,
+    economy: i64,
 }
 
 impl Transfer {
@@ -22,7 +23,8 @@ impl Transfer {
         tdsl::quantity,
         tdsl::to_user,
         tdsl::from_balance,
-        tdsl::to_balance
+        tdsl::to_balance,
+        tdsl::economy,
     ) {
         (
             tdsl::rowid,
@@ -31,7 +33,8 @@ impl Transfer {
             tdsl::quantity,
             tdsl::to_user,
             tdsl::from_balance,
-            tdsl::to_balance
+            tdsl::to_balance,
+            tdsl::economy,
         )
     }
 }
@@ -52,17 +55,17 @@ pub fn fix_transactions() {
         for orig_transfer in transfers {
             let mut new_transfer = orig_transfer.clone();
             if let Some(user) = orig_transfer.from_user {
-                let prev_balance = balances.entry((orig_transfer.ty.clone(), user)).or_insert(0i64);
+                let prev_balance = balances.entry((orig_transfer.economy, orig_transfer.ty.clone(), user)).or_insert(0i64);
                 let new_balance = *prev_balance - orig_transfer.quantity;
                 if new_balance < 0 { fail = true }
                 new_transfer.from_balance = Some(new_balance);
-                balances.insert((orig_transfer.ty.clone(), user), new_balance);
+                balances.insert((orig_transfer.economy, orig_transfer.ty.clone(), user), new_balance);
             }
             if let Some(user) = orig_transfer.to_user {
-                let prev_balance = balances.entry((orig_transfer.ty.clone(), user)).or_insert(0i64);
+                let prev_balance = balances.entry((orig_transfer.economy, orig_transfer.ty.clone(), user)).or_insert(0i64);
                 let new_balance = *prev_balance + orig_transfer.quantity;
                 new_transfer.to_balance = Some(new_balance);
-                balances.insert((orig_transfer.ty.clone(), user), new_balance);
+                balances.insert((orig_transfer.economy, orig_transfer.ty.clone(), user), new_balance);
             }
             if new_transfer != orig_transfer {
                 diesel::update(tdsl::transfers.filter(tdsl::rowid.eq(new_transfer.rowid)))
@@ -0,0 +1,134 @@
+use chrono::{DateTime,Utc};
+use diesel::prelude::*;
+use crate::schema::force_gives::dsl as fgdsl;
+use crate::models::{UserId,ForceGive};
+use crate::transfers::{CurrencyId,TransactionBuilder,TransferHandler,TransferError};
+
+#[derive(Debug)]
+pub enum ForceGiveError {
+    NotFound,
+    /// The recipient had been seen, or the window has passed, or it was already undone
+    CantUndo,
+    NotEnough,
+    Overflow,
+    Db(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for ForceGiveError {
+    fn from(e: diesel::result::Error) -> Self {
+        ForceGiveError::Db(e)
+    }
+}
+
+impl From<TransferError> for ForceGiveError {
+    fn from(e: TransferError) -> Self {
+        match e {
+            TransferError::NotEnough => ForceGiveError::NotEnough,
+            TransferError::Overflow => ForceGiveError::Overflow,
+        }
+    }
+}
+
+impl std::fmt::Display for ForceGiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ForceGiveError::NotFound => write!(f, "Force give not found."),
+            ForceGiveError::CantUndo => write!(f, "That force give can't be undone; it was already undone, its window has passed, or its recipient was known."),
+            ForceGiveError::NotEnough => write!(f, "Insufficient balance."),
+            ForceGiveError::Overflow => write!(f, "Overflow."),
+            ForceGiveError::Db(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ForceGiveError {}
+
+/// Transfers as an admin give, and records it. Gives to a recipient that wasn't seen can be undone until `now +
+/// undo_window`.
+#[allow(clippy::too_many_arguments)]
+pub fn perform(
+    conn: &diesel::PgConnection,
+    giver: UserId,
+    recipient: UserId,
+    ty: CurrencyId,
+    quantity: i64,
+    message_id: i64,
+    recipient_seen: bool,
+    now: DateTime<Utc>,
+    undo_window: chrono::Duration,
+) -> Result<ForceGive, ForceGiveError> {
+    conn.transaction(|| {
+        let t = TransactionBuilder::new(
+            quantity,
+            ty.clone(),
+            now,
+        ).give(
+            giver,
+            recipient,
+            true,
+        ).message_id_raw(message_id);
+        TransferHandler::handle_single(conn, t)??;
+        let undo_until = if recipient_seen { None } else { Some(now + undo_window) };
+        let record = diesel::insert_into(fgdsl::force_gives).values((
+            fgdsl::happened_at.eq(now),
+            fgdsl::giver.eq(giver),
+            fgdsl::recipient.eq(recipient),
+            fgdsl::ty.eq(ty),
+            fgdsl::quantity.eq(quantity),
+            fgdsl::message_id.eq(message_id),
+            fgdsl::recipient_seen.eq(recipient_seen),
+            fgdsl::undo_until.eq(undo_until),
+        )).returning(ForceGive::cols()).get_result(conn)?;
+        Ok(record)
+    })
+}
+
+/// Moves a force give back to whoever gave it. Any admin can undo, not only the one who gave it.
+pub fn undo(
+    conn: &diesel::PgConnection,
+    force_give_id: i64,
+    admin: UserId,
+    now: DateTime<Utc>,
+) -> Result<ForceGive, ForceGiveError> {
+    conn.transaction(|| {
+        let mut record:ForceGive = fgdsl::force_gives
+            .select(ForceGive::cols())
+            .filter(fgdsl::rowid.eq(force_give_id))
+            .for_update()
+            .get_result(conn)
+            .optional()?
+            .ok_or(ForceGiveError::NotFound)?;
+        if !record.can_undo(now) {
+            return Err(ForceGiveError::CantUndo);
+        }
+        let t = TransactionBuilder::new(
+            record.quantity,
+            record.ty.clone(),
+            now,
+        ).force_give_undo(
+            record.recipient,
+            record.giver,
+        );
+        TransferHandler::handle_single(conn, t)??;
+        diesel::update(fgdsl::force_gives.filter(fgdsl::rowid.eq(force_give_id)))
+            .set((
+                fgdsl::undone_at.eq(now),
+                fgdsl::undone_by.eq(admin),
+            ))
+            .execute(conn)?;
+        record.undone_at = Some(now);
+        record.undone_by = Some(admin);
+        Ok(record)
+    })
+}
+
+pub fn recent(
+    conn: &diesel::PgConnection,
+    limit: i64,
+) -> QueryResult<Vec<ForceGive>> {
+    fgdsl::force_gives
+        .select(ForceGive::cols())
+        .order(fgdsl::rowid.desc())
+        .limit(limit)
+        .get_results(conn)
+}
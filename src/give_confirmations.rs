@@ -0,0 +1,242 @@
+use chrono::{DateTime,Utc};
+use diesel::prelude::*;
+use crate::schema::pending_gives::dsl as pgdsl;
+use crate::schema::item_types::dsl as itdsl;
+use crate::schema::transfers::dsl as tdsl;
+use crate::models::{UserId,PendingGive};
+use crate::transfers::{CurrencyId,TransactionBuilder,TransferHandler,TransferError};
+
+/// Why a give has to be confirmed before it happens
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum ConfirmReason {
+    /// More than the item type's `confirm_give_above`
+    Large(i64),
+    /// The giver has never transferred anything to the recipient before
+    NewRecipient,
+    /// The recipient was found by nickname or username rather than a mention or id
+    ResolvedByName,
+}
+
+impl std::fmt::Display for ConfirmReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfirmReason::Large(threshold) => write!(f, "it's more than {}", threshold),
+            ConfirmReason::NewRecipient => write!(f, "you've never paid them before"),
+            ConfirmReason::ResolvedByName => write!(f, "they were found by name"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GiveError {
+    NotFound,
+    /// Only the giver can confirm or cancel
+    NotGiver,
+    /// Already confirmed, cancelled, or expired
+    NotPending,
+    NotEnough,
+    Overflow,
+    Db(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for GiveError {
+    fn from(e: diesel::result::Error) -> Self {
+        GiveError::Db(e)
+    }
+}
+
+impl std::fmt::Display for GiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GiveError::NotFound => write!(f, "Give not found."),
+            GiveError::NotGiver => write!(f, "Only the giver can confirm or cancel this."),
+            GiveError::NotPending => write!(f, "This give has already been confirmed, cancelled, or has expired."),
+            GiveError::NotEnough => write!(f, "Insufficient balance."),
+            GiveError::Overflow => write!(f, "Overflow."),
+            GiveError::Db(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for GiveError {}
+
+/// Which reasons apply to a give of `quantity`. Giving nothing never needs confirming.
+pub fn reasons(
+    quantity: i64,
+    threshold: Option<i64>,
+    paid_before: bool,
+    resolved_by_name: bool,
+) -> Vec<ConfirmReason> {
+    let mut reasons = Vec::new();
+    if quantity == 0 {
+        return reasons;
+    }
+    if let Some(threshold) = threshold {
+        if quantity > threshold {
+            reasons.push(ConfirmReason::Large(threshold));
+        }
+    }
+    if !paid_before {
+        reasons.push(ConfirmReason::NewRecipient);
+    }
+    if resolved_by_name {
+        reasons.push(ConfirmReason::ResolvedByName);
+    }
+    reasons
+}
+
+/// Works out whether a give needs confirming, and why. Empty if it can go ahead right away.
+pub fn check(
+    conn: &diesel::PgConnection,
+    economy: i64,
+    giver: UserId,
+    recipient: UserId,
+    ty: &CurrencyId,
+    quantity: i64,
+    resolved_by_name: bool,
+) -> QueryResult<Vec<ConfirmReason>> {
+    let threshold:Option<i64> = itdsl::item_types
+        .select(itdsl::confirm_give_above)
+        .filter(itdsl::name.eq(ty))
+        .get_result(conn)?;
+    let paid_before:bool = diesel::select(diesel::dsl::exists(
+        tdsl::transfers
+            .filter(tdsl::economy.eq(economy))
+            .filter(tdsl::from_user.eq(giver))
+            .filter(tdsl::to_user.eq(recipient))
+    )).get_result(conn)?;
+    Ok(reasons(quantity, threshold, paid_before, resolved_by_name))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn create(
+    conn: &diesel::PgConnection,
+    economy: i64,
+    giver: UserId,
+    recipient: UserId,
+    ty: CurrencyId,
+    quantity: i64,
+    command_message_id: i64,
+    now: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+) -> QueryResult<i64> {
+    diesel::insert_into(pgdsl::pending_gives).values((
+        pgdsl::created_at.eq(now),
+        pgdsl::expires_at.eq(expires_at),
+        pgdsl::giver.eq(giver),
+        pgdsl::recipient.eq(recipient),
+        pgdsl::ty.eq(ty),
+        pgdsl::quantity.eq(quantity),
+        pgdsl::command_message_id.eq(command_message_id),
+        pgdsl::economy.eq(economy),
+    )).returning(pgdsl::rowid).get_result(conn)
+}
+
+pub fn set_message_id(
+    conn: &diesel::PgConnection,
+    pending_id: i64,
+    message_id: i64,
+) -> QueryResult<()> {
+    diesel::update(pgdsl::pending_gives.filter(pgdsl::rowid.eq(pending_id)))
+        .set(pgdsl::message_id.eq(message_id))
+        .execute(conn)
+        .map(|_| ())
+}
+
+pub fn find_by_message(
+    conn: &diesel::PgConnection,
+    message_id: i64,
+) -> QueryResult<Option<i64>> {
+    pgdsl::pending_gives
+        .select(pgdsl::rowid)
+        .filter(pgdsl::message_id.eq(message_id))
+        .get_result(conn)
+        .optional()
+}
+
+fn get_for_update(
+    conn: &diesel::PgConnection,
+    pending_id: i64,
+    user: UserId,
+    now: DateTime<Utc>,
+) -> Result<PendingGive, GiveError> {
+    let pending:PendingGive = pgdsl::pending_gives
+        .select(PendingGive::cols())
+        .filter(pgdsl::rowid.eq(pending_id))
+        .for_update()
+        .get_result(conn)
+        .optional()?
+        .ok_or(GiveError::NotFound)?;
+    if pending.giver != user {
+        return Err(GiveError::NotGiver);
+    }
+    if !pending.is_pending(now) {
+        return Err(GiveError::NotPending);
+    }
+    Ok(pending)
+}
+
+/// Makes the transfer. If the giver can no longer cover it, it stays pending so they can try again before it expires.
+pub fn confirm(
+    conn: &diesel::PgConnection,
+    pending_id: i64,
+    user: UserId,
+    now: DateTime<Utc>,
+) -> Result<PendingGive, GiveError> {
+    conn.transaction(|| {
+        let mut pending = get_for_update(conn, pending_id, user, now)?;
+        let t = TransactionBuilder::new(
+            pending.quantity,
+            pending.ty.clone(),
+            now,
+        ).give(
+            pending.giver,
+            pending.recipient,
+            false,
+        ).message_id_raw(pending.command_message_id);
+        match TransferHandler::handle_single_in(conn, pending.economy, t) {
+            Err(TransferError::NotEnough) => return Err(GiveError::NotEnough),
+            Err(TransferError::Overflow) => return Err(GiveError::Overflow),
+            Ok(v) => v?,
+        }
+        diesel::update(pgdsl::pending_gives.filter(pgdsl::rowid.eq(pending_id)))
+            .set(pgdsl::confirmed_at.eq(now))
+            .execute(conn)?;
+        pending.confirmed_at = Some(now);
+        Ok(pending)
+    })
+}
+
+pub fn cancel(
+    conn: &diesel::PgConnection,
+    pending_id: i64,
+    user: UserId,
+    now: DateTime<Utc>,
+) -> Result<PendingGive, GiveError> {
+    conn.transaction(|| {
+        let mut pending = get_for_update(conn, pending_id, user, now)?;
+        diesel::update(pgdsl::pending_gives.filter(pgdsl::rowid.eq(pending_id)))
+            .set(pgdsl::cancelled_at.eq(now))
+            .execute(conn)?;
+        pending.cancelled_at = Some(now);
+        Ok(pending)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{reasons,ConfirmReason};
+
+    #[test]
+    fn confirm_reasons() {
+        assert_eq!(reasons(100, Some(1000), true, false), vec![]);
+        assert_eq!(reasons(1000, Some(1000), true, false), vec![]);
+        assert_eq!(reasons(1001, Some(1000), true, false), vec![ConfirmReason::Large(1000)]);
+        assert_eq!(reasons(i64::MAX, None, true, false), vec![]);
+        assert_eq!(
+            reasons(5000, Some(1000), false, true),
+            vec![ConfirmReason::Large(1000), ConfirmReason::NewRecipient, ConfirmReason::ResolvedByName],
+        );
+        assert_eq!(reasons(0, Some(0), false, true), vec![]);
+    }
+}
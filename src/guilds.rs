@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use diesel::prelude::*;
+use crate::schema::guilds::dsl as gdsl;
+use crate::models::GuildConfig;
+
+/// The economy of DMs, the website, and every guild that isn't isolated
+pub const SHARED_ECONOMY:i64 = 0;
+
+#[cfg(not(feature = "debug"))]
+pub const DEFAULT_PREFIX:&str = "$";
+#[cfg(feature = "debug")]
+pub const DEFAULT_PREFIX:&str = "&";
+
+pub const SETTINGS_USAGE:&str = "Use like `guild_config <setting> <value>`, where the setting is one of \
+    `motions_channel <#channel>`, `announcement_channel <#channel>`, `prefix <prefix>`, `admin_role <@role>`, or \
+    `isolated <on|off>`. Channels and the role can be `none` to go back to the default.";
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum GuildSetting {
+    MotionsChannel(Option<i64>),
+    AnnouncementChannel(Option<i64>),
+    CommandPrefix(String),
+    AdminRole(Option<i64>),
+    Isolated(bool),
+}
+
+#[derive(Debug)]
+pub enum GuildConfigError {
+    UnknownSetting,
+    BadChannel,
+    BadRole,
+    BadPrefix,
+    BadSwitch,
+    Db(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for GuildConfigError {
+    fn from(e: diesel::result::Error) -> Self {
+        GuildConfigError::Db(e)
+    }
+}
+
+impl std::fmt::Display for GuildConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GuildConfigError::UnknownSetting => write!(f, "{}", SETTINGS_USAGE),
+            GuildConfigError::BadChannel => write!(f, "Expected a channel mention, a channel id, or `none`."),
+            GuildConfigError::BadRole => write!(f, "Expected a role mention, a role id, or `none`."),
+            GuildConfigError::BadPrefix => write!(f, "Prefixes must be 1 to 5 characters, with no whitespace."),
+            GuildConfigError::BadSwitch => write!(f, "Expected `on` or `off`."),
+            GuildConfigError::Db(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for GuildConfigError {}
+
+pub fn valid_prefix(prefix: &str) -> bool {
+    (1..=5).contains(&prefix.chars().count()) && !prefix.contains(char::is_whitespace)
+}
+
+/// Parses a mention like `<#123>` with the given start, a bare id, or `none`
+fn parse_id(value: &str, mention_start: &str) -> Option<Option<i64>> {
+    if value.eq_ignore_ascii_case("none") {
+        return Some(None);
+    }
+    let id = value.strip_prefix(mention_start).and_then(|v| v.strip_suffix('>')).unwrap_or(value);
+    id.parse().ok().filter(|id| *id > 0).map(Some)
+}
+
+pub fn parse_setting(name: &str, value: &str) -> Result<GuildSetting, GuildConfigError> {
+    match name {
+        "motions_channel" => parse_id(value, "<#").map(GuildSetting::MotionsChannel).ok_or(GuildConfigError::BadChannel),
+        "announcement_channel" => parse_id(value, "<#").map(GuildSetting::AnnouncementChannel).ok_or(GuildConfigError::BadChannel),
+        "prefix" if valid_prefix(value) => Ok(GuildSetting::CommandPrefix(value.to_string())),
+        "prefix" => Err(GuildConfigError::BadPrefix),
+        "admin_role" => parse_id(value, "<@&").map(GuildSetting::AdminRole).ok_or(GuildConfigError::BadRole),
+        "isolated" => match value {
+            "on" | "yes" | "true" => Ok(GuildSetting::Isolated(true)),
+            "off" | "no" | "false" => Ok(GuildSetting::Isolated(false)),
+            _ => Err(GuildConfigError::BadSwitch),
+        },
+        _ => Err(GuildConfigError::UnknownSetting),
+    }
+}
+
+fn apply(config: &mut GuildConfig, setting: GuildSetting) {
+    match setting {
+        GuildSetting::MotionsChannel(channel) => config.motions_channel = channel,
+        GuildSetting::AnnouncementChannel(channel) => config.announcement_channel = channel,
+        GuildSetting::CommandPrefix(prefix) => config.command_prefix = prefix,
+        GuildSetting::AdminRole(role) => config.admin_role = role,
+        GuildSetting::Isolated(isolated) => config.isolated = isolated,
+    }
+}
+
+lazy_static! {
+    /// Settings of every guild looked up so far, so the prefix and economy of each message don't need a query.
+    /// Settings only change through `set` in the bot, which updates this too.
+    static ref CONFIGS:RwLock<HashMap<i64, GuildConfig>> = RwLock::new(HashMap::new());
+}
+
+/// A guild's settings if they've been looked up before, without needing a connection
+pub fn cached(guild_id: i64) -> Option<GuildConfig> {
+    CONFIGS.read().unwrap().get(&guild_id).cloned()
+}
+
+/// A guild's settings, or the defaults if it hasn't changed any
+pub fn get(
+    conn: &diesel::PgConnection,
+    guild_id: i64,
+) -> QueryResult<GuildConfig> {
+    if let Some(config) = cached(guild_id) {
+        return Ok(config);
+    }
+    let config = load(conn, guild_id)?;
+    CONFIGS.write().unwrap().insert(guild_id, config.clone());
+    Ok(config)
+}
+
+fn load(
+    conn: &diesel::PgConnection,
+    guild_id: i64,
+) -> QueryResult<GuildConfig> {
+    Ok(gdsl::guilds
+        .select(GuildConfig::cols())
+        .filter(gdsl::guild_id.eq(guild_id))
+        .get_result(conn)
+        .optional()?
+        .unwrap_or_else(|| GuildConfig::defaults(guild_id)))
+}
+
+/// Changes one setting. Turning `isolated` off and back on again picks up the guild's economy where it left off.
+pub fn set(
+    conn: &diesel::PgConnection,
+    guild_id: i64,
+    setting: GuildSetting,
+) -> QueryResult<GuildConfig> {
+    let config = conn.transaction::<_, diesel::result::Error, _>(|| {
+        let mut config = load(conn, guild_id)?;
+        apply(&mut config, setting);
+        diesel::insert_into(gdsl::guilds)
+            .values((
+                gdsl::guild_id.eq(guild_id),
+                gdsl::motions_channel.eq(config.motions_channel),
+                gdsl::announcement_channel.eq(config.announcement_channel),
+                gdsl::command_prefix.eq(&config.command_prefix),
+                gdsl::admin_role.eq(config.admin_role),
+                gdsl::isolated.eq(config.isolated),
+            ))
+            .on_conflict(gdsl::guild_id)
+            .do_update()
+            .set((
+                gdsl::motions_channel.eq(config.motions_channel),
+                gdsl::announcement_channel.eq(config.announcement_channel),
+                gdsl::command_prefix.eq(&config.command_prefix),
+                gdsl::admin_role.eq(config.admin_role),
+                gdsl::isolated.eq(config.isolated),
+            ))
+            .execute(conn)?;
+        Ok(config)
+    })?;
+    CONFIGS.write().unwrap().insert(guild_id, config.clone());
+    Ok(config)
+}
+
+pub fn describe(config: &GuildConfig) -> String {
+    let channel = |channel:Option<i64>, default:&str| match channel {
+        Some(id) => format!("<#{}>", id),
+        None => default.to_string(),
+    };
+    format!(
+        "Motions channel: {}\nAnnouncement channel: {}\nPrefix: `{}`\nAdmin role: {}\nEconomy: {}",
+        channel(config.motions_channel, "the default"),
+        channel(config.announcement_channel, "the motions channel"),
+        config.command_prefix,
+        match config.admin_role {
+            Some(id) => format!("<@&{}>", id),
+            None => "none".to_string(),
+        },
+        if config.isolated { "isolated" } else { "shared" },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn settings() {
+        assert_eq!(parse_setting("motions_channel", "<#770726979456466954>").unwrap(), GuildSetting::MotionsChannel(Some(770726979456466954)));
+        assert_eq!(parse_setting("announcement_channel", "770726979456466954").unwrap(), GuildSetting::AnnouncementChannel(Some(770726979456466954)));
+        assert_eq!(parse_setting("motions_channel", "none").unwrap(), GuildSetting::MotionsChannel(None));
+        assert!(parse_setting("motions_channel", "<@&123>").is_err());
+        assert_eq!(parse_setting("admin_role", "<@&123>").unwrap(), GuildSetting::AdminRole(Some(123)));
+        assert!(parse_setting("admin_role", "-5").is_err());
+        assert_eq!(parse_setting("prefix", "!").unwrap(), GuildSetting::CommandPrefix("!".to_string()));
+        assert!(parse_setting("prefix", "toolong").is_err());
+        assert!(parse_setting("prefix", "a b").is_err());
+        assert_eq!(parse_setting("isolated", "on").unwrap(), GuildSetting::Isolated(true));
+        assert!(parse_setting("isolated", "maybe").is_err());
+        assert!(parse_setting("colour", "blue").is_err());
+
+        let mut config = GuildConfig::defaults(42);
+        assert_eq!(config.economy(), SHARED_ECONOMY);
+        apply(&mut config, GuildSetting::Isolated(true));
+        assert_eq!(config.economy(), 42);
+    }
+}
@@ -0,0 +1,220 @@
+use chrono::{DateTime,Utc};
+use diesel::prelude::*;
+use crate::schema::item_types::dsl as it;
+use crate::schema::item_type_aliases::dsl as ita;
+
+#[derive(Debug)]
+pub enum ItemTypeError {
+    InvalidName,
+    InvalidAlias,
+    AlreadyExists,
+    NotFound,
+    AliasTaken(String),
+    AliasNotFound,
+    /// Every item type needs at least one alias, or nobody could refer to it
+    LastAlias,
+    Db(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for ItemTypeError {
+    fn from(e: diesel::result::Error) -> Self {
+        ItemTypeError::Db(e)
+    }
+}
+
+impl std::fmt::Display for ItemTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ItemTypeError::InvalidName => write!(f, "Item type names must be 1 to 16 lowercase letters, digits, or dashes, and start with a letter."),
+            ItemTypeError::InvalidAlias => write!(f, "Aliases must not be empty, contain whitespace, or start with a digit."),
+            ItemTypeError::AlreadyExists => write!(f, "An item type with that name already exists."),
+            ItemTypeError::NotFound => write!(f, "No item type with that name."),
+            ItemTypeError::AliasTaken(name) => write!(f, "That alias already refers to {}.", name),
+            ItemTypeError::AliasNotFound => write!(f, "No such alias."),
+            ItemTypeError::LastAlias => write!(f, "Can't remove the last alias of an item type."),
+            ItemTypeError::Db(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ItemTypeError {}
+
+pub fn valid_name(name: &str) -> bool {
+    (1..=16).contains(&name.len())
+    && name.starts_with(|c:char| c.is_ascii_lowercase())
+    && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Amounts are parsed by splitting at the first non-digit (`100pc`), so an alias can't start with one.
+pub fn valid_alias(alias: &str) -> bool {
+    !alias.is_empty()
+    && !alias.starts_with(|c:char| c.is_ascii_digit())
+    && !alias.contains(char::is_whitespace)
+}
+
+fn exists(conn: &diesel::PgConnection, name: &str) -> QueryResult<bool> {
+    diesel::select(diesel::dsl::exists(it::item_types.filter(it::name.eq(name)))).get_result(conn)
+}
+
+/// Creates the item type, with its name as its first alias. If position is None it goes at the end.
+pub fn create(
+    conn: &diesel::PgConnection,
+    name: &str,
+    long_name_plural: &str,
+    long_name_ambiguous: &str,
+    position: Option<i32>,
+) -> Result<(), ItemTypeError> {
+    if !valid_name(name) {
+        return Err(ItemTypeError::InvalidName);
+    }
+    conn.transaction(|| {
+        if exists(conn, name)? {
+            return Err(ItemTypeError::AlreadyExists);
+        }
+        if let Some(existing) = ita::item_type_aliases.select(ita::name).filter(ita::alias.eq(name)).get_result(conn).optional()? {
+            return Err(ItemTypeError::AliasTaken(existing));
+        }
+        if let Some(position) = position {
+            diesel::insert_into(it::item_types).values((
+                it::name.eq(name),
+                it::long_name_plural.eq(long_name_plural),
+                it::long_name_ambiguous.eq(long_name_ambiguous),
+                it::position.eq(position),
+            )).execute(conn)?;
+        } else {
+            diesel::insert_into(it::item_types).values((
+                it::name.eq(name),
+                it::long_name_plural.eq(long_name_plural),
+                it::long_name_ambiguous.eq(long_name_ambiguous),
+            )).execute(conn)?;
+        }
+        diesel::insert_into(ita::item_type_aliases).values((
+            ita::name.eq(name),
+            ita::alias.eq(name),
+        )).execute(conn)?;
+        Ok(())
+    })
+}
+
+pub fn set_position(
+    conn: &diesel::PgConnection,
+    name: &str,
+    position: i32,
+) -> Result<(), ItemTypeError> {
+    let count = diesel::update(it::item_types.filter(it::name.eq(name)))
+        .set(it::position.eq(position))
+        .execute(conn)?;
+    if count == 0 { Err(ItemTypeError::NotFound) } else { Ok(()) }
+}
+
+/// Retiring is reversible; pass `None` to bring a type back.
+pub fn set_retired(
+    conn: &diesel::PgConnection,
+    name: &str,
+    retired_at: Option<DateTime<Utc>>,
+) -> Result<(), ItemTypeError> {
+    let count = diesel::update(it::item_types.filter(it::name.eq(name)))
+        .set(it::retired_at.eq(retired_at))
+        .execute(conn)?;
+    if count == 0 { Err(ItemTypeError::NotFound) } else { Ok(()) }
+}
+
+/// Discord gives of more than `threshold` have to be confirmed first. `None` turns that off for this type.
+pub fn set_confirm_give_above(
+    conn: &diesel::PgConnection,
+    name: &str,
+    threshold: Option<i64>,
+) -> Result<(), ItemTypeError> {
+    let count = diesel::update(it::item_types.filter(it::name.eq(name)))
+        .set(it::confirm_give_above.eq(threshold))
+        .execute(conn)?;
+    if count == 0 { Err(ItemTypeError::NotFound) } else { Ok(()) }
+}
+
+pub fn add_alias(
+    conn: &diesel::PgConnection,
+    name: &str,
+    alias: &str,
+) -> Result<(), ItemTypeError> {
+    let alias = alias.to_lowercase();
+    if !valid_alias(&alias) {
+        return Err(ItemTypeError::InvalidAlias);
+    }
+    conn.transaction(|| {
+        if !exists(conn, name)? {
+            return Err(ItemTypeError::NotFound);
+        }
+        if let Some(existing) = ita::item_type_aliases.select(ita::name).filter(ita::alias.eq(&alias)).get_result(conn).optional()? {
+            return Err(ItemTypeError::AliasTaken(existing));
+        }
+        diesel::insert_into(ita::item_type_aliases).values((
+            ita::name.eq(name),
+            ita::alias.eq(&alias),
+        )).execute(conn)?;
+        Ok(())
+    })
+}
+
+pub fn remove_alias(
+    conn: &diesel::PgConnection,
+    alias: &str,
+) -> Result<(), ItemTypeError> {
+    conn.transaction(|| {
+        let name:String = ita::item_type_aliases
+            .select(ita::name)
+            .filter(ita::alias.eq(alias))
+            .for_update()
+            .get_result(conn)
+            .optional()?
+            .ok_or(ItemTypeError::AliasNotFound)?;
+        let alias_count:i64 = ita::item_type_aliases
+            .filter(ita::name.eq(&name))
+            .count()
+            .get_result(conn)?;
+        if alias_count <= 1 {
+            return Err(ItemTypeError::LastAlias);
+        }
+        diesel::delete(ita::item_type_aliases.filter(ita::alias.eq(alias))).execute(conn)?;
+        Ok(())
+    })
+}
+
+/// Aliases of item types still in use that start with `prefix`, ignoring case, each with the plural name of its type
+pub fn aliases_starting_with(
+    conn: &diesel::PgConnection,
+    prefix: &str,
+    limit: i64,
+) -> QueryResult<Vec<(String, String)>> {
+    let escaped = prefix.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    ita::item_type_aliases
+        .inner_join(it::item_types)
+        .select((ita::alias, it::long_name_plural))
+        .filter(ita::alias.ilike(format!("{}%", escaped)))
+        .filter(it::retired_at.is_null())
+        .order((it::position, ita::alias))
+        .limit(limit)
+        .load(conn)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{valid_name, valid_alias};
+
+    #[test]
+    fn names_and_aliases() {
+        assert!(valid_name("pc"));
+        assert!(valid_name("gold-star"));
+        assert!(valid_name("t2"));
+        assert!(!valid_name(""));
+        assert!(!valid_name("2t"));
+        assert!(!valid_name("PC"));
+        assert!(!valid_name("status bucks"));
+        assert!(!valid_name("abcdefghijklmnopq"));
+
+        assert!(valid_alias("$b"));
+        assert!(valid_alias("status-bucks"));
+        assert!(!valid_alias(""));
+        assert!(!valid_alias("1up"));
+        assert!(!valid_alias("two words"));
+    }
+}
@@ -0,0 +1,233 @@
+use chrono::{DateTime,Utc};
+use diesel::prelude::*;
+use crate::schema::items::dsl as idsl;
+use crate::schema::item_transfers::dsl as itdsl;
+use crate::schema::thing_ids::dsl as tid;
+use crate::models::{UserId,Item,ItemTransfer,TransferType};
+use crate::names::name_of;
+
+#[derive(Debug)]
+pub enum ItemError {
+    NotFound,
+    /// The item exists but doesn't belong to whoever tried to move it
+    NotOwner,
+    Db(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for ItemError {
+    fn from(e: diesel::result::Error) -> Self {
+        ItemError::Db(e)
+    }
+}
+
+impl std::fmt::Display for ItemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ItemError::NotFound => write!(f, "Item not found."),
+            ItemError::NotOwner => write!(f, "You don't own that item."),
+            ItemError::Db(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ItemError {}
+
+/// Parses an item reference like `item#1234` into its id, checking the damm digit.
+pub fn parse_ref(s: &str) -> Option<i64> {
+    let digits = s.strip_prefix("item#").or_else(|| s.strip_prefix("Item#"))?;
+    let digits = crate::damm::validate_ascii(digits)?;
+    atoi::atoi(digits.as_slice())
+}
+
+/// eg "Best Dressed (badge, item#1234)"
+pub fn describe(item: &Item) -> String {
+    format!("{} ({}, item#{})", item.name, item.kind, item.damm())
+}
+
+/// Describes one line of an item's history, eg "Shelvacu gave it to Colin"
+pub fn describe_transfer(t: &ItemTransfer) -> String {
+    let auction = || crate::damm::add_to_str(t.auction_id.unwrap().to_string());
+    match (t.transfer_ty, t.from_user, t.to_user) {
+        (TransferType::AdminFabricate, _, Some(to)) => format!("Created and given to {}", name_of(to)),
+        (TransferType::Give, Some(from), Some(to)) => format!("{} gave it to {}", name_of(from), name_of(to)),
+        (TransferType::AdminGive, Some(from), Some(to)) => format!("Moved from {} to {} by an admin", name_of(from), name_of(to)),
+        (TransferType::AuctionCreate, Some(from), _) => format!("{} put it up for auction#{}", name_of(from), auction()),
+        (TransferType::AuctionPayout, _, Some(to)) => format!("{} won it in auction#{}", name_of(to), auction()),
+        (TransferType::AuctionReturn, _, Some(to)) => format!("Nobody won auction#{}, returned to {}", auction(), name_of(to)),
+        _ => unreachable!("item_transfer_ty_valid constraint violated"),
+    }
+}
+
+pub fn get(
+    conn: &diesel::PgConnection,
+    item_id: i64,
+) -> QueryResult<Option<Item>> {
+    idsl::items
+        .select(Item::cols())
+        .filter(idsl::rowid.eq(item_id))
+        .get_result(conn)
+        .optional()
+}
+
+pub fn owned_by(
+    conn: &diesel::PgConnection,
+    user: UserId,
+) -> QueryResult<Vec<Item>> {
+    idsl::items
+        .select(Item::cols())
+        .filter(idsl::owner.eq(user))
+        .order((idsl::kind, idsl::name))
+        .get_results(conn)
+}
+
+pub fn history(
+    conn: &diesel::PgConnection,
+    item_id: i64,
+) -> QueryResult<Vec<ItemTransfer>> {
+    itdsl::item_transfers
+        .select(ItemTransfer::cols())
+        .filter(itdsl::item.eq(item_id))
+        .order((itdsl::happened_at, itdsl::rowid))
+        .get_results(conn)
+}
+
+/// Creates a new item owned by `owner`. Returns its id.
+pub fn create(
+    conn: &diesel::PgConnection,
+    owner: UserId,
+    kind: &str,
+    name: &str,
+    metadata: serde_json::Value,
+    now: DateTime<Utc>,
+) -> QueryResult<i64> {
+    conn.transaction(|| {
+        let item_id:i64 = diesel::insert_into(tid::thing_ids).default_values().returning(tid::rowid).get_result(conn)?;
+        diesel::insert_into(idsl::items).values((
+            idsl::rowid.eq(item_id),
+            idsl::created_at.eq(now),
+            idsl::kind.eq(kind),
+            idsl::name.eq(name),
+            idsl::metadata.eq(metadata),
+            idsl::owner.eq(owner),
+        )).execute(conn)?;
+        diesel::insert_into(itdsl::item_transfers).values((
+            itdsl::item.eq(item_id),
+            itdsl::happened_at.eq(now),
+            itdsl::to_user.eq(owner),
+            itdsl::transfer_ty.eq(TransferType::AdminFabricate),
+        )).execute(conn)?;
+        Ok(item_id)
+    })
+}
+
+/// Moves an item from `from` to `to` and records it in the item's history. `None` on either side means the item is
+/// going into or coming out of auction escrow.
+///
+/// Only succeeds if `from` is the item's current owner, so two moves racing each other can't both succeed.
+#[allow(clippy::too_many_arguments)]
+pub fn transfer(
+    conn: &diesel::PgConnection,
+    item_id: i64,
+    from: Option<UserId>,
+    to: Option<UserId>,
+    transfer_ty: TransferType,
+    auction_id: Option<i64>,
+    message_id: Option<i64>,
+    now: DateTime<Utc>,
+) -> Result<(), ItemError> {
+    conn.transaction(|| {
+        let moved = diesel::update(
+            idsl::items
+                .filter(idsl::rowid.eq(item_id))
+                .filter(idsl::owner.is_not_distinct_from(from))
+        )
+            .set(idsl::owner.eq(to))
+            .execute(conn)?;
+        if moved == 0 {
+            let exists:bool = diesel::select(diesel::dsl::exists(idsl::items.filter(idsl::rowid.eq(item_id)))).get_result(conn)?;
+            return Err(if exists { ItemError::NotOwner } else { ItemError::NotFound });
+        }
+        diesel::insert_into(itdsl::item_transfers).values((
+            itdsl::item.eq(item_id),
+            itdsl::happened_at.eq(now),
+            itdsl::from_user.eq(from),
+            itdsl::to_user.eq(to),
+            itdsl::transfer_ty.eq(transfer_ty),
+            itdsl::auction_id.eq(auction_id),
+            itdsl::message_id.eq(message_id),
+        )).execute(conn)?;
+        Ok(())
+    })
+}
+
+pub fn give(
+    conn: &diesel::PgConnection,
+    item_id: i64,
+    from: UserId,
+    to: UserId,
+    message_id: Option<i64>,
+    now: DateTime<Utc>,
+) -> Result<(), ItemError> {
+    transfer(conn, item_id, Some(from), Some(to), TransferType::Give, None, message_id, now)
+}
+
+/// Takes the item from its owner and holds it for the auction
+pub fn escrow_for_auction(
+    conn: &diesel::PgConnection,
+    item_id: i64,
+    from: UserId,
+    auction_id: i64,
+    now: DateTime<Utc>,
+) -> Result<(), ItemError> {
+    transfer(conn, item_id, Some(from), None, TransferType::AuctionCreate, Some(auction_id), None, now)
+}
+
+/// Hands an item held by an auction to the winner (`AuctionPayout`) or back to whoever put it up (`AuctionReturn`)
+pub fn release_from_auction(
+    conn: &diesel::PgConnection,
+    item_id: i64,
+    to: UserId,
+    transfer_ty: TransferType,
+    auction_id: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<()> {
+    match transfer(conn, item_id, None, Some(to), transfer_ty, Some(auction_id), None, now) {
+        Ok(()) => Ok(()),
+        Err(ItemError::Db(e)) => Err(e),
+        Err(e) => panic!("item#{} was not held by auction {}: {}", item_id, auction_id, e),
+    }
+}
+
+/// Who put the item up for the given auction, if anyone
+pub fn escrowed_by(
+    conn: &diesel::PgConnection,
+    item_id: i64,
+    auction_id: i64,
+) -> QueryResult<Option<UserId>> {
+    let from:Option<Option<UserId>> = itdsl::item_transfers
+        .select(itdsl::from_user)
+        .filter(itdsl::item.eq(item_id))
+        .filter(itdsl::auction_id.eq(auction_id))
+        .filter(itdsl::transfer_ty.eq(TransferType::AuctionCreate))
+        .get_result(conn)
+        .optional()?;
+    Ok(from.flatten())
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_ref;
+
+    #[test]
+    fn item_refs() {
+        let id = crate::damm::add_to_str("1234");
+        assert_eq!(parse_ref(&format!("item#{}", id)), Some(1234));
+        assert_eq!(parse_ref(&format!("Item#{}", id)), Some(1234));
+        assert_eq!(parse_ref(&id), None);
+        assert_eq!(parse_ref("item#"), None);
+        assert_eq!(parse_ref("item#abc"), None);
+        // Any single-digit typo is caught by the check digit
+        let typo = format!("item#1235{}", &id[4..]);
+        assert_eq!(parse_ref(&typo), None);
+    }
+}
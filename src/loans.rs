@@ -0,0 +1,552 @@
+use chrono::{DateTime,Duration,Utc};
+use diesel::prelude::*;
+use crate::schema::loans::dsl as ldsl;
+use crate::schema::loan_periods::dsl as lpdsl;
+use crate::schema::thing_ids::dsl as tid;
+use crate::models::{UserId,Loan,LoanPeriod,LoanStatus};
+use crate::transfers::{CurrencyId,TransactionBuilder,TransferHandler,TransferError};
+use crate::notifications::notify;
+use crate::names::name_of;
+
+pub const TERMS_USAGE:&str = "Usage: $lend <user> <amount> <type> at <rate>% every <schedule> for <n> payments";
+
+/// A loan can't run longer than this many installments
+pub const MAX_INSTALLMENTS:i32 = 520;
+
+/// How many periods past its last installment a loan keeps trying to collect what's still owed before it's marked as
+/// defaulted
+pub const DEFAULT_AFTER_PERIODS:i32 = 4;
+
+#[derive(Debug)]
+pub enum LoanError {
+    NotFound,
+    /// Someone other than the borrower tried to accept
+    NotBorrower,
+    /// Someone who isn't either side of the loan tried to cancel or decline
+    NotAParty,
+    NotOffered(LoanStatus),
+    /// The lender no longer has the principal to hand over
+    LenderCantCover,
+    Overflow,
+    Db(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for LoanError {
+    fn from(e: diesel::result::Error) -> Self {
+        LoanError::Db(e)
+    }
+}
+
+impl std::fmt::Display for LoanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoanError::NotFound => write!(f, "Loan offer not found."),
+            LoanError::NotBorrower => write!(f, "Only the user this loan was offered to can accept it."),
+            LoanError::NotAParty => write!(f, "You are not part of this loan."),
+            LoanError::NotOffered(LoanStatus::Active) | LoanError::NotOffered(LoanStatus::Repaid) | LoanError::NotOffered(LoanStatus::Defaulted) => write!(f, "This loan has already been accepted."),
+            LoanError::NotOffered(LoanStatus::Cancelled) => write!(f, "This loan offer was cancelled."),
+            LoanError::NotOffered(LoanStatus::Expired) => write!(f, "This loan offer has expired."),
+            LoanError::NotOffered(LoanStatus::Offered) => unreachable!(),
+            LoanError::LenderCantCover => write!(f, "Loan failed: The lender does not have enough to cover the principal."),
+            LoanError::Overflow => write!(f, "Loan failed: Overflow."),
+            LoanError::Db(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LoanError {}
+
+/// Parses a rate like "2%" or "0.25%" into basis points (hundredths of a percent)
+pub fn parse_rate(s: &str) -> Option<i32> {
+    let s = s.strip_suffix('%')?;
+    let (whole, frac) = match s.split_once('.') {
+        Some((whole, frac)) => (whole, frac),
+        None => (s, ""),
+    };
+    if whole.is_empty() || frac.len() > 2 || !whole.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let whole:i32 = whole.parse().ok()?;
+    let frac:i32 = if frac.is_empty() { 0 } else { format!("{:0<2}", frac).parse().ok()? };
+    let bps = whole.checked_mul(100)?.checked_add(frac)?;
+    if bps > 10000 {
+        return None;
+    }
+    Some(bps)
+}
+
+/// Formats basis points back into a percentage, eg 250 -> "2.5%"
+pub fn format_rate(bps: i32) -> String {
+    let whole = bps / 100;
+    let frac = bps % 100;
+    if frac == 0 {
+        format!("{}%", whole)
+    } else if frac % 10 == 0 {
+        format!("{}.{}%", whole, frac / 10)
+    } else {
+        format!("{}.{:02}%", whole, frac)
+    }
+}
+
+/// Parses the words after the amount and type, eg `at 2% every week for 4 payments`, into
+/// `(interest_bps, every_days, installments)`.
+pub fn parse_terms(
+    words: &[String],
+    now: DateTime<Utc>,
+) -> Result<(i32, i32, i32), &'static str> {
+    let lower:Vec<String> = words.iter().map(|w| w.to_ascii_lowercase()).collect();
+    let every_idx = lower.iter().position(|w| w == "every").ok_or(TERMS_USAGE)?;
+    let for_idx = lower.iter().position(|w| w == "for").ok_or(TERMS_USAGE)?;
+    if every_idx > for_idx {
+        return Err(TERMS_USAGE);
+    }
+    let interest_bps = match &lower[..every_idx] {
+        [at, rate] if at == "at" => parse_rate(rate).ok_or("The rate must be a percentage between 0% and 100%, eg `2%` or `0.5%`.")?,
+        _ => return Err(TERMS_USAGE),
+    };
+    // Payments are counted from when the loan is accepted, so a weekday schedule only contributes its period.
+    let (every_days, _) = crate::standing_orders::parse_schedule(&words[every_idx+1..for_idx], now)?;
+    let installments:i32 = match &lower[for_idx+1..] {
+        [n] => n.parse().map_err(|_| TERMS_USAGE)?,
+        [n, unit] if ["payment", "payments", "installment", "installments"].contains(&unit.as_str()) => n.parse().map_err(|_| TERMS_USAGE)?,
+        _ => return Err(TERMS_USAGE),
+    };
+    if !(1..=MAX_INSTALLMENTS).contains(&installments) {
+        return Err("A loan must be repaid in between 1 and 520 payments.");
+    }
+    Ok((interest_bps, every_days, installments))
+}
+
+/// Works out one period of a loan: the interest accrued on `outstanding`, and how much of the new total is due this
+/// period. Whatever remains is spread evenly over the remaining installments; once those have run out the whole
+/// amount is due every period.
+///
+/// Interest is rounded down, and saturates rather than overflowing.
+pub fn period_amounts(
+    outstanding: i64,
+    interest_bps: i32,
+    remaining_installments: i32,
+) -> (i64, i64) {
+    let interest = (outstanding as i128) * (interest_bps as i128) / 10000;
+    let interest = interest.min((i64::MAX - outstanding) as i128) as i64;
+    let total = outstanding + interest;
+    let due = if remaining_installments <= 1 {
+        total
+    } else {
+        let remaining = remaining_installments as i64;
+        total / remaining + if total % remaining == 0 { 0 } else { 1 }
+    };
+    (interest, due)
+}
+
+/// Whether a loan that still owes something once its `periods_run`th period has run should stop being collected
+pub fn defaults_after(periods_run: i32, installments: i32) -> bool {
+    periods_run >= installments.saturating_add(DEFAULT_AFTER_PERIODS)
+}
+
+/// A one-line human description, eg "Shelvacu lends 1000 pc to Colin at 2% every 7 days, repaid in 4 payments"
+pub fn describe(loan: &Loan) -> String {
+    format!(
+        "{} lends {} {} to {} at {} every {} day{}, repaid in {} payment{}",
+        name_of(loan.lender),
+        loan.principal,
+        loan.ty,
+        name_of(loan.borrower),
+        format_rate(loan.interest_bps),
+        loan.every_days,
+        if loan.every_days == 1 { "" } else { "s" },
+        loan.installments,
+        if loan.installments == 1 { "" } else { "s" },
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn create_offer(
+    conn: &diesel::PgConnection,
+    lender: UserId,
+    borrower: UserId,
+    principal: i64,
+    ty: CurrencyId,
+    interest_bps: i32,
+    every_days: i32,
+    installments: i32,
+    now: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+) -> QueryResult<i64> {
+    conn.transaction(|| {
+        let loan_id:i64 = diesel::insert_into(tid::thing_ids).default_values().returning(tid::rowid).get_result(conn)?;
+        diesel::insert_into(ldsl::loans).values((
+            ldsl::rowid.eq(loan_id),
+            ldsl::created_at.eq(now),
+            ldsl::lender.eq(lender),
+            ldsl::borrower.eq(borrower),
+            ldsl::ty.eq(ty),
+            ldsl::principal.eq(principal),
+            ldsl::interest_bps.eq(interest_bps),
+            ldsl::every_days.eq(every_days),
+            ldsl::installments.eq(installments),
+            ldsl::expires_at.eq(expires_at),
+        )).execute(conn)?;
+        Ok(loan_id)
+    })
+}
+
+pub fn set_message_id(
+    conn: &diesel::PgConnection,
+    loan_id: i64,
+    message_id: i64,
+) -> QueryResult<()> {
+    diesel::update(ldsl::loans.filter(ldsl::rowid.eq(loan_id)))
+        .set(ldsl::message_id.eq(message_id))
+        .execute(conn)
+        .map(|_| ())
+}
+
+pub fn find_by_message(
+    conn: &diesel::PgConnection,
+    message_id: i64,
+) -> QueryResult<Option<i64>> {
+    ldsl::loans
+        .select(ldsl::rowid)
+        .filter(ldsl::message_id.eq(message_id))
+        .get_result(conn)
+        .optional()
+}
+
+pub fn get(
+    conn: &diesel::PgConnection,
+    loan_id: i64,
+) -> QueryResult<Option<Loan>> {
+    ldsl::loans
+        .select(Loan::cols())
+        .filter(ldsl::rowid.eq(loan_id))
+        .get_result(conn)
+        .optional()
+}
+
+pub fn periods(
+    conn: &diesel::PgConnection,
+    loan_id: i64,
+) -> QueryResult<Vec<LoanPeriod>> {
+    lpdsl::loan_periods
+        .select(LoanPeriod::cols())
+        .filter(lpdsl::loan.eq(loan_id))
+        .order(lpdsl::period_start)
+        .get_results(conn)
+}
+
+/// Loans that are still being repaid or still waiting to be accepted, where `user` is either the lender or the
+/// borrower
+pub fn outstanding_for(
+    conn: &diesel::PgConnection,
+    user: UserId,
+    now: DateTime<Utc>,
+) -> QueryResult<Vec<Loan>> {
+    ldsl::loans
+        .select(Loan::cols())
+        .filter(ldsl::lender.eq(user).or(ldsl::borrower.eq(user)))
+        .filter(ldsl::repaid_at.is_null())
+        .filter(ldsl::defaulted_at.is_null())
+        .filter(ldsl::cancelled_at.is_null())
+        .filter(ldsl::accepted_at.is_not_null().or(ldsl::expires_at.gt(now)))
+        .order(ldsl::rowid)
+        .get_results(conn)
+}
+
+fn lock_offered(
+    conn: &diesel::PgConnection,
+    loan_id: i64,
+    now: DateTime<Utc>,
+) -> Result<Loan, LoanError> {
+    let loan:Loan = ldsl::loans
+        .select(Loan::cols())
+        .filter(ldsl::rowid.eq(loan_id))
+        .for_update()
+        .get_result(conn)
+        .optional()?
+        .ok_or(LoanError::NotFound)?;
+    match loan.status(now) {
+        LoanStatus::Offered => Ok(loan),
+        status => Err(LoanError::NotOffered(status)),
+    }
+}
+
+/// Hands the principal from the lender to the borrower and starts the repayment schedule.
+pub fn accept(
+    conn: &diesel::PgConnection,
+    loan_id: i64,
+    accepter: UserId,
+    now: DateTime<Utc>,
+) -> Result<Loan, LoanError> {
+    conn.transaction(|| {
+        let mut loan = lock_offered(conn, loan_id, now)?;
+        if loan.borrower != accepter {
+            return Err(LoanError::NotBorrower);
+        }
+
+        let t = TransactionBuilder::new(loan.principal, loan.ty.clone(), now)
+            .loan_principal(loan.lender, loan.borrower, loan.rowid);
+        match TransferHandler::handle_single(conn, t) {
+            Err(TransferError::NotEnough) => return Err(LoanError::LenderCantCover),
+            Err(TransferError::Overflow) => return Err(LoanError::Overflow),
+            Ok(v) => v?,
+        }
+
+        let next_payment_at = now + Duration::days(loan.every_days as i64);
+        diesel::update(ldsl::loans.filter(ldsl::rowid.eq(loan.rowid)))
+            .set((
+                ldsl::accepted_at.eq(now),
+                ldsl::next_payment_at.eq(next_payment_at),
+                ldsl::outstanding.eq(loan.principal),
+            ))
+            .execute(conn)?;
+        loan.accepted_at = Some(now);
+        loan.next_payment_at = Some(next_payment_at);
+        loan.outstanding = loan.principal;
+
+        Ok(loan)
+    })
+}
+
+/// Withdraws (if done by the lender) or declines (if done by the borrower) a loan that hasn't been accepted yet.
+pub fn cancel(
+    conn: &diesel::PgConnection,
+    loan_id: i64,
+    user: UserId,
+    now: DateTime<Utc>,
+) -> Result<Loan, LoanError> {
+    conn.transaction(|| {
+        let mut loan = lock_offered(conn, loan_id, now)?;
+        if user != loan.lender && user != loan.borrower {
+            return Err(LoanError::NotAParty);
+        }
+
+        diesel::update(ldsl::loans.filter(ldsl::rowid.eq(loan.rowid)))
+            .set(ldsl::cancelled_at.eq(now))
+            .execute(conn)?;
+        loan.cancelled_at = Some(now);
+
+        Ok(loan)
+    })
+}
+
+/// Runs every loan period that has come due: accrues interest, then collects as much of the installment from the
+/// borrower as they have. A loan still owed `DEFAULT_AFTER_PERIODS` periods after its last installment is marked as
+/// defaulted and not collected any more. Like standing orders, each period is recorded in loan_periods in the same transaction as its payment, and
+/// missed periods are caught up one at a time.
+pub fn process_due(
+    conn: &diesel::PgConnection,
+    now: DateTime<Utc>,
+) -> QueryResult<()> {
+    let due:Vec<i64> = ldsl::loans
+        .select(ldsl::rowid)
+        .filter(ldsl::repaid_at.is_null())
+        .filter(ldsl::defaulted_at.is_null())
+        .filter(ldsl::next_payment_at.le(now))
+        .order(ldsl::next_payment_at)
+        .get_results(conn)?;
+    for loan_id in due {
+        while run_next_period(conn, loan_id, now)? {}
+    }
+    Ok(())
+}
+
+/// Returns whether a period was run
+fn run_next_period(
+    conn: &diesel::PgConnection,
+    loan_id: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<bool> {
+    conn.transaction(|| {
+        let loan:Loan = ldsl::loans
+            .select(Loan::cols())
+            .filter(ldsl::rowid.eq(loan_id))
+            .for_update()
+            .get_result(conn)?;
+        let period_start = match loan.next_payment_at {
+            Some(t) if t <= now && loan.repaid_at.is_none() && loan.defaulted_at.is_none() => t,
+            _ => return Ok(false),
+        };
+        let next_payment_at = period_start + Duration::days(loan.every_days as i64);
+
+        let already_ran:bool = diesel::select(diesel::dsl::exists(
+            lpdsl::loan_periods
+                .filter(lpdsl::loan.eq(loan.rowid))
+                .filter(lpdsl::period_start.eq(period_start))
+        )).get_result(conn)?;
+        if already_ran {
+            diesel::update(ldsl::loans.filter(ldsl::rowid.eq(loan.rowid)))
+                .set(ldsl::next_payment_at.eq(next_payment_at))
+                .execute(conn)?;
+            return Ok(true);
+        }
+
+        let remaining = loan.installments - loan.periods_run;
+        let (interest, due) = period_amounts(loan.outstanding, loan.interest_bps, remaining);
+        let mut outstanding = loan.outstanding + interest;
+
+        // If the borrower can't cover all of it, whatever they do have is taken
+        let mut handle = TransferHandler::new(
+            conn,
+            vec![loan.borrower, loan.lender],
+            vec![loan.ty.clone()],
+        )?;
+        let collectable = due.min(handle.balance(loan.borrower, loan.ty.clone()).max(0));
+        let mut failure = if collectable < due { Some("insufficient balance") } else { None };
+        let mut paid = 0;
+        if collectable > 0 {
+            let t = TransactionBuilder::new(
+                collectable,
+                loan.ty.clone(),
+                now,
+            ).loan_repayment(loan.borrower, loan.lender, loan.rowid);
+            match handle.transfer(t) {
+                Ok(v) => { v?; paid = collectable; },
+                Err(TransferError::NotEnough) => unreachable!("We have a lock on transactions for this user, and already checked they have enough fungibles."),
+                Err(TransferError::Overflow) => failure = Some("overflow"),
+            }
+        }
+        outstanding -= paid;
+        let repaid_at = if outstanding == 0 { Some(now) } else { None };
+        let periods_run = loan.periods_run + 1;
+        let defaulted_at = if repaid_at.is_none() && defaults_after(periods_run, loan.installments) { Some(now) } else { None };
+
+        if let Some(reason) = failure {
+            let collected = if paid > 0 { format!("only collected {} of", paid) } else { "could not collect".to_string() };
+            notify(conn, loan.borrower, format!(
+                "Loan #{} {} your payment of {} {} to {}: {}. You now owe {} {}.",
+                loan.damm(),
+                collected,
+                due,
+                loan.ty,
+                name_of(loan.lender),
+                reason,
+                outstanding,
+                loan.ty,
+            ), now)?;
+            notify(conn, loan.lender, format!(
+                "Loan #{} {} {} {} from {} this period: {}. They now owe you {} {}.",
+                loan.damm(),
+                collected,
+                due,
+                loan.ty,
+                name_of(loan.borrower),
+                reason,
+                outstanding,
+                loan.ty,
+            ), now)?;
+        }
+        if defaulted_at.is_some() {
+            notify(conn, loan.borrower, format!(
+                "Loan #{} from {} is still owed {} periods after its last payment was due, so it has defaulted and \
+                won't be collected any more.",
+                loan.damm(),
+                name_of(loan.lender),
+                DEFAULT_AFTER_PERIODS,
+            ), now)?;
+            notify(conn, loan.lender, format!(
+                "Loan #{} to {} is still owed {} periods after its last payment was due, so it has defaulted and \
+                won't be collected any more. {} {} went unpaid.",
+                loan.damm(),
+                name_of(loan.borrower),
+                DEFAULT_AFTER_PERIODS,
+                outstanding,
+                loan.ty,
+            ), now)?;
+        } else if repaid_at.is_some() {
+            notify(conn, loan.borrower, format!(
+                "Loan #{} from {} is fully repaid.",
+                loan.damm(),
+                name_of(loan.lender),
+            ), now)?;
+            notify(conn, loan.lender, format!(
+                "Loan #{} to {} is fully repaid.",
+                loan.damm(),
+                name_of(loan.borrower),
+            ), now)?;
+        }
+
+        diesel::insert_into(lpdsl::loan_periods).values((
+            lpdsl::loan.eq(loan.rowid),
+            lpdsl::period_start.eq(period_start),
+            lpdsl::ran_at.eq(now),
+            lpdsl::interest.eq(interest),
+            lpdsl::due.eq(due),
+            lpdsl::paid.eq(paid),
+        )).execute(conn)?;
+
+        diesel::update(ldsl::loans.filter(ldsl::rowid.eq(loan.rowid)))
+            .set((
+                ldsl::next_payment_at.eq(next_payment_at),
+                ldsl::periods_run.eq(periods_run),
+                ldsl::outstanding.eq(outstanding),
+                ldsl::repaid_at.eq(repaid_at),
+                ldsl::defaulted_at.eq(defaulted_at),
+            ))
+            .execute(conn)?;
+
+        Ok(true)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_rate,format_rate,parse_terms,period_amounts,defaults_after};
+    use crate::test_util::words;
+    use chrono::{TimeZone,Utc};
+
+    #[test]
+    fn rates() {
+        assert_eq!(parse_rate("2%"), Some(200));
+        assert_eq!(parse_rate("0%"), Some(0));
+        assert_eq!(parse_rate("0.5%"), Some(50));
+        assert_eq!(parse_rate("2.25%"), Some(225));
+        assert_eq!(parse_rate("100%"), Some(10000));
+        assert_eq!(parse_rate("100.01%"), None);
+        assert_eq!(parse_rate("2"), None);
+        assert_eq!(parse_rate(".5%"), None);
+        assert_eq!(parse_rate("1.234%"), None);
+        assert_eq!(parse_rate("-1%"), None);
+        assert_eq!(format_rate(200), "2%");
+        assert_eq!(format_rate(50), "0.5%");
+        assert_eq!(format_rate(225), "2.25%");
+        assert_eq!(format_rate(5), "0.05%");
+    }
+
+    #[test]
+    fn terms() {
+        let now = Utc.with_ymd_and_hms(2023, 3, 1, 22, 0, 0).unwrap();
+        assert_eq!(parse_terms(&words("at 2% every week for 4 payments"), now), Ok((200, 7, 4)));
+        assert_eq!(parse_terms(&words("AT 0.5% EVERY 3 days FOR 10"), now), Ok((50, 3, 10)));
+        assert_eq!(parse_terms(&words("at 0% every monday for 1 payment"), now), Ok((0, 7, 1)));
+        assert!(parse_terms(&words("at 2% for 4 payments"), now).is_err());
+        assert!(parse_terms(&words("at 2% every week for 0 payments"), now).is_err());
+        assert!(parse_terms(&words("at 2% every week for 521 payments"), now).is_err());
+        assert!(parse_terms(&words("for 4 payments every week at 2%"), now).is_err());
+        assert!(parse_terms(&words("at 2 every week for 4"), now).is_err());
+    }
+
+    #[test]
+    fn periods() {
+        // 1000 at 10%: 100 interest, 1100 spread over 4 payments
+        assert_eq!(period_amounts(1000, 1000, 4), (100, 275));
+        // Rounds the installment up so the last one isn't left holding the remainder
+        assert_eq!(period_amounts(1000, 0, 3), (0, 334));
+        // Interest rounds down
+        assert_eq!(period_amounts(99, 100, 1), (0, 99));
+        // Last installment, and overdue loans, are due in full
+        assert_eq!(period_amounts(500, 200, 1), (10, 510));
+        assert_eq!(period_amounts(500, 200, -3), (10, 510));
+        assert_eq!(period_amounts(i64::MAX - 5, 10000, 1), (5, i64::MAX));
+    }
+
+    #[test]
+    fn defaults() {
+        // Four payments, then four more periods of trying to collect the rest
+        assert!(!defaults_after(4, 4));
+        assert!(!defaults_after(7, 4));
+        assert!(defaults_after(8, 4));
+        assert!(defaults_after(9, 4));
+        assert!(!defaults_after(1, i32::MAX));
+    }
+}
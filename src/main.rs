@@ -4,7 +4,6 @@
 #[macro_use] extern crate rocket;
 #[macro_use] extern crate diesel;
 #[macro_use] extern crate lazy_static;
-#[macro_use] extern crate maplit;
 #[macro_use] extern crate serde;
 
 
@@ -20,8 +19,26 @@ mod worker;
 mod tasks;
 mod fix_transactions;
 mod transfers;
+mod trades;
+mod standing_orders;
+mod notifications;
+mod motion_actions;
+mod admins;
+mod item_types;
+mod items;
+mod loans;
+mod economic_policy;
+mod auctions;
+mod auto_auctions;
+mod market;
+mod give_confirmations;
+mod guilds;
+mod force_gives;
+mod stats;
 mod web;
 mod names;
+#[cfg(test)]
+mod test_util;
 
 use std::{env,panic,process};
 
@@ -40,8 +57,9 @@ lazy_static! {
     pub static ref GENERATE_EVERY:chrono::Duration = chrono::Duration::seconds(30);
     pub static ref MOTION_EXPIRATION:chrono::Duration = chrono::Duration::minutes(20);
     pub static ref AUCTION_EXPIRATION:chrono::Duration = chrono::Duration::minutes(20);
-    pub static ref AUTO_AUCTION_AT:chrono::NaiveTime = chrono::NaiveTime::from_hms_opt(23, 34, 45).unwrap();
-    pub static ref AUTO_AUCTION_EVERY:chrono::Duration = chrono::Duration::days(1);
+    pub static ref TRADE_EXPIRATION:chrono::Duration = chrono::Duration::minutes(20);
+    pub static ref GIVE_CONFIRMATION_EXPIRATION:chrono::Duration = chrono::Duration::minutes(5);
+    pub static ref FORCE_GIVE_UNDO_WINDOW:chrono::Duration = chrono::Duration::minutes(20);
 }
 
 #[cfg(not(feature = "debug"))]
@@ -49,20 +67,23 @@ lazy_static! {
     pub static ref GENERATE_EVERY:chrono::Duration = chrono::Duration::hours(24);
     pub static ref MOTION_EXPIRATION:chrono::Duration = chrono::Duration::hours(96);
     pub static ref AUCTION_EXPIRATION:chrono::Duration = chrono::Duration::hours(96);
-    pub static ref AUTO_AUCTION_AT:chrono::NaiveTime = chrono::NaiveTime::from_hms_opt(7,0,0).unwrap();
-    pub static ref AUTO_AUCTION_EVERY:chrono::Duration = chrono::Duration::days(7);
+    pub static ref TRADE_EXPIRATION:chrono::Duration = chrono::Duration::hours(48);
+    pub static ref GIVE_CONFIRMATION_EXPIRATION:chrono::Duration = chrono::Duration::minutes(15);
+    pub static ref FORCE_GIVE_UNDO_WINDOW:chrono::Duration = chrono::Duration::hours(72);
 }
 
 lazy_static! {
-    pub static ref GIVE_DESTINATION_RE:regex::Regex = regex::Regex::new(r"^(?:([^\-\s]+)\s*-\s*)?(\d+)$").unwrap();
+    // Like `Some Name - 1234`, as listed in the give form's suggestions. Names can have spaces and dashes of their own.
+    pub static ref GIVE_DESTINATION_RE:regex::Regex = regex::Regex::new(r"^(?:(.*\S)\s*-\s*)?(\d+)$").unwrap();
 }
 
 fn main() {
     lazy_static::initialize(&GENERATE_EVERY);
     lazy_static::initialize(&MOTION_EXPIRATION);
     lazy_static::initialize(&AUCTION_EXPIRATION);
-    lazy_static::initialize(&AUTO_AUCTION_AT);
-    lazy_static::initialize(&AUTO_AUCTION_EVERY);
+    lazy_static::initialize(&TRADE_EXPIRATION);
+    lazy_static::initialize(&GIVE_CONFIRMATION_EXPIRATION);
+    lazy_static::initialize(&FORCE_GIVE_UNDO_WINDOW);
     lazy_static::initialize(&GIVE_DESTINATION_RE);
     dotenv::dotenv().unwrap();
 
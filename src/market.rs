@@ -0,0 +1,337 @@
+use chrono::{DateTime,Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{Bool,Int8,Text};
+use crate::schema::market_orders::dsl as modsl;
+use crate::schema::market_fills::dsl as mfdsl;
+use crate::schema::thing_ids::dsl as tid;
+use crate::models::{UserId,MarketOrder,MarketFill};
+use crate::transfers::{CurrencyId,TransactionBuilder,TransferHandler,TransferError};
+
+/// How many price levels `depth` returns on each side
+pub const DEPTH_LEVELS:i64 = 10;
+
+#[derive(Debug)]
+pub enum MarketError {
+    /// Tried to trade an item type for itself
+    SameType,
+    NotPositive,
+    /// Not enough to cover what the order could spend
+    NotEnough,
+    Overflow,
+    NotFound,
+    NotOwner,
+    NotOpen,
+    Db(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for MarketError {
+    fn from(e: diesel::result::Error) -> Self {
+        MarketError::Db(e)
+    }
+}
+
+impl From<TransferError> for MarketError {
+    fn from(e: TransferError) -> Self {
+        match e {
+            TransferError::NotEnough => MarketError::NotEnough,
+            TransferError::Overflow => MarketError::Overflow,
+        }
+    }
+}
+
+impl std::fmt::Display for MarketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MarketError::SameType => write!(f, "You can't trade something for itself."),
+            MarketError::NotPositive => write!(f, "Quantity and price must both be more than nothing."),
+            MarketError::NotEnough => write!(f, "You don't have enough to cover that order."),
+            MarketError::Overflow => write!(f, "Order failed: Overflow."),
+            MarketError::NotFound => write!(f, "Order not found."),
+            MarketError::NotOwner => write!(f, "That isn't your order."),
+            MarketError::NotOpen => write!(f, "That order has already been filled or cancelled."),
+            MarketError::Db(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MarketError {}
+
+/// One fill planned against a resting order: (order id, quantity, price)
+pub type PlannedFill = (i64, i64, i64);
+
+/// Walks the resting orders on the other side of the book, which must be in priority order (best price first, then
+/// oldest first), and fills as much of `quantity` as the limit price allows. Fills happen at the resting order's
+/// price.
+pub fn plan_fills(
+    is_buy: bool,
+    limit: i64,
+    quantity: i64,
+    book: &[(i64, i64, i64)],
+) -> Vec<PlannedFill> {
+    let mut left = quantity;
+    let mut fills = Vec::new();
+    for &(order_id, price, remaining) in book {
+        if left == 0 {
+            break;
+        }
+        let crosses = if is_buy { price <= limit } else { price >= limit };
+        if !crosses {
+            break;
+        }
+        let q = left.min(remaining);
+        fills.push((order_id, q, price));
+        left -= q;
+    }
+    fills
+}
+
+/// eg "buy 3 gen at 500 pc each"
+pub fn describe(order: &MarketOrder) -> String {
+    format!(
+        "{} {} {} at {} {} each",
+        if order.is_buy { "buy" } else { "sell" },
+        order.quantity,
+        order.base_ty,
+        order.price,
+        order.quote_ty,
+    )
+}
+
+fn checked_cost(quantity: i64, price: i64) -> Result<i64, MarketError> {
+    quantity.checked_mul(price).ok_or(MarketError::Overflow)
+}
+
+/// Places a limit order, holding what it could spend in escrow, and immediately fills as much of it as possible
+/// against the other side of the book. Whatever isn't filled rests on the book until it's matched or cancelled.
+#[allow(clippy::too_many_arguments)]
+pub fn place(
+    conn: &diesel::PgConnection,
+    owner: UserId,
+    is_buy: bool,
+    quantity: i64,
+    base_ty: CurrencyId,
+    price: i64,
+    quote_ty: CurrencyId,
+    now: DateTime<Utc>,
+) -> Result<(MarketOrder, Vec<MarketFill>), MarketError> {
+    if base_ty == quote_ty {
+        return Err(MarketError::SameType);
+    }
+    if quantity <= 0 || price <= 0 {
+        return Err(MarketError::NotPositive);
+    }
+    conn.transaction(|| {
+        // Matching reads then updates many orders; only one placement or cancellation runs at a time.
+        diesel::sql_query("LOCK TABLE market_orders IN EXCLUSIVE MODE;").execute(conn)?;
+
+        let order_id:i64 = diesel::insert_into(tid::thing_ids).default_values().returning(tid::rowid).get_result(conn)?;
+        let mut order:MarketOrder = diesel::insert_into(modsl::market_orders).values((
+            modsl::rowid.eq(order_id),
+            modsl::created_at.eq(now),
+            modsl::owner.eq(owner),
+            modsl::is_buy.eq(is_buy),
+            modsl::base_ty.eq(&base_ty),
+            modsl::quote_ty.eq(&quote_ty),
+            modsl::price.eq(price),
+            modsl::quantity.eq(quantity),
+            modsl::remaining.eq(quantity),
+        )).returning(MarketOrder::cols()).get_result(conn)?;
+
+        let opposite = modsl::market_orders
+            .select((modsl::rowid, modsl::price, modsl::remaining, modsl::owner))
+            .filter(modsl::base_ty.eq(&base_ty))
+            .filter(modsl::quote_ty.eq(&quote_ty))
+            .filter(modsl::is_buy.eq(!is_buy))
+            .filter(modsl::remaining.gt(0))
+            .filter(modsl::cancelled_at.is_null())
+            // No trading with yourself
+            .filter(modsl::owner.ne(owner))
+            .into_boxed();
+        let book:Vec<(i64, i64, i64, UserId)> = if is_buy {
+            opposite.filter(modsl::price.le(price)).order((modsl::price.asc(), modsl::rowid.asc())).get_results(conn)?
+        } else {
+            opposite.filter(modsl::price.ge(price)).order((modsl::price.desc(), modsl::rowid.asc())).get_results(conn)?
+        };
+        let planned = plan_fills(
+            is_buy,
+            price,
+            quantity,
+            &book.iter().map(|&(id, p, r, _)| (id, p, r)).collect::<Vec<_>>(),
+        );
+        let maker_of = |id: i64| book.iter().find(|o| o.0 == id).unwrap().3;
+
+        let mut users = vec![owner];
+        users.extend(planned.iter().map(|&(id, _, _)| maker_of(id)));
+        let mut handle = TransferHandler::new(conn, users, vec![base_ty.clone(), quote_ty.clone()])?;
+
+        let (reserve_amt, reserve_ty) = if is_buy {
+            (checked_cost(quantity, price)?, quote_ty.clone())
+        } else {
+            (quantity, base_ty.clone())
+        };
+        handle.transfer(TransactionBuilder::new(reserve_amt, reserve_ty, now).market_reserve(owner, order_id))??;
+
+        let mut fills = Vec::new();
+        for (maker_id, q, p) in planned {
+            let maker = maker_of(maker_id);
+            let cost = checked_cost(q, p)?;
+            let (buyer, buy_order, seller, sell_order) = if is_buy {
+                (owner, order_id, maker, maker_id)
+            } else {
+                (maker, maker_id, owner, order_id)
+            };
+            // The seller is paid from the buyer's escrow, and the buyer receives from the seller's
+            handle.transfer(TransactionBuilder::new(cost, quote_ty.clone(), now).market_fill(seller, buy_order))??;
+            handle.transfer(TransactionBuilder::new(q, base_ty.clone(), now).market_fill(buyer, sell_order))??;
+            if is_buy && p < price {
+                // Reserved at our limit, filled at a better price
+                let refund = checked_cost(q, price - p)?;
+                handle.transfer(TransactionBuilder::new(refund, quote_ty.clone(), now).market_refund(owner, order_id))??;
+            }
+
+            diesel::update(modsl::market_orders.filter(modsl::rowid.eq(maker_id)))
+                .set(modsl::remaining.eq(modsl::remaining - q))
+                .execute(conn)?;
+            let fill:MarketFill = diesel::insert_into(mfdsl::market_fills).values((
+                mfdsl::happened_at.eq(now),
+                mfdsl::buy_order.eq(buy_order),
+                mfdsl::sell_order.eq(sell_order),
+                mfdsl::base_ty.eq(&base_ty),
+                mfdsl::quote_ty.eq(&quote_ty),
+                mfdsl::quantity.eq(q),
+                mfdsl::price.eq(p),
+            )).returning(MarketFill::cols()).get_result(conn)?;
+            order.remaining -= q;
+            fills.push(fill);
+        }
+
+        diesel::update(modsl::market_orders.filter(modsl::rowid.eq(order_id)))
+            .set(modsl::remaining.eq(order.remaining))
+            .execute(conn)?;
+
+        Ok((order, fills))
+    })
+}
+
+/// Cancels the unfilled part of an order and returns its escrow
+pub fn cancel(
+    conn: &diesel::PgConnection,
+    order_id: i64,
+    user: UserId,
+    now: DateTime<Utc>,
+) -> Result<MarketOrder, MarketError> {
+    conn.transaction(|| {
+        diesel::sql_query("LOCK TABLE market_orders IN EXCLUSIVE MODE;").execute(conn)?;
+        let mut order:MarketOrder = modsl::market_orders
+            .select(MarketOrder::cols())
+            .filter(modsl::rowid.eq(order_id))
+            .get_result(conn)
+            .optional()?
+            .ok_or(MarketError::NotFound)?;
+        if order.owner != user {
+            return Err(MarketError::NotOwner);
+        }
+        if !order.is_open() {
+            return Err(MarketError::NotOpen);
+        }
+
+        let (refund, ty) = if order.is_buy {
+            (checked_cost(order.remaining, order.price)?, order.quote_ty.clone())
+        } else {
+            (order.remaining, order.base_ty.clone())
+        };
+        TransferHandler::handle_single(
+            conn,
+            TransactionBuilder::new(refund, ty, now).market_refund(order.owner, order.rowid),
+        )??;
+
+        diesel::update(modsl::market_orders.filter(modsl::rowid.eq(order.rowid)))
+            .set(modsl::cancelled_at.eq(now))
+            .execute(conn)?;
+        order.cancelled_at = Some(now);
+
+        Ok(order)
+    })
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,QueryableByName)]
+pub struct DepthLevel {
+    #[sql_type = "Int8"]
+    pub price: i64,
+    #[sql_type = "Int8"]
+    pub quantity: i64,
+}
+
+/// Open orders on one side of a pair, totalled by price, best price first
+pub fn depth(
+    conn: &diesel::PgConnection,
+    base_ty: &CurrencyId,
+    quote_ty: &CurrencyId,
+    is_buy: bool,
+) -> QueryResult<Vec<DepthLevel>> {
+    diesel::sql_query(format!(r#"
+        select price, sum(remaining)::bigint as quantity
+        from market_orders
+        where base_ty = $1 and quote_ty = $2 and is_buy = $3 and remaining > 0 and cancelled_at is null
+        group by price
+        order by price {}
+        limit $4
+    "#, if is_buy { "desc" } else { "asc" }))
+        .bind::<Text, _>(base_ty)
+        .bind::<Text, _>(quote_ty)
+        .bind::<Bool, _>(is_buy)
+        .bind::<Int8, _>(DEPTH_LEVELS)
+        .load(conn)
+}
+
+pub fn recent_fills(
+    conn: &diesel::PgConnection,
+    base_ty: &CurrencyId,
+    quote_ty: &CurrencyId,
+    limit: i64,
+) -> QueryResult<Vec<MarketFill>> {
+    mfdsl::market_fills
+        .select(MarketFill::cols())
+        .filter(mfdsl::base_ty.eq(base_ty))
+        .filter(mfdsl::quote_ty.eq(quote_ty))
+        .order((mfdsl::happened_at.desc(), mfdsl::rowid.desc()))
+        .limit(limit)
+        .get_results(conn)
+}
+
+pub fn open_orders_for(
+    conn: &diesel::PgConnection,
+    user: UserId,
+) -> QueryResult<Vec<MarketOrder>> {
+    modsl::market_orders
+        .select(MarketOrder::cols())
+        .filter(modsl::owner.eq(user))
+        .filter(modsl::remaining.gt(0))
+        .filter(modsl::cancelled_at.is_null())
+        .order(modsl::rowid)
+        .get_results(conn)
+}
+
+#[cfg(test)]
+mod test {
+    use super::plan_fills;
+
+    #[test]
+    fn fills() {
+        // Asks, best (lowest) first: (id, price, remaining)
+        let asks = [(1, 100, 5), (2, 100, 5), (3, 120, 10)];
+        // Oldest order at the best price goes first
+        assert_eq!(plan_fills(true, 100, 3, &asks), vec![(1, 3, 100)]);
+        assert_eq!(plan_fills(true, 100, 8, &asks), vec![(1, 5, 100), (2, 3, 100)]);
+        // Stops at the limit price
+        assert_eq!(plan_fills(true, 110, 20, &asks), vec![(1, 5, 100), (2, 5, 100)]);
+        assert_eq!(plan_fills(true, 150, 12, &asks), vec![(1, 5, 100), (2, 5, 100), (3, 2, 120)]);
+        assert_eq!(plan_fills(true, 99, 5, &asks), vec![]);
+
+        // Bids, best (highest) first
+        let bids = [(4, 120, 2), (5, 100, 10)];
+        assert_eq!(plan_fills(false, 100, 5, &bids), vec![(4, 2, 120), (5, 3, 100)]);
+        assert_eq!(plan_fills(false, 110, 5, &bids), vec![(4, 2, 120)]);
+        assert_eq!(plan_fills(false, 130, 5, &bids), vec![]);
+    }
+}
@@ -52,11 +52,14 @@ macro_rules! impl_cols {
     };
 }
 
-#[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Hash,FromSqlRow,AsExpression)]
+#[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Hash,FromSqlRow,AsExpression,Serialize,Deserialize)]
 #[sql_type = "Int8"]
 pub struct UserId(u64);
 
 impl UserId {
+    /// The CONsortium's own account. Receives motion fees and auction proceeds, and can only be spent by motions.
+    pub const TREASURY:UserId = UserId(5);
+
     pub fn into_i64(self) -> i64 {
         self.0.try_into().unwrap()
     }
@@ -251,6 +254,28 @@ impl ItemType {
 //     'auction_create', --you've offered up some fungibles for bid
 //     'auction_reserve', --placing a bid, fungibles are held
 //     'auction_refund' --someone else outbid you, held fungibles are returned
+//     'auction_payout',
+//     'trade', --one leg of an accepted trade offer
+//     'standing_order', --one period's payment of a standing order
+//     'auction_proceeds', --the winning bid, paid out of escrow to the auctioneer (or the treasury)
+//     'auction_return', --nobody won, the escrowed offer goes back to whoever put it up
+//     'treasury_mint', --fungibles created in the treasury to fund an auto-auction lot
+//     'treasury_spend', --paid out of the treasury by a passed motion
+//     'loan_principal', --the lender hands over the principal when a loan is accepted
+//     'loan_repayment', --one scheduled repayment from the borrower to the lender
+//     'gen_upkeep', --pc burned each generation period for every gen held
+//     'wealth_tax', --pc burned each generation period from the part of a balance above a threshold
+//     'pc_decay', --a percentage of every pc balance burned each generation period
+//     'market_reserve', --placing a market order, what it could spend is held in escrow
+//     'market_fill', --paid out of a matched market order's escrow to the other side
+//     'market_refund', --market order escrow returned, on cancelling or when filled at a better price
+//     'burn', --fungibles destroyed on purpose by their owner
+//     'force_give_undo', --an admin reversed a force give within its undo window
+//     'sealed_bid_escrow', --placing a sealed bid, held until the auction ends
+//     'sealed_bid_refund', --a sealed bid given back when it's replaced or the auction ends
+//     'unit_bid_escrow', --placing a bid on a multi-unit auction, quantity times price is held
+//     'unit_bid_refund', --a multi-unit bid given back, in full or whatever wasn't spent at the clearing price
+//     'auction_unit_payout', --some of a multi-unit auction's units, to one of its winners
 // );
 #[derive(Copy,Clone,PartialEq,Eq,Debug,DbEnum)]
 #[DieselType = "Transfer_type"]
@@ -266,6 +291,27 @@ pub enum TransferType {
     AuctionReserve,
     AuctionRefund,
     AuctionPayout,
+    Trade,
+    StandingOrder,
+    AuctionProceeds,
+    AuctionReturn,
+    TreasuryMint,
+    TreasurySpend,
+    LoanPrincipal,
+    LoanRepayment,
+    GenUpkeep,
+    WealthTax,
+    PcDecay,
+    MarketReserve,
+    MarketFill,
+    MarketRefund,
+    Burn,
+    ForceGiveUndo,
+    SealedBidEscrow,
+    SealedBidRefund,
+    UnitBidEscrow,
+    UnitBidRefund,
+    AuctionUnitPayout,
 }
 
 
@@ -274,8 +320,8 @@ pub struct AuctionWinner {
     pub auction_id: i64,
     pub created_at: DateTime<Utc>,
     pub auctioneer: Option<i64>,
-    pub offer_ty: CurrencyId,
-    pub offer_amt: i64,
+    pub offer_ty: Option<CurrencyId>,
+    pub offer_amt: Option<i64>,
     pub bid_ty: CurrencyId,
     pub bid_min: i64,
     pub finished: bool,
@@ -286,6 +332,39 @@ pub struct AuctionWinner {
     pub last_timer_bump: DateTime<Utc>,
     pub max_bid_user: Option<UserId>,
     pub max_bid_amt: Option<i64>,
+    pub offer_item: Option<i64>,
+    pub offer_item_name: Option<String>,
+    /// Bids are hidden until it ends, and the winner pays the second-highest bid
+    pub sealed: bool,
+    /// Set for a dutch auction, whose price drops on a schedule until someone buys it
+    pub dutch_start: Option<i64>,
+    pub dutch_drop: Option<i64>,
+    pub dutch_every_minutes: Option<i32>,
+    /// What it can be bought for right now
+    pub dutch_price: Option<i64>,
+    /// Sells `offer_amt` units to many bidders at one clearing price
+    pub multi_unit: bool,
+    /// Set once a multi-unit auction finishes with any bids
+    pub clearing_price: Option<i64>,
+    /// How long it runs, if not `AUCTION_EXPIRATION`
+    pub duration_minutes: Option<i32>,
+    /// Hidden from bidders; if the winning bid is below it, nothing is sold
+    pub reserve_price: Option<i64>,
+    /// What it can be bought outright for, until the bidding reaches it
+    pub buy_now_price: Option<i64>,
+    pub bought_at: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    /// Set for an auction with a fixed end, which a new high bid in this many final minutes pushes out
+    pub snipe_window_minutes: Option<i32>,
+    /// How long after such a bid it then ends
+    pub snipe_extend_minutes: Option<i32>,
+}
+
+/// What's up for auction
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum AuctionLot<'a> {
+    Fungible{amount: i64, ty: &'a CurrencyId},
+    Item{item_id: i64, name: &'a str},
 }
 
 pub struct MaxBid {
@@ -296,7 +375,64 @@ pub struct MaxBid {
 
 impl AuctionWinner {
     pub fn current_min_bid(&self) -> i64 { self.winner_bid.map(|n| n.checked_add(1).unwrap()).unwrap_or(self.bid_min) }
-    pub fn end_at(&self) -> DateTime<Utc> { self.last_timer_bump + *crate::AUCTION_EXPIRATION }
+    pub fn end_at(&self) -> DateTime<Utc> {
+        if let Some(ended_at) = self.cancelled_at.or(self.bought_at) {
+            return ended_at;
+        }
+        match (self.dutch_price, self.winner_bid_at, self.snipe_extend_minutes) {
+            // A dutch auction is over as soon as someone buys it
+            (Some(_), Some(bought_at), _) => bought_at,
+            (_, _, Some(extend)) => std::cmp::max(
+                self.created_at + self.duration(),
+                self.last_timer_bump + chrono::Duration::minutes(extend.into()),
+            ),
+            _ => self.last_timer_bump + self.duration(),
+        }
+    }
+    /// Whether a new high bid at `now` pushes the end out
+    pub fn bid_extends(&self, now: DateTime<Utc>) -> bool {
+        match self.snipe_window_minutes {
+            Some(window) => now > self.end_at() - chrono::Duration::minutes(window.into()),
+            None => true,
+        }
+    }
+    /// How it decides when to end, eg "Ends 4 days after it started; a new high bid in the last 10 minutes pushes the
+    /// end to 5 minutes after that bid."
+    pub fn timing_rule(&self) -> String {
+        use crate::auctions::describe_minutes;
+        let duration = describe_minutes(self.duration().num_minutes());
+        match (self.snipe_window_minutes, self.snipe_extend_minutes) {
+            (Some(window), Some(extend)) if window > 0 => format!(
+                "Ends {} after it started; a new high bid in the last {} pushes the end to {} after that bid.",
+                duration,
+                describe_minutes(window.into()),
+                describe_minutes(extend.into()),
+            ),
+            (Some(_), Some(_)) => format!("Ends {} after it started, however late the bids come in.", duration),
+            _ if self.is_dutch() => format!("Ends {} after it started, unless someone buys it first.", duration),
+            _ if self.sealed || self.multi_unit => format!("Ends {} after it started; bids don't extend it.", duration),
+            _ => format!("Ends {} after the last new high bidder.", duration),
+        }
+    }
+    /// No more bids or purchases; it may still be waiting for the worker to finish it
+    pub fn is_over(&self, now: DateTime<Utc>) -> bool {
+        self.finished || self.cancelled_at.is_some() || self.bought_at.is_some() || now > self.end_at()
+    }
+    /// Whether the winning bid, if any, is enough to sell the lot
+    pub fn reserve_met(&self) -> bool {
+        match (self.reserve_price, self.winner_bid) {
+            (Some(reserve), Some(bid)) => bid >= reserve,
+            _ => true,
+        }
+    }
+    /// The buy-it-now price, while the bidding hasn't reached it
+    pub fn buy_now_available(&self) -> Option<i64> {
+        self.buy_now_price.filter(|price| self.winner_bid.map(|bid| bid < *price).unwrap_or(true))
+    }
+    pub fn duration(&self) -> chrono::Duration {
+        self.duration_minutes.map(|m| chrono::Duration::minutes(m.into())).unwrap_or(*crate::AUCTION_EXPIRATION)
+    }
+    pub fn is_dutch(&self) -> bool { self.dutch_price.is_some() }
     pub fn damm(&self) -> String { crate::damm::add_to_str(self.auction_id.to_string()) }
     pub fn auctioneer_name(&self) -> Cow<'static, str> {
         self.auctioneer.map(|a| crate::names::name_of(serenity::model::id::UserId::from(a as u64))).unwrap_or_else(|| "The CONsortium".into())
@@ -304,6 +440,20 @@ impl AuctionWinner {
     pub fn winner(&self) -> Option<(UserId, i64)> {
         self.winner_id.map(|winner| (winner, self.winner_bid.unwrap()))
     }
+    pub fn lot(&self) -> AuctionLot<'_> {
+        match (self.offer_item, &self.offer_ty, self.offer_amt) {
+            (Some(item_id), None, None) => AuctionLot::Item{item_id, name: self.offer_item_name.as_deref().unwrap()},
+            (None, Some(ty), Some(amount)) => AuctionLot::Fungible{amount, ty},
+            _ => unreachable!("auction_offer_one_kind constraint violated"),
+        }
+    }
+    /// eg "10 gen" or "Best Dressed (item#1234)"
+    pub fn lot_description(&self) -> String {
+        match self.lot() {
+            AuctionLot::Fungible{amount, ty} => format!("{} {}", amount, ty),
+            AuctionLot::Item{item_id, name} => format!("{} (item#{})", name, crate::damm::add_to_str(item_id.to_string())),
+        }
+    }
     pub fn max_bid(&self) -> Option<MaxBid> {
         self.max_bid_amt.map(|amount| {
             MaxBid{
@@ -330,6 +480,565 @@ impl AuctionWinner {
         last_timer_bump,
         max_bid_user,
         max_bid_amt,
+        offer_item,
+        offer_item_name,
+        sealed,
+        dutch_start,
+        dutch_drop,
+        dutch_every_minutes,
+        dutch_price,
+        multi_unit,
+        clearing_price,
+        duration_minutes,
+        reserve_price,
+        buy_now_price,
+        bought_at,
+        cancelled_at,
+        snipe_window_minutes,
+        snipe_extend_minutes,
+    }
+}
+
+/// An auction the treasury puts up on a schedule
+#[derive(Debug,Clone,PartialEq,Eq,Queryable)]
+pub struct AutoAuction {
+    pub rowid: i64,
+    pub name: String,
+    pub schedule: String,
+    pub timezone: String,
+    pub offer_ty: CurrencyId,
+    pub offer_amt: i64,
+    pub bid_ty: CurrencyId,
+    pub bid_min: i64,
+    pub duration_minutes: i32,
+    pub multi_unit: bool,
+    pub enabled: bool,
+    /// When the last one started, or when it was last changed
+    pub last_run: DateTime<Utc>,
+    pub changed_at: DateTime<Utc>,
+    pub changed_by_motion: Option<i64>,
+}
+
+impl AutoAuction {
+    impl_cols!{
+        crate::schema::auto_auctions,
+        rowid,
+        name,
+        schedule,
+        timezone,
+        offer_ty,
+        offer_amt,
+        bid_ty,
+        bid_min,
+        duration_minutes,
+        multi_unit,
+        enabled,
+        last_run,
+        changed_at,
+        changed_by_motion,
+    }
+
+    pub fn def(&self) -> crate::auto_auctions::AutoAuctionDef {
+        crate::auto_auctions::AutoAuctionDef{
+            schedule: self.schedule.clone(),
+            timezone: self.timezone.clone(),
+            offer_ty: self.offer_ty.clone(),
+            offer_amt: self.offer_amt,
+            bid_ty: self.bid_ty.clone(),
+            bid_min: self.bid_min,
+            duration_minutes: self.duration_minutes,
+            multi_unit: self.multi_unit,
+        }
+    }
+
+    /// When the next one is due. None if the schedule or timezone has stopped making sense.
+    pub fn next_run(&self) -> Option<DateTime<Utc>> {
+        let (schedule, tz) = self.def().validate().ok()?;
+        schedule.next_after(tz, self.last_run)
+    }
+}
+
+/// A bid on a sealed auction, secret until it ends
+#[derive(Debug,Clone,PartialEq,Eq,Queryable)]
+pub struct SealedBid {
+    pub auction_id: i64,
+    pub bidder: UserId,
+    pub amount: i64,
+    pub placed_at: DateTime<Utc>,
+}
+
+impl SealedBid {
+    impl_cols!{
+        crate::schema::sealed_bids,
+        auction_id,
+        bidder,
+        amount,
+        placed_at,
+    }
+}
+
+/// A bid for some units of a multi-unit auction at a price each
+#[derive(Debug,Clone,PartialEq,Eq,Queryable)]
+pub struct UnitBid {
+    pub auction_id: i64,
+    pub bidder: UserId,
+    pub quantity: i64,
+    pub unit_price: i64,
+    pub placed_at: DateTime<Utc>,
+}
+
+impl UnitBid {
+    impl_cols!{
+        crate::schema::unit_bids,
+        auction_id,
+        bidder,
+        quantity,
+        unit_price,
+        placed_at,
+    }
+}
+
+pub const AUCTION_EVENT_OUTBID:&str = "outbid";
+pub const AUCTION_EVENT_MAX_BID_EXHAUSTED:&str = "max_bid_exhausted";
+
+/// Something that happened to an auction that doesn't show up as a transfer
+#[derive(Debug,Clone,PartialEq,Eq,Queryable)]
+pub struct AuctionEvent {
+    pub rowid: i64,
+    pub auction_id: i64,
+    pub happened_at: DateTime<Utc>,
+    /// One of the `AUCTION_EVENT_*` constants
+    pub kind: String,
+    pub user: UserId,
+    /// The bid they lost, or the max bid that was passed
+    pub amount: i64,
+}
+
+impl AuctionEvent {
+    impl_cols!{
+        crate::schema::auction_events,
+        rowid,
+        auction_id,
+        happened_at,
+        kind,
+        user,
+        amount,
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum TradeStatus {
+    Pending,
+    Accepted,
+    Cancelled,
+    Expired,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Queryable)]
+pub struct TradeOffer {
+    pub rowid: i64,
+    pub created_at: DateTime<Utc>,
+    pub offerer: UserId,
+    pub counterparty: UserId,
+    pub offer_ty: CurrencyId,
+    pub offer_amt: i64,
+    pub want_ty: CurrencyId,
+    pub want_amt: i64,
+    pub expires_at: DateTime<Utc>,
+    pub message_id: Option<i64>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+}
+
+impl TradeOffer {
+    pub fn damm(&self) -> String { crate::damm::add_to_str(self.rowid.to_string()) }
+    pub fn status(&self, now: DateTime<Utc>) -> TradeStatus {
+        if self.accepted_at.is_some() {
+            TradeStatus::Accepted
+        } else if self.cancelled_at.is_some() {
+            TradeStatus::Cancelled
+        } else if self.expires_at <= now {
+            TradeStatus::Expired
+        } else {
+            TradeStatus::Pending
+        }
+    }
+    impl_cols!{
+        crate::schema::trade_offers,
+        rowid,
+        created_at,
+        offerer,
+        counterparty,
+        offer_ty,
+        offer_amt,
+        want_ty,
+        want_amt,
+        expires_at,
+        message_id,
+        accepted_at,
+        cancelled_at,
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Queryable)]
+pub struct StandingOrder {
+    pub rowid: i64,
+    pub created_at: DateTime<Utc>,
+    pub payer: UserId,
+    pub payee: UserId,
+    pub ty: CurrencyId,
+    pub amount: i64,
+    pub every_days: i32,
+    pub next_run_at: DateTime<Utc>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+}
+
+impl StandingOrder {
+    pub fn damm(&self) -> String { crate::damm::add_to_str(self.rowid.to_string()) }
+    impl_cols!{
+        crate::schema::standing_orders,
+        rowid,
+        created_at,
+        payer,
+        payee,
+        ty,
+        amount,
+        every_days,
+        next_run_at,
+        cancelled_at,
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum LoanStatus {
+    /// Offered, waiting for the borrower to accept
+    Offered,
+    Cancelled,
+    Expired,
+    /// Accepted and not yet fully repaid
+    Active,
+    Repaid,
+    /// Still owed too long after the last installment, so no longer collected
+    Defaulted,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Queryable)]
+pub struct Loan {
+    pub rowid: i64,
+    pub created_at: DateTime<Utc>,
+    pub lender: UserId,
+    pub borrower: UserId,
+    pub ty: CurrencyId,
+    pub principal: i64,
+    pub interest_bps: i32,
+    pub every_days: i32,
+    pub installments: i32,
+    pub expires_at: DateTime<Utc>,
+    pub message_id: Option<i64>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    pub next_payment_at: Option<DateTime<Utc>>,
+    pub periods_run: i32,
+    pub outstanding: i64,
+    pub repaid_at: Option<DateTime<Utc>>,
+    pub defaulted_at: Option<DateTime<Utc>>,
+}
+
+impl Loan {
+    pub fn damm(&self) -> String { crate::damm::add_to_str(self.rowid.to_string()) }
+    pub fn status(&self, now: DateTime<Utc>) -> LoanStatus {
+        if self.repaid_at.is_some() {
+            LoanStatus::Repaid
+        } else if self.defaulted_at.is_some() {
+            LoanStatus::Defaulted
+        } else if self.accepted_at.is_some() {
+            LoanStatus::Active
+        } else if self.cancelled_at.is_some() {
+            LoanStatus::Cancelled
+        } else if self.expires_at <= now {
+            LoanStatus::Expired
+        } else {
+            LoanStatus::Offered
+        }
+    }
+    impl_cols!{
+        crate::schema::loans,
+        rowid,
+        created_at,
+        lender,
+        borrower,
+        ty,
+        principal,
+        interest_bps,
+        every_days,
+        installments,
+        expires_at,
+        message_id,
+        accepted_at,
+        cancelled_at,
+        next_payment_at,
+        periods_run,
+        outstanding,
+        repaid_at,
+        defaulted_at,
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Queryable)]
+pub struct LoanPeriod {
+    pub loan: i64,
+    pub period_start: DateTime<Utc>,
+    pub ran_at: DateTime<Utc>,
+    pub interest: i64,
+    pub due: i64,
+    pub paid: i64,
+}
+
+impl LoanPeriod {
+    impl_cols!{
+        crate::schema::loan_periods,
+        loan,
+        period_start,
+        ran_at,
+        interest,
+        due,
+        paid,
+    }
+}
+
+/// A limit order to buy or sell `base_ty`, priced in `quote_ty` per unit
+#[derive(Debug,Clone,PartialEq,Eq,Queryable)]
+pub struct MarketOrder {
+    pub rowid: i64,
+    pub created_at: DateTime<Utc>,
+    pub owner: UserId,
+    pub is_buy: bool,
+    pub base_ty: CurrencyId,
+    pub quote_ty: CurrencyId,
+    pub price: i64,
+    pub quantity: i64,
+    pub remaining: i64,
+    pub cancelled_at: Option<DateTime<Utc>>,
+}
+
+impl MarketOrder {
+    pub fn damm(&self) -> String { crate::damm::add_to_str(self.rowid.to_string()) }
+    pub fn is_open(&self) -> bool { self.remaining > 0 && self.cancelled_at.is_none() }
+    impl_cols!{
+        crate::schema::market_orders,
+        rowid,
+        created_at,
+        owner,
+        is_buy,
+        base_ty,
+        quote_ty,
+        price,
+        quantity,
+        remaining,
+        cancelled_at,
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Queryable)]
+pub struct MarketFill {
+    pub rowid: i64,
+    pub happened_at: DateTime<Utc>,
+    pub buy_order: i64,
+    pub sell_order: i64,
+    pub base_ty: CurrencyId,
+    pub quote_ty: CurrencyId,
+    pub quantity: i64,
+    pub price: i64,
+}
+
+impl MarketFill {
+    impl_cols!{
+        crate::schema::market_fills,
+        rowid,
+        happened_at,
+        buy_order,
+        sell_order,
+        base_ty,
+        quote_ty,
+        quantity,
+        price,
+    }
+}
+
+/// The audit record of a `$force_give`
+#[derive(Debug,Clone,PartialEq,Eq,Queryable)]
+pub struct ForceGive {
+    pub rowid: i64,
+    pub happened_at: DateTime<Utc>,
+    pub giver: UserId,
+    pub recipient: UserId,
+    pub ty: CurrencyId,
+    pub quantity: i64,
+    pub message_id: i64,
+    /// Whether the bot had seen the recipient in any guild at the time
+    pub recipient_seen: bool,
+    pub undo_until: Option<DateTime<Utc>>,
+    pub undone_at: Option<DateTime<Utc>>,
+    pub undone_by: Option<UserId>,
+}
+
+impl ForceGive {
+    pub fn can_undo(&self, now: DateTime<Utc>) -> bool {
+        self.undone_at.is_none() && self.undo_until.map(|until| until > now).unwrap_or(false)
+    }
+    impl_cols!{
+        crate::schema::force_gives,
+        rowid,
+        happened_at,
+        giver,
+        recipient,
+        ty,
+        quantity,
+        message_id,
+        recipient_seen,
+        undo_until,
+        undone_at,
+        undone_by,
+    }
+}
+
+/// A give from discord that's waiting for the giver to confirm it
+#[derive(Debug,Clone,PartialEq,Eq,Queryable)]
+pub struct PendingGive {
+    pub rowid: i64,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub giver: UserId,
+    pub recipient: UserId,
+    pub ty: CurrencyId,
+    pub quantity: i64,
+    pub command_message_id: i64,
+    pub message_id: Option<i64>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    pub economy: i64,
+}
+
+impl PendingGive {
+    pub fn is_pending(&self, now: DateTime<Utc>) -> bool {
+        self.confirmed_at.is_none() && self.cancelled_at.is_none() && self.expires_at > now
+    }
+    impl_cols!{
+        crate::schema::pending_gives,
+        rowid,
+        created_at,
+        expires_at,
+        giver,
+        recipient,
+        ty,
+        quantity,
+        command_message_id,
+        message_id,
+        confirmed_at,
+        cancelled_at,
+        economy,
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Queryable)]
+pub struct GuildConfig {
+    pub guild_id: i64,
+    pub motions_channel: Option<i64>,
+    pub announcement_channel: Option<i64>,
+    pub command_prefix: String,
+    pub admin_role: Option<i64>,
+    pub isolated: bool,
+}
+
+impl GuildConfig {
+    /// The settings of a guild that hasn't changed any
+    pub fn defaults(guild_id: i64) -> Self {
+        GuildConfig{
+            guild_id,
+            motions_channel: None,
+            announcement_channel: None,
+            command_prefix: crate::guilds::DEFAULT_PREFIX.to_string(),
+            admin_role: None,
+            isolated: false,
+        }
+    }
+
+    /// The economy that commands used in this guild act on
+    pub fn economy(&self) -> i64 {
+        if self.isolated { self.guild_id } else { crate::guilds::SHARED_ECONOMY }
+    }
+
+    impl_cols!{
+        crate::schema::guilds,
+        guild_id,
+        motions_channel,
+        announcement_channel,
+        command_prefix,
+        admin_role,
+        isolated,
+    }
+}
+
+/// A unique, non-fungible item, eg a title or a trophy
+#[derive(Debug,Clone,PartialEq,Eq,Queryable)]
+pub struct Item {
+    pub rowid: i64,
+    pub created_at: DateTime<Utc>,
+    pub kind: String,
+    pub name: String,
+    pub metadata: serde_json::Value,
+    /// None while the item is held in escrow by an auction
+    pub owner: Option<UserId>,
+}
+
+impl Item {
+    pub fn damm(&self) -> String { crate::damm::add_to_str(self.rowid.to_string()) }
+    /// The metadata as (key, value) pairs, with string values unquoted
+    pub fn metadata_pairs(&self) -> Vec<(&str, String)> {
+        match &self.metadata {
+            serde_json::Value::Object(map) => map.iter().map(|(k, v)| {
+                (k.as_str(), match v {
+                    serde_json::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+            }).collect(),
+            _ => vec![],
+        }
+    }
+    impl_cols!{
+        crate::schema::items,
+        rowid,
+        created_at,
+        kind,
+        name,
+        metadata,
+        owner,
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Queryable)]
+pub struct ItemTransfer {
+    pub rowid: i64,
+    pub item: i64,
+    pub happened_at: DateTime<Utc>,
+    pub from_user: Option<UserId>,
+    pub to_user: Option<UserId>,
+    pub transfer_ty: TransferType,
+    pub auction_id: Option<i64>,
+    pub message_id: Option<i64>,
+}
+
+impl ItemTransfer {
+    impl_cols!{
+        crate::schema::item_transfers,
+        rowid,
+        item,
+        happened_at,
+        from_user,
+        to_user,
+        transfer_ty,
+        auction_id,
+        message_id,
     }
 }
 
@@ -349,6 +1058,7 @@ impl AuctionWinner {
 //  comment      | text                     |           |          |
 //  transfer_ty  | transfer_type            |           | not null |
 //  auction_id   | bigint                   |           |          |
+//  thing_id     | bigint                   |           |          |
 
 #[derive(Debug,Clone,PartialEq,Eq,Queryable)]
 pub struct RawTransfer {
@@ -366,6 +1076,7 @@ pub struct RawTransfer {
     pub comment: Option<String>,
     pub transfer_ty: TransferType,
     pub auction_id: Option<i64>,
+    pub thing_id: Option<i64>,
 }
 
 impl RawTransfer {
@@ -385,6 +1096,7 @@ impl RawTransfer {
         comment,
         transfer_ty,
         auction_id,
+        thing_id,
     }
 
     fn from(&self) -> Option<UserBal> {
@@ -428,6 +1140,24 @@ pub enum TransferExtra {
     AuctionReserve{auction_id:i64, from:UserBal},
     AuctionRefund{ auction_id:i64, to:UserBal},
     AuctionPayout{ auction_id:i64, to:UserBal},
+    Trade{trade_id:i64, from:UserBal, to:UserBal},
+    StandingOrder{standing_order_id:i64, from:UserBal, to:UserBal},
+    AuctionProceeds{auction_id:i64, to:UserBal},
+    AuctionReturn{ auction_id:i64, to:UserBal},
+    TreasuryMint{to:UserBal},
+    TreasurySpend{motion_id:i64, from:UserBal, to:UserBal},
+    Loan{loan_id:i64, from:UserBal, to:UserBal, repayment:bool},
+    EconomicPolicy{from:UserBal, policy:crate::economic_policy::PolicyKind},
+    MarketReserve{order_id:i64, from:UserBal},
+    MarketFill{order_id:i64, to:UserBal},
+    MarketRefund{order_id:i64, to:UserBal},
+    Burn{from:UserBal},
+    ForceGiveUndo{from:UserBal, to:UserBal},
+    SealedBidEscrow{auction_id:i64, from:UserBal},
+    SealedBidRefund{auction_id:i64, to:UserBal},
+    UnitBidEscrow{auction_id:i64, from:UserBal},
+    UnitBidRefund{auction_id:i64, to:UserBal},
+    AuctionUnitPayout{auction_id:i64, to:UserBal},
 }
 
 #[derive(Debug,Clone,PartialEq,Eq)]
@@ -458,6 +1188,7 @@ impl Transfer {
         comment,
         transfer_ty,
         auction_id,
+        thing_id,
     }
 }
 
@@ -512,6 +1243,81 @@ impl From<RawTransfer> for Transfer {
                 auction_id: r.auction_id.unwrap(),
                 to: r.to().unwrap(),
             },
+            TransferType::Trade => TransferExtra::Trade{
+                trade_id: r.thing_id.unwrap(),
+                from: r.from().unwrap(),
+                to: r.to().unwrap(),
+            },
+            TransferType::StandingOrder => TransferExtra::StandingOrder{
+                standing_order_id: r.thing_id.unwrap(),
+                from: r.from().unwrap(),
+                to: r.to().unwrap(),
+            },
+            TransferType::AuctionProceeds => TransferExtra::AuctionProceeds{
+                auction_id: r.auction_id.unwrap(),
+                to: r.to().unwrap(),
+            },
+            TransferType::AuctionReturn => TransferExtra::AuctionReturn{
+                auction_id: r.auction_id.unwrap(),
+                to: r.to().unwrap(),
+            },
+            TransferType::TreasuryMint => TransferExtra::TreasuryMint{
+                to: r.to().unwrap(),
+            },
+            TransferType::TreasurySpend => TransferExtra::TreasurySpend{
+                motion_id: r.thing_id.unwrap(),
+                from: r.from().unwrap(),
+                to: r.to().unwrap(),
+            },
+            TransferType::LoanPrincipal | TransferType::LoanRepayment => TransferExtra::Loan{
+                loan_id: r.thing_id.unwrap(),
+                from: r.from().unwrap(),
+                to: r.to().unwrap(),
+                repayment: matches!(r.transfer_ty, TransferType::LoanRepayment),
+            },
+            TransferType::GenUpkeep | TransferType::WealthTax | TransferType::PcDecay => TransferExtra::EconomicPolicy{
+                from: r.from().unwrap(),
+                policy: crate::economic_policy::PolicyKind::from_transfer_type(r.transfer_ty).unwrap(),
+            },
+            TransferType::MarketReserve => TransferExtra::MarketReserve{
+                order_id: r.thing_id.unwrap(),
+                from: r.from().unwrap(),
+            },
+            TransferType::MarketFill => TransferExtra::MarketFill{
+                order_id: r.thing_id.unwrap(),
+                to: r.to().unwrap(),
+            },
+            TransferType::MarketRefund => TransferExtra::MarketRefund{
+                order_id: r.thing_id.unwrap(),
+                to: r.to().unwrap(),
+            },
+            TransferType::Burn => TransferExtra::Burn{
+                from: r.from().unwrap(),
+            },
+            TransferType::ForceGiveUndo => TransferExtra::ForceGiveUndo{
+                from: r.from().unwrap(),
+                to: r.to().unwrap(),
+            },
+            TransferType::SealedBidEscrow => TransferExtra::SealedBidEscrow{
+                auction_id: r.auction_id.unwrap(),
+                from: r.from().unwrap(),
+            },
+            TransferType::SealedBidRefund => TransferExtra::SealedBidRefund{
+                auction_id: r.auction_id.unwrap(),
+                to: r.to().unwrap(),
+            },
+            TransferType::UnitBidEscrow => TransferExtra::UnitBidEscrow{
+                auction_id: r.auction_id.unwrap(),
+                from: r.from().unwrap(),
+            },
+            TransferType::UnitBidRefund => TransferExtra::UnitBidRefund{
+                auction_id: r.auction_id.unwrap(),
+                to: r.to().unwrap(),
+            },
+            TransferType::AuctionUnitPayout => TransferExtra::AuctionUnitPayout{
+                auction_id: r.auction_id.unwrap(),
+                to: r.to().unwrap(),
+            },
         };
 
         Transfer{
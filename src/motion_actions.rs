@@ -0,0 +1,139 @@
+use chrono::{DateTime,Utc};
+use diesel::prelude::*;
+use crate::schema::motions::dsl as mdsl;
+use crate::models::UserId;
+use crate::transfers::{CurrencyId,TransactionBuilder,TransferHandler,TransferError};
+use crate::names::name_of;
+use crate::economic_policy::{PolicyKind,PolicySetting};
+use crate::auto_auctions::{AutoAuctionDef,AutoAuctionError};
+
+/// Something that happens automatically when a motion passes. Stored json-encoded in `motions.action`.
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MotionAction {
+    TreasurySpend{to: UserId, amount: i64, ty: CurrencyId},
+    SetPolicy{policy: PolicyKind, setting: PolicySetting},
+    /// Creates or replaces the named auto-auction, or removes it if `def` is None
+    SetAutoAuction{name: String, def: Option<AutoAuctionDef>},
+}
+
+impl MotionAction {
+    pub fn to_db(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_db(s: &str) -> Self {
+        serde_json::from_str(s).expect("invalid motion action in database")
+    }
+
+    /// eg "Pay 100 pc from the treasury to Colin"
+    pub fn describe(&self) -> String {
+        match self {
+            MotionAction::TreasurySpend{to, amount, ty} => format!(
+                "Pay {} {} from the treasury to {}",
+                amount,
+                ty,
+                name_of(*to),
+            ),
+            MotionAction::SetPolicy{policy, setting} => format!(
+                "Set {} to {}",
+                policy.title().to_lowercase(),
+                setting.describe(*policy),
+            ),
+            MotionAction::SetAutoAuction{name, def: Some(def)} => format!(
+                "Set the `{}` auto-auction to {}",
+                name,
+                def.describe(),
+            ),
+            MotionAction::SetAutoAuction{name, def: None} => format!("Remove the `{}` auto-auction", name),
+        }
+    }
+
+    /// Describes what happened when the action was run, given the error it failed with (if any)
+    pub fn outcome(&self, error: Option<&str>) -> String {
+        match error {
+            None => format!("Executed: {}.", self.describe()),
+            Some(e) => format!("Not executed, {}: {}.", e, self.describe()),
+        }
+    }
+
+    /// Carries out the action. The outer Err is a database error, the inner Err explains why the action couldn't be
+    /// carried out (and nothing was changed).
+    fn execute(
+        &self,
+        conn: &diesel::PgConnection,
+        motion_id: i64,
+        now: DateTime<Utc>,
+    ) -> QueryResult<Result<(), String>> {
+        match self {
+            MotionAction::TreasurySpend{to, amount, ty} => {
+                let mut handle = TransferHandler::new(
+                    conn,
+                    vec![UserId::TREASURY, *to],
+                    vec![ty.clone()],
+                )?;
+                let t = TransactionBuilder::new(
+                    *amount,
+                    ty.clone(),
+                    now,
+                ).treasury_spend(*to, motion_id);
+                Ok(match handle.transfer(t) {
+                    Ok(v) => Ok(v?),
+                    Err(TransferError::NotEnough) => Err("the treasury does not have enough".to_string()),
+                    Err(TransferError::Overflow) => Err("overflow".to_string()),
+                })
+            },
+            MotionAction::SetPolicy{policy, setting} => {
+                crate::economic_policy::set(conn, *policy, *setting, motion_id, now)?;
+                Ok(Ok(()))
+            },
+            MotionAction::SetAutoAuction{name, def} => {
+                let res = match def {
+                    Some(def) => crate::auto_auctions::set(conn, name, def, Some(motion_id), now),
+                    None => crate::auto_auctions::remove(conn, name),
+                };
+                match res {
+                    Ok(()) => Ok(Ok(())),
+                    Err(AutoAuctionError::Db(e)) => Err(e),
+                    Err(e) => Ok(Err(e.to_string().trim_end_matches('.').to_lowercase())),
+                }
+            },
+        }
+    }
+}
+
+/// Carries out the motion's action if it passed and the action hasn't been run already, and returns a line
+/// describing the outcome for the announcement. Returns None if the motion has no action or didn't pass.
+///
+/// Safe to call any number of times for the same motion; the action only ever runs once.
+pub fn run_for_motion(
+    conn: &diesel::PgConnection,
+    motion_id: i64,
+    passed: bool,
+    now: DateTime<Utc>,
+) -> QueryResult<Option<String>> {
+    conn.transaction(|| {
+        let (action, executed_at, error):(Option<String>, Option<DateTime<Utc>>, Option<String>) = mdsl::motions
+            .select((mdsl::action, mdsl::action_executed_at, mdsl::action_error))
+            .filter(mdsl::rowid.eq(motion_id))
+            .for_update()
+            .get_result(conn)?;
+        let action = match action {
+            Some(a) if passed => MotionAction::from_db(&a),
+            _ => return Ok(None),
+        };
+        let error = if executed_at.is_some() {
+            error
+        } else {
+            let error = action.execute(conn, motion_id, now)?.err();
+            diesel::update(mdsl::motions.filter(mdsl::rowid.eq(motion_id)))
+                .set((
+                    mdsl::action_executed_at.eq(now),
+                    mdsl::action_error.eq(&error),
+                ))
+                .execute(conn)?;
+            error
+        };
+        Ok(Some(action.outcome(error.as_deref())))
+    })
+}
@@ -1,33 +1,145 @@
-pub static KNOWN_NAMES: phf::Map<u64, &'static str> = phf::phf_map! {
-    0u64 => "TheOtherPitOfFire",
-    1u64 => "PitOfFire",
-    2u64 => "Dos",
-    3u64 => "Three",
-    4u64 => "Four",
-    113455311058108416u64 => "bowlercaptain",
-    125003180219170816u64 => "Colin",
-    155438323354042368u64 => "Ben",
-    165858230327574528u64 => "Shelvacu",
-    175691653770641409u64 => "DDR",
-    173650493145350145u64 => "Sparks",
-    182663630280589312u64 => "Azure",
-    189163550890000384u64 => "hemaglox",
-    189620154122895360u64 => "Leeli",
-    240939050360504320u64 => "InvisiBrony",
-    265905786469744640u64 => "AtomicTaco",
-    271540455584301057u64 => "Anthony",
-    308798067023544320u64 => "Razgriz",
-    359950109229973504u64 => "ManganeseFrog",
-    368635452925935616u64 => "TopHatimus",
-    373610438560317441u64 => "Matt",
-};
-
-pub fn name_of(user:impl Into<crate::models::UserId>) -> std::borrow::Cow<'static, str> {
-    let u:crate::models::UserId = user.into();
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime,Utc};
+use diesel::prelude::*;
+use crate::schema::users::dsl as udsl;
+use crate::models::UserId;
+
+/// How long the bot, website and worker each go before picking up names recorded by the others
+pub const REFRESH_EVERY:Duration = Duration::from_secs(60);
+
+#[derive(Debug,Default)]
+struct Names {
+    by_id: HashMap<UserId, String>,
+    loaded_at: Option<Instant>,
+}
+
+lazy_static! {
+    /// Every name in the users table, so pages and embeds can name people without a query each
+    static ref NAMES:RwLock<Names> = RwLock::new(Names::default());
+}
+
+/// The name we know `user` by, or their id if we've never seen them
+pub fn name_of(user:impl Into<UserId>) -> Cow<'static, str> {
+    let u:UserId = user.into();
     trace!("name_of");
-    if let Some(name) = KNOWN_NAMES.get(&u.into_u64()) {
-        (*name).into()
+    if let Some(name) = NAMES.read().unwrap().by_id.get(&u) {
+        name.clone().into()
     } else {
         u.to_string().into()
     }
 }
+
+/// Everyone we know the name of, ordered by name
+pub fn all() -> Vec<(UserId, String)> {
+    let mut all:Vec<_> = NAMES.read().unwrap().by_id.iter().map(|(id, name)| (*id, name.clone())).collect();
+    all.sort_by(|a, b| a.1.to_lowercase().cmp(&b.1.to_lowercase()).then(a.0.cmp(&b.0)));
+    all
+}
+
+/// Reloads every name from the database
+pub fn refresh(conn: &diesel::PgConnection) -> QueryResult<()> {
+    let rows:Vec<(UserId, String)> = udsl::users
+        .select((udsl::discord_id, udsl::name))
+        .get_results(conn)?;
+    let mut names = NAMES.write().unwrap();
+    names.by_id = rows.into_iter().collect();
+    names.loaded_at = Some(Instant::now());
+    Ok(())
+}
+
+pub fn refresh_if_stale(conn: &diesel::PgConnection) -> QueryResult<()> {
+    let stale = match NAMES.read().unwrap().loaded_at {
+        Some(loaded_at) => loaded_at.elapsed() >= REFRESH_EVERY,
+        None => true,
+    };
+    if stale {
+        refresh(conn)?;
+    }
+    Ok(())
+}
+
+/// The name recorded for `user`, straight from the database rather than this process's copy
+pub fn lookup(conn: &diesel::PgConnection, user: UserId) -> QueryResult<Option<String>> {
+    udsl::users
+        .select(udsl::name)
+        .filter(udsl::discord_id.eq(user))
+        .get_result(conn)
+        .optional()
+}
+
+/// Which of the `seen` names are new or different from what's `known`. If someone was seen more than once, the last
+/// name wins.
+fn changed<'a>(known: &HashMap<UserId, String>, seen: &'a [(UserId, String)]) -> Vec<(UserId, &'a str)> {
+    let mut latest:HashMap<UserId, &str> = HashMap::new();
+    for (id, name) in seen {
+        if !name.is_empty() {
+            latest.insert(*id, name.as_str());
+        }
+    }
+    let mut changed:Vec<_> = latest.into_iter()
+        .filter(|(id, name)| known.get(id).map(|k| k.as_str()) != Some(*name))
+        .collect();
+    changed.sort_unstable();
+    changed
+}
+
+/// Records the names of users seen on discord or logging in to the website. Names that haven't changed aren't
+/// written.
+pub fn record(
+    conn: &diesel::PgConnection,
+    seen: &[(UserId, String)],
+    now: DateTime<Utc>,
+) -> QueryResult<()> {
+    use diesel::upsert::excluded;
+    refresh_if_stale(conn)?;
+    let changed = changed(&NAMES.read().unwrap().by_id, seen);
+    if changed.is_empty() {
+        return Ok(());
+    }
+    let rows:Vec<_> = changed.iter().map(|(id, name)| (
+        udsl::discord_id.eq(*id),
+        udsl::name.eq(*name),
+        udsl::updated_at.eq(now),
+    )).collect();
+    diesel::insert_into(udsl::users)
+        .values(&rows)
+        .on_conflict(udsl::discord_id)
+        .do_update()
+        .set((
+            udsl::name.eq(excluded(udsl::name)),
+            udsl::updated_at.eq(excluded(udsl::updated_at)),
+        ))
+        .execute(conn)?;
+    let mut names = NAMES.write().unwrap();
+    for (id, name) in changed {
+        names.by_id.insert(id, name.to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn changed_names() {
+        let id = |n:u64| UserId::try_from(n).unwrap();
+        let known:HashMap<UserId, String> = vec![
+            (id(10), "Colin".to_string()),
+            (id(11), "Ben".to_string()),
+        ].into_iter().collect();
+        let seen = vec![
+            (id(10), "Colin".to_string()),
+            (id(11), "Benjamin".to_string()),
+            (id(12), "shelvacu".to_string()),
+            (id(12), "Shelvacu".to_string()),
+            (id(13), String::new()),
+        ];
+        assert_eq!(changed(&known, &seen), vec![(id(11), "Benjamin"), (id(12), "Shelvacu")]);
+        assert!(changed(&known, &seen[..1]).is_empty());
+    }
+}
@@ -0,0 +1,19 @@
+use chrono::{DateTime,Utc};
+use diesel::prelude::*;
+use crate::schema::notifications::dsl as ndsl;
+use crate::models::UserId;
+
+/// Queues a direct message to `recipient`. The worker sends it on its next pass, so this is safe to call from inside a
+/// transaction; if the transaction rolls back, the message is never sent.
+pub fn notify(
+    conn: &diesel::PgConnection,
+    recipient: UserId,
+    body: impl Into<String>,
+    now: DateTime<Utc>,
+) -> QueryResult<()> {
+    diesel::insert_into(ndsl::notifications).values((
+        ndsl::created_at.eq(now),
+        ndsl::recipient.eq(recipient),
+        ndsl::body.eq(body.into()),
+    )).execute(conn).map(|_| ())
+}
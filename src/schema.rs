@@ -1,3 +1,38 @@
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    admins (user) {
+        user -> Int8,
+        added_at -> Timestamptz,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    auction_events (rowid) {
+        rowid -> Int8,
+        auction_id -> Int8,
+        happened_at -> Timestamptz,
+        kind -> Text,
+        user -> Int8,
+        amount -> Int8,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    auction_watchers (auction_id, user) {
+        auction_id -> Int8,
+        user -> Int8,
+        created_at -> Timestamptz,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use crate::models::Transfer_type;
@@ -6,14 +41,51 @@ table! {
         rowid -> Int8,
         created_at -> Timestamptz,
         auctioneer -> Nullable<Int8>,
-        offer_ty -> Text,
-        offer_amt -> Int8,
+        offer_ty -> Nullable<Text>,
+        offer_amt -> Nullable<Int8>,
         bid_ty -> Text,
         bid_min -> Int8,
         finished -> Bool,
         max_bid_user -> Nullable<Int8>,
         max_bid_amt -> Nullable<Int8>,
         last_timer_bump -> Timestamptz,
+        offer_item -> Nullable<Int8>,
+        sealed -> Bool,
+        dutch_start -> Nullable<Int8>,
+        dutch_drop -> Nullable<Int8>,
+        dutch_every_minutes -> Nullable<Int4>,
+        dutch_price -> Nullable<Int8>,
+        multi_unit -> Bool,
+        clearing_price -> Nullable<Int8>,
+        duration_minutes -> Nullable<Int4>,
+        reserve_price -> Nullable<Int8>,
+        buy_now_price -> Nullable<Int8>,
+        bought_at -> Nullable<Timestamptz>,
+        cancelled_at -> Nullable<Timestamptz>,
+        snipe_window_minutes -> Nullable<Int4>,
+        snipe_extend_minutes -> Nullable<Int4>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    auto_auctions (rowid) {
+        rowid -> Int8,
+        name -> Text,
+        schedule -> Text,
+        timezone -> Text,
+        offer_ty -> Text,
+        offer_amt -> Int8,
+        bid_ty -> Text,
+        bid_min -> Int8,
+        duration_minutes -> Int4,
+        multi_unit -> Bool,
+        enabled -> Bool,
+        last_run -> Timestamptz,
+        changed_at -> Timestamptz,
+        changed_by_motion -> Nullable<Int8>,
     }
 }
 
@@ -36,6 +108,159 @@ table! {
         long_name_plural -> Text,
         long_name_ambiguous -> Text,
         position -> Int4,
+        retired_at -> Nullable<Timestamptz>,
+        confirm_give_above -> Nullable<Int8>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    economic_policies (name) {
+        name -> Text,
+        enabled -> Bool,
+        rate -> Int8,
+        threshold -> Int8,
+        changed_at -> Nullable<Timestamptz>,
+        changed_by_motion -> Nullable<Int8>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    force_gives (rowid) {
+        rowid -> Int8,
+        happened_at -> Timestamptz,
+        giver -> Int8,
+        recipient -> Int8,
+        ty -> Text,
+        quantity -> Int8,
+        message_id -> Int8,
+        recipient_seen -> Bool,
+        undo_until -> Nullable<Timestamptz>,
+        undone_at -> Nullable<Timestamptz>,
+        undone_by -> Nullable<Int8>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    guilds (guild_id) {
+        guild_id -> Int8,
+        motions_channel -> Nullable<Int8>,
+        announcement_channel -> Nullable<Int8>,
+        command_prefix -> Text,
+        admin_role -> Nullable<Int8>,
+        isolated -> Bool,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    item_transfers (rowid) {
+        rowid -> Int8,
+        item -> Int8,
+        happened_at -> Timestamptz,
+        from_user -> Nullable<Int8>,
+        to_user -> Nullable<Int8>,
+        transfer_ty -> Transfer_type,
+        auction_id -> Nullable<Int8>,
+        message_id -> Nullable<Int8>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    items (rowid) {
+        rowid -> Int8,
+        created_at -> Timestamptz,
+        kind -> Text,
+        name -> Text,
+        metadata -> Jsonb,
+        owner -> Nullable<Int8>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    loan_periods (loan, period_start) {
+        loan -> Int8,
+        period_start -> Timestamptz,
+        ran_at -> Timestamptz,
+        interest -> Int8,
+        due -> Int8,
+        paid -> Int8,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    loans (rowid) {
+        rowid -> Int8,
+        created_at -> Timestamptz,
+        lender -> Int8,
+        borrower -> Int8,
+        ty -> Text,
+        principal -> Int8,
+        interest_bps -> Int4,
+        every_days -> Int4,
+        installments -> Int4,
+        expires_at -> Timestamptz,
+        message_id -> Nullable<Int8>,
+        accepted_at -> Nullable<Timestamptz>,
+        cancelled_at -> Nullable<Timestamptz>,
+        next_payment_at -> Nullable<Timestamptz>,
+        periods_run -> Int4,
+        outstanding -> Int8,
+        repaid_at -> Nullable<Timestamptz>,
+        defaulted_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    market_fills (rowid) {
+        rowid -> Int8,
+        happened_at -> Timestamptz,
+        buy_order -> Int8,
+        sell_order -> Int8,
+        base_ty -> Text,
+        quote_ty -> Text,
+        quantity -> Int8,
+        price -> Int8,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    market_orders (rowid) {
+        rowid -> Int8,
+        created_at -> Timestamptz,
+        owner -> Int8,
+        is_buy -> Bool,
+        base_ty -> Text,
+        quote_ty -> Text,
+        price -> Int8,
+        quantity -> Int8,
+        remaining -> Int8,
+        cancelled_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -66,6 +291,57 @@ table! {
         needs_update -> Bool,
         motioned_by -> Int8,
         power -> Numeric,
+        action -> Nullable<Text>,
+        action_executed_at -> Nullable<Timestamptz>,
+        action_error -> Nullable<Text>,
+        economy -> Int8,
+        guild_id -> Nullable<Int8>,
+        channel_id -> Nullable<Int8>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    notifications (rowid) {
+        rowid -> Int8,
+        created_at -> Timestamptz,
+        recipient -> Int8,
+        body -> Text,
+        sent_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    pending_gives (rowid) {
+        rowid -> Int8,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        giver -> Int8,
+        recipient -> Int8,
+        ty -> Text,
+        quantity -> Int8,
+        command_message_id -> Int8,
+        message_id -> Nullable<Int8>,
+        confirmed_at -> Nullable<Timestamptz>,
+        cancelled_at -> Nullable<Timestamptz>,
+        economy -> Int8,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    sealed_bids (auction_id, bidder) {
+        auction_id -> Int8,
+        bidder -> Int8,
+        amount -> Int8,
+        placed_at -> Timestamptz,
     }
 }
 
@@ -77,7 +353,35 @@ table! {
         enforce_single_row -> Bool,
         last_gen -> Timestamptz,
         last_task_run -> Timestamptz,
-        last_auto_auction -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    standing_order_runs (standing_order, period_start) {
+        standing_order -> Int8,
+        period_start -> Timestamptz,
+        ran_at -> Timestamptz,
+        paid -> Bool,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    standing_orders (rowid) {
+        rowid -> Int8,
+        created_at -> Timestamptz,
+        payer -> Int8,
+        payee -> Int8,
+        ty -> Text,
+        amount -> Int8,
+        every_days -> Int4,
+        next_run_at -> Timestamptz,
+        cancelled_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -90,6 +394,39 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    trade_offers (rowid) {
+        rowid -> Int8,
+        created_at -> Timestamptz,
+        offerer -> Int8,
+        counterparty -> Int8,
+        offer_ty -> Text,
+        offer_amt -> Int8,
+        want_ty -> Text,
+        want_amt -> Int8,
+        expires_at -> Timestamptz,
+        message_id -> Nullable<Int8>,
+        accepted_at -> Nullable<Timestamptz>,
+        cancelled_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    unit_bids (auction_id, bidder) {
+        auction_id -> Int8,
+        bidder -> Int8,
+        quantity -> Int8,
+        unit_price -> Int8,
+        placed_at -> Timestamptz,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use crate::models::Transfer_type;
@@ -109,23 +446,78 @@ table! {
         comment -> Nullable<Text>,
         transfer_ty -> Transfer_type,
         auction_id -> Nullable<Int8>,
+        thing_id -> Nullable<Int8>,
+        economy -> Int8,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::models::Transfer_type;
+
+    users (discord_id) {
+        discord_id -> Int8,
+        name -> Text,
+        updated_at -> Timestamptz,
     }
 }
 
+joinable!(auction_events -> auctions (auction_id));
+joinable!(auction_watchers -> auctions (auction_id));
+joinable!(auctions -> items (offer_item));
 joinable!(auctions -> thing_ids (rowid));
+joinable!(auto_auctions -> motions (changed_by_motion));
+joinable!(economic_policies -> motions (changed_by_motion));
+joinable!(force_gives -> item_types (ty));
+joinable!(item_transfers -> auctions (auction_id));
+joinable!(item_transfers -> items (item));
 joinable!(item_type_aliases -> item_types (name));
+joinable!(items -> thing_ids (rowid));
+joinable!(loan_periods -> loans (loan));
+joinable!(loans -> item_types (ty));
+joinable!(loans -> thing_ids (rowid));
+joinable!(market_orders -> thing_ids (rowid));
 joinable!(motion_votes -> motions (motion));
 joinable!(motions -> thing_ids (rowid));
+joinable!(pending_gives -> item_types (ty));
+joinable!(sealed_bids -> auctions (auction_id));
+joinable!(standing_order_runs -> standing_orders (standing_order));
+joinable!(standing_orders -> item_types (ty));
+joinable!(standing_orders -> thing_ids (rowid));
+joinable!(trade_offers -> thing_ids (rowid));
 joinable!(transfers -> auctions (auction_id));
 joinable!(transfers -> item_types (ty));
+joinable!(transfers -> thing_ids (thing_id));
+joinable!(unit_bids -> auctions (auction_id));
 
 allow_tables_to_appear_in_same_query!(
+    admins,
+    auction_events,
+    auction_watchers,
     auctions,
+    auto_auctions,
+    economic_policies,
+    force_gives,
+    guilds,
+    item_transfers,
     item_type_aliases,
     item_types,
+    items,
+    loan_periods,
+    loans,
+    market_fills,
+    market_orders,
     motion_votes,
     motions,
+    notifications,
+    pending_gives,
+    sealed_bids,
     single,
+    standing_order_runs,
+    standing_orders,
     thing_ids,
+    trade_offers,
     transfers,
+    unit_bids,
+    users,
 );
@@ -0,0 +1,281 @@
+use chrono::{DateTime,Datelike,Duration,TimeZone,Utc,Weekday};
+use diesel::prelude::*;
+use crate::schema::standing_orders::dsl as sodsl;
+use crate::schema::standing_order_runs::dsl as sordsl;
+use crate::schema::thing_ids::dsl as tid;
+use crate::models::{UserId,StandingOrder};
+use crate::transfers::{CurrencyId,TransactionBuilder,TransferHandler,TransferError};
+use crate::notifications::notify;
+use crate::names::name_of;
+
+pub const SCHEDULE_USAGE:&str = "Schedule must be one of: `every day`, `every week`, `every <n> days`, `every <n> weeks`, or `every <weekday>`";
+
+/// Parses the words after "every" into a period in days and the start of the first period.
+///
+/// Weekday schedules start at midnight Pacific on the next such day (or today, if it's that day already); everything
+/// else starts immediately.
+pub fn parse_schedule(
+    words: &[String],
+    now: DateTime<Utc>,
+) -> Result<(i32, DateTime<Utc>), &'static str> {
+    let lower:Vec<String> = words.iter().map(|w| w.to_ascii_lowercase()).collect();
+    let lower:Vec<&str> = lower.iter().map(|w| w.as_str()).collect();
+    match lower.as_slice() {
+        ["day"] | ["daily"] => Ok((1, now)),
+        ["week"] | ["weekly"] => Ok((7, now)),
+        [count, unit] => {
+            let count:i32 = count.parse().map_err(|_| SCHEDULE_USAGE)?;
+            let days = match *unit {
+                "day" | "days" => count,
+                "week" | "weeks" => count.checked_mul(7).ok_or(SCHEDULE_USAGE)?,
+                _ => return Err(SCHEDULE_USAGE),
+            };
+            if days < 1 {
+                return Err("The period must be at least one day.");
+            }
+            Ok((days, now))
+        },
+        [day] => {
+            let weekday:Weekday = day.trim_end_matches('s').parse().map_err(|_| SCHEDULE_USAGE)?;
+            let today = chrono_tz::US::Pacific.from_utc_datetime(&now.naive_utc()).date_naive();
+            let days_ahead = (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+            let start = (today + Duration::days(days_ahead as i64))
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+                .and_local_timezone(chrono_tz::US::Pacific)
+                .unwrap()
+                .with_timezone(&Utc);
+            Ok((7, start))
+        },
+        _ => Err(SCHEDULE_USAGE),
+    }
+}
+
+/// The start of the period after the one starting at `start`. Periods are counted in days on the Pacific calendar, so
+/// an order paying at midnight on Mondays stays there across daylight saving time.
+pub fn next_period(start: DateTime<Utc>, every_days: i32) -> DateTime<Utc> {
+    let local = chrono_tz::US::Pacific.from_utc_datetime(&start.naive_utc()).naive_local() + Duration::days(every_days as i64);
+    local.and_local_timezone(chrono_tz::US::Pacific)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+        // That wall clock time was skipped by the clocks going forward
+        .unwrap_or_else(|| start + Duration::days(every_days as i64))
+}
+
+/// The latest period that has started by `now`, and the start of the one after it
+fn latest_period(next_run_at: DateTime<Utc>, every_days: i32, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let mut period_start = next_run_at;
+    let mut next = next_period(period_start, every_days);
+    while next <= now {
+        period_start = next;
+        next = next_period(period_start, every_days);
+    }
+    (period_start, next)
+}
+
+/// A one-line human description, eg "Shelvacu pays 100 pc to Colin every 7 days"
+pub fn describe(order: &StandingOrder) -> String {
+    format!(
+        "{} pays {} {} to {} every {} day{}",
+        name_of(order.payer),
+        order.amount,
+        order.ty,
+        name_of(order.payee),
+        order.every_days,
+        if order.every_days == 1 { "" } else { "s" },
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn create(
+    conn: &diesel::PgConnection,
+    payer: UserId,
+    payee: UserId,
+    amount: i64,
+    ty: CurrencyId,
+    every_days: i32,
+    first_run_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> QueryResult<i64> {
+    conn.transaction(|| {
+        let order_id:i64 = diesel::insert_into(tid::thing_ids).default_values().returning(tid::rowid).get_result(conn)?;
+        diesel::insert_into(sodsl::standing_orders).values((
+            sodsl::rowid.eq(order_id),
+            sodsl::created_at.eq(now),
+            sodsl::payer.eq(payer),
+            sodsl::payee.eq(payee),
+            sodsl::ty.eq(ty),
+            sodsl::amount.eq(amount),
+            sodsl::every_days.eq(every_days),
+            sodsl::next_run_at.eq(first_run_at),
+        )).execute(conn)?;
+        Ok(order_id)
+    })
+}
+
+/// Active standing orders where `user` is either the payer or the payee
+pub fn active_for(
+    conn: &diesel::PgConnection,
+    user: UserId,
+) -> QueryResult<Vec<StandingOrder>> {
+    sodsl::standing_orders
+        .select(StandingOrder::cols())
+        .filter(sodsl::cancelled_at.is_null())
+        .filter(sodsl::payer.eq(user).or(sodsl::payee.eq(user)))
+        .order(sodsl::rowid)
+        .get_results(conn)
+}
+
+/// Cancels the order if it's active and `user` is either side of it. Returns the cancelled order, or None if there
+/// was nothing to cancel.
+pub fn cancel(
+    conn: &diesel::PgConnection,
+    order_id: i64,
+    user: UserId,
+    now: DateTime<Utc>,
+) -> QueryResult<Option<StandingOrder>> {
+    diesel::update(
+        sodsl::standing_orders
+            .filter(sodsl::rowid.eq(order_id))
+            .filter(sodsl::cancelled_at.is_null())
+            .filter(sodsl::payer.eq(user).or(sodsl::payee.eq(user)))
+    )
+        .set(sodsl::cancelled_at.eq(now))
+        .returning(StandingOrder::cols())
+        .get_result(conn)
+        .optional()
+}
+
+/// Executes every order that has come due. Each period is recorded in standing_order_runs in the same transaction
+/// as its payment, so a period is paid at most once even if the worker is restarted midway. If the worker was down
+/// for more than a period, only the latest one is paid and the ones it missed are skipped, rather than paying them
+/// all back to back.
+pub fn process_due(
+    conn: &diesel::PgConnection,
+    now: DateTime<Utc>,
+) -> QueryResult<()> {
+    let due:Vec<i64> = sodsl::standing_orders
+        .select(sodsl::rowid)
+        .filter(sodsl::cancelled_at.is_null())
+        .filter(sodsl::next_run_at.le(now))
+        .order(sodsl::next_run_at)
+        .get_results(conn)?;
+    for order_id in due {
+        run_latest_period(conn, order_id, now)?;
+    }
+    Ok(())
+}
+
+fn run_latest_period(
+    conn: &diesel::PgConnection,
+    order_id: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<()> {
+    conn.transaction(|| {
+        let order:StandingOrder = sodsl::standing_orders
+            .select(StandingOrder::cols())
+            .filter(sodsl::rowid.eq(order_id))
+            .for_update()
+            .get_result(conn)?;
+        if order.cancelled_at.is_some() || order.next_run_at > now {
+            return Ok(());
+        }
+        let (period_start, next_run_at) = latest_period(order.next_run_at, order.every_days, now);
+
+        let already_ran:bool = diesel::select(diesel::dsl::exists(
+            sordsl::standing_order_runs
+                .filter(sordsl::standing_order.eq(order.rowid))
+                .filter(sordsl::period_start.eq(period_start))
+        )).get_result(conn)?;
+
+        if !already_ran {
+            let t = TransactionBuilder::new(
+                order.amount,
+                order.ty.clone(),
+                now,
+            ).standing_order(order.payer, order.payee, order.rowid);
+            let failure = match TransferHandler::handle_single(conn, t) {
+                Ok(v) => { v?; None },
+                Err(TransferError::NotEnough) => Some("insufficient balance"),
+                Err(TransferError::Overflow) => Some("overflow"),
+            };
+            if let Some(reason) = failure {
+                notify(conn, order.payer, format!(
+                    "Standing order #{} could not pay {} {} to {}: {}. This period has been skipped.",
+                    order.damm(),
+                    order.amount,
+                    order.ty,
+                    name_of(order.payee),
+                    reason,
+                ), now)?;
+                notify(conn, order.payee, format!(
+                    "Standing order #{} from {} could not pay you {} {} this period: {}.",
+                    order.damm(),
+                    name_of(order.payer),
+                    order.amount,
+                    order.ty,
+                    reason,
+                ), now)?;
+            }
+            diesel::insert_into(sordsl::standing_order_runs).values((
+                sordsl::standing_order.eq(order.rowid),
+                sordsl::period_start.eq(period_start),
+                sordsl::ran_at.eq(now),
+                sordsl::paid.eq(failure.is_none()),
+            )).execute(conn)?;
+        }
+
+        diesel::update(sodsl::standing_orders.filter(sodsl::rowid.eq(order.rowid)))
+            .set(sodsl::next_run_at.eq(next_run_at))
+            .execute(conn)?;
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_schedule,next_period,latest_period};
+    use crate::test_util::words;
+    use chrono::{TimeZone,Utc};
+
+    #[test]
+    fn schedules() {
+        // A Wednesday afternoon in Pacific time
+        let now = Utc.with_ymd_and_hms(2023, 2, 8, 22, 0, 0).unwrap();
+        assert_eq!(parse_schedule(&words("day"), now), Ok((1, now)));
+        assert_eq!(parse_schedule(&words("Week"), now), Ok((7, now)));
+        assert_eq!(parse_schedule(&words("3 days"), now), Ok((3, now)));
+        assert_eq!(parse_schedule(&words("2 weeks"), now), Ok((14, now)));
+        // Next Monday, midnight PST
+        assert_eq!(parse_schedule(&words("monday"), now), Ok((7, Utc.with_ymd_and_hms(2023, 2, 13, 8, 0, 0).unwrap())));
+        assert_eq!(parse_schedule(&words("Mondays"), now), Ok((7, Utc.with_ymd_and_hms(2023, 2, 13, 8, 0, 0).unwrap())));
+        // Today is Wednesday, so the first period already started at midnight
+        assert_eq!(parse_schedule(&words("wed"), now), Ok((7, Utc.with_ymd_and_hms(2023, 2, 8, 8, 0, 0).unwrap())));
+        assert!(parse_schedule(&words("0 days"), now).is_err());
+        assert!(parse_schedule(&words("fortnight"), now).is_err());
+        assert!(parse_schedule(&words(""), now).is_err());
+    }
+
+    #[test]
+    fn periods_follow_pacific_time() {
+        // Midnight PST on the Monday before daylight saving starts, then midnight PDT
+        let monday = Utc.with_ymd_and_hms(2023, 3, 6, 8, 0, 0).unwrap();
+        assert_eq!(next_period(monday, 7), Utc.with_ymd_and_hms(2023, 3, 13, 7, 0, 0).unwrap());
+        assert_eq!(next_period(monday, 1), Utc.with_ymd_and_hms(2023, 3, 7, 8, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn missed_periods_are_skipped() {
+        let start = Utc.with_ymd_and_hms(2023, 2, 6, 8, 0, 0).unwrap();
+        // Due, and on time
+        let now = Utc.with_ymd_and_hms(2023, 2, 6, 9, 0, 0).unwrap();
+        assert_eq!(latest_period(start, 7, now), (start, Utc.with_ymd_and_hms(2023, 2, 13, 8, 0, 0).unwrap()));
+        // Three weeks late, so only the third week's period is paid
+        let now = Utc.with_ymd_and_hms(2023, 2, 28, 0, 0, 0).unwrap();
+        assert_eq!(latest_period(start, 7, now), (
+            Utc.with_ymd_and_hms(2023, 2, 27, 8, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2023, 3, 6, 8, 0, 0).unwrap(),
+        ));
+    }
+}
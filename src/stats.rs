@@ -0,0 +1,310 @@
+//! Aggregate numbers about the economy and about individual users, for the stats and transaction pages. Only the
+//! shared economy is counted.
+
+use std::collections::BTreeMap;
+use chrono::{DateTime,Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{Int8,Text,Timestamptz};
+use crate::models::UserId;
+
+/// Transfers that move fungibles into or out of auction or market escrow rather than creating or destroying them
+const ESCROW_IN:&str = "('auction_create', 'auction_reserve', 'market_reserve', 'sealed_bid_escrow', 'unit_bid_escrow')";
+const ESCROW_OUT:&str = "('auction_refund', 'auction_payout', 'auction_return', 'auction_proceeds', 'market_fill', 'market_refund', 'sealed_bid_refund', 'unit_bid_refund', 'auction_unit_payout')";
+
+#[derive(Debug,Clone,PartialEq,Eq,QueryableByName)]
+pub struct PeriodChange {
+    #[sql_type = "Timestamptz"]
+    pub period: DateTime<Utc>,
+    #[sql_type = "Text"]
+    pub ty: String,
+    /// Created out of thin air: generation and fabrication
+    #[sql_type = "Int8"]
+    pub created: i64,
+    /// Destroyed: economic policies, `$burn`, and fees burned before the treasury existed
+    #[sql_type = "Int8"]
+    pub burned: i64,
+    /// Everything that entered or left users' balances, including auction escrow
+    #[sql_type = "Int8"]
+    pub net: i64,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,QueryableByName)]
+pub struct Holding {
+    #[sql_type = "Int8"]
+    pub user: UserId,
+    #[sql_type = "Text"]
+    pub ty: String,
+    #[sql_type = "Int8"]
+    pub balance: i64,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,QueryableByName)]
+pub struct PeriodTotal {
+    #[sql_type = "Timestamptz"]
+    pub period: DateTime<Utc>,
+    #[sql_type = "Int8"]
+    pub total: i64,
+}
+
+/// How much of each item type was created and destroyed in each `unit` ("day" or "week") since `since`.
+pub fn changes(
+    conn: &diesel::PgConnection,
+    unit: &'static str,
+    since: DateTime<Utc>,
+) -> QueryResult<Vec<PeriodChange>> {
+    diesel::sql_query(format!(r#"
+        select
+            date_trunc('{unit}', happened_at) as period,
+            ty,
+            coalesce(sum(quantity) filter (where from_user is null and transfer_ty::text not in {escrow_out}), 0)::bigint as created,
+            coalesce(sum(quantity) filter (where to_user is null and transfer_ty::text not in {escrow_in}), 0)::bigint as burned,
+            coalesce(sum(case when from_user is null then quantity else -quantity end), 0)::bigint as net
+        from transfers
+        where (from_user is null) <> (to_user is null) and happened_at >= $1 and economy = 0
+        group by 1, 2
+        order by 1, 2
+    "#, unit = unit, escrow_in = ESCROW_IN, escrow_out = ESCROW_OUT))
+        .bind::<Timestamptz, _>(since)
+        .load(conn)
+}
+
+/// Everyone's current balance of everything. User ids up to the treasury's are the pits of fire, debug users and the
+/// treasury itself, and are left out.
+pub fn holdings(
+    conn: &diesel::PgConnection,
+) -> QueryResult<Vec<Holding>> {
+    diesel::sql_query(r#"
+        select * from (
+            select distinct on ("user", ty) "user", ty, balance
+            from balance_history
+            where economy = 0
+            order by "user", ty, happened_at desc, rowid desc, sign desc
+        ) latest
+        where "user" > $1
+        order by balance desc
+    "#)
+        .bind::<Int8, _>(UserId::TREASURY)
+        .load(conn)
+}
+
+/// pc spent on creating and voting on motions, per week since `since`
+pub fn motion_spending(
+    conn: &diesel::PgConnection,
+    since: DateTime<Utc>,
+) -> QueryResult<Vec<PeriodTotal>> {
+    diesel::sql_query(r#"
+        select date_trunc('week', happened_at) as period, sum(quantity)::bigint as total
+        from transfers
+        where transfer_ty in ('motion_create', 'motion_vote') and ty = 'pc' and from_user is not null and happened_at >= $1
+            and economy = 0
+        group by 1
+        order by 1
+    "#)
+        .bind::<Timestamptz, _>(since)
+        .load(conn)
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,QueryableByName)]
+pub struct PeriodBalance {
+    #[sql_type = "Timestamptz"]
+    pub period: DateTime<Utc>,
+    #[sql_type = "Text"]
+    pub ty: String,
+    #[sql_type = "Int8"]
+    pub balance: i64,
+}
+
+/// What one user has done with one item type over some time
+#[derive(Debug,Clone,PartialEq,Eq,QueryableByName)]
+pub struct UserTotals {
+    #[sql_type = "Text"]
+    pub ty: String,
+    #[sql_type = "Int8"]
+    pub generated: i64,
+    /// Spent creating and voting on motions
+    #[sql_type = "Int8"]
+    pub motions: i64,
+    #[sql_type = "Int8"]
+    pub given: i64,
+    #[sql_type = "Int8"]
+    pub received: i64,
+    /// Everything in and out through auctions: bids placed and refunded, lots put up, won, and returned, proceeds
+    #[sql_type = "Int8"]
+    pub auction_net: i64,
+}
+
+/// `user`'s balance of each item type at the end of each day since `since`, for days that had any transactions
+pub fn daily_balances(
+    conn: &diesel::PgConnection,
+    user: UserId,
+    since: DateTime<Utc>,
+) -> QueryResult<Vec<PeriodBalance>> {
+    diesel::sql_query(r#"
+        select distinct on (ty, date_trunc('day', happened_at))
+            date_trunc('day', happened_at) as period,
+            ty,
+            balance
+        from balance_history
+        where "user" = $1 and happened_at >= $2 and economy = 0
+        order by ty, date_trunc('day', happened_at), happened_at desc, rowid desc, sign desc
+    "#)
+        .bind::<Int8, _>(user)
+        .bind::<Timestamptz, _>(since)
+        .load(conn)
+}
+
+pub fn user_totals(
+    conn: &diesel::PgConnection,
+    user: UserId,
+    since: DateTime<Utc>,
+) -> QueryResult<Vec<UserTotals>> {
+    diesel::sql_query(r#"
+        select
+            ty,
+            coalesce(sum(quantity) filter (where transfer_ty = 'generated'), 0)::bigint as generated,
+            coalesce(sum(quantity) filter (where transfer_ty in ('motion_create', 'motion_vote') and sign < 0), 0)::bigint as motions,
+            coalesce(sum(quantity) filter (where transfer_ty in ('give', 'admin_give') and sign < 0), 0)::bigint as given,
+            coalesce(sum(quantity) filter (where transfer_ty in ('give', 'admin_give') and sign > 0), 0)::bigint as received,
+            coalesce(sum(quantity * sign) filter (where transfer_ty::text like 'auction\_%' or transfer_ty::text like 'sealed\_bid\_%' or transfer_ty::text like 'unit\_bid\_%'), 0)::bigint as auction_net
+        from balance_history
+        where "user" = $1 and happened_at >= $2 and economy = 0
+        group by ty
+        order by ty
+    "#)
+        .bind::<Int8, _>(user)
+        .bind::<Timestamptz, _>(since)
+        .load(conn)
+}
+
+/// A finished auction of fungibles that sold some of its lot
+#[derive(Debug,Clone,PartialEq,Eq,QueryableByName)]
+pub struct AuctionSale {
+    #[sql_type = "Int8"]
+    pub auction_id: i64,
+    /// When the lot was paid out
+    #[sql_type = "Timestamptz"]
+    pub cleared_at: DateTime<Utc>,
+    #[sql_type = "Text"]
+    pub offer_ty: String,
+    #[sql_type = "Text"]
+    pub bid_ty: String,
+    /// How much of the lot went to winners
+    #[sql_type = "Int8"]
+    pub units: i64,
+    /// What the winners paid in total
+    #[sql_type = "Int8"]
+    pub paid: i64,
+}
+
+impl AuctionSale {
+    pub fn unit_price(&self) -> f64 {
+        self.paid as f64 / self.units as f64
+    }
+}
+
+/// Every auction of fungibles that sold anything, oldest first. What was sold and for how much comes from the payout
+/// and proceeds transfers, so a sealed auction counts at the second-highest bid, a multi-unit one at its clearing price
+/// and only the units that sold, and one that missed its reserve or was cancelled not at all. Auctions from before the
+/// treasury had no proceeds transfer; their winning bid is what was paid.
+pub fn auction_sales(
+    conn: &diesel::PgConnection,
+) -> QueryResult<Vec<AuctionSale>> {
+    diesel::sql_query(r#"
+        select
+            a.auction_id,
+            max(t.happened_at) as cleared_at,
+            a.offer_ty,
+            a.bid_ty,
+            sum(t.quantity) filter (where t.transfer_ty in ('auction_payout', 'auction_unit_payout'))::bigint as units,
+            coalesce(sum(t.quantity) filter (where t.transfer_ty = 'auction_proceeds'), a.winner_bid)::bigint as paid
+        from auction_and_winner a
+        join transfers t on t.auction_id = a.auction_id and t.transfer_ty in ('auction_payout', 'auction_unit_payout', 'auction_proceeds')
+        where a.finished and a.offer_ty is not null
+        group by a.auction_id, a.offer_ty, a.bid_ty, a.winner_bid
+        having sum(t.quantity) filter (where t.transfer_ty in ('auction_payout', 'auction_unit_payout')) > 0
+        order by cleared_at, a.auction_id
+    "#)
+        .load(conn)
+}
+
+/// For each sale, the average price per unit over it and up to `window - 1` sales before it, weighted by units so a big
+/// lot counts for more than a small one. `sales` should all be of one item type for another, oldest first.
+pub fn moving_average(sales: &[AuctionSale], window: usize) -> Vec<f64> {
+    (0..sales.len()).map(|i| {
+        let recent = &sales[(i + 1).saturating_sub(window)..=i];
+        let paid:f64 = recent.iter().map(|s| s.paid as f64).sum();
+        let units:f64 = recent.iter().map(|s| s.units as f64).sum();
+        paid / units
+    }).collect()
+}
+
+/// Running total of `net` per item type, ie the circulating supply at the end of each period.
+pub fn supply_over_time(changes: &[PeriodChange]) -> BTreeMap<String, Vec<(DateTime<Utc>, i64)>> {
+    let mut supply:BTreeMap<String, Vec<(DateTime<Utc>, i64)>> = BTreeMap::new();
+    for change in changes {
+        let points = supply.entry(change.ty.clone()).or_default();
+        let last = points.last().map(|(_, total)| *total).unwrap_or(0);
+        points.push((change.period, last.saturating_add(change.net)));
+    }
+    supply
+}
+
+/// The Gini coefficient of the given balances: 0 when everyone holds the same amount, approaching 1 when one person
+/// holds everything. None if there's nothing to measure.
+pub fn gini(balances: &[i64]) -> Option<f64> {
+    let mut sorted:Vec<f64> = balances.iter().map(|b| (*b).max(0) as f64).collect();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let n = sorted.len() as f64;
+    let total:f64 = sorted.iter().sum();
+    if sorted.is_empty() || total == 0.0 {
+        return None;
+    }
+    // G = (2 * sum(i * x_i)) / (n * sum(x_i)) - (n + 1) / n, with x sorted ascending and i starting at 1
+    let weighted:f64 = sorted.iter().enumerate().map(|(i, x)| (i as f64 + 1.0) * x).sum();
+    Some((2.0 * weighted) / (n * total) - (n + 1.0) / n)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{gini,moving_average,AuctionSale};
+    use chrono::{TimeZone,Utc};
+
+    fn close(a: Option<f64>, b: f64) -> bool {
+        (a.unwrap() - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn gini_coefficient() {
+        assert_eq!(gini(&[]), None);
+        assert_eq!(gini(&[0, 0]), None);
+        assert!(close(gini(&[5, 5, 5, 5]), 0.0));
+        // One of four holds everything: (n-1)/n
+        assert!(close(gini(&[0, 0, 0, 100]), 0.75));
+        assert!(close(gini(&[100, 0, 0, 0]), 0.75));
+        assert!(close(gini(&[1, 2, 3, 4]), 0.25));
+    }
+
+    fn sale(units: i64, paid: i64) -> AuctionSale {
+        AuctionSale{
+            auction_id: 1,
+            cleared_at: Utc.with_ymd_and_hms(2023, 5, 27, 12, 0, 0).unwrap(),
+            offer_ty: "gen".to_string(),
+            bid_ty: "pc".to_string(),
+            units,
+            paid,
+        }
+    }
+
+    #[test]
+    fn moving_averages() {
+        assert!(moving_average(&[], 4).is_empty());
+        let sales = [sale(10, 1000), sale(10, 2000), sale(20, 6000), sale(5, 500)];
+        assert_eq!(sales[2].unit_price(), 300.0);
+        assert_eq!(moving_average(&sales, 1), vec![100.0, 200.0, 300.0, 100.0]);
+        // Weighted by units: (2000 + 6000) / (10 + 20)
+        assert!(close(Some(moving_average(&sales, 2)[2]), 8000.0 / 30.0));
+        assert_eq!(moving_average(&sales, 4)[3], 9500.0 / 45.0);
+        // A window longer than the history averages everything so far
+        assert_eq!(moving_average(&sales, 12), moving_average(&sales, 4));
+    }
+}
@@ -1,59 +1,101 @@
 use std::sync::Arc;
+use std::collections::HashSet;
 use serenity::framework::standard::CommandResult;
 use serenity::http::CacheHttp;
 use diesel::prelude::*;
 use tokio_diesel::{AsyncRunQueryDsl,AsyncConnection};
-use chrono::{Utc,TimeZone};
 use crate::damm;
 use crate::schema;
 use crate::view_schema;
 use crate::bot;
 use crate::bot::DbPool;
 use crate::is_win::is_win;
+use crate::models::{UserId,TransferType,AuctionLot};
+use crate::transfers::{CurrencyId,TransactionBuilder,TransferHandler};
+use crate::guilds::SHARED_ECONOMY;
 
 pub async fn create_auto_auctions(
     pool: &Arc<DbPool>,
     cnh: &impl CacheHttp,
 ) -> CommandResult {
     use diesel::prelude::*;
-    use schema::single::dsl as sdsl;
     use schema::auctions::dsl as adsl; //asymmetric digital subscriber line
     use schema::thing_ids::dsl as tid;
 
     let now = chrono::Utc::now();
-    let maybe_last_auction:Option<chrono::DateTime<chrono::Utc>> = sdsl::single.select(sdsl::last_auto_auction).get_result_async(pool).await?;
-    if let Some(last_auction) = maybe_last_auction {
-        let mut next_auction = chrono_tz::US::Pacific.from_utc_datetime(&last_auction.naive_utc());
-        next_auction = next_auction + *crate::AUTO_AUCTION_EVERY;
-        next_auction = next_auction.date_naive().and_time(*crate::AUTO_AUCTION_AT).and_local_timezone(chrono_tz::US::Pacific).unwrap();
-
-        if now > next_auction {
-            let now = Utc::now();
-            let auction_id:i64 = pool.transaction(|conn| {
-                let auction_id:i64 = diesel::insert_into(tid::thing_ids).default_values().returning(tid::rowid).get_result(conn)?;
-                diesel::insert_into(adsl::auctions).values((
-                    adsl::rowid.eq(auction_id),
-                    adsl::created_at.eq(now),
-                    adsl::auctioneer.eq(None:Option<i64>),
-                    adsl::offer_ty.eq("gen"),
-                    adsl::offer_amt.eq(10i64),
-                    adsl::bid_ty.eq("pc"),
-                    adsl::bid_min.eq(1i64),
-                    adsl::last_timer_bump.eq(now),
-                ))
-                .execute(conn)?;
-                diesel::update(sdsl::single).set(sdsl::last_auto_auction.eq(next_auction)).execute(conn)?;
-                Ok(auction_id)
-            }).await?;
+    // Any that were missed while the worker was down start once, now, rather than once for each time they missed
+    let started:Vec<(i64, crate::models::AutoAuction)> = pool.transaction(move |conn| {
+        let mut started = Vec::new();
+        for auto_auction in crate::auto_auctions::take_due(conn, now)? {
+            let offer_amt = auto_auction.offer_amt;
+            let offer_ty = auto_auction.offer_ty.clone();
+            let auction_id:i64 = diesel::insert_into(tid::thing_ids).default_values().returning(tid::rowid).get_result(conn)?;
+            diesel::insert_into(adsl::auctions).values((
+                adsl::rowid.eq(auction_id),
+                adsl::created_at.eq(now),
+                adsl::auctioneer.eq(Some(UserId::TREASURY.into_i64())),
+                adsl::offer_ty.eq(&offer_ty),
+                adsl::offer_amt.eq(offer_amt),
+                adsl::bid_ty.eq(&auto_auction.bid_ty),
+                adsl::bid_min.eq(auto_auction.bid_min),
+                adsl::last_timer_bump.eq(now),
+                adsl::multi_unit.eq(auto_auction.multi_unit),
+                adsl::duration_minutes.eq(auto_auction.duration_minutes),
+            ))
+            .execute(conn)?;
 
-            serenity::model::id::ChannelId::from(bot::MOTIONS_CHANNEL).send_message(cnh.http(), |m| {
-                m.content(format!(
-                    "New auction#{0} started! The Consortium offers 10 gens for bids in pc. Visit {1}/auctions/{0} to bid.",
-                    damm::add_to_str(auction_id.to_string()),
-                    crate::SITE_URL,
-                ))
-            }).await?;
+            // The lot comes out of the treasury, topped up with freshly minted fungibles if it doesn't have enough
+            let mut handle = TransferHandler::new(
+                conn,
+                vec![UserId::TREASURY],
+                vec![offer_ty.clone()],
+            )?;
+            let treasury_has = handle.balance(UserId::TREASURY, offer_ty.clone());
+            if treasury_has < offer_amt {
+                let t = TransactionBuilder::new(
+                    offer_amt - treasury_has,
+                    offer_ty.clone(),
+                    now,
+                ).treasury_mint();
+                handle.transfer(t).unwrap()?;
+            }
+            let t = TransactionBuilder::new(
+                offer_amt,
+                offer_ty,
+                now,
+            ).auction_create(UserId::TREASURY, auction_id);
+            handle.transfer(t).unwrap()?;
+            started.push((auction_id, auto_auction));
         }
+        Ok(started)
+    }).await?;
+
+    for (auction_id, auto_auction) in started {
+        let damm_id = damm::add_to_str(auction_id.to_string());
+        let how = if auto_auction.multi_unit {
+            format!(
+                "sold by the unit for bids in {0}; everyone who gets any pays the same price each. Visit {1}/auctions/{2} to bid, or `$bid {2} <quantity> at <price each>`.",
+                auto_auction.bid_ty,
+                crate::SITE_URL,
+                damm_id,
+            )
+        } else {
+            format!(
+                "for bids in {0}. Visit {1}/auctions/{2} to bid, or `$bid {2} <amount>`.",
+                auto_auction.bid_ty,
+                crate::SITE_URL,
+                damm_id,
+            )
+        };
+        serenity::model::id::ChannelId::from(bot::MOTIONS_CHANNEL).send_message(cnh.http(), |m| {
+            m.content(format!(
+                "New auction#{} started! The Consortium offers {} {}, {}",
+                damm_id,
+                auto_auction.offer_amt,
+                auto_auction.offer_ty,
+                how,
+            ))
+        }).await?;
     }
     Ok(())
 }
@@ -64,7 +106,6 @@ pub async fn process_auctions(
 ) -> CommandResult {
     use diesel::prelude::*;
     use view_schema::auction_and_winner::dsl as anw;
-    use schema::auctions::dsl as adsl; //asymmetric digital subscriber line
 
     let now = chrono::Utc::now();
 
@@ -76,56 +117,235 @@ pub async fn process_auctions(
         .get_results_async(pool).await?;
 
     for auction in auctions_needing_processing {
-        let finishes_at = auction.last_timer_bump + *crate::AUCTION_EXPIRATION;
-        if finishes_at < now {
-            if let Some(user_id) = auction.winner_id {
-                pool.transaction(|conn| {
-                    let mut handle = crate::transfers::TransferHandler::new(
-                        conn,
-                        vec![user_id],
-                        vec![auction.offer_ty.clone()],
-                    )?;
-
-                    let t = crate::transfers::TransactionBuilder::new(
-                        auction.offer_amt,
-                        auction.offer_ty.clone(),
+        if auction.end_at() >= now {
+            continue;
+        }
+        let auction_id = auction.auction_id;
+        // An auction that can't be settled is left unfinished to try again next time, rather than holding up the others
+        // and everything else the worker does
+        if let Err(e) = finish_auction(pool, cnh, auction, now).await {
+            warn!("Could not finish auction#{}: {:?}", auction_id, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Settles an auction that has ended and announces how it went
+async fn finish_auction(
+    pool: &Arc<DbPool>,
+    cnh: &impl CacheHttp,
+    mut auction: crate::models::AuctionWinner,
+    now: chrono::DateTime<chrono::Utc>,
+) -> CommandResult {
+    use diesel::prelude::*;
+    use schema::auctions::dsl as adsl; //asymmetric digital subscriber line
+
+    if auction.multi_unit {
+        let (fills, clearing_price) = pool.transaction(|conn| {
+            crate::auctions::settle_multi_unit(conn, &auction, now)
+        }).await?;
+        let winners = fills.iter()
+            .filter(|(_, fill)| *fill > 0)
+            .map(|(bid, fill)| format!("{} {}", crate::names::name_of(bid.bidder), fill))
+            .collect::<Vec<_>>();
+        let summary = match clearing_price {
+            Some(price) => format!(
+                "Units went to {}, at {} {} each.",
+                winners.join(", "),
+                price,
+                auction.bid_ty,
+            ),
+            None => "There were no bids, and the lot goes back to the auctioneer.".to_string(),
+        };
+        serenity::model::id::ChannelId::from(bot::MOTIONS_CHANNEL).send_message(cnh.http(), |m| {
+            m.content(format!(
+                "Auction#{0} finished. {2} Visit {1}/auctions/{0} for more details.",
+                damm::add_to_str(auction.auction_id.to_string()),
+                crate::SITE_URL,
+                summary,
+            ))
+        }).await?;
+    } else {
+        // A sealed auction has no winner until its bids are revealed. Settling it turns the winning bid into an
+        // ordinary winning bid at the second-highest price, so this is what auction_and_winner will show afterwards.
+        let sealed_bids = if auction.sealed {
+            let auction_id = auction.auction_id;
+            let bids = pool.transaction(move |conn| crate::auctions::sealed_bids(conn, auction_id)).await?;
+            if let Some((winner, price)) = crate::auctions::sealed_price(&bids, auction.bid_min) {
+                auction.winner_id = Some(winner);
+                auction.winner_bid = Some(price);
+            }
+            bids
+        } else { vec![] };
+        // A high bid below the reserve is given back, and the lot goes unsold as if nobody had bid
+        let unmet_reserve = auction.winner().filter(|_| !auction.reserve_met());
+        if unmet_reserve.is_some() {
+            auction.winner_id = None;
+            auction.winner_bid = None;
+        }
+        if let Some(user_id) = auction.winner_id {
+            // Auctions from before the treasury existed have no auctioneer; their proceeds go to the treasury too
+            let proceeds_to = auction.auctioneer
+                .map(|a| UserId::try_from(a).unwrap())
+                .unwrap_or(UserId::TREASURY);
+            pool.transaction(|conn| {
+                let mut currencies = vec![auction.bid_ty.clone()];
+                currencies.extend(auction.offer_ty.clone());
+                let mut users = vec![user_id, proceeds_to];
+                users.extend(sealed_bids.iter().map(|b| b.bidder));
+                let mut handle = TransferHandler::new(
+                    conn,
+                    users,
+                    currencies,
+                )?;
+
+                if auction.sealed {
+                    crate::auctions::settle_sealed(&mut handle, &auction, &sealed_bids, now)?;
+                }
+
+                match auction.lot() {
+                    AuctionLot::Fungible{amount, ty} => {
+                        let t = TransactionBuilder::new(
+                            amount,
+                            ty.clone(),
+                            now,
+                        ).auction_payout(
+                            user_id,
+                            &auction,
+                        );
+
+                        handle.transfer(t).unwrap()?;
+                    },
+                    AuctionLot::Item{item_id, name: _} => {
+                        crate::items::release_from_auction(conn, item_id, user_id, TransferType::AuctionPayout, auction.auction_id, now)?;
+                    },
+                }
+
+                let t = TransactionBuilder::new(
+                    auction.winner_bid.unwrap(),
+                    auction.bid_ty.clone(),
+                    now,
+                ).auction_proceeds(
+                    proceeds_to,
+                    &auction,
+                );
+
+                handle.transfer(t).unwrap()?;
+                diesel::update(
+                    adsl::auctions.filter(
+                        adsl::rowid.eq(auction.auction_id)
+                    )
+                ).set(adsl::finished.eq(true)).execute(conn)?;
+        
+                Ok(())
+            }).await?;
+
+            // The reveal: every sealed bid, highest first
+            let details = if auction.sealed {
+                format!(
+                    " They pay {} {}, the second-highest bid. Sealed bids: {}.",
+                    auction.winner_bid.unwrap(),
+                    auction.bid_ty,
+                    sealed_bids.iter()
+                        .map(|b| format!("{} {} {}", crate::names::name_of(b.bidder), b.amount, auction.bid_ty))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                )
+            } else if auction.is_dutch() || auction.bought_at.is_some() {
+                format!(" They bought it outright for {} {}.", auction.winner_bid.unwrap(), auction.bid_ty)
+            } else { String::new() };
+            serenity::model::id::ChannelId::from(bot::MOTIONS_CHANNEL).send_message(cnh.http(), |m| {
+                use serenity::prelude::Mentionable;
+                m.content(format!(
+                    "Auction#{0} finished. {2} received {3}.{4} Visit {1}/auctions/{0} for more details.",
+                    damm::add_to_str(auction.auction_id.to_string()),
+                    crate::SITE_URL,
+                    user_id.into_serenity().mention(),
+                    auction.lot_description(),
+                    details,
+                ))
+            }).await?;
+        } else {
+            let returned_to:Option<UserId> = pool.transaction(|conn| {
+                use schema::transfers::dsl as tdsl;
+                if let Some((bidder, amount)) = unmet_reserve {
+                    let t = TransactionBuilder::new(
+                        amount,
+                        auction.bid_ty.clone(),
                         now,
-                    ).auction_payout(
-                        user_id,
+                    ).auction_refund(
+                        bidder,
                         &auction,
                     );
-
-                    handle.transfer(t).unwrap()?;
-                    diesel::update(
-                        adsl::auctions.filter(
-                            adsl::rowid.eq(auction.auction_id)
-                        )
-                    ).set(adsl::finished.eq(true)).execute(conn)?;
-            
-                    Ok(())
-                }).await?;
-
-                serenity::model::id::ChannelId::from(bot::MOTIONS_CHANNEL).send_message(cnh.http(), |m| {
-                    use serenity::prelude::Mentionable;
-                    m.content(format!(
-                        "Auction#{0} finished. {2} received {3} {4}. Visit {1}/auctions/{0} for more details.",
-                        damm::add_to_str(auction.auction_id.to_string()),
-                        crate::SITE_URL,
-                        user_id.into_serenity().mention(),
-                        auction.offer_amt,
-                        auction.offer_ty.as_str(),
-                    ))
-                }).await?;
+                    TransferHandler::handle_single(conn, t).unwrap()?;
+                }
+                let escrowed_by = match auction.lot() {
+                    AuctionLot::Fungible{amount, ty} => {
+                        // Only auctions whose offer was escrowed at creation have anything to give back
+                        let escrowed_by:Option<Option<UserId>> = tdsl::transfers
+                            .select(tdsl::from_user)
+                            .filter(tdsl::auction_id.eq(auction.auction_id))
+                            .filter(tdsl::transfer_ty.eq(TransferType::AuctionCreate))
+                            .get_result(conn)
+                            .optional()?;
+                        let escrowed_by = escrowed_by.flatten();
+                        if let Some(creator) = escrowed_by {
+                            let t = TransactionBuilder::new(
+                                amount,
+                                ty.clone(),
+                                now,
+                            ).auction_return(
+                                creator,
+                                &auction,
+                            );
+                            TransferHandler::handle_single(conn, t).unwrap()?;
+                        }
+                        escrowed_by
+                    },
+                    AuctionLot::Item{item_id, name: _} => {
+                        let escrowed_by = crate::items::escrowed_by(conn, item_id, auction.auction_id)?;
+                        if let Some(creator) = escrowed_by {
+                            crate::items::release_from_auction(conn, item_id, creator, TransferType::AuctionReturn, auction.auction_id, now)?;
+                        }
+                        escrowed_by
+                    },
+                };
+                diesel::update(adsl::auctions.filter(adsl::rowid.eq(auction.auction_id))).set(adsl::finished.eq(true)).execute(conn)?;
+                Ok(escrowed_by)
+            }).await?;
+            let returned = returned_to.map(|u| format!(" and the lot goes back to {}", crate::names::name_of(u))).unwrap_or_default();
+            let content = if auction.cancelled_at.is_some() {
+                format!(
+                    "Auction#{0} was cancelled by {2}{3}. Visit {1}/auctions/{0} for no details.",
+                    damm::add_to_str(auction.auction_id.to_string()),
+                    crate::SITE_URL,
+                    auction.auctioneer_name(),
+                    returned,
+                )
+            } else if let Some((bidder, amount)) = unmet_reserve {
+                format!(
+                    "Auction#{0} finished. The high bid of {2} {3} didn't meet the reserve price of {4} {3}, so {5} gets their bid back, \
+                    no one gets anything{6}. Visit {1}/auctions/{0} for more details.",
+                    damm::add_to_str(auction.auction_id.to_string()),
+                    crate::SITE_URL,
+                    amount,
+                    auction.bid_ty,
+                    auction.reserve_price.unwrap(),
+                    crate::names::name_of(bidder),
+                    returned,
+                )
             } else {
-                diesel::update(adsl::auctions.filter(adsl::rowid.eq(auction.auction_id))).set(adsl::finished.eq(true)).execute_async(pool).await?;
-                serenity::model::id::ChannelId::from(bot::MOTIONS_CHANNEL).send_message(cnh.http(), |m| {
-                    m.content(format!(
-                        "Auction#{0} finished. There were no bids, no one gets anything. Visit {1}/auctions/{0} for no details.",
-                        damm::add_to_str(auction.auction_id.to_string()),
-                        crate::SITE_URL,
-                    ))
-                }).await?;
-            }
+                format!(
+                    "Auction#{0} finished. There were no bids, no one gets anything{2}. Visit {1}/auctions/{0} for no details.",
+                    damm::add_to_str(auction.auction_id.to_string()),
+                    crate::SITE_URL,
+                    returned,
+                )
+            };
+            serenity::model::id::ChannelId::from(bot::MOTIONS_CHANNEL).send_message(cnh.http(), |m| {
+                m.content(content)
+            }).await?;
         }
     }
 
@@ -138,8 +358,6 @@ pub fn process_generators(
     use diesel::prelude::*;
     use crate::schema::transfers::dsl as tdsl;
     use crate::schema::single::dsl as sdsl;
-    use crate::models::UserId;
-    use crate::transfers::{CurrencyId,TransactionBuilder,TransferHandler};
     let now = chrono::Utc::now();
     let last_gen:chrono::DateTime<chrono::Utc> = sdsl::single.select(sdsl::last_gen).get_result(&*conn)?;
 
@@ -153,23 +371,38 @@ pub fn process_generators(
     conn.transaction::<_, diesel::result::Error, _>(|| {
         diesel::sql_query("LOCK TABLE transfers IN EXCLUSIVE MODE;").execute(&*conn)?;
 
-        let users:Vec<Option<UserId>> = tdsl::transfers.select(tdsl::to_user).distinct().filter(tdsl::ty.eq(CurrencyId::GEN)).filter(tdsl::to_user.is_not_null()).get_results(&*conn)?;
-        for userid_o in &users {
-            let userid = userid_o.unwrap();
-
-            let mut handle = TransferHandler::new(
+        let gen_holders:Vec<(i64, Option<UserId>)> = tdsl::transfers.select((tdsl::economy, tdsl::to_user)).distinct().filter(tdsl::ty.eq(CurrencyId::GEN)).filter(tdsl::to_user.is_not_null()).get_results(&*conn)?;
+        let gen_users:HashSet<(i64, UserId)> = gen_holders.iter().map(|(economy, u)| (*economy, u.unwrap())).collect();
+        let policies = crate::economic_policy::get_all(&*conn)?;
+        let mut users:Vec<(i64, UserId)> = gen_users.iter().copied().collect();
+        // Economic policies are set by motions in the shared economy, so they only apply there
+        if crate::economic_policy::charges_pc_holders(&policies) {
+            let pc_users:Vec<Option<UserId>> = tdsl::transfers.select(tdsl::to_user).distinct().filter(tdsl::ty.eq(CurrencyId::PC)).filter(tdsl::to_user.is_not_null()).filter(tdsl::economy.eq(SHARED_ECONOMY)).get_results(&*conn)?;
+            users.extend(pc_users.into_iter().map(|u| (SHARED_ECONOMY, u.unwrap())));
+        }
+        users.sort();
+        users.dedup();
+        for (economy, userid) in users {
+            let mut handle = TransferHandler::in_economy(
                 &*conn,
+                economy,
                 vec![userid],
                 vec![CurrencyId::PC, CurrencyId::GEN],
             )?;
 
-            let gen_balance = handle.balance(userid, CurrencyId::GEN);
-            let t = TransactionBuilder::new(
-                gen_balance,
-                CurrencyId::PC,
-                now,
-            ).fabricate(userid, true);
-            handle.transfer(t).unwrap()?;
+            if gen_users.contains(&(economy, userid)) {
+                let gen_balance = handle.balance(userid, CurrencyId::GEN);
+                let t = TransactionBuilder::new(
+                    gen_balance,
+                    CurrencyId::PC,
+                    now,
+                ).fabricate(userid, true);
+                handle.transfer(t).unwrap()?;
+            }
+
+            if economy == SHARED_ECONOMY {
+                crate::economic_policy::apply(&mut handle, &policies, userid, now)?;
+            }
         }
 
         diesel::update(sdsl::single).set(sdsl::last_gen.eq(this_gen)).execute(&*conn)?;
@@ -191,14 +424,15 @@ pub async fn process_motion_completions(
     use diesel::prelude::*;
     use schema::motions::dsl as mdsl;
     use schema::motion_votes::dsl as mvdsl;
+    use schema::guilds::dsl as gdsl;
     use bigdecimal::BigDecimal;
     let now = chrono::Utc::now();
-    let motions:Vec<(String, i64, BigDecimal)> = mdsl::motions
+    let motions:Vec<(String, i64, BigDecimal, Option<i64>, Option<i64>)> = mdsl::motions
         .filter(mdsl::announcement_message_id.is_null())
         .filter(mdsl::last_result_change.lt(now - *crate::MOTION_EXPIRATION))
-        .select((mdsl::motion_text, mdsl::rowid, mdsl::power))
+        .select((mdsl::motion_text, mdsl::rowid, mdsl::power, mdsl::guild_id, mdsl::channel_id))
         .get_results_async(pool).await?;
-    for (motion_text, motion_id, power) in &motions {
+    for (motion_text, motion_id, power, guild_id, channel_id) in &motions {
         #[derive(Queryable,Debug)]
         struct MotionVote {
             amount:i64,
@@ -219,7 +453,22 @@ pub async fn process_motion_completions(
         }
         let pass = is_win(yes_votes, no_votes, power);
         let pass_msg = if pass { "PASSED" } else { "FAILED" }; 
-        let announce_msg = serenity::model::id::ChannelId::from(bot::MOTIONS_CHANNEL).send_message(cnh.http(), |m| {
+        let action_outcome = {
+            let motion_id = *motion_id;
+            pool.transaction(move |conn| crate::motion_actions::run_for_motion(conn, motion_id, pass, now)).await?
+        };
+        let announcement_channel:Option<i64> = match guild_id {
+            Some(guild_id) => gdsl::guilds
+                .select(gdsl::announcement_channel)
+                .filter(gdsl::guild_id.eq(*guild_id))
+                .get_result_async(pool)
+                .await
+                .optional()?
+                .flatten(),
+            None => None,
+        };
+        let announce_to = announcement_channel.or(*channel_id).map(|id| id as u64).unwrap_or(bot::MOTIONS_CHANNEL);
+        let announce_msg = serenity::model::id::ChannelId::from(announce_to).send_message(cnh.http(), |m| {
             m.embed(|e| {
                 e.title(
                     format!(
@@ -235,6 +484,9 @@ pub async fn process_motion_completions(
                 }else{
                     e.field("Votes", format!("**against {}**/{} for", no_votes, yes_votes), false);
                 }
+                if let Some(outcome) = &action_outcome {
+                    e.field("Action", outcome, false);
+                }
                 e
             })
         }).await?;
@@ -244,18 +496,63 @@ pub async fn process_motion_completions(
         ).execute_async(pool).await?;
     }
 
-    let mmids:Vec<i64> = mdsl::motions
+    let mmids:Vec<(i64, Option<i64>)> = mdsl::motions
         .filter(mdsl::announcement_message_id.is_null())
         .filter(mdsl::needs_update)
-        .select(mdsl::bot_message_id)
+        .select((mdsl::bot_message_id, mdsl::channel_id))
         .get_results_async(pool).await?;
-    for mmid in &mmids {
-        let mut motion_message = cnh.http().get_message(bot::MOTIONS_CHANNEL, *mmid as u64).await?;
+    for (mmid, channel_id) in &mmids {
+        let channel_id = channel_id.map(|id| id as u64).unwrap_or(bot::MOTIONS_CHANNEL);
+        let mut motion_message = cnh.http().get_message(channel_id, *mmid as u64).await?;
         bot::update_motion_message(cnh, Arc::clone(pool), &mut motion_message).await?;
     }
     Ok(())
 }
 
+pub fn process_standing_orders(
+    conn: &diesel::PgConnection
+) -> Result<(), diesel::result::Error> {
+    crate::standing_orders::process_due(conn, chrono::Utc::now())
+}
+
+pub fn process_dutch_auctions(
+    conn: &diesel::PgConnection
+) -> Result<(), diesel::result::Error> {
+    crate::auctions::reprice_dutch(conn, chrono::Utc::now())
+}
+
+pub fn process_loans(
+    conn: &diesel::PgConnection
+) -> Result<(), diesel::result::Error> {
+    crate::loans::process_due(conn, chrono::Utc::now())
+}
+
+pub async fn send_notifications(
+    pool: &Arc<DbPool>,
+    cnh: &impl CacheHttp,
+) -> CommandResult {
+    use schema::notifications::dsl as ndsl;
+
+    let unsent:Vec<(i64, UserId, String)> = ndsl::notifications
+        .select((ndsl::rowid, ndsl::recipient, ndsl::body))
+        .filter(ndsl::sent_at.is_null())
+        .order(ndsl::rowid)
+        .get_results_async(pool).await?;
+    for (rowid, recipient, body) in unsent {
+        // Mark it sent first; a DM that gets lost is better than one that gets sent every five seconds forever.
+        diesel::update(ndsl::notifications.filter(ndsl::rowid.eq(rowid)))
+            .set(ndsl::sent_at.eq(diesel::dsl::now))
+            .execute_async(pool).await?;
+        let res = async {
+            recipient.into_serenity().create_dm_channel(cnh.http()).await?.say(cnh.http(), body).await
+        }.await;
+        if let Err(e) = res {
+            warn!("Could not send notification {} to {}: {:?}", rowid, recipient, e);
+        }
+    }
+    Ok(())
+}
+
 pub fn update_last_task_run(
     conn: &diesel::PgConnection
 ) -> Result<(), diesel::result::Error> {
@@ -0,0 +1,6 @@
+//! Helpers shared by the unit tests
+
+/// Splits arguments into words the way commands receive them
+pub fn words(s: &str) -> Vec<String> {
+    s.split_whitespace().map(String::from).collect()
+}
@@ -0,0 +1,188 @@
+use chrono::{DateTime,Utc};
+use diesel::prelude::*;
+use crate::schema::trade_offers::dsl as todsl;
+use crate::schema::thing_ids::dsl as tid;
+use crate::models::{UserId,TradeOffer,TradeStatus};
+use crate::transfers::{CurrencyId,TransactionBuilder,TransferHandler,TransferError};
+use crate::names::name_of;
+
+#[derive(Debug)]
+pub enum TradeError {
+    NotFound,
+    /// Someone other than the counterparty tried to accept
+    NotCounterparty,
+    /// Someone who isn't either side of the trade tried to cancel or decline
+    NotAParty,
+    NotPending(TradeStatus),
+    /// The given user doesn't have enough to cover their side of the trade
+    NotEnough(UserId),
+    Overflow,
+    Db(diesel::result::Error),
+}
+
+impl From<diesel::result::Error> for TradeError {
+    fn from(e: diesel::result::Error) -> Self {
+        TradeError::Db(e)
+    }
+}
+
+impl std::fmt::Display for TradeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TradeError::NotFound => write!(f, "Trade offer not found."),
+            TradeError::NotCounterparty => write!(f, "Only the user this offer was made to can accept it."),
+            TradeError::NotAParty => write!(f, "You are not part of this trade."),
+            TradeError::NotPending(TradeStatus::Accepted) => write!(f, "This trade has already been accepted."),
+            TradeError::NotPending(TradeStatus::Cancelled) => write!(f, "This trade offer was cancelled."),
+            TradeError::NotPending(TradeStatus::Expired) => write!(f, "This trade offer has expired."),
+            TradeError::NotPending(TradeStatus::Pending) => unreachable!(),
+            TradeError::NotEnough(user) => write!(f, "Trade failed: {} does not have enough to cover their side.", name_of(*user)),
+            TradeError::Overflow => write!(f, "Trade failed: Overflow."),
+            TradeError::Db(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TradeError {}
+
+/// A one-line human description, eg "Shelvacu offers 10 gen to Colin for 400 pc"
+pub fn describe(trade: &TradeOffer) -> String {
+    format!(
+        "{} offers {} {} to {} for {} {}",
+        name_of(trade.offerer),
+        trade.offer_amt,
+        trade.offer_ty,
+        name_of(trade.counterparty),
+        trade.want_amt,
+        trade.want_ty,
+    )
+}
+
+pub fn create_offer(
+    conn: &diesel::PgConnection,
+    offerer: UserId,
+    counterparty: UserId,
+    offer: (i64, CurrencyId),
+    want: (i64, CurrencyId),
+    now: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+) -> QueryResult<i64> {
+    conn.transaction(|| {
+        let trade_id:i64 = diesel::insert_into(tid::thing_ids).default_values().returning(tid::rowid).get_result(conn)?;
+        diesel::insert_into(todsl::trade_offers).values((
+            todsl::rowid.eq(trade_id),
+            todsl::created_at.eq(now),
+            todsl::offerer.eq(offerer),
+            todsl::counterparty.eq(counterparty),
+            todsl::offer_amt.eq(offer.0),
+            todsl::offer_ty.eq(offer.1),
+            todsl::want_amt.eq(want.0),
+            todsl::want_ty.eq(want.1),
+            todsl::expires_at.eq(expires_at),
+        )).execute(conn)?;
+        Ok(trade_id)
+    })
+}
+
+pub fn set_message_id(
+    conn: &diesel::PgConnection,
+    trade_id: i64,
+    message_id: i64,
+) -> QueryResult<()> {
+    diesel::update(todsl::trade_offers.filter(todsl::rowid.eq(trade_id)))
+        .set(todsl::message_id.eq(message_id))
+        .execute(conn)
+        .map(|_| ())
+}
+
+pub fn find_by_message(
+    conn: &diesel::PgConnection,
+    message_id: i64,
+) -> QueryResult<Option<i64>> {
+    todsl::trade_offers
+        .select(todsl::rowid)
+        .filter(todsl::message_id.eq(message_id))
+        .get_result(conn)
+        .optional()
+}
+
+fn lock_pending(
+    conn: &diesel::PgConnection,
+    trade_id: i64,
+    now: DateTime<Utc>,
+) -> Result<TradeOffer, TradeError> {
+    let trade:TradeOffer = todsl::trade_offers
+        .select(TradeOffer::cols())
+        .filter(todsl::rowid.eq(trade_id))
+        .for_update()
+        .get_result(conn)
+        .optional()?
+        .ok_or(TradeError::NotFound)?;
+    match trade.status(now) {
+        TradeStatus::Pending => Ok(trade),
+        status => Err(TradeError::NotPending(status)),
+    }
+}
+
+/// Executes both legs of the trade, or neither.
+pub fn accept(
+    conn: &diesel::PgConnection,
+    trade_id: i64,
+    accepter: UserId,
+    now: DateTime<Utc>,
+) -> Result<TradeOffer, TradeError> {
+    conn.transaction(|| {
+        let mut trade = lock_pending(conn, trade_id, now)?;
+        if trade.counterparty != accepter {
+            return Err(TradeError::NotCounterparty);
+        }
+
+        let mut handle = TransferHandler::new(
+            conn,
+            vec![trade.offerer, trade.counterparty],
+            vec![trade.offer_ty.clone(), trade.want_ty.clone()],
+        )?;
+
+        let legs = [
+            (trade.offerer, trade.counterparty, trade.offer_amt, trade.offer_ty.clone()),
+            (trade.counterparty, trade.offerer, trade.want_amt, trade.want_ty.clone()),
+        ];
+        for (from, to, amount, ty) in legs {
+            let t = TransactionBuilder::new(amount, ty, now).trade(from, to, trade.rowid);
+            match handle.transfer(t) {
+                Err(TransferError::NotEnough) => return Err(TradeError::NotEnough(from)),
+                Err(TransferError::Overflow) => return Err(TradeError::Overflow),
+                Ok(v) => v?,
+            }
+        }
+
+        diesel::update(todsl::trade_offers.filter(todsl::rowid.eq(trade.rowid)))
+            .set(todsl::accepted_at.eq(now))
+            .execute(conn)?;
+        trade.accepted_at = Some(now);
+
+        Ok(trade)
+    })
+}
+
+/// Withdraws (if done by the offerer) or declines (if done by the counterparty) a pending trade.
+pub fn cancel(
+    conn: &diesel::PgConnection,
+    trade_id: i64,
+    user: UserId,
+    now: DateTime<Utc>,
+) -> Result<TradeOffer, TradeError> {
+    conn.transaction(|| {
+        let mut trade = lock_pending(conn, trade_id, now)?;
+        if user != trade.offerer && user != trade.counterparty {
+            return Err(TradeError::NotAParty);
+        }
+
+        diesel::update(todsl::trade_offers.filter(todsl::rowid.eq(trade.rowid)))
+            .set(todsl::cancelled_at.eq(now))
+            .execute(conn)?;
+        trade.cancelled_at = Some(now);
+
+        Ok(trade)
+    })
+}
@@ -13,7 +13,7 @@ use crate::models::{UserId,TransferType,AuctionWinner};
 
 type CurrencyIdStr = Cow<'static, str>;
 
-#[derive(Debug,Clone,PartialEq,Eq,PartialOrd,Ord,Hash,FromSqlRow,AsExpression)]
+#[derive(Debug,Clone,PartialEq,Eq,PartialOrd,Ord,Hash,FromSqlRow,AsExpression,Serialize,Deserialize)]
 #[sql_type = "Text"]
 pub struct CurrencyId(CurrencyIdStr);
 
@@ -73,6 +73,7 @@ pub struct TransactionBuilder {
     comment: Option<String>,
     transfer_ty: Option<TransferType>,
     auction_id: Option<i64>,
+    thing_id: Option<i64>,
 }
 
 impl TransactionBuilder {
@@ -93,6 +94,7 @@ impl TransactionBuilder {
             comment: None,
             transfer_ty: None,
             auction_id: None,
+            thing_id: None,
         }
     }
 
@@ -118,6 +120,7 @@ impl TransactionBuilder {
     ) -> Self {
         assert!(self.transfer_ty.is_none());
         self.source = Some(source);
+        self.dest = Some(UserId::TREASURY);
         self.to_motion = Some(motion_id);
         self.to_votes = Some(num_votes);
         self.transfer_ty = Some(if create { TransferType::MotionCreate } else { TransferType::MotionVote });
@@ -135,17 +138,17 @@ impl TransactionBuilder {
         self
     }
 
-    // pub fn auction_create(
-    //     mut self,
-    //     source: UserId,
-    //     auction_id: i64,
-    // ) -> Self {
-    //     assert!(self.transfer_ty.is_none());
-    //     self.auction_id = Some(auction_id);
-    //     self.source = Some(source);
-    //     self.transfer_ty = Some(TransferType::AuctionCreate);
-    //     self
-    // }
+    pub fn auction_create(
+        mut self,
+        source: UserId,
+        auction_id: i64,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        self.auction_id = Some(auction_id);
+        self.source = Some(source);
+        self.transfer_ty = Some(TransferType::AuctionCreate);
+        self
+    }
 
     pub fn auction_reserve(
         mut self,
@@ -179,7 +182,7 @@ impl TransactionBuilder {
         auction: &AuctionWinner,
     ) -> Self {
         assert!(self.transfer_ty.is_none());
-        assert!(self.currency_ty == auction.offer_ty);
+        assert!(auction.offer_ty.as_ref() == Some(&self.currency_ty));
         assert!(dest == auction.winner_id.unwrap());
         self.auction_id = Some(auction.auction_id);
         self.dest = Some(dest);
@@ -187,6 +190,254 @@ impl TransactionBuilder {
         self
     }
 
+    pub fn auction_proceeds(
+        mut self,
+        dest: UserId,
+        auction: &AuctionWinner,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        assert!(self.currency_ty == auction.bid_ty);
+        self.auction_id = Some(auction.auction_id);
+        self.dest = Some(dest);
+        self.transfer_ty = Some(TransferType::AuctionProceeds);
+        self
+    }
+
+    pub fn auction_return(
+        mut self,
+        dest: UserId,
+        auction: &AuctionWinner,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        assert!(auction.offer_ty.as_ref() == Some(&self.currency_ty));
+        assert!(auction.winner_id.is_none());
+        self.auction_id = Some(auction.auction_id);
+        self.dest = Some(dest);
+        self.transfer_ty = Some(TransferType::AuctionReturn);
+        self
+    }
+
+    pub fn sealed_bid_escrow(
+        mut self,
+        source: UserId,
+        auction: &AuctionWinner,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        assert!(auction.sealed);
+        assert!(self.currency_ty == auction.bid_ty);
+        self.auction_id = Some(auction.auction_id);
+        self.source = Some(source);
+        self.transfer_ty = Some(TransferType::SealedBidEscrow);
+        self
+    }
+
+    pub fn sealed_bid_refund(
+        mut self,
+        dest: UserId,
+        auction: &AuctionWinner,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        assert!(auction.sealed);
+        assert!(self.currency_ty == auction.bid_ty);
+        self.auction_id = Some(auction.auction_id);
+        self.dest = Some(dest);
+        self.transfer_ty = Some(TransferType::SealedBidRefund);
+        self
+    }
+
+    pub fn unit_bid_escrow(
+        mut self,
+        source: UserId,
+        auction: &AuctionWinner,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        assert!(auction.multi_unit);
+        assert!(self.currency_ty == auction.bid_ty);
+        self.auction_id = Some(auction.auction_id);
+        self.source = Some(source);
+        self.transfer_ty = Some(TransferType::UnitBidEscrow);
+        self
+    }
+
+    pub fn unit_bid_refund(
+        mut self,
+        dest: UserId,
+        auction: &AuctionWinner,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        assert!(auction.multi_unit);
+        assert!(self.currency_ty == auction.bid_ty);
+        self.auction_id = Some(auction.auction_id);
+        self.dest = Some(dest);
+        self.transfer_ty = Some(TransferType::UnitBidRefund);
+        self
+    }
+
+    /// Some of the units of a multi-unit auction, to one of its winners
+    pub fn auction_unit_payout(
+        mut self,
+        dest: UserId,
+        auction: &AuctionWinner,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        assert!(auction.multi_unit);
+        assert!(auction.offer_ty.as_ref() == Some(&self.currency_ty));
+        self.auction_id = Some(auction.auction_id);
+        self.dest = Some(dest);
+        self.transfer_ty = Some(TransferType::AuctionUnitPayout);
+        self
+    }
+
+    pub fn treasury_mint(
+        mut self,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        self.dest = Some(UserId::TREASURY);
+        self.transfer_ty = Some(TransferType::TreasuryMint);
+        self
+    }
+
+    pub fn treasury_spend(
+        mut self,
+        dest: UserId,
+        motion_id: i64,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        self.source = Some(UserId::TREASURY);
+        self.dest = Some(dest);
+        self.thing_id = Some(motion_id);
+        self.transfer_ty = Some(TransferType::TreasurySpend);
+        self
+    }
+
+    pub fn trade(
+        mut self,
+        source: UserId,
+        dest: UserId,
+        trade_id: i64,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        self.source = Some(source);
+        self.dest = Some(dest);
+        self.thing_id = Some(trade_id);
+        self.transfer_ty = Some(TransferType::Trade);
+        self
+    }
+
+    pub fn standing_order(
+        mut self,
+        source: UserId,
+        dest: UserId,
+        standing_order_id: i64,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        self.source = Some(source);
+        self.dest = Some(dest);
+        self.thing_id = Some(standing_order_id);
+        self.transfer_ty = Some(TransferType::StandingOrder);
+        self
+    }
+
+    pub fn loan_principal(
+        mut self,
+        lender: UserId,
+        borrower: UserId,
+        loan_id: i64,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        self.source = Some(lender);
+        self.dest = Some(borrower);
+        self.thing_id = Some(loan_id);
+        self.transfer_ty = Some(TransferType::LoanPrincipal);
+        self
+    }
+
+    pub fn loan_repayment(
+        mut self,
+        borrower: UserId,
+        lender: UserId,
+        loan_id: i64,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        self.source = Some(borrower);
+        self.dest = Some(lender);
+        self.thing_id = Some(loan_id);
+        self.transfer_ty = Some(TransferType::LoanRepayment);
+        self
+    }
+
+    pub fn economic_policy(
+        mut self,
+        source: UserId,
+        policy: crate::economic_policy::PolicyKind,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        assert!(self.currency_ty == CurrencyId::PC);
+        self.source = Some(source);
+        self.transfer_ty = Some(policy.transfer_type());
+        self
+    }
+
+    pub fn market_reserve(
+        mut self,
+        source: UserId,
+        order_id: i64,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        self.source = Some(source);
+        self.thing_id = Some(order_id);
+        self.transfer_ty = Some(TransferType::MarketReserve);
+        self
+    }
+
+    /// Paid out of the escrow of `order_id`, to the other side of the fill
+    pub fn market_fill(
+        mut self,
+        dest: UserId,
+        order_id: i64,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        self.dest = Some(dest);
+        self.thing_id = Some(order_id);
+        self.transfer_ty = Some(TransferType::MarketFill);
+        self
+    }
+
+    pub fn market_refund(
+        mut self,
+        dest: UserId,
+        order_id: i64,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        self.dest = Some(dest);
+        self.thing_id = Some(order_id);
+        self.transfer_ty = Some(TransferType::MarketRefund);
+        self
+    }
+
+    pub fn burn(
+        mut self,
+        source: UserId,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        self.source = Some(source);
+        self.transfer_ty = Some(TransferType::Burn);
+        self
+    }
+
+    /// Takes back a force give from `source`, who received it, and returns it to `dest`
+    pub fn force_give_undo(
+        mut self,
+        source: UserId,
+        dest: UserId,
+    ) -> Self {
+        assert!(self.transfer_ty.is_none());
+        self.source = Some(source);
+        self.dest = Some(dest);
+        self.transfer_ty = Some(TransferType::ForceGiveUndo);
+        self
+    }
+
     pub fn message_id(
         self,
         message_id: serenity::model::id::MessageId,
@@ -219,6 +470,7 @@ pub enum TransferError {
 
 pub struct TransferHandler<'a> {
     conn: &'a diesel::pg::PgConnection,
+    economy: i64,
     users_balances: HashMap<(UserId, CurrencyId), i64>,
 }
 
@@ -226,6 +478,16 @@ impl<'a> TransferHandler<'a> {
     /// conn must already be in a transaction, else this will err
     pub fn new(
         conn: &'a diesel::pg::PgConnection,
+        users: Vec<UserId>,
+        currencies: Vec<CurrencyId>,
+    ) -> diesel::result::QueryResult<Self> {
+        Self::in_economy(conn, crate::guilds::SHARED_ECONOMY, users, currencies)
+    }
+
+    /// Like `new`, but for the balances in an isolated guild's economy
+    pub fn in_economy(
+        conn: &'a diesel::pg::PgConnection,
+        economy: i64,
         mut users: Vec<UserId>,
         mut currencies: Vec<CurrencyId>,
     ) -> diesel::result::QueryResult<Self> {
@@ -243,6 +505,7 @@ impl<'a> TransferHandler<'a> {
                     .select(bhdsl::balance)
                     .filter(bhdsl::user.eq(u))
                     .filter(bhdsl::ty.eq(&c))
+                    .filter(bhdsl::economy.eq(economy))
                     .order((bhdsl::happened_at.desc(), bhdsl::rowid.desc(), bhdsl::sign.desc()))
                     .limit(1)
                     .for_update()
@@ -257,18 +520,27 @@ impl<'a> TransferHandler<'a> {
             }
         }
 
-        Ok(TransferHandler{conn, users_balances})
+        Ok(TransferHandler{conn, economy, users_balances})
     }
 
     pub fn handle_single(
         conn: &'a diesel::pg::PgConnection,
         transfer: TransactionBuilder,
+    ) -> Result<diesel::QueryResult<()>, TransferError> {
+        Self::handle_single_in(conn, crate::guilds::SHARED_ECONOMY, transfer)
+    }
+
+    pub fn handle_single_in(
+        conn: &'a diesel::pg::PgConnection,
+        economy: i64,
+        transfer: TransactionBuilder,
     ) -> Result<diesel::QueryResult<()>, TransferError> {
         let mut users = vec![];
         if let Some(user) = transfer.source { users.push(user); }
         if let Some(user) = transfer.dest { users.push(user); }
-        let mut handle = match Self::new(
+        let mut handle = match Self::in_economy(
             conn,
+            economy,
             users,
             vec![transfer.currency_ty.clone()],
         ) {
@@ -330,7 +602,9 @@ impl<'a> TransferHandler<'a> {
                     tdsl::comment.eq(transfer.comment),
                     tdsl::transfer_ty.eq(transfer.transfer_ty.unwrap()),
                     tdsl::auction_id.eq(transfer.auction_id),
+                    tdsl::thing_id.eq(transfer.thing_id),
                     tdsl::happened_at.eq(transfer.happened_at),
+                    tdsl::economy.eq(self.economy),
                 ))
                 .execute(self.conn)
                 .map(|_| ())
@@ -17,6 +17,8 @@ table! {
         to_votes -> Nullable<Int8>,
         transfer_ty -> Transfer_type,
         auction_id -> Nullable<Int8>,
+        thing_id -> Nullable<Int8>,
+        economy -> Int8,
     }
 }
 
@@ -25,8 +27,8 @@ table! {
         auction_id -> Int8,
         created_at -> Timestamptz,
         auctioneer -> Nullable<Int8>,
-        offer_ty -> Text,
-        offer_amt -> Int8,
+        offer_ty -> Nullable<Text>,
+        offer_amt -> Nullable<Int8>,
         bid_ty -> Text,
         bid_min -> Int8,
         finished -> Bool,
@@ -39,5 +41,21 @@ table! {
         winner_bid -> Nullable<Int8>,
         bid_at -> Nullable<Timestamptz>,
         last_timer_bump -> Timestamptz,
+        offer_item -> Nullable<Int8>,
+        offer_item_name -> Nullable<Text>,
+        sealed -> Bool,
+        dutch_start -> Nullable<Int8>,
+        dutch_drop -> Nullable<Int8>,
+        dutch_every_minutes -> Nullable<Int4>,
+        dutch_price -> Nullable<Int8>,
+        multi_unit -> Bool,
+        clearing_price -> Nullable<Int8>,
+        duration_minutes -> Nullable<Int4>,
+        reserve_price -> Nullable<Int8>,
+        buy_now_price -> Nullable<Int8>,
+        bought_at -> Nullable<Timestamptz>,
+        cancelled_at -> Nullable<Timestamptz>,
+        snipe_window_minutes -> Nullable<Int4>,
+        snipe_extend_minutes -> Nullable<Int4>,
     }
 }
\ No newline at end of file
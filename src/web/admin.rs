@@ -0,0 +1,422 @@
+use rocket::response::Redirect;
+use super::prelude::*;
+use super::template::ErrorResponse;
+use crate::models::{ItemType, AutoAuction};
+use crate::item_types;
+use crate::auto_auctions::{self, AutoAuctionDef};
+use crate::transfers::CurrencyId;
+
+#[derive(Debug, Clone, FromForm)]
+pub struct ItemTypeCreateForm {
+    csrf: String,
+    name: String,
+    long_name_plural: String,
+    long_name_ambiguous: String,
+    position: Option<i32>,
+}
+
+#[derive(Debug, Clone, FromForm)]
+pub struct PositionForm {
+    csrf: String,
+    position: i32,
+}
+
+#[derive(Debug, Clone, FromForm)]
+pub struct ConfirmAboveForm {
+    csrf: String,
+    /// Empty to turn confirmation by amount off
+    threshold: Option<i64>,
+}
+
+#[derive(Debug, Clone, FromForm)]
+pub struct RetireForm {
+    csrf: String,
+    retired: bool,
+}
+
+#[derive(Debug, Clone, FromForm)]
+pub struct AliasForm {
+    csrf: String,
+    alias: String,
+}
+
+#[derive(Debug, Clone, FromForm)]
+pub struct AutoAuctionForm {
+    csrf: String,
+    name: String,
+    schedule: String,
+    timezone: String,
+    offer_amt: i64,
+    offer_ty: String,
+    bid_min: i64,
+    bid_ty: String,
+    duration_hours: i32,
+    multi_unit: bool,
+}
+
+#[derive(Debug, Clone, FromForm)]
+pub struct EnabledForm {
+    csrf: String,
+    enabled: bool,
+}
+
+/// Checks the csrf token and that the user is an admin
+fn require_admin(ctx: &CommonContext, csrf: Option<&str>) -> Result<(), ErrorResponse> {
+    if let Some(csrf) = csrf {
+        if ctx.cookies.get(CSRF_COOKIE_NAME).map(|token| token.value()) != Some(csrf) {
+            return hard_err(Status::BadRequest);
+        }
+    }
+    let deets = if let Some(d) = ctx.deets.as_ref() {
+        d
+    } else {
+        return soft_err("You're not logged in.");
+    };
+    if !crate::admins::is_admin(&**ctx, deets.id()).unwrap() {
+        return soft_err("Only admins can do that.");
+    }
+    Ok(())
+}
+
+fn item_type_result(res: Result<(), item_types::ItemTypeError>) -> Result<Redirect, ErrorResponse> {
+    match res {
+        Ok(()) => Ok(Redirect::to(uri!(item_types_index))),
+        Err(item_types::ItemTypeError::Db(e)) => panic!("{:?}", e),
+        Err(e) => soft_err(e.to_string()),
+    }
+}
+
+#[get("/admin/item-types")]
+pub fn item_types_index(
+    mut ctx: CommonContext,
+) -> PlutoResponse {
+    use schema::item_types::dsl as it;
+    use schema::item_type_aliases::dsl as ita;
+    require_admin(&ctx, None)?;
+
+    let types:Vec<(ItemType, i32, Option<DateTime<Utc>>, Option<i64>)> = it::item_types
+        .select((ItemType::cols(), it::position, it::retired_at, it::confirm_give_above))
+        .order(it::position)
+        .get_results(&*ctx)
+        .unwrap();
+    let aliases:Vec<(String, String)> = ita::item_type_aliases
+        .select((ita::name, ita::alias))
+        .order(ita::alias)
+        .get_results(&*ctx)
+        .unwrap();
+    let csrf = ctx.csrf_token.clone();
+
+    let body = html!{
+        h1 { "Item types" }
+        p { a href=(uri!(auto_auctions_index)) { "Auto-auctions" } }
+        @for (ty, position, retired_at, confirm_give_above) in &types {
+            article.item-type {
+                h2 {
+                    (ty.long_name_plural)
+                    " (" code { (ty.id) } ")"
+                }
+                p {
+                    "Ambiguous name: " (ty.long_name_ambiguous)
+                    br;
+                    @if let Some(retired_at) = retired_at {
+                        "Retired at " (show_ts(*retired_at))
+                    } @else {
+                        "Active"
+                    }
+                }
+                form action=(uri!(item_type_position: name = ty.db_name())) method="post" {
+                    input type="hidden" name="csrf" value=(csrf);
+                    "Position "
+                    input type="number" name="position" value=(position);
+                    button type="submit" { "Move" }
+                }
+                form action=(uri!(item_type_confirm_above: name = ty.db_name())) method="post" {
+                    input type="hidden" name="csrf" value=(csrf);
+                    "Confirm gives from discord above "
+                    input type="number" min="0" name="threshold" value=[confirm_give_above] placeholder="(never)";
+                    button type="submit" { "Set" }
+                }
+                form action=(uri!(item_type_retire: name = ty.db_name())) method="post" {
+                    input type="hidden" name="csrf" value=(csrf);
+                    input type="hidden" name="retired" value=(retired_at.is_none());
+                    button type="submit" {
+                        @if retired_at.is_some() { "Unretire" } @else { "Retire" }
+                    }
+                }
+                h3 { "Aliases" }
+                ul {
+                    @for (_, alias) in aliases.iter().filter(|(name, _)| name == ty.db_name()) {
+                        li {
+                            form action=(uri!(item_type_alias_remove)) method="post" {
+                                input type="hidden" name="csrf" value=(csrf);
+                                input type="hidden" name="alias" value=(alias);
+                                code { (alias) }
+                                " "
+                                button type="submit" { "Remove" }
+                            }
+                        }
+                    }
+                }
+                form action=(uri!(item_type_alias_add: name = ty.db_name())) method="post" {
+                    input type="hidden" name="csrf" value=(csrf);
+                    input type="text" name="alias" placeholder="alias";
+                    button type="submit" { "Add alias" }
+                }
+            }
+            hr;
+        }
+        h2 { "New item type" }
+        form.tall-form action=(uri!(item_type_create)) method="post" {
+            input type="hidden" name="csrf" value=(csrf);
+            label { "Name " input type="text" name="name" placeholder="gold" required; }
+            label { "Plural name " input type="text" name="long_name_plural" placeholder="Gold Stars" required; }
+            label { "Ambiguous name " input type="text" name="long_name_ambiguous" placeholder="gold star(s)" required; }
+            label { "Position " input type="number" name="position" placeholder="(last)"; }
+            button type="submit" { "Create" }
+        }
+    };
+
+    page(
+        &mut ctx,
+        PageTitle("Item types"),
+        CanonicalUrl(None),
+        html!{},
+        body,
+    )
+}
+
+#[post("/admin/item-types", data = "<data>")]
+pub fn item_type_create(
+    ctx: CommonContext,
+    data: LenientForm<ItemTypeCreateForm>,
+) -> Result<Redirect, ErrorResponse> {
+    require_admin(&ctx, Some(&data.csrf))?;
+    item_type_result(item_types::create(
+        &*ctx,
+        data.name.trim(),
+        data.long_name_plural.trim(),
+        data.long_name_ambiguous.trim(),
+        data.position,
+    ))
+}
+
+#[post("/admin/item-types/<name>/position", data = "<data>")]
+pub fn item_type_position(
+    ctx: CommonContext,
+    name: String,
+    data: LenientForm<PositionForm>,
+) -> Result<Redirect, ErrorResponse> {
+    require_admin(&ctx, Some(&data.csrf))?;
+    item_type_result(item_types::set_position(&*ctx, &name, data.position))
+}
+
+#[post("/admin/item-types/<name>/retire", data = "<data>")]
+pub fn item_type_retire(
+    ctx: CommonContext,
+    name: String,
+    data: LenientForm<RetireForm>,
+) -> Result<Redirect, ErrorResponse> {
+    require_admin(&ctx, Some(&data.csrf))?;
+    let retired_at = if data.retired { Some(Utc::now()) } else { None };
+    item_type_result(item_types::set_retired(&*ctx, &name, retired_at))
+}
+
+#[post("/admin/item-types/<name>/confirm-above", data = "<data>")]
+pub fn item_type_confirm_above(
+    ctx: CommonContext,
+    name: String,
+    data: LenientForm<ConfirmAboveForm>,
+) -> Result<Redirect, ErrorResponse> {
+    require_admin(&ctx, Some(&data.csrf))?;
+    if data.threshold.map(|t| t < 0).unwrap_or(false) {
+        return soft_err("The threshold can't be negative.");
+    }
+    item_type_result(item_types::set_confirm_give_above(&*ctx, &name, data.threshold))
+}
+
+#[post("/admin/item-types/<name>/aliases", data = "<data>")]
+pub fn item_type_alias_add(
+    ctx: CommonContext,
+    name: String,
+    data: LenientForm<AliasForm>,
+) -> Result<Redirect, ErrorResponse> {
+    require_admin(&ctx, Some(&data.csrf))?;
+    item_type_result(item_types::add_alias(&*ctx, &name, data.alias.trim()))
+}
+
+#[post("/admin/item-types/remove-alias", data = "<data>")]
+pub fn item_type_alias_remove(
+    ctx: CommonContext,
+    data: LenientForm<AliasForm>,
+) -> Result<Redirect, ErrorResponse> {
+    require_admin(&ctx, Some(&data.csrf))?;
+    item_type_result(item_types::remove_alias(&*ctx, &data.alias))
+}
+
+fn auto_auction_result(res: Result<(), auto_auctions::AutoAuctionError>) -> Result<Redirect, ErrorResponse> {
+    match res {
+        Ok(()) => Ok(Redirect::to(uri!(auto_auctions_index))),
+        Err(auto_auctions::AutoAuctionError::Db(e)) => panic!("{:?}", e),
+        Err(e) => soft_err(e.to_string()),
+    }
+}
+
+#[get("/admin/auto-auctions")]
+pub fn auto_auctions_index(
+    mut ctx: CommonContext,
+) -> PlutoResponse {
+    use schema::item_types::dsl as it;
+    require_admin(&ctx, None)?;
+
+    let auto_auctions:Vec<AutoAuction> = auto_auctions::get_all(&*ctx).unwrap();
+    let types:Vec<ItemType> = it::item_types
+        .select(ItemType::cols())
+        .filter(it::retired_at.is_null())
+        .order(it::position)
+        .get_results(&*ctx)
+        .unwrap();
+    let csrf = ctx.csrf_token.clone();
+
+    let body = html!{
+        h1 { "Auto-auctions" }
+        p {
+            "Auctions the treasury puts up on a schedule, minting whatever it doesn't have. Schedules are cron "
+            "expressions, " code { "<minute> <hour> <day of month> <month> <day of week>" } ", in the given timezone; "
+            code { "0 7 * * 6" } " is 7am every Saturday. They can also be changed by a motion called with "
+            code { "$auto_auction" } "."
+        }
+        @for auto_auction in &auto_auctions {
+            article.auto-auction {
+                h2 { code { (auto_auction.name) } }
+                p {
+                    (auto_auction.def().describe())
+                    br;
+                    @if !auto_auction.enabled {
+                        "Paused"
+                    } @else if let Some(next) = auto_auction.next_run() {
+                        "Next " (show_ts(next))
+                    } @else {
+                        "Never runs"
+                    }
+                    br;
+                    "Changed " (show_ts(auto_auction.changed_at))
+                    @if let Some(motion_id) = auto_auction.changed_by_motion {
+                        " by "
+                        a href={"/motions/" (crate::damm::add_to_str(motion_id.to_string()))} { "a motion" }
+                    }
+                }
+                form action=(uri!(auto_auction_enabled: name = &auto_auction.name)) method="post" {
+                    input type="hidden" name="csrf" value=(csrf);
+                    input type="hidden" name="enabled" value=(!auto_auction.enabled);
+                    button type="submit" {
+                        @if auto_auction.enabled { "Pause" } @else { "Resume" }
+                    }
+                }
+                form action=(uri!(auto_auction_remove: name = &auto_auction.name)) method="post" {
+                    input type="hidden" name="csrf" value=(csrf);
+                    button type="submit" { "Remove" }
+                }
+            }
+            hr;
+        }
+        h2 { "New or replace" }
+        form.tall-form action=(uri!(auto_auction_set)) method="post" {
+            input type="hidden" name="csrf" value=(csrf);
+            label { "Name " input type="text" name="name" placeholder="weekly" required; }
+            label { "Schedule " input type="text" name="schedule" placeholder="0 7 * * 6" required; }
+            label { "Timezone " input type="text" name="timezone" value="US/Pacific" required; }
+            label {
+                "Offer "
+                input type="number" min="1" name="offer_amt" placeholder="10" required;
+                select name="offer_ty" {
+                    @for ty in &types {
+                        option value=(ty.id) { (ty.long_name_ambiguous) }
+                    }
+                }
+            }
+            label {
+                "Minimum bid "
+                input type="number" min="1" name="bid_min" placeholder="1" required;
+                select name="bid_ty" {
+                    @for ty in &types {
+                        option value=(ty.id) { (ty.long_name_ambiguous) }
+                    }
+                }
+            }
+            label {
+                select name="multi_unit" {
+                    option value="true" selected { "Sold by the unit" }
+                    option value="false" { "Sold to one winner" }
+                }
+            }
+            label { "Lasting " input type="number" min="1" name="duration_hours" value="96" required; " hours" }
+            button type="submit" { "Save" }
+        }
+    };
+
+    page(
+        &mut ctx,
+        PageTitle("Auto-auctions"),
+        CanonicalUrl(None),
+        html!{},
+        body,
+    )
+}
+
+#[post("/admin/auto-auctions", data = "<data>")]
+pub fn auto_auction_set(
+    ctx: CommonContext,
+    data: LenientForm<AutoAuctionForm>,
+) -> Result<Redirect, ErrorResponse> {
+    require_admin(&ctx, Some(&data.csrf))?;
+    let duration_minutes = match data.duration_hours.checked_mul(60) {
+        Some(m) => m,
+        None => return soft_err("That's too long."),
+    };
+    let active_type = |name: &str| -> Option<CurrencyId> {
+        use schema::item_types::dsl as it;
+        it::item_types
+            .select(ItemType::cols())
+            .filter(it::name.eq(name))
+            .filter(it::retired_at.is_null())
+            .get_result::<ItemType>(&*ctx)
+            .optional()
+            .unwrap()
+            .map(|ty| ty.id)
+    };
+    let (offer_ty, bid_ty) = match (active_type(&data.offer_ty), active_type(&data.bid_ty)) {
+        (Some(offer_ty), Some(bid_ty)) => (offer_ty, bid_ty),
+        _ => return hard_err(Status::BadRequest),
+    };
+    let def = AutoAuctionDef{
+        schedule: data.schedule.trim().to_string(),
+        timezone: data.timezone.trim().to_string(),
+        offer_ty,
+        offer_amt: data.offer_amt,
+        bid_ty,
+        bid_min: data.bid_min,
+        duration_minutes,
+        multi_unit: data.multi_unit,
+    };
+    auto_auction_result(auto_auctions::set(&*ctx, data.name.trim(), &def, None, Utc::now()))
+}
+
+#[post("/admin/auto-auctions/<name>/enabled", data = "<data>")]
+pub fn auto_auction_enabled(
+    ctx: CommonContext,
+    name: String,
+    data: LenientForm<EnabledForm>,
+) -> Result<Redirect, ErrorResponse> {
+    require_admin(&ctx, Some(&data.csrf))?;
+    auto_auction_result(auto_auctions::set_enabled(&*ctx, &name, data.enabled, None, Utc::now()))
+}
+
+#[post("/admin/auto-auctions/<name>/remove", data = "<data>")]
+pub fn auto_auction_remove(
+    ctx: CommonContext,
+    name: String,
+    data: LenientForm<super::csrf::CSRFForm>,
+) -> Result<Redirect, ErrorResponse> {
+    require_admin(&ctx, Some(&data.csrf))?;
+    auto_auction_result(auto_auctions::remove(&*ctx, &name))
+}
@@ -1,5 +1,47 @@
+use rocket::response::Redirect;
 use super::prelude::*;
-use crate::models::{AuctionWinner, Transfer, TransferExtra};
+use super::template::ErrorResponse;
+use super::csrf::CSRFForm;
+use crate::models::{AuctionWinner, AuctionLot, ItemTransfer, Transfer, TransferExtra, TransferType, AUCTION_EVENT_MAX_BID_EXHAUSTED};
+
+#[derive(Debug, Clone, FromForm)]
+pub struct CreateAuctionForm {
+    csrf: String,
+    /// Either an item, or an amount and type of fungible
+    offer_item: Option<i64>,
+    offer_amt: Option<i64>,
+    offer_ty: Option<String>,
+    bid_min: i64,
+    bid_ty: String,
+    /// "open", "sealed", "dutch" or "multi"
+    kind: String,
+    dutch_start: Option<i64>,
+    dutch_drop: Option<i64>,
+    dutch_every_minutes: Option<i32>,
+    /// Only for open bidding
+    reserve_price: Option<i64>,
+    buy_now_price: Option<i64>,
+    ends_after_hours: Option<i32>,
+    snipe_window_minutes: Option<i32>,
+    snipe_extend_minutes: Option<i32>,
+}
+
+/// How many recent sales the short and long moving averages on the price history cover
+const SHORT_AVERAGE:usize = 4;
+const LONG_AVERAGE:usize = 12;
+
+#[derive(Debug, Clone, FromForm)]
+pub struct WatchForm {
+    csrf: String,
+    watch: bool,
+}
+
+#[derive(Debug, Clone, FromForm)]
+pub struct UnitBidForm {
+    csrf: String,
+    quantity: i64,
+    unit_price: i64,
+}
 
 #[derive(Debug, Clone, FromForm)]
 pub struct BidForm {
@@ -8,6 +50,39 @@ pub struct BidForm {
     is_max_bid: bool,
 }
 
+fn auction_kind_fields() -> maud::Markup {
+    maud::html!{
+        select name="kind" {
+            option value="open" selected { "Open bidding" }
+            option value="sealed" { "Sealed bids" }
+            option value="dutch" { "Dutch" }
+            option value="multi" { "By the unit" }
+        }
+        br;
+        "If dutch, starting at "
+        input type="number" min="2" name="dutch_start" placeholder="1000";
+        " and dropping by "
+        input type="number" min="1" name="dutch_drop" placeholder="10";
+        " every "
+        input type="number" min="1" name="dutch_every_minutes" placeholder="30";
+        " minutes"
+        br;
+        "If open bidding, a hidden reserve of "
+        input type="number" min="1" name="reserve_price" placeholder="none";
+        " and buy it now for "
+        input type="number" min="1" name="buy_now_price" placeholder="none";
+        br;
+        "If open bidding, end exactly "
+        input type="number" min="1" name="ends_after_hours" placeholder="96";
+        " hours after it starts, except that a new high bid in the last "
+        input type="number" min="0" name="snipe_window_minutes" placeholder="0";
+        " minutes pushes the end to "
+        input type="number" min="0" name="snipe_extend_minutes" placeholder="0";
+        " minutes after it. Leave blank to end 96 hours after the last new high bidder."
+        br;
+    }
+}
+
 fn display_auction(auction:&AuctionWinner) -> maud::Markup {
     maud::html!{
         div class=(if auction.finished { "auction auction-finished" } else { "auction auction-pending" }) {
@@ -24,12 +99,35 @@ fn display_auction(auction:&AuctionWinner) -> maud::Markup {
                 } @else {
                     " offers "
                 }
-                (auction.offer_amt) " " (auction.offer_ty)
+                @match auction.lot() {
+                    AuctionLot::Fungible{amount, ty} => { (amount) " " (ty) },
+                    AuctionLot::Item{item_id, name} => {
+                        a href=(uri!(super::items::item_view: damm_id = crate::damm::add_to_str(item_id.to_string()))) { (name) }
+                    },
+                }
                 " for "
                 (auction.bid_ty)
                 "."
                 br;
-                @if auction.finished {
+                @if auction.multi_unit {
+                    @if let (true, Some(price)) = (auction.finished, auction.clearing_price) {
+                        "Sold by the unit, at " (price) " " (auction.bid_ty) " each."
+                    } @else if auction.finished {
+                        "Auction expired with no bids."
+                    } @else {
+                        "Sold by the unit. Minimum bid is " (auction.bid_min) " " (auction.bid_ty)
+                        " each; the highest bids get their units, and everyone pays the lowest winning price."
+                        br;
+                        "Auction ends at "
+                        (super::template::show_ts(auction.end_at()))
+                        "."
+                    }
+                } @else if auction.cancelled_at.is_some() {
+                    "Auction cancelled by the auctioneer."
+                } @else if auction.finished && !auction.reserve_met() {
+                    "Auction ended without meeting the reserve price of " (auction.reserve_price.unwrap()) " "
+                    (auction.bid_ty) "; nothing was sold."
+                } @else if auction.finished {
                     @if let Some(winner_id) = auction.winner_id {
                         "Auction won by "
                         (crate::names::name_of(winner_id.into_serenity()))
@@ -39,6 +137,27 @@ fn display_auction(auction:&AuctionWinner) -> maud::Markup {
                     } @else {
                         "Auction expired with no winner."
                     }
+                } @else if let (Some(price), None) = (auction.dutch_price, auction.winner_id) {
+                    "Dutch auction: buy it now for " (price) " " (auction.bid_ty) ". The price drops by "
+                    (auction.dutch_drop.unwrap()) " every " (auction.dutch_every_minutes.unwrap()) " minutes, down to "
+                    (auction.bid_min) " " (auction.bid_ty) "."
+                    br;
+                    "Auction ends at "
+                    (super::template::show_ts(auction.end_at()))
+                    " if nobody buys it."
+                } @else if auction.is_dutch() || auction.bought_at.is_some() {
+                    "Bought by "
+                    (crate::names::name_of(auction.winner_id.unwrap().into_serenity()))
+                    " for "
+                    (auction.winner_bid.unwrap()) " " (auction.bid_ty)
+                    "; finishing shortly."
+                } @else if auction.sealed {
+                    "Sealed bids, revealed when it ends. Minimum bid is " (auction.bid_min) " " (auction.bid_ty)
+                    "; the highest bidder pays the second-highest bid."
+                    br;
+                    "Auction ends at "
+                    (super::template::show_ts(auction.end_at()))
+                    "."
                 } @else {
                     @if let Some(winner_id) = auction.winner_id {
                         "Current bid is "
@@ -48,10 +167,25 @@ fn display_auction(auction:&AuctionWinner) -> maud::Markup {
                     } @else {
                         "No bids. Minimum bid is " (auction.bid_min) " " (auction.bid_ty) "."
                     }
+                    @if auction.reserve_price.is_some() {
+                        @if auction.reserve_met() && auction.winner_id.is_some() {
+                            " The reserve price has been met."
+                        } @else {
+                            " The reserve price hasn't been met."
+                        }
+                    }
+                    @if let Some(price) = auction.buy_now_available() {
+                        br;
+                        "Buy it now for " (price) " " (auction.bid_ty) "."
+                    }
                     br;
                     "Auction will end at "
                     (super::template::show_ts(auction.end_at()))
-                    " if no further bids are placed."
+                    @if auction.snipe_window_minutes.is_some() {
+                        " unless it's extended by a late bid."
+                    } @else {
+                        " if no further bids are placed."
+                    }
                 }
             }
         }
@@ -87,270 +221,149 @@ pub fn auction_bid(
         return hard_err(rocket::http::Status::BadRequest);
     }
 
-    let mut res:Option<PlutoResponse> = None;
-    let mut status_msg:Option<String> = None;
-
-    let transaction_res = ctx.conn.transaction::<_,diesel::result::Error,_>(|| {
-        use schema::auctions::dsl as adsl;
-        use view_schema::auction_and_winner::dsl as anw;
-        
-        // This needs to be a separate statement from the AuctionWinner because AuctionWinner joins multiple tables
-        // and thus cannot be locked with .for_update().
-        let maybe_auction_id:Option<i64> = adsl::auctions
-            .select(adsl::rowid)
-            .filter(adsl::rowid.eq(id))
-            .for_update()
-            .get_result(&*ctx)
-            .optional()
-            .unwrap();
-
-        if maybe_auction_id.is_none() {
-            res = Some(hard_err(rocket::http::Status::NotFound));
-            return Ok(());
-        }
-
-        let auction:AuctionWinner = anw::auction_and_winner
-            .select(AuctionWinner::cols())
-            .filter(anw::auction_id.eq(id))
-            .get_result(&*ctx)
-            .unwrap();
-
-        if now > auction.end_at() {
-            status_msg = Some("Bid failed: Auction has ended".to_string());
-            return Ok(());
-        }
-
-        if data.amount < auction.current_min_bid() {
-            status_msg = Some("Bid failed: You must bid more than that.".to_string());
-            return Ok(());
-        }
-
-        let mut to_lock = vec![deets.id()];
-        if let Some(prev_bidder) = auction.winner_id {
-            if prev_bidder != deets.id() {
-                to_lock.push(prev_bidder);
-            }
-        }
-
-        let mut handle = TransferHandler::new(
-            &*ctx,
-            to_lock,
-            vec![auction.offer_ty.clone(), auction.bid_ty.clone()],
-        )?;
-
-        let challenger_id = deets.id();
+    let status_msg = match crate::auctions::bid(&*ctx, id, deets.id(), data.amount, data.is_max_bid, now) {
+        Ok(outcome) => outcome.message,
+        Err(crate::auctions::AuctionError::NotFound) => return hard_err(rocket::http::Status::NotFound),
+        Err(crate::auctions::AuctionError::Db(e)) => panic!("{:?}", e),
+        Err(e) => e.to_string(),
+    };
 
-        let mut challenger_available_balance = handle.balance(challenger_id, auction.bid_ty.clone());
-        if let Some((user, amt)) = auction.winner() {
-            if user == challenger_id {
-                challenger_available_balance = challenger_available_balance.checked_add(amt).unwrap();
-            }
-        }
-        
-        // This test must be *before* the bid is tested against the current max bid; Otherwise the bid could be increased if someone else has a higher max bid, regardless of if you have the dough to support it.
-        if challenger_available_balance < data.amount {
-            status_msg = Some(
-                format!(
-                    "Bid failed: You do not have enough {}",
-                    auction.bid_ty
-                )
-            );
-            return Ok(());
+    page(
+        &mut ctx,
+        PageTitle("Auction bid"),
+        CanonicalUrl(None),
+        html!{},
+        html!{
+            main { (status_msg) }
+            br;
+            a href={"/auctions/" (damm_id)} { "Return to auction" }
+            br;
+            a href="/" { "Return home" }
         }
+    )
+}
 
-        let maybe_old_bid = auction.winner();
-
-        let new_bid;
-        let new_max_bid;
-
-        if let Some(max_bid_bad) = auction.max_bid() {
-            let max_bid_user = max_bid_bad.user;
-            
-            let attempted_max_bid = max_bid_bad.amount;
-            // The highest value that could be the actual max bid
-            let max_max_bid = handle.balance(max_bid_bad.user, max_bid_bad.currency) + auction.winner().unwrap().1;
-            let max_bid_amount = if attempted_max_bid > max_max_bid { max_max_bid } else { attempted_max_bid };
-
-            if challenger_id == max_bid_user {
-                if data.is_max_bid {
-                    new_bid = (max_bid_user, auction.winner().unwrap().1);
-                    new_max_bid = Some((max_bid_user, data.amount));
-                    status_msg = Some(
-                        format!(
-                            "You have set your max bid to {amount}{ty}.",
-                            ty = auction.bid_ty,
-                            amount = data.amount,
-                        )
-                    );
-                } else if data.amount >= auction.winner().unwrap().1 {
-                    new_bid = (max_bid_user, data.amount);
-                    new_max_bid = Some((max_bid_user, attempted_max_bid));
-                    status_msg = Some(
-                        format!(
-                            "You have increased your actual bid to {amount}{ty}",
-                            ty = auction.bid_ty,
-                            amount = data.amount,
-                        )
-                    );
-                } else {
-                    unreachable!();
-                }
-            } else if data.amount <= max_bid_amount {
-                new_bid = (max_bid_user, data.amount);
-                new_max_bid = Some((max_bid_user, attempted_max_bid));
-                status_msg = Some(
-                    format!(
-                        "Your bid was not greater than {champion}'s existing max bid; The bid is now at {amount}{ty}.",
-                        ty = auction.bid_ty,
-                        champion = name_of(new_bid.0.into_serenity()),
-                        amount = new_bid.1,
-                    )
-                );
-            } else if data.is_max_bid {
-                new_bid = (challenger_id, max_bid_amount.checked_add(1).unwrap());
-                new_max_bid = Some((challenger_id, data.amount));
-                status_msg = Some(
-                    format!(
-                        "You have set a max bid of {max}{ty}. The bid is now at {amount}{ty}",
-                        ty = auction.bid_ty,
-                        max = data.amount,
-                        amount = new_bid.1,
-                    )
-                );
-            } else {
-                new_bid = (challenger_id, data.amount);
-                new_max_bid = None;
-                status_msg = Some(
-                    format!(
-                        "You have successfully bid {amount}{ty}",
-                        ty = auction.bid_ty,
-                        amount = new_bid.1
-                    )
-                );
-            }
-        } else if let Some(old_bid) = auction.winner() {
-            if data.amount <= old_bid.1 {
-                new_bid = old_bid;
-                new_max_bid = None;
-                status_msg = Some(
-                    format!(
-                        "Your bid is below the current bid of {amount}{ty}.",
-                        ty = auction.bid_ty,
-                        amount = old_bid.1,
-                    )
-                );
-            } else if data.is_max_bid {
-                if challenger_id == old_bid.0 {
-                    new_bid = old_bid;
-                } else {
-                    new_bid = (challenger_id, old_bid.1.checked_add(1).unwrap());
-                }
-                new_max_bid = Some((challenger_id, data.amount));
-                status_msg = Some(
-                    format!(
-                        "You have set a max bid of {max}{ty}. The bid is now {amount}{ty}",
-                        ty = auction.bid_ty,
-                        max = data.amount,
-                        amount = new_bid.1,
-                    )
-                );
-            } else {
-                new_bid = (challenger_id, data.amount);
-                new_max_bid = None;
-                status_msg = Some(
-                    format!(
-                        "You have successfully bid {amount}{ty}",
-                        ty = auction.bid_ty,
-                        amount = new_bid.1,
-                    )
-                )
-            }
-        } else if data.amount >= auction.bid_min {
-            if data.is_max_bid {
-                new_bid = (challenger_id, auction.bid_min);
-                new_max_bid = Some((challenger_id, data.amount));
-                status_msg = Some(
-                    format!(
-                        "You have placed the first bid of {amount}{ty}, and set your max bid to {max}{ty}",
-                        ty = auction.bid_ty,
-                        amount = auction.bid_min,
-                        max = data.amount,
-                    )
-                )
-            } else {
-                new_bid = (challenger_id, data.amount);
-                new_max_bid = None;
-                status_msg = Some(
-                    format!(
-                        "You have placed the first bid of {amount}{ty}",
-                        ty = auction.bid_ty,
-                        amount = data.amount,
-                    )
-                )
-            }
-        } else {
-            status_msg = Some("Your bid is below the minimum bid amount".to_string());
-            return Ok(());
-        }
+/// Buys a dutch auction at whatever it costs when the request arrives, which is never more than the page showed, or
+/// an open auction at its buy-it-now price
+#[post("/auctions/<damm_id>/buy-now", data = "<data>")]
+pub fn auction_buy_now(
+    ctx: CommonContext,
+    data: LenientForm<CSRFForm>,
+    damm_id: String,
+) -> Result<Redirect, ErrorResponse> {
+    use crate::auctions::AuctionError;
+    let now = Utc::now();
+    let id:i64 = if let Some(digits) = crate::damm::validate_ascii(damm_id.as_str()) {
+        atoi::atoi(digits.as_slice()).unwrap()
+    } else {
+        return hard_err(rocket::http::Status::NotFound);
+    };
+    if ctx.cookies.get(CSRF_COOKIE_NAME).map(|token| token.value()) != Some(data.csrf.as_str()) {
+        return hard_err(rocket::http::Status::BadRequest);
+    }
+    let user = if let Some(d) = ctx.deets.as_ref() {
+        d.id()
+    } else {
+        return hard_err(rocket::http::Status::Unauthorized);
+    };
+    match crate::auctions::buy_now(&*ctx, id, user, now) {
+        Ok(_) => Ok(Redirect::to(uri!(auction_view: damm_id = damm_id, cb = _))),
+        Err(AuctionError::NotFound) => hard_err(rocket::http::Status::NotFound),
+        Err(AuctionError::Db(e)) => panic!("{:?}", e),
+        Err(e) => soft_err(e.to_string()),
+    }
+}
 
-        if maybe_old_bid != Some(new_bid) {
-            if let Some(old_bid) = maybe_old_bid {
-                // refund old bidder
-                let t = TransactionBuilder::new(old_bid.1, auction.bid_ty.clone(), now)
-                    .auction_refund(old_bid.0, &auction);
-                match handle.transfer(t) {
-                    Err(TransferError::NotEnough) => unreachable!("It is nonsensical for an addition of funds to lack sufficient balance."),
-                    Err(TransferError::Overflow) => panic!("The consortium has reached a state such that more than 2^63 fungibles exist, and this code was not designed to handle that"),
-                    Ok(v) => v?,
-                }
-            }
-            // charge new bidder
-            let t = TransactionBuilder::new(new_bid.1, auction.bid_ty.clone(), now)
-                .auction_reserve(new_bid.0, &auction);
-            match handle.transfer(t) {
-                Err(TransferError::NotEnough) => unreachable!("We have a lock on transactions for this user, and already checked they have enough fungibles."),
-                Err(TransferError::Overflow) => panic!("The consortium has reached a state such that more than 2^63 fungibles exist, and this code was not designed to handle that"),
-                Ok(v) => v?,
-            }
-        }
+#[post("/auctions/<damm_id>/cancel", data = "<data>")]
+pub fn auction_cancel(
+    ctx: CommonContext,
+    data: LenientForm<CSRFForm>,
+    damm_id: String,
+) -> Result<Redirect, ErrorResponse> {
+    use crate::auctions::AuctionError;
+    let now = Utc::now();
+    let id:i64 = if let Some(digits) = crate::damm::validate_ascii(damm_id.as_str()) {
+        atoi::atoi(digits.as_slice()).unwrap()
+    } else {
+        return hard_err(rocket::http::Status::NotFound);
+    };
+    if ctx.cookies.get(CSRF_COOKIE_NAME).map(|token| token.value()) != Some(data.csrf.as_str()) {
+        return hard_err(rocket::http::Status::BadRequest);
+    }
+    let user = if let Some(d) = ctx.deets.as_ref() {
+        d.id()
+    } else {
+        return hard_err(rocket::http::Status::Unauthorized);
+    };
+    match crate::auctions::cancel(&*ctx, id, user, now) {
+        Ok(_) => Ok(Redirect::to(uri!(auction_view: damm_id = damm_id, cb = _))),
+        Err(AuctionError::NotFound) => hard_err(rocket::http::Status::NotFound),
+        Err(AuctionError::Db(e)) => panic!("{:?}", e),
+        Err(e) => soft_err(e.to_string()),
+    }
+}
 
-        // The timer is bumped iff the winning bidder changed
-        let new_timer_bumped = if maybe_old_bid.map(|b| b.0) != Some(new_bid.0) {
-            now
-        } else { auction.last_timer_bump };
-
-        diesel::update(adsl::auctions).set((
-            adsl::last_timer_bump.eq(new_timer_bumped),
-            adsl::max_bid_user.eq(new_max_bid.map(|a| a.0)),
-            adsl::max_bid_amt.eq(new_max_bid.map(|a| a.1)),
-        )).execute(&*ctx.conn).unwrap();
-        
-        Ok(())
-    });
-
-    match transaction_res {
-        Ok(()) => (),
-        Err(diesel::result::Error::RollbackTransaction) => (),
-        e => e.unwrap(),
+#[post("/auctions/<damm_id>/unit-bid", data = "<data>")]
+pub fn auction_unit_bid(
+    ctx: CommonContext,
+    data: LenientForm<UnitBidForm>,
+    damm_id: String,
+) -> Result<Redirect, ErrorResponse> {
+    use crate::auctions::AuctionError;
+    let now = Utc::now();
+    let id:i64 = if let Some(digits) = crate::damm::validate_ascii(damm_id.as_str()) {
+        atoi::atoi(digits.as_slice()).unwrap()
+    } else {
+        return hard_err(rocket::http::Status::NotFound);
+    };
+    if ctx.cookies.get(CSRF_COOKIE_NAME).map(|token| token.value()) != Some(data.csrf.as_str()) {
+        return hard_err(rocket::http::Status::BadRequest);
     }
+    let user = if let Some(d) = ctx.deets.as_ref() {
+        d.id()
+    } else {
+        return hard_err(rocket::http::Status::Unauthorized);
+    };
+    match crate::auctions::place_unit_bid(&*ctx, id, user, data.quantity, data.unit_price, now) {
+        Ok(_) => Ok(Redirect::to(uri!(auction_view: damm_id = damm_id, cb = _))),
+        Err(AuctionError::NotFound) => hard_err(rocket::http::Status::NotFound),
+        Err(AuctionError::Db(e)) => panic!("{:?}", e),
+        Err(e) => soft_err(e.to_string()),
+    }
+}
 
-    if let Some(status_msg) = status_msg {
-        page(
-            &mut ctx,
-            PageTitle("Auction bid"),
-            CanonicalUrl(None),
-            html!{},
-            html!{
-                main { (status_msg) }
-                br;
-                a href={"/auctions/" (damm_id)} { "Return to auction" }
-                br;
-                a href="/" { "Return home" }
-            }
-        )
+#[post("/auctions/<damm_id>/watch", data = "<data>")]
+pub fn auction_watch(
+    ctx: CommonContext,
+    data: LenientForm<WatchForm>,
+    damm_id: String,
+) -> Result<Redirect, ErrorResponse> {
+    use crate::auctions::AuctionError;
+    let now = Utc::now();
+    let id:i64 = if let Some(digits) = crate::damm::validate_ascii(damm_id.as_str()) {
+        atoi::atoi(digits.as_slice()).unwrap()
     } else {
-        res.unwrap()
+        return hard_err(rocket::http::Status::NotFound);
+    };
+    if ctx.cookies.get(CSRF_COOKIE_NAME).map(|token| token.value()) != Some(data.csrf.as_str()) {
+        return hard_err(rocket::http::Status::BadRequest);
     }
+    let user = if let Some(d) = ctx.deets.as_ref() {
+        d.id()
+    } else {
+        return hard_err(rocket::http::Status::Unauthorized);
+    };
+    if data.watch {
+        match crate::auctions::watch(&*ctx, id, user, now) {
+            Ok(()) => (),
+            Err(AuctionError::NotFound) => return hard_err(rocket::http::Status::NotFound),
+            Err(AuctionError::Db(e)) => panic!("{:?}", e),
+            Err(e) => return soft_err(e.to_string()),
+        }
+    } else {
+        crate::auctions::unwatch(&*ctx, id, user).unwrap();
+    }
+    Ok(Redirect::to(uri!(auction_view: damm_id = damm_id, cb = _)))
 }
 
 #[get("/auctions/<damm_id>?<cb>")]
@@ -359,6 +372,7 @@ pub fn auction_view(
     cb: Option<String>, // CB = Cache Buster, purposefully ignored
     mut ctx: CommonContext,
 ) -> PlutoResponse {
+    let now = Utc::now();
     let detailed_embed = cb.is_some();
     let id:i64 = if let Some(digits) = crate::damm::validate_ascii(damm_id.as_str()) {
         atoi::atoi(digits.as_slice()).unwrap()
@@ -399,6 +413,30 @@ pub fn auction_view(
     for t in &transaction_history {
         match &t.extra {
             TransferExtra::AuctionCreate{ auction_id: _, from: _} => (), // Already covered above
+            TransferExtra::AuctionReserve{auction_id: _, from} if auction.bought_at == Some(t.happened_at) => {
+                auction_history.push((t.happened_at, format!(
+                    "{} buys it now for {} {}",
+                    name_of(from.discord_id()),
+                    t.quantity,
+                    auction.bid_ty,
+                )))
+            },
+            TransferExtra::AuctionReserve{auction_id: _, from} if auction.is_dutch() => {
+                auction_history.push((t.happened_at, format!(
+                    "{} buys it for {} {}",
+                    name_of(from.discord_id()),
+                    t.quantity,
+                    auction.bid_ty,
+                )))
+            },
+            TransferExtra::AuctionReserve{auction_id: _, from} if auction.sealed => {
+                auction_history.push((t.happened_at, format!(
+                    "{} wins the sealed bidding, paying {} {}",
+                    name_of(from.discord_id()),
+                    t.quantity,
+                    auction.bid_ty,
+                )))
+            },
             TransferExtra::AuctionReserve{auction_id: _, from} => {
                 auction_history.push((t.happened_at, format!(
                     "{} bids {} {}",
@@ -407,57 +445,322 @@ pub fn auction_view(
                     auction.bid_ty,
                 )))
             },
-            TransferExtra::AuctionRefund{ auction_id: _, to: _} => (), // We don't need to show anything, as this always happens at the same instant as an AuctionReserve
+            // Who bid what stays secret until the auction ends
+            TransferExtra::SealedBidEscrow{auction_id: _, from} => {
+                auction_history.push((t.happened_at, if auction.finished {
+                    format!("{} places a sealed bid of {} {}", name_of(from.discord_id()), t.quantity, auction.bid_ty)
+                } else {
+                    "Someone places a sealed bid".to_string()
+                }))
+            },
+            TransferExtra::SealedBidRefund{auction_id: _, to: _} => (), // Replaced bids and the reveal are covered elsewhere
+            TransferExtra::UnitBidEscrow{auction_id: _, from} => {
+                auction_history.push((t.happened_at, format!(
+                    "{} bids, holding {} {}",
+                    name_of(from.discord_id()),
+                    t.quantity,
+                    auction.bid_ty,
+                )))
+            },
+            TransferExtra::UnitBidRefund{auction_id: _, to} => {
+                auction_history.push((t.happened_at, format!(
+                    "{} gets back {} {}",
+                    name_of(to.discord_id()),
+                    t.quantity,
+                    auction.bid_ty,
+                )))
+            },
+            TransferExtra::AuctionUnitPayout{auction_id: _, to} => {
+                auction_history.push((t.happened_at, format!(
+                    "{} receives {} {}.",
+                    name_of(to.discord_id()),
+                    t.quantity,
+                    t.ty,
+                )))
+            },
+            TransferExtra::AuctionProceeds{auction_id: _, to} if auction.multi_unit => {
+                auction_history.push((t.happened_at, format!(
+                    "{} receives the winning bids, {} {}.",
+                    name_of(to.discord_id()),
+                    t.quantity,
+                    auction.bid_ty,
+                )))
+            },
+            TransferExtra::AuctionReturn{ auction_id: _, to} if auction.multi_unit => {
+                auction_history.push((t.happened_at, format!(
+                    "{} unsold {} returned to {}.",
+                    t.quantity,
+                    t.ty,
+                    name_of(to.discord_id()),
+                )))
+            },
+            TransferExtra::AuctionRefund{ auction_id: _, to} if auction.finished && !auction.reserve_met() && t.happened_at >= auction.end_at() => {
+                auction_history.push((t.happened_at, format!(
+                    "The reserve price of {} {} wasn't met, so {} gets back their bid of {} {}.",
+                    auction.reserve_price.unwrap(),
+                    auction.bid_ty,
+                    name_of(to.discord_id()),
+                    t.quantity,
+                    auction.bid_ty,
+                )))
+            },
+            TransferExtra::AuctionRefund{ auction_id: _, to: _} => (), // Otherwise this always happens at the same instant as an AuctionReserve, or is covered by the outbid events
             TransferExtra::AuctionPayout{ auction_id: _, to} => {
                 auction_history.push(
                     (t.happened_at, format!(
-                        "{} wins the auction, receiving {} {}.",
+                        "{} wins the auction, receiving {}.",
+                        name_of(to.discord_id()),
+                        auction.lot_description(),
+                    ))
+                )
+            },
+            TransferExtra::AuctionProceeds{auction_id: _, to} => {
+                auction_history.push(
+                    (t.happened_at, format!(
+                        "{} receives the winning bid of {} {}.",
                         name_of(to.discord_id()),
                         t.quantity,
-                        auction.offer_ty,
+                        auction.bid_ty,
+                    ))
+                )
+            },
+            TransferExtra::AuctionReturn{ auction_id: _, to} if auction.cancelled_at.is_some() => {
+                auction_history.push((t.happened_at, format!(
+                    "Cancelled, {} returned to {}.",
+                    auction.lot_description(),
+                    name_of(to.discord_id()),
+                )))
+            },
+            TransferExtra::AuctionReturn{ auction_id: _, to} => {
+                auction_history.push(
+                    (t.happened_at, format!(
+                        "Nobody won, {} returned to {}.",
+                        auction.lot_description(),
+                        name_of(to.discord_id()),
                     ))
                 )
             },
             _ => unreachable!(),
         }
     }
+    // An item lot moves in the item's own history rather than as a transfer
+    if let AuctionLot::Item{item_id: _, name: _} = auction.lot() {
+        use crate::schema::item_transfers::dsl as itdsl;
+        let item_history:Vec<ItemTransfer> = itdsl::item_transfers
+            .select(ItemTransfer::cols())
+            .filter(itdsl::auction_id.eq(auction.auction_id))
+            .order((itdsl::happened_at.asc(), itdsl::rowid.asc()))
+            .get_results(&*ctx)
+            .unwrap();
+        for t in &item_history {
+            match (t.transfer_ty, t.to_user) {
+                (TransferType::AuctionPayout, Some(to)) => auction_history.push((t.happened_at, format!(
+                    "{} wins the auction, receiving {}.",
+                    name_of(to),
+                    auction.lot_description(),
+                ))),
+                (TransferType::AuctionReturn, Some(to)) if auction.cancelled_at.is_some() => auction_history.push((t.happened_at, format!(
+                    "Cancelled, {} returned to {}.",
+                    auction.lot_description(),
+                    name_of(to),
+                ))),
+                (TransferType::AuctionReturn, Some(to)) => auction_history.push((t.happened_at, format!(
+                    "Nobody won, {} returned to {}.",
+                    auction.lot_description(),
+                    name_of(to),
+                ))),
+                _ => (), // Putting it up is already covered by the creation line
+            }
+        }
+    }
+    for event in crate::auctions::events(&*ctx, auction.auction_id).unwrap() {
+        let msg = if event.kind == AUCTION_EVENT_MAX_BID_EXHAUSTED {
+            format!("{}'s max bid of {} {} was exhausted.", name_of(event.user), event.amount, auction.bid_ty)
+        } else {
+            format!("{} was outbid, and their bid of {} {} returned.", name_of(event.user), event.amount, auction.bid_ty)
+        };
+        auction_history.push((event.happened_at, msg));
+    }
+    auction_history.sort_by_key(|(happened_at, _)| *happened_at);
+    let watching = ctx.deets.as_ref().map(|d| crate::auctions::is_watching(&*ctx, auction.auction_id, d.id()).unwrap()).unwrap_or(false);
+    let my_sealed_bid = if auction.sealed {
+        ctx.deets.as_ref().and_then(|d| crate::auctions::sealed_bid_of(&*ctx, auction.auction_id, d.id()).unwrap())
+    } else { None };
+    let revealed = if auction.finished && auction.sealed {
+        crate::auctions::sealed_bids(&*ctx, auction.auction_id).unwrap()
+    } else { vec![] };
+    // Unit bids are public, along with how the auction would clear if it ended now
+    let unit_bids:Vec<(crate::models::UnitBid, i64)> = if auction.multi_unit {
+        let bids = crate::auctions::unit_bids(&*ctx, auction.auction_id).unwrap();
+        let (fills, _) = crate::auctions::clear_units(auction.offer_amt.unwrap(), &bids);
+        bids.into_iter().zip(fills).collect()
+    } else { vec![] };
+    let my_unit_bid = ctx.deets.as_ref().and_then(|d| unit_bids.iter().find(|(b, _)| b.bidder == d.id()));
+    // Hidden sealed bids aren't known here, so cancelling one that has them fails with an explanation instead
+    let can_cancel = ctx.deets.as_ref().map(|d| auction.auctioneer == Some(d.id().into_i64())).unwrap_or(false)
+        && !auction.is_over(now)
+        && auction.winner_id.is_none()
+        && unit_bids.is_empty();
 
     let content = html!{
         main {
             (display_auction(&auction))
-            @if !auction.finished {
+            @if !auction.is_over(now) {
+                p { (auction.timing_rule()) }
+            }
+            @if auction.is_dutch() {
+                @if let (false, None, Some(price)) = (auction.finished, auction.winner_id, auction.dutch_price) {
+                    @if ctx.deets.is_some() {
+                        form action=(uri!(auction_buy_now: damm_id = &damm_id)) method="post" {
+                            input type="hidden" name="csrf" value=(ctx.csrf_token.clone());
+                            button type="submit" { "Buy now for " (price) " " (auction.bid_ty) }
+                        }
+                    } @else {
+                        div { "Log in to buy" }
+                    }
+                }
+            } @else if auction.multi_unit {
+                @if !auction.finished {
+                    @if ctx.deets.is_some() {
+                        form action=(uri!(auction_unit_bid: damm_id = &damm_id)) method="post" {
+                            input type="hidden" name="csrf" value=(ctx.csrf_token.clone());
+                            label {
+                                "Bid for "
+                                input type="number" name="quantity" min="1" max=(auction.offer_amt.unwrap()) value=(my_unit_bid.map(|(b, _)| b.quantity).unwrap_or(1));
+                            }
+                            label {
+                                " at "
+                                input type="number" name="unit_price" min=(auction.bid_min) value=(my_unit_bid.map(|(b, _)| b.unit_price).unwrap_or(auction.bid_min));
+                                " " (auction.bid_ty) " each"
+                            }
+                            br;
+                            button type="submit" { "Place bid" }
+                        }
+                        @if let Some((bid, fill)) = my_unit_bid {
+                            p {
+                                "Your bid is for " (bid.quantity) " at " (bid.unit_price) " " (auction.bid_ty)
+                                " each. If the auction ended now you'd get " (fill) ". Bidding again replaces it."
+                            }
+                        } @else {
+                            p { "You haven't bid." }
+                        }
+                    } @else {
+                        div { "Log in to bid" }
+                    }
+                }
+                h2 { "Bids" }
+                @if unit_bids.is_empty() {
+                    p { "Nobody has bid." }
+                } @else {
+                    table.tabley-table {
+                        tr {
+                            th { "Bidder" }
+                            th { "Quantity" }
+                            th { "Price each" }
+                            th { @if auction.finished { "Got" } @else { "Would get" } }
+                        }
+                        @for (bid, fill) in &unit_bids {
+                            tr {
+                                td { (name_of(bid.bidder)) }
+                                td { (bid.quantity) }
+                                td { (bid.unit_price) " " (auction.bid_ty) }
+                                td { (fill) }
+                            }
+                        }
+                    }
+                }
+            } @else if !auction.is_over(now) {
                 @if let Some(ref deets) = ctx.deets {
+                    @if let Some(price) = auction.buy_now_available() {
+                        form action=(uri!(auction_buy_now: damm_id = &damm_id)) method="post" {
+                            input type="hidden" name="csrf" value=(ctx.csrf_token.clone());
+                            button type="submit" { "Buy now for " (price) " " (auction.bid_ty) }
+                            " to win it right away."
+                        }
+                    }
                     form action={"/auctions/" (damm_id) "/bid"} method="post" {
                         input type="hidden" name="csrf" value=(ctx.csrf_token.clone());
                         label {
-                            "Bid "
-                            input type="number" name="amount" min=(auction.current_min_bid()) value=(auction.current_min_bid());
+                            @if auction.sealed { "Sealed bid " } @else { "Bid " }
+                            input type="number" name="amount" min=(auction.current_min_bid()) value=(my_sealed_bid.unwrap_or(auction.current_min_bid()));
                             (auction.bid_ty)
                         }
                         br;
-                        select name="is_max_bid" {
-                            option value="false" selected { "Actually bid" }
-                            option value="true" { "Set my maximum bid" }
+                        @if auction.sealed {
+                            input type="hidden" name="is_max_bid" value="false";
+                        } @else {
+                            select name="is_max_bid" {
+                                option value="false" selected { "Actually bid" }
+                                option value="true" { "Set my maximum bid" }
+                            }
+                            br;
                         }
-                        br;
                         button type="submit" { "Place bid" }
                     }
-                    details {
-                        summary { "Tap to show max bid information" }
-                        @if auction.max_bid_user == Some(deets.id()) {
-                            "Your max bid is "
-                            (auction.max_bid_amt.unwrap())
-                            " "
-                            (auction.bid_ty)
-                            "."
+                    @if auction.sealed {
+                        @if let Some(amount) = my_sealed_bid {
+                            p { "Your sealed bid is " (amount) " " (auction.bid_ty) ". Bidding again replaces it." }
                         } @else {
-                            "You have no max bid set."
+                            p { "You haven't bid." }
+                        }
+                    } @else {
+                        @if let (Some(reserve), true) = (auction.reserve_price, auction.auctioneer == Some(deets.id().into_i64())) {
+                            p { "Your reserve price is " (reserve) " " (auction.bid_ty) ". Only you can see it." }
+                        }
+                        details {
+                            summary { "Tap to show max bid information" }
+                            @if auction.max_bid_user == Some(deets.id()) {
+                                "Your max bid is "
+                                (auction.max_bid_amt.unwrap())
+                                " "
+                                (auction.bid_ty)
+                                "."
+                            } @else {
+                                "You have no max bid set."
+                            }
+                        }
+                        form action=(uri!(auction_watch: damm_id = &damm_id)) method="post" {
+                            input type="hidden" name="csrf" value=(ctx.csrf_token.clone());
+                            input type="hidden" name="watch" value=(if watching { "false" } else { "true" });
+                            @if watching {
+                                "You'll get a DM for each new bid. "
+                                button type="submit" { "Stop watching" }
+                            } @else {
+                                button type="submit" { "Watch this auction" }
+                                " to get a DM for each new bid."
+                            }
                         }
                     }
                 } @else {
                     div { "Log in to bid" }
                 }
             }
+            @if can_cancel {
+                form action=(uri!(auction_cancel: damm_id = &damm_id)) method="post" {
+                    input type="hidden" name="csrf" value=(ctx.csrf_token.clone());
+                    button type="submit" { "Cancel auction" }
+                    " and get the lot back. You can only cancel before anyone bids."
+                }
+            }
+            @if auction.finished && auction.sealed {
+                h2 { "Sealed bids" }
+                @if revealed.is_empty() {
+                    p { "Nobody bid." }
+                } @else {
+                    table.tabley-table {
+                        tr {
+                            th { "Bidder" }
+                            th { "Bid" }
+                        }
+                        @for bid in &revealed {
+                            tr {
+                                td { (name_of(bid.bidder)) }
+                                td { (bid.amount) " " (auction.bid_ty) }
+                            }
+                        }
+                    }
+                }
+            }
             h2 { "Auction history" }
             table.tabley-table {
                 tr {
@@ -477,23 +780,29 @@ pub fn auction_view(
     };
 
     let meta_title = format!(
-        "Auction#{} for {} {}",
+        "Auction#{} for {}",
         auction.damm(),
-        auction.offer_amt,
-        auction.offer_ty,
+        auction.lot_description(),
     );
 
     let meta_description = if detailed_embed || auction.finished {
         if auction.finished {
-            if let Some(winner) = auction.winner() {
+            if auction.cancelled_at.is_some() {
+                "Auction cancelled by the auctioneer.".to_string()
+            } else if !auction.reserve_met() {
+                format!(
+                    "Auction ended without meeting the reserve price of {} {}",
+                    auction.reserve_price.unwrap(),
+                    auction.bid_ty,
+                )
+            } else if let Some(winner) = auction.winner() {
                 format!(
-                    "Auction finished: {winner} won {offer_amt} {offer_ty} for {bid_amt} {bid_ty} at {ts}.",
+                    "Auction finished: {winner} won {lot} for {bid_amt} {bid_ty} at {ts}.",
                     winner     = crate::names::name_of(winner.0),
                     bid_amt    = winner.1,
                     bid_ty     = auction.bid_ty,
                     ts         = ts_plain(auction.end_at()),
-                    offer_amt  = auction.offer_amt,
-                    offer_ty   = auction.offer_ty,
+                    lot        = auction.lot_description(),
                 )
             } else {
                 format!(
@@ -560,6 +869,17 @@ pub fn auction_index(
         .get_results(&*ctx)
         .unwrap()
     ;
+    let item_types:Vec<crate::models::ItemType> = {
+        use schema::item_types::dsl as itdsl;
+        itdsl::item_types
+            .select(crate::models::ItemType::cols())
+            .filter(itdsl::retired_at.is_null())
+            .order(itdsl::position)
+            .get_results(&*ctx)
+            .unwrap()
+    };
+    let my_items = ctx.deets.as_ref().map(|d| crate::items::owned_by(&*ctx, d.id()).unwrap());
+    let csrf = ctx.csrf_token.clone();
     page(
         &mut ctx,
         PageTitle("Auctions"),
@@ -567,6 +887,71 @@ pub fn auction_index(
         html!{},
         html!{
             h1 { "Auctions" }
+            p { a href=(uri!(auction_history)) { "What things have sold for" } }
+            @if let Some(my_items) = &my_items {
+                details {
+                    summary { "Start an auction" }
+                    p {
+                        "What you put up is held until the auction ends, then goes to the highest bidder, or back to you "
+                        "if nobody bids. You receive the winning bid. From discord, use "
+                        code { "$auction <amount> <type> for <minimum bid> <type>" }
+                        ". Bids on a sealed auction are secret until it ends, and the highest bidder pays the "
+                        "second-highest bid. A dutch auction's price starts high and drops on a schedule down to the "
+                        "minimum bid, and the first person to buy it wins. An auction of fungibles by the unit lets each "
+                        "bidder ask for a quantity at a price each; the highest bids get their units, and everyone pays "
+                        "the lowest winning price. An auction with open bidding can have a reserve price, kept secret from "
+                        "bidders, below which nothing is sold, and a price anyone can pay to win it right away until the "
+                        "bidding reaches it. It can also end at a fixed time, with late bids only extending it a little. You "
+                        "can cancel an auction until someone bids."
+                    }
+                    form action=(uri!(auction_create)) method="post" {
+                        input type="hidden" name="csrf" value=(csrf);
+                        "Offer "
+                        input type="number" min="1" name="offer_amt" placeholder="10" required;
+                        " "
+                        select name="offer_ty" {
+                            @for it in &item_types {
+                                option value=(it.id) { (it.long_name_ambiguous) }
+                            }
+                        }
+                        " for bids of at least "
+                        input type="number" min="1" name="bid_min" placeholder="100" required;
+                        " "
+                        select name="bid_ty" {
+                            @for it in &item_types {
+                                option value=(it.id) { (it.long_name_ambiguous) }
+                            }
+                        }
+                        " "
+                        (auction_kind_fields())
+                        " "
+                        button type="submit" { "Start auction" }
+                    }
+                    @if !my_items.is_empty() {
+                        form action=(uri!(auction_create)) method="post" {
+                            input type="hidden" name="csrf" value=(csrf);
+                            "Offer "
+                            select name="offer_item" {
+                                @for item in my_items {
+                                    option value=(item.rowid) { (crate::items::describe(item)) }
+                                }
+                            }
+                            " for bids of at least "
+                            input type="number" min="1" name="bid_min" placeholder="100" required;
+                            " "
+                            select name="bid_ty" {
+                                @for it in &item_types {
+                                    option value=(it.id) { (it.long_name_ambiguous) }
+                                }
+                            }
+                            " "
+                            (auction_kind_fields())
+                            " "
+                            button type="submit" { "Start auction" }
+                        }
+                    }
+                }
+            }
             h2 { "Pending auctions" }
             @for auction in pending_auctions {
                 article { (display_auction(&auction)) }
@@ -579,4 +964,224 @@ pub fn auction_index(
             }
         }
     )
-}
\ No newline at end of file
+}
+/// The sales of one item type for another, eg gen for pc, oldest first, with their moving averages
+struct PriceHistory {
+    offer_ty: String,
+    bid_ty: String,
+    sales: Vec<crate::stats::AuctionSale>,
+    short_average: Vec<f64>,
+    long_average: Vec<f64>,
+}
+
+fn price_histories(ctx: &CommonContext) -> Vec<PriceHistory> {
+    let mut by_pair:std::collections::BTreeMap<(String, String), Vec<crate::stats::AuctionSale>> = Default::default();
+    for sale in crate::stats::auction_sales(&**ctx).unwrap() {
+        by_pair.entry((sale.offer_ty.clone(), sale.bid_ty.clone())).or_default().push(sale);
+    }
+    by_pair.into_iter().map(|((offer_ty, bid_ty), sales)| PriceHistory{
+        offer_ty,
+        bid_ty,
+        short_average: crate::stats::moving_average(&sales, SHORT_AVERAGE),
+        long_average: crate::stats::moving_average(&sales, LONG_AVERAGE),
+        sales,
+    }).collect()
+}
+
+/// Charts only draw whole numbers, so prices are rounded
+fn price_points(sales: &[crate::stats::AuctionSale], prices: impl Iterator<Item = f64>) -> Vec<(DateTime<Utc>, i64)> {
+    sales.iter().map(|s| s.cleared_at).zip(prices.map(|p| p.round() as i64)).collect()
+}
+
+#[get("/auctions/history")]
+pub fn auction_history(
+    mut ctx: CommonContext,
+) -> PlutoResponse {
+    use super::charts::{self, Series};
+    let histories = price_histories(&ctx);
+    let body = html!{
+        main {
+            h1 { "Auction prices" }
+            p {
+                "What each finished auction of fungibles sold for, per unit of the lot, and the average over the last "
+                (SHORT_AVERAGE) " and " (LONG_AVERAGE) " sales, weighted by how much each sold. Sealed auctions count at "
+                "the price the winner paid, auctions by the unit at their clearing price, and auctions that sold nothing "
+                "not at all. Also available as "
+                a href=(uri!(auction_history_json)) { "JSON" }
+                "."
+            }
+            @if histories.is_empty() {
+                p { "No auctions of fungibles have sold anything yet." }
+            }
+            @for history in &histories {
+                section {
+                    h2 { (history.offer_ty) " for " (history.bid_ty) }
+                    (charts::line_chart(
+                        &format!("Price of one {} in {}", history.offer_ty, history.bid_ty),
+                        &[
+                            Series{label: "Each sale".to_string(), points: price_points(&history.sales, history.sales.iter().map(|s| s.unit_price()))},
+                            Series{label: format!("Last {} sales", SHORT_AVERAGE), points: price_points(&history.sales, history.short_average.iter().copied())},
+                            Series{label: format!("Last {} sales", LONG_AVERAGE), points: price_points(&history.sales, history.long_average.iter().copied())},
+                        ],
+                    ))
+                    table.tabley-table {
+                        tr {
+                            th { "Auction" }
+                            th { "Sold" }
+                            th { "Units" }
+                            th { "Paid" }
+                            th { "Each" }
+                            th { "Last " (SHORT_AVERAGE) }
+                            th { "Last " (LONG_AVERAGE) }
+                        }
+                        @for (i, sale) in history.sales.iter().enumerate().rev() {
+                            @let damm_id = crate::damm::add_to_str(sale.auction_id.to_string());
+                            tr {
+                                td { a href=(uri!(auction_view: damm_id = &damm_id, cb = _)) { "Auction#" (damm_id) } }
+                                td { (show_ts(sale.cleared_at)) }
+                                td { (sale.units) " " (history.offer_ty) }
+                                td { (sale.paid) " " (history.bid_ty) }
+                                td { (format!("{:.2}", sale.unit_price())) }
+                                td { (format!("{:.2}", history.short_average[i])) }
+                                td { (format!("{:.2}", history.long_average[i])) }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+    page(
+        &mut ctx,
+        PageTitle("Auction prices"),
+        full_url(uri!(auction_history)).into(),
+        html!{
+            meta name="description" content="What fungibles have sold for at auction in the CONsortium.";
+            link rel="alternate" type="application/json" href=(uri!(auction_history_json));
+        },
+        body,
+    )
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SaleJson {
+    auction: String,
+    cleared_at: String,
+    offer_ty: String,
+    bid_ty: String,
+    units: i64,
+    paid: i64,
+    unit_price: f64,
+    short_average: f64,
+    long_average: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct HistoryJson {
+    short_average_sales: usize,
+    long_average_sales: usize,
+    /// Oldest first, grouped by what was sold for what
+    sales: Vec<SaleJson>,
+}
+
+/// The same as `/auctions/history`, for anyone who'd rather draw their own charts
+#[get("/auctions/history.json")]
+pub fn auction_history_json(
+    ctx: CommonContext,
+) -> rocket::response::content::Json<String> {
+    let sales = price_histories(&ctx).into_iter().flat_map(|history| {
+        let PriceHistory{offer_ty, bid_ty, sales, short_average, long_average} = history;
+        sales.into_iter().zip(short_average).zip(long_average).map(move |((sale, short_average), long_average)| SaleJson{
+            auction: crate::damm::add_to_str(sale.auction_id.to_string()),
+            cleared_at: sale.cleared_at.to_rfc3339(),
+            offer_ty: offer_ty.clone(),
+            bid_ty: bid_ty.clone(),
+            units: sale.units,
+            paid: sale.paid,
+            unit_price: sale.unit_price(),
+            short_average,
+            long_average,
+        })
+    }).collect();
+    rocket::response::content::Json(serde_json::to_string(&HistoryJson{
+        short_average_sales: SHORT_AVERAGE,
+        long_average_sales: LONG_AVERAGE,
+        sales,
+    }).unwrap())
+}
+
+#[post("/auctions/create", data = "<data>")]
+pub fn auction_create(
+    ctx: CommonContext,
+    data: LenientForm<CreateAuctionForm>,
+) -> Result<Redirect, ErrorResponse> {
+    use crate::auctions::{self, NewLot, AuctionError, AuctionKind, DutchSchedule, OpenTerms, FixedEnd};
+    use crate::transfers::CurrencyId;
+    let now = Utc::now();
+    if ctx.cookies.get(CSRF_COOKIE_NAME).map(|token| token.value()) != Some(data.csrf.as_str()) {
+        return hard_err(Status::BadRequest);
+    }
+    let user = if let Some(d) = ctx.deets.as_ref() {
+        d.id()
+    } else {
+        return hard_err(Status::Unauthorized);
+    };
+
+    let active_type = |name: &str| -> Option<CurrencyId> {
+        use schema::item_types::dsl as itdsl;
+        itdsl::item_types
+            .select(crate::models::ItemType::cols())
+            .filter(itdsl::name.eq(name))
+            .filter(itdsl::retired_at.is_null())
+            .get_result::<crate::models::ItemType>(&*ctx)
+            .optional()
+            .unwrap()
+            .map(|it| it.id)
+    };
+    let lot = match (data.offer_item, data.offer_amt, data.offer_ty.as_deref()) {
+        (Some(item_id), None, None) => NewLot::Item{item_id},
+        (None, Some(amount), Some(ty)) => match active_type(ty) {
+            Some(ty) => NewLot::Fungible{amount, ty},
+            None => return hard_err(Status::BadRequest),
+        },
+        _ => return hard_err(Status::BadRequest),
+    };
+    let bid_ty = match active_type(&data.bid_ty) {
+        Some(ty) => ty,
+        None => return hard_err(Status::BadRequest),
+    };
+
+    let kind = match (data.kind.as_str(), data.dutch_start, data.dutch_drop, data.dutch_every_minutes) {
+        ("open", _, _, _) => AuctionKind::Open(Default::default()),
+        ("sealed", _, _, _) => AuctionKind::Sealed,
+        ("multi", _, _, _) => AuctionKind::MultiUnit,
+        ("dutch", Some(start), Some(drop), Some(every_minutes)) => AuctionKind::Dutch(DutchSchedule{start, drop, every_minutes}),
+        ("dutch", _, _, _) => return soft_err("A dutch auction needs a starting price, how much it drops, and how often."),
+        _ => return hard_err(Status::BadRequest),
+    };
+    let fixed_end = match data.ends_after_hours {
+        Some(hours) => match hours.checked_mul(60) {
+            Some(after_minutes) => Some(FixedEnd{
+                after_minutes,
+                window_minutes: data.snipe_window_minutes.unwrap_or(0),
+                extend_minutes: data.snipe_extend_minutes.unwrap_or(0),
+            }),
+            None => return hard_err(Status::BadRequest),
+        },
+        None => None,
+    };
+    let terms = OpenTerms{reserve_price: data.reserve_price, buy_now_price: data.buy_now_price, fixed_end};
+    let kind = match kind.with_terms(terms) {
+        Ok(kind) => kind,
+        Err(e) => return soft_err(format!("Failed: {}", e)),
+    };
+
+    match auctions::create(&*ctx, user, lot, bid_ty, data.bid_min, kind, now) {
+        Ok(auction_id) => {
+            let damm_id = crate::damm::add_to_str(auction_id.to_string());
+            Ok(Redirect::to(uri!(auction_view: damm_id = damm_id, cb = _)))
+        },
+        Err(AuctionError::Db(e)) => panic!("{:?}", e),
+        Err(e) => soft_err(format!("Failed: {}", e)),
+    }
+}
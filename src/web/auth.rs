@@ -63,11 +63,12 @@ pub fn oauth_finish(token: TokenResponse<DiscordOauth>, mut cookies: Cookies<'_>
 /// In theory, this should all be something I could do in /oauth-finish
 /// This makes the user wait extra long for no good reason.
 /// This asks discord (synchonously, mumble grumble) for users details (username, discriminator, id)
-/// and stores that in the "deets" cookie. The agent is "logged in"
+/// and stores that in the "deets" cookie. The agent is "logged in", and their username is recorded for name_of
 /// Redirects to whatevers in "login_redirect" or / if unset
 #[get("/get-deets")]
 pub fn get_deets(
-    mut cookies: Cookies<'_>
+    mut cookies: Cookies<'_>,
+    conn: super::rocket_diesel::DbConn,
 ) -> Result<Redirect, template::ErrorResponse> {
     let token = if let Some(val) = cookies.get_private("token") {
         val.value().to_string()
@@ -85,6 +86,10 @@ pub fn get_deets(
     let user:DiscordUser = res.json().unwrap();
     let deets = Deets{discord_user: user};
     info!("User logged in: {:?}", deets);
+    let seen = [(deets.id(), deets.discord_user.username.clone())];
+    if let Err(e) = crate::names::record(&*conn, &seen, chrono::Utc::now()) {
+        warn!("Failed to record name of {}: {:?}", deets.id(), e);
+    }
     cookies.add_private(
         Cookie::build("deets", serde_json::to_string(&deets).unwrap())
             .same_site(SameSite::Lax)
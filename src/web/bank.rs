@@ -41,16 +41,225 @@ pub struct GiveForm {
     destination: GiveDestination,
 }
 
+#[derive(Debug, Clone, FromForm)]
+pub struct GiveItemForm {
+    csrf: String,
+    item: i64,
+    destination: GiveDestination,
+}
+
+/// If a name was given along with the id, checks that it's the name we know that id by
+fn check_destination_name(
+    conn: &diesel::PgConnection,
+    destination: &GiveDestination,
+) -> Result<(), super::template::ErrorResponse> {
+    if let Some(ref name) = destination.expected_name {
+        let maybe_known_name = crate::names::lookup(conn, destination.id).unwrap();
+        if let Some(known_name) = maybe_known_name {
+            if known_name != name.as_str() {
+                return soft_err(format!(
+                    r#"Failed: The name "{name}" does not match the name on record, "{known_name}"."#
+                ));
+            } // else it matches, all is well
+        } else {
+            return soft_err(format!(
+                r#"Failed: The name "{name}" is not known."#
+            ));
+        }
+    }
+    Ok(())
+}
+
 sql_function!{
     #[sql_name = "coalesce"]
     fn coalesce_2<T: diesel::sql_types::NotNull>(a: diesel::sql_types::Nullable<T>, b: T) -> T;
 }
 
-#[get("/my-transactions?<before_ms>&<fun_ty>")]
+/// The periods the summary on /my-transactions can cover, as (query value, label, length). None is all time.
+const SUMMARY_PERIODS:[(&str, &str, Option<i64>); 4] = [
+    ("week", "Past week", Some(7)),
+    ("month", "Past 30 days", Some(30)),
+    ("year", "Past year", Some(365)),
+    ("all", "All time", None),
+];
+
+fn balance_summary(
+    ctx: &CommonContext,
+    user: models::UserId,
+    period: &str,
+    now: DateTime<Utc>,
+) -> Option<Markup> {
+    let days = SUMMARY_PERIODS.iter().find(|(name, _, _)| *name == period)?.2;
+    let since = match days {
+        Some(days) => now - chrono::Duration::days(days),
+        None => Utc.timestamp_opt(0, 0).unwrap(),
+    };
+    let balances = crate::stats::daily_balances(&**ctx, user, since).unwrap();
+    let totals = crate::stats::user_totals(&**ctx, user, since).unwrap();
+    let mut series:Vec<super::charts::Series> = Vec::new();
+    for b in balances {
+        match series.last_mut() {
+            Some(s) if s.label == b.ty => s.points.push((b.period, b.balance)),
+            _ => series.push(super::charts::Series{label: b.ty, points: vec![(b.period, b.balance)]}),
+        }
+    }
+    Some(html!{
+        form.tall-form {
+            div { "Summary for:" }
+            @for (name, label, _) in &SUMMARY_PERIODS {
+                label {
+                    input type="radio" name="period" value=(name) checked?[*name == period];
+                    (label)
+                }
+            }
+            .spacer-tall {}
+            button type="submit" { "Go" }
+            .spacer-tall {}
+        }
+        (super::charts::line_chart("Balances at the end of each day", &series))
+        @if !totals.is_empty() {
+            table.tabley-table {
+                tr {
+                    th {}
+                    th { "Generated" }
+                    th { "Spent on motions" }
+                    th { "Given" }
+                    th { "Received" }
+                    th { "Auctions (net)" }
+                }
+                @for t in &totals {
+                    tr {
+                        th { (t.ty) }
+                        td { (t.generated) }
+                        td { (t.motions) }
+                        td { (t.given) }
+                        td { (t.received) }
+                        td.negative[t.auction_net < 0] { (t.auction_net) }
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[get("/my-transactions?<before_ms>&<fun_ty>&<period>")]
 pub fn my_transactions(
     mut ctx: CommonContext,
     fun_ty: Option<String>,
     before_ms: Option<i64>,
+    period: Option<String>,
+) -> PlutoResponse {
+    let user = ctx.deets.as_ref().map(|deets| deets.id());
+    let now = Utc::now();
+    let period = period.unwrap_or_else(|| String::from("month"));
+    let summary = match user {
+        Some(user) => match balance_summary(&ctx, user, &period, now) {
+            Some(summary) => Some(summary),
+            None => return hard_err(Status::BadRequest),
+        },
+        None => None,
+    };
+    let loans = user.map(|user| crate::loans::outstanding_for(&*ctx, user, now).unwrap()).unwrap_or_default();
+    let header = html!{
+        @if let Some(summary) = summary {
+            h2 { "Summary" }
+            (summary)
+        }
+        @if !loans.is_empty() {
+            h2 { "Outstanding loans" }
+            @for loan in &loans {
+                article { (super::loans::display_loan(loan, now)) }
+            }
+        }
+        @if user.is_some() {
+            h2 { "Transactions" }
+        }
+    };
+    transaction_history(
+        &mut ctx,
+        user,
+        "My Transactions",
+        header,
+        fun_ty,
+        before_ms,
+        |before_ms, fun_ty| uri!(my_transactions: before_ms = before_ms, fun_ty = fun_ty, period = &period).to_string(),
+    )
+}
+
+#[get("/treasury?<before_ms>&<fun_ty>")]
+pub fn treasury(
+    mut ctx: CommonContext,
+    fun_ty: Option<String>,
+    before_ms: Option<i64>,
+) -> PlutoResponse {
+    use crate::view_schema::balance_history::dsl as bh;
+    use crate::schema::item_types::dsl as it;
+    let item_types:Vec<models::ItemType> = it::item_types
+        .select(models::ItemType::cols())
+        .order(it::position)
+        .get_results(&*ctx)
+        .unwrap();
+    let mut balances = Vec::new();
+    for item_type in item_types {
+        let bal:i64 = bh::balance_history
+            .select(bh::balance)
+            .filter(bh::user.eq(models::UserId::TREASURY))
+            .filter(bh::ty.eq(item_type.db_name()))
+            .filter(bh::economy.eq(SHARED_ECONOMY))
+            .order((bh::happened_at.desc(), bh::rowid.desc(), bh::sign.desc()))
+            .limit(1)
+            .get_result(&*ctx)
+            .optional()
+            .unwrap()
+            .unwrap_or(0);
+        balances.push((item_type, bal));
+    }
+    let policies = crate::economic_policy::get_all(&*ctx).unwrap();
+    let header = html!{
+        p {
+            "The treasury is the CONsortium's own account. It collects motion fees and auction proceeds, and funds the auto-auctions. "
+            "It can only be spent by passing a motion, called with "
+            code { "$treasury_spend <user> <amount> <type> <reason>" }
+            "."
+        }
+        dl.treasury-balances {
+            @for (item_type, bal) in &balances {
+                dt { (item_type.long_name_plural) }
+                dd { (bal) }
+            }
+        }
+        h2 { "Economic policy" }
+        p {
+            "Applied to everyone but the treasury every time pc is generated, burning what they charge. Changed by passing a motion, called with "
+            code { "$policy <policy> <setting> <reason>" }
+            "."
+        }
+        dl.treasury-balances {
+            @for (kind, setting) in &policies {
+                dt { (kind.title()) }
+                dd { (setting.describe(*kind)) }
+            }
+        }
+    };
+    transaction_history(
+        &mut ctx,
+        Some(models::UserId::TREASURY),
+        "Treasury",
+        header,
+        fun_ty,
+        before_ms,
+        |before_ms, fun_ty| uri!(treasury: before_ms = before_ms, fun_ty = fun_ty).to_string(),
+    )
+}
+
+fn transaction_history(
+    ctx: &mut CommonContext,
+    user: Option<models::UserId>,
+    title: &str,
+    header: Markup,
+    fun_ty: Option<String>,
+    before_ms: Option<i64>,
+    next_link: impl Fn(i64, &str) -> String,
 ) -> PlutoResponse {
     use crate::view_schema::balance_history::dsl as bh;
     use crate::schema::item_types::dsl as it;
@@ -96,6 +305,7 @@ pub fn my_transactions(
         //pub message_id:Option<i64>,
         pub transfer_ty:TransferType,
         pub auction_id:Option<i64>,
+        pub thing_id:Option<i64>,
     }
     let transaction_cols = (
         //bh::rowid,
@@ -111,13 +321,14 @@ pub fn my_transactions(
         //bh::message_id,
         bh::transfer_ty,
         bh::auction_id,
+        bh::thing_id,
     );
     #[derive(Debug,Clone)]
     enum TransactionView {
         Generated{amt: i64, bal: i64},
         Trans(Transaction),
     }
-    let fun_tys:Vec<String> = it::item_types.select(it::name).order(it::position).get_results(&*ctx).unwrap();
+    let fun_tys:Vec<String> = it::item_types.select(it::name).order(it::position).get_results(&**ctx).unwrap();
     let fun_ty = if fun_ty_string == "all" {
         FungibleSelection::All
     } else if fun_tys.iter().any(|ft| ft.as_str() == fun_ty_string) {
@@ -125,29 +336,31 @@ pub fn my_transactions(
     } else {
         return hard_err(Status::BadRequest)
     };
-    let txns:Option<(Vec<_>,bool)> = ctx.deets.as_ref().map(|deets| {
+    let txns:Option<(Vec<_>,bool)> = user.map(|user| {
         let q = bh::balance_history
             .select(transaction_cols)
-            .filter(bh::user.eq(deets.id()))
+            .filter(bh::user.eq(user))
+            .filter(bh::economy.eq(SHARED_ECONOMY))
             .filter(coalesce_2(bh::ty.nullable().eq(fun_ty.as_option()).nullable(), true))
             .filter(coalesce_2(bh::happened_at.nullable().lt(Utc.timestamp_millis_opt(before_ms).single()).nullable(),true))
             .filter(bh::transfer_ty.ne(TransferType::Generated))
             .order((bh::happened_at.desc(), bh::rowid.desc(), bh::sign.desc()))
             .limit(limit+1);
         info!("{}", diesel::debug_query(&q));
-        let txns:Vec<Transaction> = q.get_results(&*ctx)
+        let txns:Vec<Transaction> = q.get_results(&**ctx)
             .unwrap();
         info!("{} txns results", txns.len());
         let mut gen_txns:Vec<Transaction> = if let [.., last] = txns.as_slice() {
             bh::balance_history
                 .select(transaction_cols)
-                .filter(bh::user.eq(deets.id()))
+                .filter(bh::user.eq(user))
+                .filter(bh::economy.eq(SHARED_ECONOMY))
                 .filter(coalesce_2(bh::ty.nullable().eq(fun_ty.as_option()).nullable(), true))
                 .filter(coalesce_2(bh::happened_at.nullable().lt(Utc.timestamp_millis_opt(before_ms).single()).nullable(),true))
                 .filter(bh::happened_at.gt(last.happened_at))
                 .filter(bh::transfer_ty.eq(TransferType::Generated))
                 .order((bh::happened_at.desc(), bh::rowid.desc()))
-                .get_results(&*ctx)
+                .get_results(&**ctx)
                 .unwrap()
         } else { Vec::new() };
         let mut txn_views = Vec::new();
@@ -181,7 +394,8 @@ pub fn my_transactions(
     });
     let main_body = html!{
         main {
-            h1 { "My Transactions" }
+            h1 { (title) }
+            (header)
             @if let Some((txns, hit_limit)) = txns {
                 form.tall-form {
                     div { "Show transactions in:" }
@@ -231,6 +445,11 @@ pub fn my_transactions(
                                             },
                                             TransferType::MotionCreate => {
                                                 @let damm_id = crate::damm::add_to_str(txn.to_motion.unwrap().to_string());
+                                                @if txn.sign > 0 {
+                                                    "fee from user#\u{200B}"
+                                                    (txn.other_party.unwrap())
+                                                    " for "
+                                                }
                                                 "1 vote, created "
                                                 a href=(uri!(motions::motion_view:damm_id = &damm_id, cb = _)) {
                                                     "motion #"
@@ -241,6 +460,11 @@ pub fn my_transactions(
                                                 @let motion_id = &txn.to_motion.unwrap();
                                                 @let votes = &txn.to_votes.unwrap();
                                                 @let damm_id = crate::damm::add_to_str(motion_id.to_string());
+                                                @if txn.sign > 0 {
+                                                    "fee from user#\u{200B}"
+                                                    (txn.other_party.unwrap())
+                                                    " for "
+                                                }
                                                 (votes)
                                                 " vote(s) on "
                                                 a href=(uri!(motions::motion_view:damm_id = &damm_id, cb = _)) {
@@ -283,6 +507,149 @@ pub fn my_transactions(
                                                     (&damm_id)
                                                 }
                                             },
+                                            TransferType::Trade => {
+                                                @let damm_id = crate::damm::add_to_str(txn.thing_id.unwrap().to_string());
+                                                "traded with user#\u{200B}"
+                                                (txn.other_party.unwrap())
+                                                " in "
+                                                a href=(uri!(super::trades::trade_view:damm_id = &damm_id)) {
+                                                    "trade #"
+                                                    (&damm_id)
+                                                }
+                                            },
+                                            TransferType::StandingOrder => {
+                                                "standing order #"
+                                                (crate::damm::add_to_str(txn.thing_id.unwrap().to_string()))
+                                                @if txn.sign < 0 {
+                                                    " to "
+                                                } @else {
+                                                    " from "
+                                                }
+                                                "user#\u{200B}"
+                                                (txn.other_party.unwrap())
+                                            },
+                                            TransferType::AuctionProceeds => {
+                                                @let damm_id = crate::damm::add_to_str(txn.auction_id.unwrap().to_string());
+                                                "Winning bid from "
+                                                a href=(uri!(super::auctions::auction_view:damm_id = &damm_id, cb = _)) {
+                                                    "auction #"
+                                                    (&damm_id)
+                                                }
+                                            },
+                                            TransferType::AuctionReturn => {
+                                                @let damm_id = crate::damm::add_to_str(txn.auction_id.unwrap().to_string());
+                                                "Nobody won, returned from "
+                                                a href=(uri!(super::auctions::auction_view:damm_id = &damm_id, cb = _)) {
+                                                    "auction #"
+                                                    (&damm_id)
+                                                }
+                                            },
+                                            TransferType::TreasuryMint => {
+                                                "minted for an auto-auction"
+                                            },
+                                            TransferType::TreasurySpend => {
+                                                @let damm_id = crate::damm::add_to_str(txn.thing_id.unwrap().to_string());
+                                                @if txn.sign < 0 {
+                                                    "paid to user#\u{200B}"
+                                                    (txn.other_party.unwrap())
+                                                } @else {
+                                                    "paid by the treasury"
+                                                }
+                                                " per "
+                                                a href=(uri!(motions::motion_view:damm_id = &damm_id, cb = _)) {
+                                                    "motion #"
+                                                    (&damm_id)
+                                                }
+                                            },
+                                            TransferType::LoanPrincipal | TransferType::LoanRepayment => {
+                                                @let damm_id = crate::damm::add_to_str(txn.thing_id.unwrap().to_string());
+                                                @if txn.transfer_ty == TransferType::LoanRepayment {
+                                                    "repayment "
+                                                } @else {
+                                                    "principal "
+                                                }
+                                                @if txn.sign < 0 {
+                                                    "to "
+                                                } @else {
+                                                    "from "
+                                                }
+                                                "user#\u{200B}"
+                                                (txn.other_party.unwrap())
+                                                " for "
+                                                a href=(uri!(super::loans::loan_view:damm_id = &damm_id)) {
+                                                    "loan #"
+                                                    (&damm_id)
+                                                }
+                                            },
+                                            TransferType::GenUpkeep | TransferType::WealthTax | TransferType::PcDecay => {
+                                                (crate::economic_policy::PolicyKind::from_transfer_type(txn.transfer_ty).unwrap().title())
+                                            },
+                                            TransferType::MarketReserve | TransferType::MarketFill | TransferType::MarketRefund => {
+                                                @if txn.transfer_ty == TransferType::MarketReserve {
+                                                    "Placed "
+                                                } @else if txn.transfer_ty == TransferType::MarketFill {
+                                                    "Filled "
+                                                } @else {
+                                                    "Refund from "
+                                                }
+                                                a href=(uri!(super::market::market_index:base = _, quote = _)) {
+                                                    "market order #"
+                                                    (crate::damm::add_to_str(txn.thing_id.unwrap().to_string()))
+                                                }
+                                            },
+                                            TransferType::Burn => {
+                                                "burned"
+                                            },
+                                            TransferType::ForceGiveUndo => {
+                                                "force give "
+                                                @if txn.sign < 0 {
+                                                    "taken back by an admin, returned to "
+                                                } @else {
+                                                    "undone by an admin, returned from "
+                                                }
+                                                "user#\u{200B}"
+                                                (txn.other_party.unwrap())
+                                            },
+                                            TransferType::SealedBidEscrow => {
+                                                @let damm_id = crate::damm::add_to_str(txn.auction_id.unwrap().to_string());
+                                                "Sealed bid on "
+                                                a href=(uri!(super::auctions::auction_view:damm_id = &damm_id, cb = _)) {
+                                                    "auction #"
+                                                    (&damm_id)
+                                                }
+                                            },
+                                            TransferType::SealedBidRefund => {
+                                                @let damm_id = crate::damm::add_to_str(txn.auction_id.unwrap().to_string());
+                                                "Sealed bid returned from "
+                                                a href=(uri!(super::auctions::auction_view:damm_id = &damm_id, cb = _)) {
+                                                    "auction #"
+                                                    (&damm_id)
+                                                }
+                                            },
+                                            TransferType::UnitBidEscrow => {
+                                                @let damm_id = crate::damm::add_to_str(txn.auction_id.unwrap().to_string());
+                                                "Bid for units of "
+                                                a href=(uri!(super::auctions::auction_view:damm_id = &damm_id, cb = _)) {
+                                                    "auction #"
+                                                    (&damm_id)
+                                                }
+                                            },
+                                            TransferType::UnitBidRefund => {
+                                                @let damm_id = crate::damm::add_to_str(txn.auction_id.unwrap().to_string());
+                                                "Unit bid returned from "
+                                                a href=(uri!(super::auctions::auction_view:damm_id = &damm_id, cb = _)) {
+                                                    "auction #"
+                                                    (&damm_id)
+                                                }
+                                            },
+                                            TransferType::AuctionUnitPayout => {
+                                                @let damm_id = crate::damm::add_to_str(txn.auction_id.unwrap().to_string());
+                                                "Won units in "
+                                                a href=(uri!(super::auctions::auction_view:damm_id = &damm_id, cb = _)) {
+                                                    "auction #"
+                                                    (&damm_id)
+                                                }
+                                            },
                                             TransferType::Generated => "unreachable",
                                         }
                                         @if let Some(comment) = &txn.comment {
@@ -329,7 +696,7 @@ pub fn my_transactions(
                 }
                 @if hit_limit {
                     @let txn = match txns.iter().rev().find(|t| matches!(t, TransactionView::Trans(_))) { Some(TransactionView::Trans(t)) => t, d => {dbg!(d);unreachable!()} };
-                    a href=(uri!(my_transactions: before_ms = txn.happened_at.timestamp_millis(), fun_ty = fun_ty.as_str())) { "Next" }
+                    a href=(next_link(txn.happened_at.timestamp_millis(), fun_ty.as_str())) { "Next" }
                 }
             } @else {
                 p { "You must be logged in to view your transactions." }
@@ -338,8 +705,8 @@ pub fn my_transactions(
     };
 
     page(
-        &mut ctx,
-        PageTitle("My Transactions".to_string()),
+        ctx,
+        PageTitle(title.to_string()),
         CanonicalUrl(None),
         html!{},
         main_body,
@@ -358,15 +725,18 @@ pub fn give_form(
 
     let item_types:Vec<models::ItemType> = itdsl::item_types
         .select(models::ItemType::cols())
+        .filter(itdsl::retired_at.is_null())
         .order(itdsl::position)
         .get_results(&*ctx)
         .unwrap();
 
+    let my_items = crate::items::owned_by(&*ctx, ctx.deets.as_ref().unwrap().id()).unwrap();
+
     let csrf = ctx.csrf_token.clone();
 
     let body = html!{
         datalist id="known_users" {
-            @for (discord_id,name) in crate::names::KNOWN_NAMES.entries() {
+            @for (discord_id,name) in crate::names::all() {
                 option {
                     (name) " - " (discord_id)
                 }
@@ -397,6 +767,27 @@ pub fn give_form(
                 "Send (no backsies)"
             }
         }
+        @if !my_items.is_empty() {
+            hr;
+            form action=(uri!(give_item_perform)) method="post" {
+                input type="hidden" name="csrf" value=(csrf);
+                "Give"
+                br;
+                select name="item" {
+                    @for item in &my_items {
+                        option value=(item.rowid) { (crate::items::describe(item)) }
+                    }
+                }
+                br;
+                "to"
+                br;
+                input name="destination" type="text" list="known_users" pattern="(\\w+\\s*-\\s*)?\\d+";
+                br;
+                button type="submit" {
+                    "Send (no backsies)"
+                }
+            }
+        }
     };
 
     page(
@@ -429,6 +820,7 @@ pub fn give_perform(
     let maybe_ty:Option<models::ItemType> = itdsl::item_types
         .select(models::ItemType::cols())
         .filter(itdsl::name.eq(data.ty.as_str()))
+        .filter(itdsl::retired_at.is_null())
         .get_result(&*ctx)
         .optional()
         .unwrap();
@@ -438,20 +830,7 @@ pub fn give_perform(
         None => return hard_err(rocket::http::Status::BadRequest),
     };
 
-    if let Some(ref name) = data.destination.expected_name {
-        let maybe_known_name = crate::names::KNOWN_NAMES.get(&(data.destination.id.into_u64()));
-        if let Some(known_name) = maybe_known_name {
-            if *known_name != name.as_str() {
-                return soft_err(format!(
-                    r#"Failed: The name "{name}" does not match the name on record, "{known_name}"."#
-                ));
-            } // else it matches, all is well
-        } else {
-            return soft_err(format!(
-                r#"Failed: The name "{name}" is not known."#
-            ));
-        }
-    }
+    check_destination_name(&*ctx, &data.destination)?;
 
     let t = TransactionBuilder::new(
         data.quantity,
@@ -506,4 +885,52 @@ pub fn give_perform(
             a href="/give" { "Transfer some more" }
         }
     )
-}
\ No newline at end of file
+}
+
+#[post("/give/item", data = "<data>")]
+pub fn give_item_perform(
+    mut ctx: CommonContext,
+    data: LenientForm<GiveItemForm>,
+) -> PlutoResponse {
+    let now = Utc::now();
+
+    if ctx.cookies.get(CSRF_COOKIE_NAME).map(|token| token.value()) != Some(data.csrf.as_str()) {
+        return hard_err(rocket::http::Status::BadRequest);
+    }
+
+    let deets = if let Some(ref d) = ctx.deets { d } else { return hard_err(rocket::http::Status::BadRequest); };
+
+    check_destination_name(&*ctx, &data.destination)?;
+
+    let item = match crate::items::get(&*ctx, data.item).unwrap() {
+        Some(item) => item,
+        None => return hard_err(rocket::http::Status::BadRequest),
+    };
+
+    match crate::items::give(&*ctx, item.rowid, deets.id(), data.destination.id, None, now) {
+        Ok(()) => (),
+        Err(crate::items::ItemError::Db(e)) => panic!("{:?}", e),
+        Err(e) => return soft_err(format!("Failed: {}", e)),
+    }
+
+    page(
+        &mut ctx,
+        PageTitle("Successfully transferred"),
+        CanonicalUrl(None),
+        html!{},
+        html!{
+            "Success: Gave "
+            a href=(uri!(super::items::item_view: damm_id = item.damm())) { (item.name) }
+            " to "
+            @if let Some(ref name) = data.destination.expected_name {
+                (name) " (id " (data.destination.id) ")"
+            } @else {
+                "id " (data.destination.id)
+            }
+            "."
+            br;
+            br;
+            a href="/give" { "Transfer some more" }
+        }
+    )
+}
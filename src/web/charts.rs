@@ -0,0 +1,141 @@
+//! Server-rendered SVG charts. Everything is drawn with presentation attributes and classes from main.css, since the
+//! CSP allows neither scripts nor inline styles.
+
+use super::prelude::*;
+
+const WIDTH:f64 = 800.0;
+const HEIGHT:f64 = 300.0;
+const MARGIN_LEFT:f64 = 70.0;
+const MARGIN_RIGHT:f64 = 10.0;
+const MARGIN_TOP:f64 = 10.0;
+const MARGIN_BOTTOM:f64 = 25.0;
+/// Number of distinct `chart-series-<n>` classes in main.css
+const SERIES_CLASSES:usize = 6;
+
+pub struct Series {
+    pub label: String,
+    pub points: Vec<(DateTime<Utc>, i64)>,
+}
+
+/// Maps `v` from the range `from` onto the range `to`. A zero-width `from` maps to the middle of `to`.
+fn scale(v: f64, from: (f64, f64), to: (f64, f64)) -> f64 {
+    if from.1 == from.0 {
+        return (to.0 + to.1) / 2.0;
+    }
+    to.0 + (v - from.0) / (from.1 - from.0) * (to.1 - to.0)
+}
+
+/// The y range to draw, always including zero
+fn value_range(values: impl Iterator<Item = i64>) -> (f64, f64) {
+    values.fold((0.0, 0.0), |(min, max), v| (min.min(v as f64), max.max(v as f64)))
+}
+
+/// eg "2023-03-01", in Pacific time like the rest of the site
+fn date_label(ts: DateTime<Utc>) -> String {
+    ts.with_timezone(&chrono_tz::America::Los_Angeles).format("%Y-%m-%d").to_string()
+}
+
+fn series_class(i: usize) -> String {
+    format!("chart-series-{}", i % SERIES_CLASSES)
+}
+
+fn legend(labels: &[&str]) -> Markup {
+    html!{
+        ul.chart-legend {
+            @for (i, label) in labels.iter().enumerate() {
+                li class=(series_class(i)) { (label) }
+            }
+        }
+    }
+}
+
+fn y_axis(range: (f64, f64)) -> Markup {
+    let y = |v: f64| scale(v, range, (HEIGHT - MARGIN_BOTTOM, MARGIN_TOP));
+    html!{
+        line.chart-axis x1=(MARGIN_LEFT) x2=(WIDTH - MARGIN_RIGHT) y1=(y(0.0)) y2=(y(0.0)) {}
+        text.chart-label x=(MARGIN_LEFT - 5.0) y=(y(range.1) + 10.0) text-anchor="end" { (range.1 as i64) }
+        @if range.0 < 0.0 {
+            text.chart-label x=(MARGIN_LEFT - 5.0) y=(y(range.0)) text-anchor="end" { (range.0 as i64) }
+        }
+        text.chart-label x=(MARGIN_LEFT - 5.0) y=(y(0.0)) text-anchor="end" { "0" }
+    }
+}
+
+/// One line per series, over time
+pub fn line_chart(title: &str, series: &[Series]) -> Markup {
+    let points = || series.iter().flat_map(|s| s.points.iter());
+    let (start, end) = match (points().map(|(t, _)| *t).min(), points().map(|(t, _)| *t).max()) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return html!{ p { "No data for " (title) "." } },
+    };
+    let range = value_range(points().map(|(_, v)| *v));
+    let x = |t: DateTime<Utc>| scale(t.timestamp() as f64, (start.timestamp() as f64, end.timestamp() as f64), (MARGIN_LEFT, WIDTH - MARGIN_RIGHT));
+    let y = |v: i64| scale(v as f64, range, (HEIGHT - MARGIN_BOTTOM, MARGIN_TOP));
+    let labels:Vec<&str> = series.iter().map(|s| s.label.as_str()).collect();
+    html!{
+        figure.chart {
+            figcaption { (title) }
+            svg viewBox=(format!("0 0 {} {}", WIDTH, HEIGHT)) role="img" aria-label=(title) {
+                (y_axis(range))
+                text.chart-label x=(MARGIN_LEFT) y=(HEIGHT - 5.0) text-anchor="start" { (date_label(start)) }
+                text.chart-label x=(WIDTH - MARGIN_RIGHT) y=(HEIGHT - 5.0) text-anchor="end" { (date_label(end)) }
+                @for (i, s) in series.iter().enumerate() {
+                    @let coords:Vec<String> = s.points.iter().map(|(t, v)| format!("{:.1},{:.1}", x(*t), y(*v))).collect();
+                    polyline class=(format!("chart-line {}", series_class(i))) points=(coords.join(" ")) {}
+                }
+            }
+            @if series.len() > 1 {
+                (legend(&labels))
+            }
+        }
+    }
+}
+
+/// Side-by-side bars for each period, one per series. Every series must have a value for every period.
+pub fn bar_chart(title: &str, periods: &[DateTime<Utc>], series: &[(&str, Vec<i64>)]) -> Markup {
+    if periods.is_empty() {
+        return html!{ p { "No data for " (title) "." } };
+    }
+    let range = value_range(series.iter().flat_map(|(_, values)| values.iter().copied()));
+    let y = |v: i64| scale(v as f64, range, (HEIGHT - MARGIN_BOTTOM, MARGIN_TOP));
+    let slot = (WIDTH - MARGIN_LEFT - MARGIN_RIGHT) / periods.len() as f64;
+    let bar_width = slot * 0.8 / series.len().max(1) as f64;
+    let labels:Vec<&str> = series.iter().map(|(label, _)| *label).collect();
+    html!{
+        figure.chart {
+            figcaption { (title) }
+            svg viewBox=(format!("0 0 {} {}", WIDTH, HEIGHT)) role="img" aria-label=(title) {
+                (y_axis(range))
+                text.chart-label x=(MARGIN_LEFT) y=(HEIGHT - 5.0) text-anchor="start" { (date_label(periods[0])) }
+                text.chart-label x=(WIDTH - MARGIN_RIGHT) y=(HEIGHT - 5.0) text-anchor="end" { (date_label(*periods.last().unwrap())) }
+                @for (i, (label, values)) in series.iter().enumerate() {
+                    @for (p, value) in values.iter().enumerate() {
+                        @let left = MARGIN_LEFT + slot * p as f64 + slot * 0.1 + bar_width * i as f64;
+                        @let (top, bottom) = if *value >= 0 { (y(*value), y(0)) } else { (y(0), y(*value)) };
+                        rect class=(format!("chart-bar {}", series_class(i))) x=(format!("{:.1}", left)) y=(format!("{:.1}", top)) width=(format!("{:.1}", bar_width)) height=(format!("{:.1}", bottom - top)) {
+                            title { (label) ", " (date_label(periods[p])) ": " (value) }
+                        }
+                    }
+                }
+            }
+            @if series.len() > 1 {
+                (legend(&labels))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{scale,value_range};
+
+    #[test]
+    fn scaling() {
+        assert_eq!(scale(5.0, (0.0, 10.0), (0.0, 100.0)), 50.0);
+        // Flipped, like svg y coordinates
+        assert_eq!(scale(10.0, (0.0, 10.0), (300.0, 0.0)), 0.0);
+        assert_eq!(scale(3.0, (3.0, 3.0), (0.0, 100.0)), 50.0);
+        assert_eq!(value_range([5, 10, 3].iter().copied()), (0.0, 10.0));
+        assert_eq!(value_range([-5, 10].iter().copied()), (-5.0, 10.0));
+    }
+}
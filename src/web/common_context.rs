@@ -82,6 +82,10 @@ impl <'a, 'r> FromRequest<'a, 'r> for CommonContext<'a> {
         };
 
         let conn = super::rocket_diesel::DbConn::from_request(request).map_failure(|(a,_)| (a, CommonContextError::from(())))?;
+        // Pages name people with name_of, which only knows what was last loaded
+        if let Err(e) = crate::names::refresh_if_stale(&*conn) {
+            warn!("Failed to refresh names, {:?}", e);
+        }
         Outcome::Success(Self{
             csrf_token,
             cookies,
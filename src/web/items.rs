@@ -0,0 +1,75 @@
+use super::prelude::*;
+use crate::items;
+
+#[get("/items/<damm_id>")]
+pub fn item_view(
+    damm_id: String,
+    mut ctx: CommonContext,
+) -> PlutoResponse {
+    let id:i64 = if let Some(digits) = crate::damm::validate_ascii(damm_id.as_str()) {
+        atoi::atoi(digits.as_slice()).unwrap()
+    } else {
+        return not_found();
+    };
+
+    let item = match items::get(&*ctx, id).unwrap() {
+        Some(item) => item,
+        None => return not_found(),
+    };
+    let history = items::history(&*ctx, id).unwrap();
+
+    let content = html!{
+        main {
+            h1 { (item.name) }
+            p {
+                "Item#" (item.damm()) ", a " (item.kind) "."
+                br;
+                @if let Some(owner) = item.owner {
+                    "Owned by " (name_of(owner)) "."
+                } @else {
+                    "Currently up for auction."
+                }
+            }
+            @let metadata = item.metadata_pairs();
+            @if !metadata.is_empty() {
+                table.tabley-table {
+                    @for (key, value) in &metadata {
+                        tr {
+                            th { (key) }
+                            td { (value) }
+                        }
+                    }
+                }
+            }
+            h2 { "History" }
+            table.tabley-table {
+                tr {
+                    th { "At" }
+                    th {}
+                }
+                @for t in &history {
+                    tr {
+                        td { (show_ts(t.happened_at)) }
+                        td { (items::describe_transfer(t)) }
+                    }
+                }
+            }
+        }
+    };
+
+    let self_uri = full_url(uri!(item_view: damm_id = &damm_id));
+    let description = match item.owner {
+        Some(owner) => format!("A {} owned by {}.", item.kind, name_of(owner)),
+        None => format!("A {}, currently up for auction.", item.kind),
+    };
+
+    page(
+        &mut ctx,
+        PageTitle(format!("Item#{}", damm_id)),
+        self_uri.clone().into(),
+        html!{
+            (embed_head_html(item.name.clone(), description, &self_uri))
+        },
+        content,
+    )
+}
@@ -0,0 +1,268 @@
+use super::prelude::*;
+use super::csrf::CSRFForm;
+use crate::models::{Loan, LoanStatus};
+use crate::loans;
+
+pub fn display_loan(loan: &Loan, now: DateTime<Utc>) -> maud::Markup {
+    let status = loan.status(now);
+    maud::html!{
+        div class=(if matches!(status, LoanStatus::Offered | LoanStatus::Active) { "loan loan-open" } else { "loan loan-finished" }) {
+            div style="font-weight: bold" {
+                a href=(uri!(loan_view: damm_id = loan.damm())) {
+                    "Loan#"
+                    (loan.damm())
+                }
+            }
+            div {
+                (loans::describe(loan))
+                "."
+                br;
+                @match status {
+                    LoanStatus::Offered => {
+                        "Offer expires at "
+                        (show_ts(loan.expires_at))
+                    },
+                    LoanStatus::Active => {
+                        (loan.outstanding) " " (loan.ty)
+                        " outstanding, next payment at "
+                        (show_ts(loan.next_payment_at.unwrap()))
+                    },
+                    LoanStatus::Repaid => {
+                        "Repaid at "
+                        (show_ts(loan.repaid_at.unwrap()))
+                    },
+                    LoanStatus::Defaulted => {
+                        "Defaulted at "
+                        (show_ts(loan.defaulted_at.unwrap()))
+                        " with " (loan.outstanding) " " (loan.ty) " unpaid"
+                    },
+                    LoanStatus::Cancelled => {
+                        "Cancelled at "
+                        (show_ts(loan.cancelled_at.unwrap()))
+                    },
+                    LoanStatus::Expired => {
+                        "Expired at "
+                        (show_ts(loan.expires_at))
+                    },
+                }
+            }
+        }
+    }
+}
+
+fn loan_actions(loan: &Loan, ctx: &CommonContext, now: DateTime<Utc>) -> maud::Markup {
+    let deets = match ctx.deets.as_ref() {
+        Some(d) => d,
+        None => return maud::html!{},
+    };
+    if loan.status(now) != LoanStatus::Offered {
+        return maud::html!{};
+    }
+    maud::html!{
+        @if deets.id() == loan.borrower {
+            form action=(uri!(loan_accept: damm_id = loan.damm())) method="post" {
+                input type="hidden" name="csrf" value=(ctx.csrf_token);
+                button type="submit" { "Accept" }
+            }
+            form action=(uri!(loan_cancel: damm_id = loan.damm())) method="post" {
+                input type="hidden" name="csrf" value=(ctx.csrf_token);
+                button type="submit" { "Decline" }
+            }
+        }
+        @if deets.id() == loan.lender {
+            form action=(uri!(loan_cancel: damm_id = loan.damm())) method="post" {
+                input type="hidden" name="csrf" value=(ctx.csrf_token);
+                button type="submit" { "Withdraw offer" }
+            }
+        }
+    }
+}
+
+#[get("/loans")]
+pub fn loan_index(
+    mut ctx: CommonContext,
+) -> PlutoResponse {
+    use schema::loans::dsl as ldsl;
+    let now = Utc::now();
+    let open_loans:Vec<Loan> = ldsl::loans
+        .select(Loan::cols())
+        .filter(ldsl::repaid_at.is_null())
+        .filter(ldsl::defaulted_at.is_null())
+        .filter(ldsl::cancelled_at.is_null())
+        .filter(ldsl::accepted_at.is_not_null().or(ldsl::expires_at.gt(now)))
+        .order(ldsl::created_at.desc())
+        .get_results(&*ctx)
+        .unwrap();
+    let finished_loans:Vec<Loan> = ldsl::loans
+        .select(Loan::cols())
+        .filter(
+            ldsl::repaid_at.is_not_null()
+            .or(ldsl::defaulted_at.is_not_null())
+            .or(ldsl::cancelled_at.is_not_null())
+            .or(ldsl::accepted_at.is_null().and(ldsl::expires_at.le(now)))
+        )
+        .order(ldsl::created_at.desc())
+        .limit(100)
+        .get_results(&*ctx)
+        .unwrap();
+
+    let body = html!{
+        h1 { "Loans" }
+        p {
+            "Offer a loan from discord with "
+            code { "$lend <user> <amount> <type> at <rate>% every <schedule> for <n> payments" }
+            "."
+        }
+        h2 { "Offers and outstanding loans" }
+        @for loan in &open_loans {
+            article {
+                (display_loan(loan, now))
+                (loan_actions(loan, &ctx, now))
+            }
+        }
+        @if open_loans.is_empty() {
+            p { "No outstanding loans." }
+        }
+
+        hr;
+        h2 { "Recently closed" }
+        @for loan in &finished_loans {
+            article { (display_loan(loan, now)) }
+        }
+    };
+
+    page(
+        &mut ctx,
+        PageTitle("Loans"),
+        full_url(uri!(loan_index)).into(),
+        html!{},
+        body,
+    )
+}
+
+#[get("/loans/<damm_id>")]
+pub fn loan_view(
+    damm_id: String,
+    mut ctx: CommonContext,
+) -> PlutoResponse {
+    let now = Utc::now();
+    let id:i64 = if let Some(digits) = crate::damm::validate_ascii(damm_id.as_str()) {
+        atoi::atoi(digits.as_slice()).unwrap()
+    } else {
+        return not_found();
+    };
+
+    let loan = match loans::get(&*ctx, id).unwrap() {
+        Some(loan) => loan,
+        None => return not_found(),
+    };
+    let periods = loans::periods(&*ctx, id).unwrap();
+
+    let body = html!{
+        main {
+            (display_loan(&loan, now))
+            (loan_actions(&loan, &ctx, now))
+            @if !periods.is_empty() {
+                h2 { "Payments" }
+                table.tabley-table {
+                    tr {
+                        th { "Period starting" }
+                        th { "Interest" }
+                        th { "Due" }
+                        th { "Paid" }
+                    }
+                    @for p in &periods {
+                        tr {
+                            td { (show_ts(p.period_start)) }
+                            td { (p.interest) " " (loan.ty) }
+                            td { (p.due) " " (loan.ty) }
+                            td.negative[p.paid < p.due] { (p.paid) " " (loan.ty) }
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    let self_uri = full_url(uri!(loan_view: damm_id = &damm_id));
+    page(
+        &mut ctx,
+        PageTitle(format!("Loan#{}", damm_id)),
+        self_uri.clone().into(),
+        html!{
+            (embed_head_html(format!("Loan#{}", damm_id), loans::describe(&loan), &self_uri))
+
+            link rel="index" href=(uri!(loan_index));
+        },
+        body,
+    )
+}
+
+fn loan_action_common(
+    ctx: &mut CommonContext,
+    data: &CSRFForm,
+    damm_id: &str,
+    accept: bool,
+) -> PlutoResponse {
+    let now = Utc::now();
+    let id:i64 = if let Some(digits) = crate::damm::validate_ascii(damm_id) {
+        atoi::atoi(digits.as_slice()).unwrap()
+    } else {
+        info!("bad id");
+        return hard_err(Status::NotFound);
+    };
+    if ctx.cookies.get(CSRF_COOKIE_NAME).map(|token| token.value()) != Some(data.csrf.as_str()) {
+        return hard_err(Status::BadRequest);
+    }
+    let user = if let Some(d) = ctx.deets.as_ref() {
+        d.id()
+    } else {
+        info!("no deets");
+        return hard_err(Status::Unauthorized);
+    };
+
+    let res = if accept {
+        loans::accept(&**ctx, id, user, now)
+    } else {
+        loans::cancel(&**ctx, id, user, now)
+    };
+    let status_msg = match res {
+        Ok(loan) if accept => format!("Loan accepted: {}.", loans::describe(&loan)),
+        Ok(loan) if user == loan.lender => "Your offer has been withdrawn.".to_string(),
+        Ok(_) => "You have declined the offer.".to_string(),
+        Err(loans::LoanError::Db(e)) => panic!("{:?}", e),
+        Err(e) => e.to_string(),
+    };
+
+    page(
+        ctx,
+        PageTitle("Loan"),
+        CanonicalUrl(None),
+        html!{},
+        html!{
+            main { (status_msg) }
+            br;
+            a href=(uri!(loan_view: damm_id = damm_id)) { "Return to loan" }
+            br;
+            a href=(uri!(loan_index)) { "Return to loans" }
+        }
+    )
+}
+
+#[post("/loans/<damm_id>/accept", data = "<data>")]
+pub fn loan_accept(
+    mut ctx: CommonContext,
+    data: LenientForm<CSRFForm>,
+    damm_id: String,
+) -> PlutoResponse {
+    loan_action_common(&mut ctx, &data, &damm_id, true)
+}
+
+#[post("/loans/<damm_id>/cancel", data = "<data>")]
+pub fn loan_cancel(
+    mut ctx: CommonContext,
+    data: LenientForm<CSRFForm>,
+    damm_id: String,
+) -> PlutoResponse {
+    loan_action_common(&mut ctx, &data, &damm_id, false)
+}
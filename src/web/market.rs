@@ -0,0 +1,263 @@
+use super::prelude::*;
+use super::csrf::CSRFForm;
+use crate::models::{ItemType, MarketOrder};
+use crate::market;
+
+/// How many recent trades to list for a pair
+const RECENT_FILLS:i64 = 25;
+
+#[derive(Debug, Clone, FromForm)]
+pub struct MarketOrderForm {
+    csrf: String,
+    side: String,
+    quantity: i64,
+    base: String,
+    price: i64,
+    quote: String,
+}
+
+fn active_item_types(ctx: &CommonContext) -> Vec<ItemType> {
+    use schema::item_types::dsl as itdsl;
+    itdsl::item_types
+        .select(ItemType::cols())
+        .filter(itdsl::retired_at.is_null())
+        .order(itdsl::position)
+        .get_results(&**ctx)
+        .unwrap()
+}
+
+fn order_row(order: &MarketOrder, csrf: &str) -> maud::Markup {
+    html!{
+        tr {
+            td { "#" (order.damm()) }
+            td { (market::describe(order)) }
+            td { (order.remaining) " of " (order.quantity) " left" }
+            td {
+                form action=(uri!(market_order_cancel: damm_id = order.damm())) method="post" {
+                    input type="hidden" name="csrf" value=(csrf);
+                    button type="submit" { "Cancel" }
+                }
+            }
+        }
+    }
+}
+
+#[get("/market?<base>&<quote>")]
+pub fn market_index(
+    base: Option<String>,
+    quote: Option<String>,
+    mut ctx: CommonContext,
+) -> PlutoResponse {
+    let item_types = active_item_types(&ctx);
+    let find = |name: &str| item_types.iter().find(|it| it.db_name() == name).cloned();
+    let base_ty = match find(base.as_deref().unwrap_or("gen")) {
+        Some(t) => t,
+        None => return not_found(),
+    };
+    let quote_ty = match find(quote.as_deref().unwrap_or("pc")) {
+        Some(t) => t,
+        None => return not_found(),
+    };
+
+    let bids = market::depth(&*ctx, &base_ty.id, &quote_ty.id, true).unwrap();
+    let asks = market::depth(&*ctx, &base_ty.id, &quote_ty.id, false).unwrap();
+    let fills = market::recent_fills(&*ctx, &base_ty.id, &quote_ty.id, RECENT_FILLS).unwrap();
+    let my_orders = ctx.deets.as_ref().map(|d| market::open_orders_for(&*ctx, d.id()).unwrap());
+    let csrf = ctx.csrf_token.clone();
+
+    let body = html!{
+        main {
+            h1 { "Market: " (base_ty.long_name_plural) " for " (quote_ty.long_name_plural) }
+            p {
+                "Prices are in " (quote_ty.id) " per " (base_ty.id) ". "
+                "Everything an order could spend is held until it's filled or cancelled, and fills happen at the price of "
+                "the order that was already on the book. Place orders here or from discord with "
+                code { "$buy <quantity> <type> at <price> <type>" }
+                " and "
+                code { "$sell" }
+                "."
+            }
+            form action=(uri!(market_index: base = _, quote = _)) method="get" {
+                select name="base" {
+                    @for it in &item_types {
+                        option value=(it.id) selected[it.id == base_ty.id] { (it.long_name_plural) }
+                    }
+                }
+                " for "
+                select name="quote" {
+                    @for it in &item_types {
+                        option value=(it.id) selected[it.id == quote_ty.id] { (it.long_name_plural) }
+                    }
+                }
+                " "
+                button type="submit" { "Show" }
+            }
+            h2 { "Order book" }
+            table.tabley-table {
+                tr {
+                    th { "Bids (" (base_ty.id) ")" }
+                    th { "Price (" (quote_ty.id) ")" }
+                    th { "Asks (" (base_ty.id) ")" }
+                }
+                @for level in asks.iter().rev() {
+                    tr {
+                        td {}
+                        td { (level.price) }
+                        td { (level.quantity) }
+                    }
+                }
+                @for level in &bids {
+                    tr {
+                        td { (level.quantity) }
+                        td { (level.price) }
+                        td {}
+                    }
+                }
+            }
+            @if bids.is_empty() && asks.is_empty() {
+                p { "No open orders." }
+            }
+            @if let Some(my_orders) = &my_orders {
+                h2 { "Place an order" }
+                form action=(uri!(market_order_place)) method="post" {
+                    input type="hidden" name="csrf" value=(csrf);
+                    input type="hidden" name="base" value=(base_ty.id);
+                    input type="hidden" name="quote" value=(quote_ty.id);
+                    select name="side" {
+                        option value="buy" { "Buy" }
+                        option value="sell" { "Sell" }
+                    }
+                    " "
+                    input type="number" min="1" name="quantity" placeholder="quantity";
+                    " " (base_ty.id) " at "
+                    input type="number" min="1" name="price" placeholder="price";
+                    " " (quote_ty.id) " each "
+                    button type="submit" { "Place order" }
+                }
+                h2 { "Your open orders" }
+                @if my_orders.is_empty() {
+                    p { "None." }
+                } @else {
+                    table.tabley-table {
+                        @for order in my_orders {
+                            (order_row(order, &csrf))
+                        }
+                    }
+                }
+            }
+            h2 { "Recent trades" }
+            @if fills.is_empty() {
+                p { "None yet." }
+            } @else {
+                table.tabley-table {
+                    tr {
+                        th { "When" }
+                        th { "Quantity (" (base_ty.id) ")" }
+                        th { "Price (" (quote_ty.id) ")" }
+                    }
+                    @for fill in &fills {
+                        tr {
+                            td { (show_ts(fill.happened_at)) }
+                            td { (fill.quantity) }
+                            td { (fill.price) }
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    page(
+        &mut ctx,
+        PageTitle("Market"),
+        full_url(uri!(market_index: base = base_ty.db_name(), quote = quote_ty.db_name())).into(),
+        html!{},
+        body,
+    )
+}
+
+fn market_result_page(ctx: &mut CommonContext, title: &'static str, status_msg: String) -> PlutoResponse {
+    page(
+        ctx,
+        PageTitle(title),
+        CanonicalUrl(None),
+        html!{},
+        html!{
+            main { (status_msg) }
+            br;
+            a href=(uri!(market_index: base = _, quote = _)) { "Return to the market" }
+        }
+    )
+}
+
+#[post("/market/orders", data = "<data>")]
+pub fn market_order_place(
+    mut ctx: CommonContext,
+    data: LenientForm<MarketOrderForm>,
+) -> PlutoResponse {
+    let now = Utc::now();
+    if ctx.cookies.get(CSRF_COOKIE_NAME).map(|token| token.value()) != Some(data.csrf.as_str()) {
+        return hard_err(Status::BadRequest);
+    }
+    let user = if let Some(d) = ctx.deets.as_ref() {
+        d.id()
+    } else {
+        return hard_err(Status::Unauthorized);
+    };
+    let is_buy = match data.side.as_str() {
+        "buy" => true,
+        "sell" => false,
+        _ => return hard_err(Status::BadRequest),
+    };
+    let item_types = active_item_types(&ctx);
+    let find = |name: &str| item_types.iter().find(|it| it.db_name() == name).map(|it| it.id.clone());
+    let (base_ty, quote_ty) = match (find(&data.base), find(&data.quote)) {
+        (Some(b), Some(q)) => (b, q),
+        _ => return hard_err(Status::BadRequest),
+    };
+
+    let status_msg = match market::place(&*ctx, user, is_buy, data.quantity, base_ty, data.price, quote_ty, now) {
+        Ok((order, fills)) => {
+            let filled:i64 = fills.iter().map(|f| f.quantity).sum();
+            format!(
+                "Placed order #{} to {}. {} filled immediately, {} left on the book.",
+                order.damm(),
+                market::describe(&order),
+                filled,
+                order.remaining,
+            )
+        },
+        Err(market::MarketError::Db(e)) => panic!("{:?}", e),
+        Err(e) => format!("Failed: {}", e),
+    };
+    market_result_page(&mut ctx, "Market order", status_msg)
+}
+
+#[post("/market/orders/<damm_id>/cancel", data = "<data>")]
+pub fn market_order_cancel(
+    mut ctx: CommonContext,
+    data: LenientForm<CSRFForm>,
+    damm_id: String,
+) -> PlutoResponse {
+    let now = Utc::now();
+    let id:i64 = if let Some(digits) = crate::damm::validate_ascii(damm_id.as_str()) {
+        atoi::atoi(digits.as_slice()).unwrap()
+    } else {
+        return hard_err(Status::NotFound);
+    };
+    if ctx.cookies.get(CSRF_COOKIE_NAME).map(|token| token.value()) != Some(data.csrf.as_str()) {
+        return hard_err(Status::BadRequest);
+    }
+    let user = if let Some(d) = ctx.deets.as_ref() {
+        d.id()
+    } else {
+        return hard_err(Status::Unauthorized);
+    };
+
+    let status_msg = match market::cancel(&*ctx, id, user, now) {
+        Ok(order) => format!("Cancelled order #{}; the unfilled {} have been returned.", order.damm(), order.remaining),
+        Err(market::MarketError::Db(e)) => panic!("{:?}", e),
+        Err(e) => format!("Failed: {}", e),
+    };
+    market_result_page(&mut ctx, "Market order", status_msg)
+}
@@ -1,9 +1,14 @@
+mod admin;
 mod auctions;
 mod auth;
 mod bank;
+mod charts;
 mod common_context;
 mod csrf;
 mod deets;
+mod items;
+mod loans;
+mod market;
 mod misc_error;
 mod motions;
 mod prelude;
@@ -13,7 +18,9 @@ mod secure_headers;
 mod shortlink;
 mod static_responders;
 mod statics;
+mod stats;
 mod template;
+mod trades;
 
 #[cfg(feature = "debug")]
 mod debug_utils;
@@ -53,12 +60,45 @@ pub fn main() {
             motions::motion_view,
             motions::motion_vote,
             bank::my_transactions,
+            bank::treasury,
+            stats::stats_index,
             bank::give_form,
             bank::give_perform,
+            bank::give_item_perform,
             auctions::auction_index,
+            auctions::auction_history,
+            auctions::auction_history_json,
             auctions::auction_bid,
             auctions::auction_view,
+            auctions::auction_create,
+            auctions::auction_watch,
+            auctions::auction_buy_now,
+            auctions::auction_cancel,
+            auctions::auction_unit_bid,
+            trades::trade_index,
+            trades::trade_view,
+            trades::trade_accept,
+            trades::trade_cancel,
+            loans::loan_index,
+            loans::loan_view,
+            loans::loan_accept,
+            loans::loan_cancel,
+            market::market_index,
+            market::market_order_place,
+            market::market_order_cancel,
+            items::item_view,
             shortlink::shortlink,
+            admin::item_types_index,
+            admin::item_type_create,
+            admin::item_type_position,
+            admin::item_type_retire,
+            admin::item_type_confirm_above,
+            admin::item_type_alias_add,
+            admin::item_type_alias_remove,
+            admin::auto_auctions_index,
+            admin::auto_auction_set,
+            admin::auto_auction_enabled,
+            admin::auto_auction_remove,
         ]);
     #[cfg(feature = "debug")]
     let r = r.mount("/", routes![
@@ -4,6 +4,7 @@ use rocket::request::{FromQuery, Query};
 
 use super::prelude::*;
 use crate::models::{Motion,MotionWithCount,MotionVote,Transfer,TransferExtra};
+use crate::motion_actions::MotionAction;
 use crate::motion_label::motion_label;
 
 #[derive(Debug, Clone, FromForm)]
@@ -205,6 +206,7 @@ pub fn motion_index(
     use crate::schema::motion_votes::dsl as mvdsl;
     let bare_motions:Vec<Motion> = mdsl::motions
         .select(Motion::cols())
+        .filter(mdsl::economy.eq(SHARED_ECONOMY))
         .order((mdsl::announcement_message_id.is_null().desc(), mdsl::rowid.desc()))
         .get_results(&*ctx)
         .unwrap();
@@ -296,6 +298,7 @@ pub fn motion_view(
     let maybe_motion:Option<Motion> = mdsl::motions
         .select(Motion::cols())
         .filter(mdsl::rowid.eq(id))
+        .filter(mdsl::economy.eq(SHARED_ECONOMY))
         .get_result(&*ctx)
         .optional()
         .unwrap();
@@ -325,6 +328,12 @@ pub fn motion_view(
         .order(tdsl::happened_at.asc())
         .get_results(&*ctx)
         .unwrap();
+    let (action, action_executed_at, action_error):(Option<String>, Option<DateTime<Utc>>, Option<String>) = mdsl::motions
+        .select((mdsl::action, mdsl::action_executed_at, mdsl::action_error))
+        .filter(mdsl::rowid.eq(motion.rowid))
+        .get_result(&*ctx)
+        .unwrap();
+    let action = action.map(|a| MotionAction::from_db(&a));
     let motion = MotionWithCount::from_motion(motion, yes_vote_count as u64, no_vote_count as u64);
     let voting_html = if let Some(deets) = ctx.deets.as_ref(){
         if motion.end_at() > Utc::now() {
@@ -404,6 +413,14 @@ pub fn motion_view(
         ))
     }
 
+    if let (Some(action), Some(executed_at)) = (&action, action_executed_at) {
+        motion_history.push((
+            executed_at,
+            "".into(),
+            action.outcome(action_error.as_deref()),
+        ))
+    }
+
     #[allow(unreachable_code)]
     let markup:Markup = html!{
         main {
@@ -413,6 +430,12 @@ pub fn motion_view(
             )) }
             div.motion {
                 (motion_snippet(&motion))
+                @if let Some(action) = &action {
+                    p.motion-action {
+                        "If this motion passes: "
+                        (action.describe())
+                    }
+                }
                 hr;
                 (voting_html)
                 hr;
@@ -502,6 +525,16 @@ pub fn motion_vote(
         info!("bad vote direction {:?}", data.direction);
         return hard_err(rocket::http::Status::BadRequest);
     }
+    // Motions in isolated economies aren't shown here, so they can't be voted on here either
+    let shared:bool = {
+        use schema::motions::dsl as mdsl;
+        diesel::select(diesel::dsl::exists(
+            mdsl::motions.filter(mdsl::rowid.eq(id)).filter(mdsl::economy.eq(SHARED_ECONOMY))
+        )).get_result(&*ctx).unwrap()
+    };
+    if !shared {
+        return not_found();
+    }
     let resp = crate::bot::vote_common(
         &ctx.conn, 
         Some(vote_direction),
@@ -6,6 +6,7 @@ pub(crate) use rocket::request::LenientForm;
 pub(crate) use crate::schema;
 pub(crate) use crate::view_schema;
 pub(crate) use crate::SITE_URL;
+pub(crate) use crate::guilds::SHARED_ECONOMY;
 pub(crate) use crate::names::name_of;
 pub(crate) use crate::transfers::{
     TransactionBuilder,
@@ -17,6 +17,10 @@ pub fn shortlink(
 
     use schema::motions::dsl as mdsl;
     use schema::auctions::dsl as adsl;
+    use schema::trade_offers::dsl as todsl;
+    use schema::items::dsl as idsl;
+    use schema::loans::dsl as ldsl;
+    use schema::market_orders::dsl as modsl;
     use diesel::dsl::{select, exists};
 
 
@@ -40,5 +44,30 @@ pub fn shortlink(
         return Some(Redirect::permanent(full_url(u)));
     }
 
+    let is_trade = select(exists(todsl::trade_offers.filter(todsl::rowid.eq(id)))).get_result(&*ctx).unwrap();
+    if is_trade {
+        return Some(Redirect::permanent(full_url(uri!(super::trades::trade_view: damm_id = damm_id))));
+    }
+
+    let is_item = select(exists(idsl::items.filter(idsl::rowid.eq(id)))).get_result(&*ctx).unwrap();
+    if is_item {
+        return Some(Redirect::permanent(full_url(uri!(super::items::item_view: damm_id = damm_id))));
+    }
+
+    let is_loan = select(exists(ldsl::loans.filter(ldsl::rowid.eq(id)))).get_result(&*ctx).unwrap();
+    if is_loan {
+        return Some(Redirect::permanent(full_url(uri!(super::loans::loan_view: damm_id = damm_id))));
+    }
+
+    let market_pair:Option<(crate::transfers::CurrencyId, crate::transfers::CurrencyId)> = modsl::market_orders
+        .select((modsl::base_ty, modsl::quote_ty))
+        .filter(modsl::rowid.eq(id))
+        .get_result(&*ctx)
+        .optional()
+        .unwrap();
+    if let Some((base, quote)) = market_pair {
+        return Some(Redirect::permanent(full_url(uri!(super::market::market_index: base = base.as_str(), quote = quote.as_str()))));
+    }
+
     None
 }
\ No newline at end of file
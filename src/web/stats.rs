@@ -0,0 +1,112 @@
+use chrono::TimeZone;
+use super::prelude::*;
+use super::charts::{self, Series};
+use crate::stats;
+
+/// How many of the biggest holders of each item type to list
+const TOP_HOLDERS:usize = 10;
+
+#[get("/stats")]
+pub fn stats_index(
+    mut ctx: CommonContext,
+) -> PlutoResponse {
+    use schema::item_types::dsl as it;
+    let now = Utc::now();
+    let year_ago = now - chrono::Duration::weeks(52);
+    let item_types:Vec<crate::models::ItemType> = it::item_types
+        .select(crate::models::ItemType::cols())
+        .order(it::position)
+        .get_results(&*ctx)
+        .unwrap();
+    let daily = stats::changes(&*ctx, "day", Utc.timestamp_opt(0, 0).unwrap()).unwrap();
+    let weekly = stats::changes(&*ctx, "week", year_ago).unwrap();
+    let holdings = stats::holdings(&*ctx).unwrap();
+    let motion_spending = stats::motion_spending(&*ctx, year_ago).unwrap();
+
+    let supply = stats::supply_over_time(&daily);
+    let mut weeks:Vec<DateTime<Utc>> = weekly.iter().map(|c| c.period).collect();
+    weeks.dedup();
+
+    let body = html!{
+        main {
+            h1 { "Statistics" }
+            p {
+                "Circulating supply counts what's held in everyone's balances, so fungibles held by an auction or an "
+                "open market order drop out until they are paid out. \"Created\" is generation and fabrication; "
+                "\"burned\" is economic policy charges, deliberate burns, and motion fees from before the treasury existed."
+            }
+            p {
+                "What fungibles sell for at auction is on the "
+                a href=(uri!(super::auctions::auction_history)) { "auction prices" }
+                " page."
+            }
+            @for item_type in &item_types {
+                @let name = item_type.db_name();
+                @let type_holdings:Vec<&stats::Holding> = holdings.iter().filter(|h| h.ty == name).collect();
+                @let balances:Vec<i64> = type_holdings.iter().map(|h| h.balance).collect();
+                @let created:Vec<i64> = weeks.iter().map(|w| weekly.iter().find(|c| c.period == *w && c.ty == name).map(|c| c.created).unwrap_or(0)).collect();
+                @let burned:Vec<i64> = weeks.iter().map(|w| weekly.iter().find(|c| c.period == *w && c.ty == name).map(|c| c.burned).unwrap_or(0)).collect();
+                section {
+                    h2 { (item_type.long_name_plural) }
+                    (charts::line_chart(
+                        "Circulating supply",
+                        &[Series{
+                            label: item_type.long_name_plural.clone(),
+                            points: supply.get(name).cloned().unwrap_or_default(),
+                        }],
+                    ))
+                    (charts::bar_chart(
+                        "Created and burned per week",
+                        &weeks,
+                        &[("Created", created), ("Burned", burned)],
+                    ))
+                    p {
+                        "Gini coefficient: "
+                        @match stats::gini(&balances) {
+                            Some(g) => (format!("{:.3}", g)),
+                            None => "n/a",
+                        }
+                        " across " (balances.len()) " accounts that have ever held any."
+                    }
+                    table.tabley-table {
+                        tr {
+                            th { "Top holders" }
+                            th { "Balance" }
+                            th { "Share" }
+                        }
+                        @let total:i64 = balances.iter().map(|b| (*b).max(0)).sum();
+                        @for holding in type_holdings.iter().take(TOP_HOLDERS) {
+                            tr {
+                                td { (name_of(holding.user)) }
+                                td { (holding.balance) }
+                                td {
+                                    @if total > 0 {
+                                        (format!("{:.1}%", holding.balance as f64 * 100.0 / total as f64))
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            section {
+                h2 { "Motions" }
+                (charts::bar_chart(
+                    "Capital spent on motions and votes per week",
+                    &motion_spending.iter().map(|t| t.period).collect::<Vec<_>>(),
+                    &[("Capital", motion_spending.iter().map(|t| t.total).collect())],
+                ))
+            }
+        }
+    };
+
+    page(
+        &mut ctx,
+        PageTitle("Statistics"),
+        full_url(uri!(stats_index)).into(),
+        html!{
+            meta name="description" content="Money supply and distribution in the CONsortium.";
+        },
+        body,
+    )
+}
@@ -113,6 +113,7 @@ pub fn page<E, T: AsRef<str>>(
     let top_content = if let Some(deets) = ctx.deets.as_ref() {
         let item_types:Vec<String> = itdsl::item_types
             .select(itdsl::name)
+            .filter(itdsl::retired_at.is_null())
             .order(itdsl::position)
             .get_results(&**ctx)
             .unwrap();
@@ -122,6 +123,7 @@ pub fn page<E, T: AsRef<str>>(
                 .select(bhdsl::balance)
                 .filter(bhdsl::user.eq(id))
                 .filter(bhdsl::ty.eq(name))
+                .filter(bhdsl::economy.eq(crate::guilds::SHARED_ECONOMY))
                 .order(bhdsl::happened_at.desc())
                 .limit(1)
                 .get_result(&**ctx)
@@ -172,11 +174,31 @@ pub fn page<E, T: AsRef<str>>(
                     " | "
                 }
                 a href=(uri!(super::auctions::auction_index)) { "Auctions" }
+                span role="separator" aria-orientation="vertical" {
+                    " | "
+                }
+                a href=(uri!(super::trades::trade_index)) { "Trades" }
+                span role="separator" aria-orientation="vertical" {
+                    " | "
+                }
+                a href=(uri!(super::loans::loan_index)) { "Loans" }
+                span role="separator" aria-orientation="vertical" {
+                    " | "
+                }
+                a href=(uri!(super::market::market_index: base = _, quote = _)) { "Market" }
+                span role="separator" aria-orientation="vertical" {
+                    " | "
+                }
+                a href=(uri!(super::bank::treasury: fun_ty = _, before_ms = _)) { "Treasury" }
+                span role="separator" aria-orientation="vertical" {
+                    " | "
+                }
+                a href=(uri!(super::stats::stats_index)) { "Stats" }
                 @if ctx.deets.is_some() {
                     span role="separator" aria-orientation="vertical" {
                         " | "
                     }
-                    a href=(uri!(super::bank::my_transactions: fun_ty = _, before_ms = _)) { "My Transactions" }
+                    a href=(uri!(super::bank::my_transactions: fun_ty = _, before_ms = _, period = _)) { "My Transactions" }
                     span role="separator" aria-orientation="vertical" {
                         " | "
                     }
@@ -0,0 +1,250 @@
+use super::prelude::*;
+use super::csrf::CSRFForm;
+use crate::models::{TradeOffer, TradeStatus};
+use crate::trades;
+
+fn display_trade(trade: &TradeOffer, now: DateTime<Utc>) -> maud::Markup {
+    let status = trade.status(now);
+    maud::html!{
+        div class=(if status == TradeStatus::Pending { "trade trade-pending" } else { "trade trade-finished" }) {
+            div style="font-weight: bold" {
+                a href=(uri!(trade_view: damm_id = trade.damm())) {
+                    "Trade#"
+                    (trade.damm())
+                }
+            }
+            div {
+                (name_of(trade.offerer))
+                " offers "
+                (trade.offer_amt) " " (trade.offer_ty)
+                " to "
+                (name_of(trade.counterparty))
+                " for "
+                (trade.want_amt) " " (trade.want_ty)
+                "."
+                br;
+                @match status {
+                    TradeStatus::Pending => {
+                        "Offer expires at "
+                        (show_ts(trade.expires_at))
+                    },
+                    TradeStatus::Accepted => {
+                        "Accepted at "
+                        (show_ts(trade.accepted_at.unwrap()))
+                    },
+                    TradeStatus::Cancelled => {
+                        "Cancelled at "
+                        (show_ts(trade.cancelled_at.unwrap()))
+                    },
+                    TradeStatus::Expired => {
+                        "Expired at "
+                        (show_ts(trade.expires_at))
+                    },
+                }
+            }
+        }
+    }
+}
+
+fn trade_actions(trade: &TradeOffer, ctx: &CommonContext, now: DateTime<Utc>) -> maud::Markup {
+    let deets = match ctx.deets.as_ref() {
+        Some(d) => d,
+        None => return maud::html!{},
+    };
+    if trade.status(now) != TradeStatus::Pending {
+        return maud::html!{};
+    }
+    maud::html!{
+        @if deets.id() == trade.counterparty {
+            form action=(uri!(trade_accept: damm_id = trade.damm())) method="post" {
+                input type="hidden" name="csrf" value=(ctx.csrf_token);
+                button type="submit" { "Accept" }
+            }
+            form action=(uri!(trade_cancel: damm_id = trade.damm())) method="post" {
+                input type="hidden" name="csrf" value=(ctx.csrf_token);
+                button type="submit" { "Decline" }
+            }
+        }
+        @if deets.id() == trade.offerer {
+            form action=(uri!(trade_cancel: damm_id = trade.damm())) method="post" {
+                input type="hidden" name="csrf" value=(ctx.csrf_token);
+                button type="submit" { "Withdraw offer" }
+            }
+        }
+    }
+}
+
+#[get("/trades")]
+pub fn trade_index(
+    mut ctx: CommonContext,
+) -> PlutoResponse {
+    use schema::trade_offers::dsl as todsl;
+    let now = Utc::now();
+    let pending_trades:Vec<TradeOffer> = todsl::trade_offers
+        .select(TradeOffer::cols())
+        .filter(todsl::accepted_at.is_null())
+        .filter(todsl::cancelled_at.is_null())
+        .filter(todsl::expires_at.gt(now))
+        .order(todsl::created_at.desc())
+        .get_results(&*ctx)
+        .unwrap();
+    let finished_trades:Vec<TradeOffer> = todsl::trade_offers
+        .select(TradeOffer::cols())
+        .filter(
+            todsl::accepted_at.is_not_null()
+            .or(todsl::cancelled_at.is_not_null())
+            .or(todsl::expires_at.le(now))
+        )
+        .order(todsl::created_at.desc())
+        .limit(100)
+        .get_results(&*ctx)
+        .unwrap();
+
+    let body = html!{
+        h1 { "Trades" }
+        p {
+            "Offer a trade from discord with "
+            code { "$trade <user> <amount> <type> for <amount> <type>" }
+            "."
+        }
+        h2 { "Pending offers" }
+        @for trade in &pending_trades {
+            article {
+                (display_trade(trade, now))
+                (trade_actions(trade, &ctx, now))
+            }
+        }
+        @if pending_trades.is_empty() {
+            p { "No pending offers." }
+        }
+
+        hr;
+        h2 { "Recent trades" }
+        @for trade in &finished_trades {
+            article { (display_trade(trade, now)) }
+        }
+    };
+
+    page(
+        &mut ctx,
+        PageTitle("Trades"),
+        full_url(uri!(trade_index)).into(),
+        html!{},
+        body,
+    )
+}
+
+#[get("/trades/<damm_id>")]
+pub fn trade_view(
+    damm_id: String,
+    mut ctx: CommonContext,
+) -> PlutoResponse {
+    use schema::trade_offers::dsl as todsl;
+    let now = Utc::now();
+    let id:i64 = if let Some(digits) = crate::damm::validate_ascii(damm_id.as_str()) {
+        atoi::atoi(digits.as_slice()).unwrap()
+    } else {
+        return not_found();
+    };
+
+    let maybe_trade:Option<TradeOffer> = todsl::trade_offers
+        .select(TradeOffer::cols())
+        .filter(todsl::rowid.eq(id))
+        .get_result(&*ctx)
+        .optional()
+        .unwrap();
+    let trade = if let Some(t) = maybe_trade {
+        t
+    } else {
+        return not_found();
+    };
+
+    let body = html!{
+        main {
+            (display_trade(&trade, now))
+            (trade_actions(&trade, &ctx, now))
+        }
+    };
+
+    let self_uri = full_url(uri!(trade_view: damm_id = &damm_id));
+    page(
+        &mut ctx,
+        PageTitle(format!("Trade#{}", damm_id)),
+        self_uri.clone().into(),
+        html!{
+            (embed_head_html(format!("Trade#{}", damm_id), trades::describe(&trade), &self_uri))
+
+            link rel="index" href=(uri!(trade_index));
+        },
+        body,
+    )
+}
+
+fn trade_action_common(
+    ctx: &mut CommonContext,
+    data: &CSRFForm,
+    damm_id: &str,
+    accept: bool,
+) -> PlutoResponse {
+    let now = Utc::now();
+    let id:i64 = if let Some(digits) = crate::damm::validate_ascii(damm_id) {
+        atoi::atoi(digits.as_slice()).unwrap()
+    } else {
+        info!("bad id");
+        return hard_err(Status::NotFound);
+    };
+    if ctx.cookies.get(CSRF_COOKIE_NAME).map(|token| token.value()) != Some(data.csrf.as_str()) {
+        return hard_err(Status::BadRequest);
+    }
+    let user = if let Some(d) = ctx.deets.as_ref() {
+        d.id()
+    } else {
+        info!("no deets");
+        return hard_err(Status::Unauthorized);
+    };
+
+    let res = if accept {
+        trades::accept(&**ctx, id, user, now)
+    } else {
+        trades::cancel(&**ctx, id, user, now)
+    };
+    let status_msg = match res {
+        Ok(trade) if accept => format!("Trade accepted: {}.", trades::describe(&trade)),
+        Ok(trade) if user == trade.offerer => "Your offer has been withdrawn.".to_string(),
+        Ok(_) => "You have declined the offer.".to_string(),
+        Err(trades::TradeError::Db(e)) => panic!("{:?}", e),
+        Err(e) => e.to_string(),
+    };
+
+    page(
+        ctx,
+        PageTitle("Trade"),
+        CanonicalUrl(None),
+        html!{},
+        html!{
+            main { (status_msg) }
+            br;
+            a href=(uri!(trade_view: damm_id = damm_id)) { "Return to trade" }
+            br;
+            a href=(uri!(trade_index)) { "Return to trades" }
+        }
+    )
+}
+
+#[post("/trades/<damm_id>/accept", data = "<data>")]
+pub fn trade_accept(
+    mut ctx: CommonContext,
+    data: LenientForm<CSRFForm>,
+    damm_id: String,
+) -> PlutoResponse {
+    trade_action_common(&mut ctx, &data, &damm_id, true)
+}
+
+#[post("/trades/<damm_id>/cancel", data = "<data>")]
+pub fn trade_cancel(
+    mut ctx: CommonContext,
+    data: LenientForm<CSRFForm>,
+    damm_id: String,
+) -> PlutoResponse {
+    trade_action_common(&mut ctx, &data, &damm_id, false)
+}
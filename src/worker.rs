@@ -13,6 +13,14 @@ pub async fn main() {
     ).expect("could not build DB pool");
     let arc_pool = Arc::new(raw_pool);
     loop {
+        // Announcements and DMs name people with name_of, so pick up anyone new first
+        let names_arc = Arc::clone(&arc_pool);
+        tokio::task::spawn_blocking(move || {
+            // A stale cache only means older names, so keep going with it
+            if let Err(e) = crate::names::refresh_if_stale(&*names_arc.get().unwrap()) {
+                warn!("Could not refresh names: {:?}", e);
+            }
+        }).await.unwrap();
         tasks::process_motion_completions(&arc_pool, &http).await.expect("Failed to process motion completions");
         tasks::create_auto_auctions(&arc_pool, &http).await.expect("Failed create_auto_auctions");
         tasks::process_auctions(&arc_pool, &http).await.expect("Failed process_auctions");
@@ -20,8 +28,12 @@ pub async fn main() {
         tokio::task::spawn_blocking(move ||{
             let conn = blocking_arc.get().unwrap();
             tasks::process_generators(&*conn).expect("Failed to process generators");
+            tasks::process_standing_orders(&*conn).expect("Failed to process standing orders");
+            tasks::process_loans(&*conn).expect("Failed to process loans");
+            tasks::process_dutch_auctions(&*conn).expect("Failed to process dutch auctions");
             tasks::update_last_task_run(&*conn).expect("Failed update_last_task_run");
         }).await.unwrap();
+        tasks::send_notifications(&arc_pool, &http).await.expect("Failed send_notifications");
 
         tokio::time::sleep(Duration::from_millis(5000)).await;
     }